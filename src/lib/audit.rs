@@ -0,0 +1,215 @@
+use crate::process::{self, Identity};
+use crate::service::{
+  read_pid, running_containers, running_pid, runtime_file, stop_container, ServiceType,
+};
+use crate::state::SessionState;
+use crate::Project;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long a terminated process is given to exit before it is killed.
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The label used to mark containers that belong to a conductor project.
+pub const PROJECT_LABEL: &str = "conductor.project";
+
+/// Something left behind by a conductor session that is no longer running.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Finding {
+  /// A state or pid file written by a session that is no longer running.
+  StaleFile {
+    path: PathBuf,
+  },
+  /// A process, along with its identity when it was found so a pid that has since been reused
+  /// is not signalled.
+  Process {
+    pid: u32,
+    identity: Identity,
+    reason: String,
+  },
+  Container {
+    name: String,
+    reason: String,
+  },
+}
+
+impl Finding {
+  pub fn describe(&self) -> String {
+    match self {
+      Finding::StaleFile { path } => format!("Stale file {}", path.display()),
+      Finding::Process { pid, reason, .. } => format!("Process {}: {}", pid, reason),
+      Finding::Container { name, reason } => format!("Container {}: {}", name, reason),
+    }
+  }
+
+  /// Cleans up the finding by removing the file, terminating the process or stopping the
+  /// container. A process leading a group, as components do, is terminated along with the rest
+  /// of its group, and killed if it has not exited after a few seconds. A process is only
+  /// signalled while its pid still belongs to the process that was found.
+  pub fn clean(&self) -> io::Result<()> {
+    match self {
+      Finding::StaleFile { path } => fs::remove_file(path),
+      Finding::Process { pid, identity, .. } => {
+        if !process::is_same(*pid, identity) {
+          return Ok(());
+        }
+        process::terminate_group(*pid).or_else(|_| process::terminate(*pid))?;
+        let deadline = Instant::now() + STOP_TIMEOUT;
+        while process::is_same(*pid, identity) {
+          if Instant::now() >= deadline {
+            return process::kill(*pid);
+          }
+          thread::sleep(Duration::from_millis(100));
+        }
+        Ok(())
+      }
+      Finding::Container { name, .. } => stop_container(name).map(|_| ()),
+    }
+  }
+}
+
+/// The result of auditing a project for leftovers from crashed sessions.
+pub struct Audit {
+  /// The pid of a conductor session that is currently running for the project, if any.
+  pub live_session: Option<u32>,
+  pub findings: Vec<Finding>,
+}
+
+/// Scans for processes, containers and files that look like they belong to the project but are
+/// not owned by a live session. Nothing is reported while a session is running since everything
+/// found would belong to it.
+pub fn audit(project: &Project) -> Audit {
+  let root_path = &project.root_path;
+  let own_pid = std::process::id();
+  let state = SessionState::read(root_path).ok();
+  if let Some(state) = &state {
+    if state.pid != own_pid && state.is_live() {
+      return Audit {
+        live_session: Some(state.pid),
+        findings: vec![],
+      };
+    }
+  }
+
+  let mut findings = match state {
+    Some(state) => recorded(root_path, &state),
+    None => vec![],
+  };
+
+  audit_process_services(project, root_path, &mut findings);
+  audit_containers(project, &mut findings);
+  Audit {
+    live_session: None,
+    findings,
+  }
+}
+
+/// What a session that has ended without stopping it left running, as recorded in its state
+/// file: the processes of its components and the containers of its services. The stale state
+/// file is included last. Nothing is returned while the session is still running. Processes are
+/// only included while their start time and command line match those recorded, so processes that
+/// were given their pids after a reboot or once they were freed are left alone.
+pub fn leftovers(root_path: &Path) -> Vec<Finding> {
+  match SessionState::read(root_path) {
+    Ok(state) if !state.is_live() => recorded(root_path, &state),
+    _ => vec![],
+  }
+}
+
+fn recorded(root_path: &Path, state: &SessionState) -> Vec<Finding> {
+  let mut findings = vec![];
+  for c in state.components.iter() {
+    let recorded = c.pid.zip(c.process.clone());
+    if let Some((pid, identity)) = recorded.filter(|(pid, id)| process::is_same(*pid, id)) {
+      findings.push(Finding::Process {
+        pid,
+        identity,
+        reason: format!("component {} from a previous session", c.name),
+      });
+    }
+  }
+  if !state.services.is_empty() {
+    let running: Vec<String> = running_containers()
+      .unwrap_or_default()
+      .iter()
+      .flat_map(|c| {
+        c.Names
+          .iter()
+          .map(|n| n.trim_start_matches('/').to_string())
+      })
+      .collect();
+    for s in state
+      .services
+      .iter()
+      .filter(|s| running.contains(&s.container))
+    {
+      findings.push(Finding::Container {
+        name: s.container.clone(),
+        reason: format!("service {} from a previous session", s.name),
+      });
+    }
+  }
+  findings.push(Finding::StaleFile {
+    path: SessionState::path(root_path),
+  });
+  findings
+}
+
+fn audit_process_services(project: &Project, root_path: &Path, findings: &mut Vec<Finding>) {
+  for service in project
+    .services
+    .iter()
+    .filter(|s| s.service_type == ServiceType::Process)
+  {
+    let pid_file = runtime_file(root_path, &service.name, "pid");
+    match running_pid(&pid_file) {
+      Some((pid, identity)) => findings.push(Finding::Process {
+        pid,
+        identity,
+        reason: format!("process service {}", service.name),
+      }),
+      None if read_pid(&pid_file).is_some() => findings.push(Finding::StaleFile { path: pid_file }),
+      None => {}
+    }
+  }
+}
+
+fn audit_containers(project: &Project, findings: &mut Vec<Finding>) {
+  let containers = match running_containers() {
+    Ok(containers) => containers,
+    Err(_) => return,
+  };
+  for container in containers.iter() {
+    let labelled = container
+      .Labels
+      .as_ref()
+      .and_then(|labels| labels.get(PROJECT_LABEL))
+      .map(|p| *p == project.name)
+      .unwrap_or(false);
+    for name in container.Names.iter().map(|n| n.trim_start_matches('/')) {
+      if findings
+        .iter()
+        .any(|f| matches!(f, Finding::Container { name: n, .. } if n == name))
+      {
+        break;
+      }
+      let service = project
+        .services
+        .iter()
+        .find(|s| s.service_type == ServiceType::DockerContainer && s.get_container_name() == name);
+      let reason = match service {
+        Some(s) => format!("service {}", s.name),
+        None if labelled => format!("labelled for project {}", project.name),
+        None => continue,
+      };
+      findings.push(Finding::Container {
+        name: name.to_string(),
+        reason,
+      });
+      break;
+    }
+  }
+}
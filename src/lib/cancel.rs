@@ -0,0 +1,116 @@
+use crossbeam::channel::{bounded, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+#[derive(Default)]
+struct TokenState {
+  cancelled: AtomicBool,
+  children: Mutex<Vec<Weak<TokenState>>>,
+  waiters: Mutex<Vec<Sender<()>>>,
+}
+
+/// A cancellation token is used to request the shutdown of running workers. Tokens form a tree,
+/// cancelling a token also cancels all of its children. The supervisor holds a root token,
+/// groups hold a child of the root and every worker holds a child of either.
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+  state: Arc<TokenState>,
+}
+
+impl CancellationToken {
+  pub fn new() -> Self {
+    CancellationToken::default()
+  }
+
+  /// Creates a child token which is cancelled when this token is cancelled. A child created
+  /// from an already cancelled token starts out cancelled.
+  pub fn child(&self) -> CancellationToken {
+    let child = CancellationToken::new();
+    let mut children = self.state.children.lock().unwrap();
+    if self.is_cancelled() {
+      child.cancel();
+    } else {
+      children.retain(|c| c.strong_count() > 0);
+      children.push(Arc::downgrade(&child.state));
+    }
+    child
+  }
+
+  /// Requests cancellation of this token and all of its children.
+  pub fn cancel(&self) {
+    if self.state.cancelled.swap(true, Ordering::SeqCst) {
+      return;
+    }
+    for waiter in self.state.waiters.lock().unwrap().drain(..) {
+      let _ = waiter.send(());
+    }
+    let children: Vec<Weak<TokenState>> = self.state.children.lock().unwrap().drain(..).collect();
+    for state in children.into_iter().filter_map(|c| c.upgrade()) {
+      CancellationToken { state }.cancel();
+    }
+  }
+
+  pub fn is_cancelled(&self) -> bool {
+    self.state.cancelled.load(Ordering::SeqCst)
+  }
+
+  /// Returns a receiver that yields a message once the token has been cancelled. This can be
+  /// used in a select alongside other channels.
+  pub fn cancelled(&self) -> Receiver<()> {
+    let (tx, rx) = bounded(1);
+    let mut waiters = self.state.waiters.lock().unwrap();
+    if self.is_cancelled() {
+      let _ = tx.send(());
+    } else {
+      waiters.push(tx);
+    }
+    rx
+  }
+}
+
+/// Returned when cancellation is requested for one or more workers. It resolves once all of the
+/// cancelled workers have shut down.
+pub struct Completion {
+  pending: Vec<Receiver<()>>,
+}
+
+impl Completion {
+  /// Creates a completion from the done receivers of workers. Each receiver is disconnected when
+  /// its worker's thread exits.
+  pub(crate) fn new(pending: Vec<Receiver<()>>) -> Self {
+    Completion { pending }
+  }
+
+  /// Returns true if all workers have shut down.
+  pub fn is_complete(&self) -> bool {
+    self
+      .pending
+      .iter()
+      .all(|r| r.try_recv() == Err(TryRecvError::Disconnected))
+  }
+
+  /// Blocks until all workers have shut down.
+  pub fn wait(self) {
+    for r in self.pending.iter() {
+      while r.recv().is_ok() {}
+    }
+  }
+
+  /// Blocks until all workers have shut down or the timeout elapses. Returns false if the
+  /// timeout elapsed first.
+  pub fn wait_timeout(self, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    for r in self.pending.iter() {
+      loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match r.recv_timeout(remaining) {
+          Ok(()) => continue,
+          Err(RecvTimeoutError::Disconnected) => break,
+          Err(RecvTimeoutError::Timeout) => return false,
+        }
+      }
+    }
+    true
+  }
+}
@@ -0,0 +1,45 @@
+use crate::random;
+use std::time::Duration;
+
+/// Kills running components at random while a session runs, to check that the rest of the
+/// environment copes with them going away and that they recover.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chaos {
+  /// How often the running components are considered for killing.
+  pub interval: Duration,
+  /// The chance of each running component being killed every interval, from 0 to 1.
+  pub probability: f64,
+}
+
+impl Default for Chaos {
+  fn default() -> Self {
+    Chaos {
+      interval: Duration::from_secs(30),
+      probability: 0.2,
+    }
+  }
+}
+
+impl Chaos {
+  /// Chaos killing components with the probability every interval seconds.
+  pub fn new(interval: u64, probability: f64) -> Result<Self, String> {
+    if interval == 0 {
+      return Err("Expected a number of seconds above 0 for --chaos-interval".into());
+    }
+    if !(0.0..=1.0).contains(&probability) {
+      return Err(format!(
+        "Expected a probability from 0 to 1 for --chaos-probability, got {}",
+        probability
+      ));
+    }
+    Ok(Chaos {
+      interval: Duration::from_secs(interval),
+      probability,
+    })
+  }
+
+  /// Whether to kill a component this time round.
+  pub fn strikes(&self) -> bool {
+    random::fraction().is_ok_and(|r| r < self.probability)
+  }
+}
@@ -0,0 +1,116 @@
+use crate::Component;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// An operation on a running session, carried out by Supervisor::execute. Commands are typed
+/// into the command palette prefixed with a `:`, for example `:restart api`, or sent over the
+/// control socket.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Command {
+  Start(String),
+  Stop(String),
+  Restart(String),
+  /// Freezes a running component without stopping it, until it is resumed.
+  Pause(String),
+  Resume(String),
+  Mute(String),
+  Unmute(String),
+  /// Shows only the output of a component or service, giving the same name again or no name
+  /// shows everything.
+  Filter(Option<String>),
+  /// Shows only lines of output matching a pattern, no pattern shows every line again.
+  Grep(Option<String>),
+  Logs(String),
+  /// Shows the last lines of output of a component, or all of its recent output.
+  Tail(String, Option<usize>),
+  RunTask(String),
+  /// Writes text to the stdin of a running component, used when attached to it.
+  Input(String, String),
+  /// Writes a line to the stdin of the named running components, or every running component
+  /// when no names are given.
+  Broadcast(Vec<String>, String),
+  Status,
+  Help,
+  /// Reconciles the session toward a set of components. Missing components are started, those
+  /// not in the set are stopped and those whose definition changed are restarted.
+  Apply(Vec<Component>),
+  /// Stops a component and starts it again with the given definition, such as one re-read from
+  /// the config by `conductor restart`.
+  Replace(Box<Component>),
+}
+
+/// The usage and description of every command, listed by `:help`.
+pub const USAGE: &[(&str, &str)] = &[
+  ("start <component>", "start a component that is not running"),
+  ("stop <component>", "stop a running component"),
+  ("restart <component>", "stop a component and start it again"),
+  ("pause <component>", "freeze a running component"),
+  ("resume <component>", "continue a paused component"),
+  ("mute <name>", "hide the output of a component or service"),
+  (
+    "unmute <name>",
+    "show the output of a component or service again",
+  ),
+  (
+    "filter [name]",
+    "show only the output of a component or service, or everything again",
+  ),
+  (
+    "grep [pattern]",
+    "show only lines of output matching a pattern, or every line again",
+  ),
+  ("logs <component>", "show the recent output of a component"),
+  (
+    "tail <component> [lines]",
+    "show the last lines of output of a component",
+  ),
+  (
+    "run <task>",
+    "run a project or component:task in the background",
+  ),
+  ("status", "show the state of every component"),
+  ("help", "list the available commands"),
+];
+
+impl FromStr for Command {
+  type Err = String;
+
+  /// Parses a command without its `:` prefix. The longer phrasings `open logs <component>` and
+  /// `run task <task>` are accepted as well.
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let words: Vec<&str> = s.split_whitespace().collect();
+    let command = match words.as_slice() {
+      ["start", name] => Command::Start(name.to_string()),
+      ["stop", name] => Command::Stop(name.to_string()),
+      ["restart", name] => Command::Restart(name.to_string()),
+      ["pause", name] => Command::Pause(name.to_string()),
+      ["resume", name] => Command::Resume(name.to_string()),
+      ["mute", name] => Command::Mute(name.to_string()),
+      ["unmute", name] => Command::Unmute(name.to_string()),
+      ["filter"] => Command::Filter(None),
+      ["filter", name] => Command::Filter(Some(name.to_string())),
+      ["grep"] => Command::Grep(None),
+      ["grep", ..] => Command::Grep(Some(s.trim()["grep".len()..].trim().to_string())),
+      ["logs", name] | ["open", "logs", name] => Command::Logs(name.to_string()),
+      ["tail", name] => Command::Tail(name.to_string(), None),
+      ["tail", name, lines] => Command::Tail(
+        name.to_string(),
+        Some(
+          lines
+            .parse()
+            .map_err(|_| format!("Invalid number of lines {}", lines))?,
+        ),
+      ),
+      ["run", name] | ["run", "task", name] => Command::RunTask(name.to_string()),
+      ["status"] => Command::Status,
+      ["help"] => Command::Help,
+      _ => {
+        return Err(format!(
+          "Unknown command :{}, type :help to list the commands",
+          s.trim()
+        ))
+      }
+    };
+    Ok(command)
+  }
+}
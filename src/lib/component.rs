@@ -1,10 +1,23 @@
 use crate::git;
-use serde::Deserialize;
+use crate::journal::LogRotation;
+use crate::limits::Limits;
+use crate::lines;
+use crate::platform;
+use crate::source::Source;
+use crate::telemetry::Span;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::{SocketAddr, TcpListener, TcpStream};
 use std::path::{Path, PathBuf};
+use std::time::Duration;
+use subprocess::{Exec, NullFile};
 
-#[derive(Clone, Deserialize, PartialEq, Debug)]
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug, Default)]
 pub enum TerminalColor {
+  /// A color picked from the palette when the project is loaded.
+  #[default]
+  Auto,
   Blue,
   Green,
   Yellow,
@@ -12,15 +25,340 @@ pub enum TerminalColor {
   White,
   Red,
   Cyan,
+  /// A color from the 256 color table.
+  Indexed(u8),
 }
 
-impl Default for TerminalColor {
+/// Distinct hues from the 256 color table, ordered so that neighbouring entries are easy to tell
+/// apart.
+const PALETTE: &[u8] = &[39, 208, 41, 170, 220, 45, 203, 141, 118, 214, 75, 205];
+
+impl TerminalColor {
+  /// The automatically assigned color for the nth component.
+  pub fn palette(n: usize) -> Self {
+    TerminalColor::Indexed(PALETTE[n % PALETTE.len()])
+  }
+}
+
+/// Describes when a running component is considered ready. Any of the configured checks
+/// passing marks the component ready.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(default)]
+pub struct Readiness {
+  /// Ready once a line of output contains this text.
+  pub output: Option<String>,
+  /// Ready once this local TCP port accepts connections.
+  pub port: Option<u16>,
+  /// Ready once this shell command exits successfully. It is run in the component's path with
+  /// its environment.
+  pub command: Option<String>,
+  /// Milliseconds between port and command checks.
+  pub interval_ms: u64,
+}
+
+impl Default for Readiness {
   fn default() -> Self {
-    TerminalColor::Yellow
+    Readiness {
+      output: None,
+      port: None,
+      command: None,
+      interval_ms: 500,
+    }
+  }
+}
+
+impl Readiness {
+  /// Returns true if the readiness has checks that need to be polled.
+  pub fn has_probe(&self) -> bool {
+    self.port.is_some() || self.command.is_some()
+  }
+
+  /// Runs the port and command checks once.
+  pub fn probe(&self, path: &Path, env: &[(String, String)]) -> bool {
+    if let Some(port) = self.port {
+      let addr = SocketAddr::from(([127, 0, 0, 1], port));
+      if TcpStream::connect_timeout(&addr, Duration::from_secs(1)).is_ok() {
+        return true;
+      }
+    }
+    if let Some(command) = &self.command {
+      let status = Exec::shell(command)
+        .env_extend(env)
+        .cwd(path)
+        .stdout(NullFile)
+        .stderr(NullFile)
+        .join();
+      if let Ok(status) = status {
+        return status.success();
+      }
+    }
+    false
   }
 }
 
-#[derive(Clone, Deserialize, PartialEq, Debug)]
+/// Checks run periodically while a component is up to notice it has stopped working without
+/// exiting. A component failing them several times in a row is restarted.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(default)]
+pub struct HealthCheck {
+  /// Healthy while this shell command exits successfully. It is run in the component's path
+  /// with its environment.
+  pub command: Option<String>,
+  /// Healthy while this URL responds successfully.
+  pub url: Option<String>,
+  /// Seconds between checks.
+  pub interval_secs: u64,
+  /// Consecutive failed checks after which the component is restarted.
+  pub failures: u32,
+}
+
+impl Default for HealthCheck {
+  fn default() -> Self {
+    HealthCheck {
+      command: None,
+      url: None,
+      interval_secs: 10,
+      failures: 3,
+    }
+  }
+}
+
+impl HealthCheck {
+  /// Runs the checks once. Returns true if all of them passed.
+  pub fn passes(&self, path: &Path, env: &[(String, String)]) -> bool {
+    if let Some(url) = &self.url {
+      if !responds(url, Duration::from_secs(5)) {
+        return false;
+      }
+    }
+    if let Some(command) = &self.command {
+      let status = Exec::shell(command)
+        .env_extend(env)
+        .cwd(path)
+        .stdout(NullFile)
+        .stderr(NullFile)
+        .join();
+      return status.map(|s| s.success()).unwrap_or(false);
+    }
+    true
+  }
+}
+
+/// Returns true if a request for the URL gets a successful response within the timeout.
+/// Redirects are followed.
+fn responds(url: &str, timeout: Duration) -> bool {
+  match ureq::get(url).timeout(timeout).call() {
+    Ok(response) => (200..400).contains(&response.status()),
+    Err(_) => false,
+  }
+}
+
+/// Conditions that must all hold before a component is started, such as a database accepting
+/// connections or another component being ready.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(default)]
+pub struct WaitFor {
+  /// A local TCP port that accepts connections.
+  pub port: Option<u16>,
+  /// A URL that responds successfully.
+  pub url: Option<String>,
+  /// A file, relative to the component's path, that exists.
+  pub file: Option<String>,
+  /// Another component that is ready.
+  pub component: Option<String>,
+  /// Seconds to wait before giving up, the component is not started then.
+  pub timeout_secs: u64,
+  /// Milliseconds between checks.
+  pub interval_ms: u64,
+}
+
+impl Default for WaitFor {
+  fn default() -> Self {
+    WaitFor {
+      port: None,
+      url: None,
+      file: None,
+      component: None,
+      timeout_secs: 60,
+      interval_ms: 500,
+    }
+  }
+}
+
+impl WaitFor {
+  /// Checks the conditions once and describes the first one that does not hold yet. Whether
+  /// another component is ready is answered by `ready`.
+  pub fn unmet<F: Fn(&str) -> bool>(&self, path: &Path, ready: F) -> Option<String> {
+    if let Some(port) = self.port {
+      let addr = SocketAddr::from(([127, 0, 0, 1], port));
+      if TcpStream::connect_timeout(&addr, Duration::from_secs(1)).is_err() {
+        return Some(format!("port {}", port));
+      }
+    }
+    if let Some(url) = &self.url {
+      if !responds(url, Duration::from_secs(2)) {
+        return Some(url.clone());
+      }
+    }
+    if let Some(file) = &self.file {
+      if !path.join(file).exists() {
+        return Some(format!("file {}", file));
+      }
+    }
+    if let Some(component) = &self.component {
+      if !ready(component) {
+        return Some(format!("component {}", component));
+      }
+    }
+    None
+  }
+}
+
+/// Decides whether a component is included when the project runs. A condition written as a
+/// string is a command. When several checks are given all of them have to pass.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug, Default)]
+#[serde(from = "ConditionConfig")]
+pub struct Condition {
+  /// Passes when this shell command exits successfully. It is run from the project root.
+  pub command: Option<String>,
+  /// Passes when this environment variable is set and not empty, or equal to `equals`.
+  pub env: Option<String>,
+  pub equals: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ConditionConfig {
+  Command(String),
+  Full {
+    #[serde(default)]
+    command: Option<String>,
+    #[serde(default)]
+    env: Option<String>,
+    #[serde(default)]
+    equals: Option<String>,
+  },
+}
+
+impl From<ConditionConfig> for Condition {
+  fn from(config: ConditionConfig) -> Self {
+    match config {
+      ConditionConfig::Command(command) => Condition {
+        command: Some(command),
+        ..Condition::default()
+      },
+      ConditionConfig::Full {
+        command,
+        env,
+        equals,
+      } => Condition {
+        command,
+        env,
+        equals,
+      },
+    }
+  }
+}
+
+impl Condition {
+  /// Evaluates the condition against an environment, which the command is also run with.
+  pub fn holds(&self, root_path: &Path, env: &[(String, String)]) -> bool {
+    if let Some(name) = &self.env {
+      let value = env.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str());
+      let passes = match (&self.equals, value) {
+        (Some(expected), Some(value)) => value == expected,
+        (None, Some(value)) => !value.is_empty(),
+        (_, None) => false,
+      };
+      if !passes {
+        return false;
+      }
+    }
+    if let Some(command) = &self.command {
+      let status = Exec::shell(command)
+        .env_extend(env)
+        .cwd(root_path)
+        .stdout(NullFile)
+        .stderr(NullFile)
+        .join();
+      if !matches!(status, Ok(status) if status.success()) {
+        return false;
+      }
+    }
+    true
+  }
+}
+
+/// When a component that is retried is considered to be crash looping. It is no longer
+/// restarted once it fails this many times within the window.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(default)]
+pub struct CrashLoop {
+  /// Failures within `window_secs` that stop restarts, 0 restarts the component forever.
+  pub failures: usize,
+  pub window_secs: u64,
+}
+
+impl Default for CrashLoop {
+  fn default() -> Self {
+    CrashLoop {
+      failures: 5,
+      window_secs: 30,
+    }
+  }
+}
+
+/// How escape sequences in a component's output, such as colors and cursor movement, are
+/// treated.
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Ansi {
+  /// Sequences are written to the terminal as they are.
+  Passthrough,
+  /// Sequences are removed, leaving plain text for the terminal and logs.
+  Strip,
+}
+
+impl Ansi {
+  /// A line of the component's output as it is shown.
+  pub fn apply(self, line: String) -> String {
+    match self {
+      Ansi::Passthrough => line,
+      Ansi::Strip => lines::strip_ansi(&line),
+    }
+  }
+}
+
+/// What happens to a component's output when the session falls behind reading it.
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Overflow {
+  /// Lines are dropped and a marker with the number dropped is shown once there is room again.
+  Drop,
+  /// The component is made to wait, its writes block until the session catches up.
+  Block,
+}
+
+/// How much of a component's output may be waiting to be shown before the overflow policy
+/// applies.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(default)]
+pub struct Backpressure {
+  /// Lines held for the session before overflowing.
+  pub buffer: usize,
+  pub overflow: Overflow,
+}
+
+impl Default for Backpressure {
+  fn default() -> Self {
+    Backpressure {
+      buffer: 10_000,
+      overflow: Overflow::Drop,
+    }
+  }
+}
+
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
 #[serde(default)]
 pub struct Component {
   pub name: String,
@@ -28,15 +366,113 @@ pub struct Component {
   pub keep_alive: bool,
   pub color: TerminalColor,
   pub env: HashMap<String, String>,
+  /// A dotenv style file, relative to the component's path, loaded below the explicit env.
+  pub env_file: Option<String>,
+  /// Environment variables set to the output of a command, written in env as
+  /// `{ from_command: ... }`. See Project::env_commands.
+  pub env_commands: HashMap<String, String>,
+  /// Environment variables whose values are produced by running a command at launch, such as
+  /// reading a credential from a password manager. The values are never written to disk.
+  pub secrets: HashMap<String, String>,
   pub tasks: HashMap<String, Vec<String>>,
+  /// Tasks whose commands differ on Windows, replacing the tasks of the same name there.
+  pub tasks_windows: HashMap<String, Vec<String>>,
+  /// Tasks whose commands differ on unix like systems, replacing the tasks of the same name
+  /// there.
+  pub tasks_unix: HashMap<String, Vec<String>>,
   pub repo: Option<String>,
-  pub delay: Option<u64>,
+  /// Initializes the repo's submodules, recursively, when it is cloned or updated.
+  pub submodules: bool,
+  /// An archive the component's path is unpacked from during setup, in place of a repo.
+  pub source: Option<Source>,
+  /// The checkout holds a conductor.yml of its own, which is merged into the project under the
+  /// component's name as with workspace `projects`. The component itself only stands for the
+  /// checkout, it is set up but never run.
+  pub includes_project: bool,
+  /// Seconds to wait before the component is first started. `delay` is accepted as an alias.
+  #[serde(alias = "delay")]
+  pub initial_delay: Option<u64>,
+  /// Conditions to wait for before the component is started, after its initial delay.
+  pub wait_for: Option<WaitFor>,
+  /// Seconds the component has to produce output or pass its readiness check once started. A
+  /// component that does neither is killed and treated as failed, so it is retried if retry is
+  /// enabled.
+  pub start_timeout: Option<u64>,
+  /// Seconds to wait before a retry enabled component is restarted after exiting.
+  pub restart_delay: Option<u64>,
+  /// Stops restarting a retry enabled component that keeps failing.
+  pub crash_loop: CrashLoop,
+  /// Restarts the running components that wait for this one with `wait_for` once it is ready
+  /// again after a restart, for those that do not reconnect on their own.
+  pub restart_dependents: bool,
   pub start: String,
+  /// The start command on Windows, in place of `start`.
+  pub start_windows: Option<String>,
+  /// The start command on unix like systems, in place of `start`.
+  pub start_unix: Option<String>,
+  /// Commands run to completion before the start command, each time the component is started.
+  /// The component is not started if one of them fails.
+  pub build: Vec<String>,
+  /// Commands that run the component's test suite, used by `conductor test`.
+  pub test: Vec<String>,
   pub init: Vec<String>,
+  /// The init commands on Windows, in place of `init`.
+  pub init_windows: Option<Vec<String>>,
+  /// The init commands on unix like systems, in place of `init`.
+  pub init_unix: Option<Vec<String>>,
+  /// Files in the component's path whose changes call for running the init commands again on
+  /// `setup --sync --reinit`. The manifests and lockfiles of common package managers are used
+  /// when none are given.
+  pub init_inputs: Vec<String>,
   pub tags: Vec<String>,
   pub retry: bool,
   pub default: bool,
+  /// Includes the component only when this condition holds.
+  pub only_if: Option<Condition>,
+  /// Leaves the component out when this condition holds.
+  pub unless: Option<Condition>,
+  /// Profiles that enable the component, such as `dev` or `ci`.
+  pub profiles: Vec<String>,
   pub services: Vec<String>,
+  /// Ports the component listens on. These are reported in the session state file.
+  pub ports: Vec<u16>,
+  /// Names of ports picked from the free ones when the component is first started in a session.
+  /// They are passed to it as `PORT_<NAME>`, the first one also as `PORT`, and follow the
+  /// declared ports.
+  pub auto_ports: Vec<String>,
+  /// Determines when the component is ready. Components without readiness checks are ready as
+  /// soon as they start.
+  pub ready: Option<Readiness>,
+  /// Checks run periodically once the component is ready. A component failing them repeatedly
+  /// is restarted.
+  pub health_check: Option<HealthCheck>,
+  /// Caps on the memory and CPU the component's processes may use.
+  pub limits: Option<Limits>,
+  /// The nice level the component runs at, from -20 to 19. Background components can be given
+  /// a higher level so they yield the CPU to others. `priority` is accepted as an alias.
+  #[serde(alias = "priority")]
+  pub nice: Option<i32>,
+  /// A file, relative to the project root, that the component's output is written to with
+  /// timestamps. Overrides the run's `--log-dir`.
+  pub log_file: Option<String>,
+  /// When the component's logs are rotated and how many rotated files are kept.
+  pub log_rotation: LogRotation,
+  /// Hides the component's output. Its lifecycle events and errors are still shown and it can
+  /// be unmuted while running.
+  pub quiet: bool,
+  /// Runs the start command under a pseudo-terminal so it colors its output and shows progress
+  /// as it would when run interactively. Overrides the project's `pty`.
+  pub pty: Option<bool>,
+  /// Bounds the output waiting to be shown, for components that write faster than it can be.
+  pub backpressure: Backpressure,
+  /// Whether escape sequences in the component's output are kept or removed.
+  pub ansi: Ansi,
+  /// Commands run once the component has started, alongside it.
+  pub on_start: Vec<String>,
+  /// Commands run after the component was stopped or exited successfully.
+  pub on_stop: Vec<String>,
+  /// Commands run after the component exited with an error.
+  pub on_crash: Vec<String>,
 }
 
 impl Default for Component {
@@ -44,18 +480,56 @@ impl Default for Component {
     Component {
       name: "Unknown".into(),
       default: true,
+      only_if: None,
+      unless: None,
+      profiles: vec![],
       path: None,
       env: HashMap::new(),
+      env_file: None,
+      env_commands: HashMap::new(),
+      secrets: HashMap::new(),
       tasks: HashMap::new(),
+      tasks_windows: HashMap::new(),
+      tasks_unix: HashMap::new(),
       repo: None,
-      color: TerminalColor::Yellow,
-      delay: None,
+      color: TerminalColor::Auto,
+      initial_delay: None,
+      wait_for: None,
+      restart_delay: None,
+      crash_loop: CrashLoop::default(),
+      restart_dependents: false,
+      start_timeout: None,
       start: "".into(),
+      start_windows: None,
+      start_unix: None,
+      build: vec![],
+      test: vec![],
       tags: vec![],
       init: vec![],
+      init_windows: None,
+      init_unix: None,
+      source: None,
+      includes_project: false,
+      submodules: false,
+      init_inputs: vec![],
       retry: false,
       keep_alive: false,
       services: vec![],
+      ports: vec![],
+      auto_ports: vec![],
+      ready: None,
+      health_check: None,
+      limits: None,
+      nice: None,
+      log_file: None,
+      log_rotation: LogRotation::default(),
+      quiet: false,
+      pty: None,
+      backpressure: Backpressure::default(),
+      ansi: Ansi::Passthrough,
+      on_start: vec![],
+      on_stop: vec![],
+      on_crash: vec![],
     }
   }
 }
@@ -68,6 +542,95 @@ impl Component {
     self.tags.iter().any(|a| tags.iter().any(|b| a == b))
   }
 
+  /// Returns true if one of the component's profiles is active. Components without profiles
+  /// are only enabled when they run by default.
+  pub fn in_profiles(&self, profiles: &[&str]) -> bool {
+    if self.profiles.is_empty() {
+      return self.default;
+    }
+    self
+      .profiles
+      .iter()
+      .any(|a| profiles.iter().any(|b| a == b))
+  }
+
+  /// The condition that leaves the component out, `only_if` or `unless`, none if its
+  /// conditions include it.
+  pub fn excluded_by(&self, root_path: &Path, env: &[(String, String)]) -> Option<&'static str> {
+    if let Some(only_if) = &self.only_if {
+      if !only_if.holds(root_path, env) {
+        return Some("only_if");
+      }
+    }
+    match &self.unless {
+      Some(unless) if unless.holds(root_path, env) => Some("unless"),
+      _ => None,
+    }
+  }
+
+  /// Returns the declared ports that something else is already listening on.
+  pub fn ports_in_use(&self) -> Vec<u16> {
+    self
+      .ports
+      .iter()
+      .filter(|port| {
+        let addr = SocketAddr::from(([0, 0, 0, 0], **port));
+        matches!(TcpListener::bind(addr), Err(e) if e.kind() == ErrorKind::AddrInUse)
+      })
+      .cloned()
+      .collect()
+  }
+
+  /// The start command for the platform conductor runs on.
+  pub fn start_command(&self) -> Result<&str, String> {
+    platform::select(
+      &self.name,
+      &self.start,
+      self.start_windows.as_ref(),
+      self.start_unix.as_ref(),
+    )
+    .map(String::as_str)
+  }
+
+  /// The init commands for the platform conductor runs on.
+  pub fn init_commands(&self) -> Result<&Vec<String>, String> {
+    platform::select(
+      &self.name,
+      &self.init,
+      self.init_windows.as_ref(),
+      self.init_unix.as_ref(),
+    )
+  }
+
+  /// The names of the component's tasks on any platform, sorted.
+  pub fn task_names(&self) -> Vec<&String> {
+    let mut names: Vec<&String> = self
+      .tasks
+      .keys()
+      .chain(self.tasks_windows.keys())
+      .chain(self.tasks_unix.keys())
+      .collect();
+    names.sort();
+    names.dedup();
+    names
+  }
+
+  /// The commands of a task for the platform conductor runs on, none if there is no such task.
+  pub fn task_commands(&self, task: &str) -> Option<Result<&Vec<String>, String>> {
+    static NONE: Vec<String> = Vec::new();
+    let windows = self.tasks_windows.get(task);
+    let unix = self.tasks_unix.get(task);
+    match self.tasks.get(task) {
+      None if windows.is_none() && unix.is_none() => None,
+      shared => Some(platform::select(
+        &format!("{}:{}", self.name, task),
+        shared.unwrap_or(&NONE),
+        windows,
+        unix,
+      )),
+    }
+  }
+
   pub fn get_path(&self) -> PathBuf {
     let path_str = self.path.clone().unwrap_or_else(|| self.name.clone());
     Path::new(&path_str).to_owned()
@@ -75,11 +638,42 @@ impl Component {
 
   pub fn clone_repo(&self, root_path: &Path) -> Result<(), std::io::Error> {
     match &self.repo {
-      Some(repo) => git::clone_repo(&repo, root_path).map(|_| ()),
+      Some(repo) => {
+        let span = Span::new(format!("clone {}", self.name))
+          .attribute("conductor.component", self.name.as_str())
+          .attribute("conductor.repo", repo.as_str());
+        let result = git::clone_repo(repo, root_path)
+          .map(|_| ())
+          .and_then(|_| self.update_submodules(root_path));
+        span.end(result.is_err());
+        result
+      }
       None => Err(std::io::Error::new(
         std::io::ErrorKind::NotFound,
         "Repo not specified",
       )),
     }
   }
+
+  /// Updates the checkout at root_path from its upstream. Returns true if anything changed.
+  pub fn sync_repo(&self, root_path: &Path) -> Result<bool, std::io::Error> {
+    let updated = git::sync_repo(root_path)?;
+    self.update_submodules(root_path)?;
+    Ok(updated)
+  }
+
+  /// Checks out a commit of the checkout at root_path. Returns true if anything changed.
+  pub fn checkout(&self, root_path: &Path, commit: &str) -> Result<bool, std::io::Error> {
+    let updated = git::checkout_commit(root_path, commit)?;
+    self.update_submodules(root_path)?;
+    Ok(updated)
+  }
+
+  /// Brings the submodules up to date when enabled, including those added since the clone.
+  fn update_submodules(&self, root_path: &Path) -> Result<(), std::io::Error> {
+    match self.submodules {
+      true => git::update_submodules(root_path),
+      false => Ok(()),
+    }
+  }
 }
@@ -0,0 +1,129 @@
+use std::io::{self, BufRead, Write};
+
+/// Reads lines typed at a prompt, with the lines typed earlier available through the up and down
+/// keys and the word being typed completed with tab.
+#[derive(Default)]
+pub struct LineEditor {
+  history: Vec<String>,
+}
+
+impl LineEditor {
+  /// Reads a line, none once stdin is closed or ctrl-d is pressed on an empty line. `complete`
+  /// is given the line up to the word being completed and returns the words it could be.
+  #[cfg(unix)]
+  pub fn read<F>(&mut self, prompt: &str, complete: F) -> Option<String>
+  where
+    F: Fn(&str) -> Vec<String>,
+  {
+    use termion::event::Key;
+    use termion::input::TermRead;
+    use termion::raw::IntoRawMode;
+
+    let mut stdout = match io::stdout().into_raw_mode() {
+      Ok(stdout) => stdout,
+      // Without a terminal the line is read as it is.
+      Err(_) => return self.read_plain(prompt),
+    };
+    let mut line = String::new();
+    let mut browsing = self.history.len();
+    let redraw = |stdout: &mut dyn Write, line: &str| {
+      let _ = write!(stdout, "\r\x1b[K{}{}", prompt, line);
+      let _ = stdout.flush();
+    };
+    redraw(&mut stdout, &line);
+    for key in io::stdin().keys() {
+      match key.ok()? {
+        Key::Char('\n') | Key::Char('\r') => {
+          let _ = write!(stdout, "\r\n");
+          let _ = stdout.flush();
+          if !line.trim().is_empty() && self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+          }
+          return Some(line);
+        }
+        Key::Char('\t') => {
+          let start = line.rfind(' ').map_or(0, |i| i + 1);
+          let candidates: Vec<String> = complete(&line[..start])
+            .into_iter()
+            .filter(|c| c.starts_with(&line[start..]))
+            .collect();
+          match candidates.as_slice() {
+            [] => {}
+            [only] => {
+              line.truncate(start);
+              line.push_str(only);
+              line.push(' ');
+            }
+            _ => {
+              let common = common_prefix(&candidates);
+              if common.len() > line.len() - start {
+                line.truncate(start);
+                line.push_str(&common);
+              } else {
+                let _ = write!(stdout, "\r\n{}\r\n", candidates.join("  "));
+              }
+            }
+          }
+        }
+        Key::Char(c) => line.push(c),
+        Key::Backspace => {
+          line.pop();
+        }
+        Key::Up if browsing > 0 => {
+          browsing -= 1;
+          line = self.history[browsing].clone();
+        }
+        Key::Down if browsing < self.history.len() => {
+          browsing += 1;
+          line = self.history.get(browsing).cloned().unwrap_or_default();
+        }
+        Key::Ctrl('c') => line.clear(),
+        Key::Ctrl('d') if line.is_empty() => {
+          let _ = write!(stdout, "\r\n");
+          return None;
+        }
+        _ => {}
+      }
+      redraw(&mut stdout, &line);
+    }
+    None
+  }
+
+  #[cfg(not(unix))]
+  pub fn read<F>(&mut self, prompt: &str, _complete: F) -> Option<String>
+  where
+    F: Fn(&str) -> Vec<String>,
+  {
+    self.read_plain(prompt)
+  }
+
+  fn read_plain(&mut self, prompt: &str) -> Option<String> {
+    print!("{}", prompt);
+    let _ = io::stdout().flush();
+    let mut line = String::new();
+    match io::stdin().lock().read_line(&mut line) {
+      Ok(0) | Err(_) => None,
+      Ok(_) => Some(line.trim_end_matches(&['\r', '\n'][..]).to_string()),
+    }
+  }
+}
+
+/// The longest prefix the words share.
+fn common_prefix(words: &[String]) -> String {
+  let first = match words.first() {
+    Some(first) => first,
+    None => return String::new(),
+  };
+  let mut len = first.len();
+  for word in words.iter().skip(1) {
+    len = first
+      .char_indices()
+      .zip(word.chars())
+      .take_while(|((_, a), b)| a == b)
+      .map(|((i, a), _)| i + a.len_utf8())
+      .last()
+      .unwrap_or(0)
+      .min(len);
+  }
+  first[..len].to_string()
+}
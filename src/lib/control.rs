@@ -0,0 +1,255 @@
+use crate::scrollback::Scrollback;
+use crate::Command;
+use crossbeam::channel::{bounded, Receiver, Sender};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// A command for the running session along with where to send its result.
+pub struct Request {
+  pub command: Command,
+  pub reply: Sender<Result<(), String>>,
+}
+
+impl Request {
+  /// Creates a request and the receiver its result is sent to.
+  pub fn new(command: Command) -> (Self, Receiver<Result<(), String>>) {
+    let (reply, result) = bounded(1);
+    (Request { command, reply }, result)
+  }
+}
+
+/// The control socket of the running session, a named pipe on Windows. The socket is removed
+/// when it is dropped.
+pub struct Socket {
+  #[cfg_attr(not(unix), allow(dead_code))]
+  path: PathBuf,
+}
+
+#[cfg(unix)]
+impl Drop for Socket {
+  fn drop(&mut self) {
+    let _ = fs::remove_file(&self.path);
+  }
+}
+
+/// The location of the control socket for a project rooted at the given path.
+pub fn socket_path(root_path: &Path) -> PathBuf {
+  root_path.join(".conductor").join("control.sock")
+}
+
+/// The name of the control pipe for a project rooted at the given path. Named pipes live in a
+/// namespace of their own, so the name is derived from the project root to tell sessions of
+/// different projects apart.
+#[cfg(windows)]
+fn pipe_name(root_path: &Path) -> PathBuf {
+  use std::hash::{Hash, Hasher};
+  let mut hasher = std::collections::hash_map::DefaultHasher::new();
+  fs::canonicalize(root_path)
+    .unwrap_or_else(|_| root_path.to_path_buf())
+    .hash(&mut hasher);
+  PathBuf::from(format!(r"\\.\pipe\conductor-{:016x}", hasher.finish()))
+}
+
+/// Accepts commands from other conductor invocations on the control socket and forwards them to
+/// the session. Each connection sends a single command as a line of JSON and gets the result
+/// back the same way, and is served on a thread of its own so a slow client holds up no one else.
+/// Tails are answered from the scrollback without going through the session.
+/// Fails if another session is already listening on the socket.
+#[cfg(unix)]
+pub fn listen(
+  root_path: &Path,
+  requests: Sender<Request>,
+  scrollback: Arc<Scrollback>,
+) -> io::Result<Socket> {
+  let path = socket_path(root_path);
+  if let Some(dir) = path.parent() {
+    fs::create_dir_all(dir)?;
+  }
+  if UnixStream::connect(&path).is_ok() {
+    return Err(io::Error::new(
+      io::ErrorKind::AddrInUse,
+      format!("Another session is listening on {}", path.display()),
+    ));
+  }
+  // Nothing answers on a socket left behind by a crashed session, it would prevent binding.
+  let _ = fs::remove_file(&path);
+  let listener = UnixListener::bind(&path)?;
+  std::thread::spawn(move || {
+    for stream in listener.incoming().filter_map(Result::ok) {
+      let requests = requests.clone();
+      let scrollback = scrollback.clone();
+      std::thread::spawn(move || {
+        if let Err(e) = serve(stream, &requests, &scrollback) {
+          log::warn!("Could not serve control request: {}", e);
+        }
+      });
+    }
+  });
+  Ok(Socket { path })
+}
+
+/// Accepts commands on a named pipe, see the Unix version. A new instance of the pipe is created
+/// for every client, the first one fails if another session already created the pipe.
+#[cfg(windows)]
+pub fn listen(
+  root_path: &Path,
+  requests: Sender<Request>,
+  scrollback: Arc<Scrollback>,
+) -> io::Result<Socket> {
+  let path = pipe_name(root_path);
+  let first = pipe_instance(&path, true).map_err(|e| match e.kind() {
+    io::ErrorKind::PermissionDenied => io::Error::new(
+      io::ErrorKind::AddrInUse,
+      format!("Another session is listening on {}", path.display()),
+    ),
+    _ => e,
+  })?;
+  let name = path.clone();
+  std::thread::spawn(move || {
+    let mut pipe = Some(first);
+    loop {
+      let instance = match pipe.take().map_or_else(|| pipe_instance(&name, false), Ok) {
+        Ok(instance) => instance,
+        Err(e) => {
+          log::warn!("Could not create control pipe: {}", e);
+          return;
+        }
+      };
+      let stream = match connect(instance) {
+        Ok(stream) => stream,
+        Err(e) => {
+          log::warn!("Could not accept control request: {}", e);
+          continue;
+        }
+      };
+      let requests = requests.clone();
+      let scrollback = scrollback.clone();
+      std::thread::spawn(move || {
+        if let Err(e) = serve(stream, &requests, &scrollback) {
+          log::warn!("Could not serve control request: {}", e);
+        }
+      });
+    }
+  });
+  Ok(Socket { path })
+}
+
+#[cfg(windows)]
+fn pipe_instance(name: &Path, first: bool) -> io::Result<fs::File> {
+  use std::os::windows::ffi::OsStrExt;
+  use std::os::windows::io::FromRawHandle;
+  use windows_sys::Win32::Foundation::INVALID_HANDLE_VALUE;
+  use windows_sys::Win32::Storage::FileSystem::{
+    FILE_FLAG_FIRST_PIPE_INSTANCE, PIPE_ACCESS_DUPLEX,
+  };
+  use windows_sys::Win32::System::Pipes::{
+    CreateNamedPipeW, PIPE_READMODE_BYTE, PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+  };
+  let wide: Vec<u16> = name.as_os_str().encode_wide().chain(Some(0)).collect();
+  let mode = match first {
+    true => PIPE_ACCESS_DUPLEX | FILE_FLAG_FIRST_PIPE_INSTANCE,
+    false => PIPE_ACCESS_DUPLEX,
+  };
+  // SAFETY: the name is a nul terminated wide string that outlives the call.
+  let handle = unsafe {
+    CreateNamedPipeW(
+      wide.as_ptr(),
+      mode,
+      PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT,
+      PIPE_UNLIMITED_INSTANCES,
+      4096,
+      4096,
+      0,
+      std::ptr::null(),
+    )
+  };
+  if handle == INVALID_HANDLE_VALUE {
+    return Err(io::Error::last_os_error());
+  }
+  // SAFETY: the handle was just created and is owned by the file from here on.
+  Ok(unsafe { fs::File::from_raw_handle(handle as _) })
+}
+
+/// Waits for a client to open an instance of the pipe.
+#[cfg(windows)]
+fn connect(pipe: fs::File) -> io::Result<fs::File> {
+  use std::os::windows::io::AsRawHandle;
+  use windows_sys::Win32::Foundation::ERROR_PIPE_CONNECTED;
+  use windows_sys::Win32::System::Pipes::ConnectNamedPipe;
+  // SAFETY: the handle belongs to the pipe, which is alive for the call.
+  if unsafe { ConnectNamedPipe(pipe.as_raw_handle() as _, std::ptr::null_mut()) } == 0 {
+    let e = io::Error::last_os_error();
+    // A client that opened the pipe before it was waited on is connected all the same.
+    if e.raw_os_error() != Some(ERROR_PIPE_CONNECTED as i32) {
+      return Err(e);
+    }
+  }
+  Ok(pipe)
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn listen(
+  _root_path: &Path,
+  _requests: Sender<Request>,
+  _scrollback: Arc<Scrollback>,
+) -> io::Result<Socket> {
+  Err(io::Error::other(
+    "The control socket is not supported on this platform",
+  ))
+}
+
+#[cfg(any(unix, windows))]
+fn serve<S: Read + Write>(
+  mut stream: S,
+  requests: &Sender<Request>,
+  scrollback: &Scrollback,
+) -> io::Result<()> {
+  let mut line = String::new();
+  BufReader::new(&mut stream).read_line(&mut line)?;
+  let result = match serde_json::from_str(&line) {
+    Ok(Command::Tail(name, count)) => scrollback
+      .tail(&name, count)
+      .ok_or_else(|| format!("Component {} has no output", name)),
+    Ok(command) => {
+      let (request, result) = Request::new(command);
+      requests
+        .send(request)
+        .ok()
+        .and_then(|_| result.recv().ok())
+        .unwrap_or_else(|| Err("The session is shutting down".into()))
+        .map(|()| vec![])
+    }
+    Err(e) => Err(format!("Invalid command: {}", e)),
+  };
+  writeln!(stream, "{}", serde_json::to_string(&result)?)
+}
+
+/// Sends a command to the session running for a project and returns its result, which holds the
+/// lines of output for a tail and nothing for the other commands.
+#[cfg(any(unix, windows))]
+pub fn send(root_path: &Path, command: &Command) -> io::Result<Result<Vec<String>, String>> {
+  #[cfg(unix)]
+  let stream = UnixStream::connect(socket_path(root_path));
+  #[cfg(windows)]
+  let stream = fs::OpenOptions::new()
+    .read(true)
+    .write(true)
+    .open(pipe_name(root_path));
+  let mut stream =
+    stream.map_err(|e| io::Error::new(e.kind(), format!("No session is running: {}", e)))?;
+  writeln!(stream, "{}", serde_json::to_string(command)?)?;
+  let mut line = String::new();
+  BufReader::new(stream).read_line(&mut line)?;
+  serde_json::from_str(&line).map_err(io::Error::other)
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn send(_root_path: &Path, _command: &Command) -> io::Result<Result<Vec<String>, String>> {
+  Err(io::Error::other(
+    "The control socket is not supported on this platform",
+  ))
+}
@@ -0,0 +1,111 @@
+use crate::logs;
+use crate::process;
+use crate::state::SessionState;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Set for a session started in the background, which runs in its own process rather than
+/// detaching again.
+const DETACHED_VAR: &str = "CONDUCTOR_DETACHED";
+
+/// Where the output of a session running in the background is written.
+pub fn log_path(root_path: &Path) -> PathBuf {
+  root_path.join(".conductor").join("daemon.log")
+}
+
+/// Where the pid of the session running in the background is kept.
+fn pid_path(root_path: &Path) -> PathBuf {
+  root_path.join(".conductor").join("daemon.pid")
+}
+
+/// The pid of the session running for a project, if there is one. A session just started in the
+/// background counts before it has written its state.
+pub fn live_session(root_path: &Path) -> Option<u32> {
+  SessionState::read(root_path)
+    .ok()
+    .map(|state| state.pid)
+    .filter(|pid| process::is_alive(*pid))
+    .or_else(|| background_session(root_path))
+}
+
+/// The pid of the session running in the background, if there is one.
+fn background_session(root_path: &Path) -> Option<u32> {
+  fs::read_to_string(pid_path(root_path))
+    .ok()
+    .and_then(|pid| pid.trim().parse().ok())
+    .filter(|pid| process::is_alive(*pid))
+}
+
+/// Returns true if this process is a session started in the background by `detach`. The mark is
+/// cleared so the processes the session starts do not inherit it.
+pub fn take_detached() -> bool {
+  let detached = std::env::var_os(DETACHED_VAR).is_some();
+  std::env::remove_var(DETACHED_VAR);
+  detached
+}
+
+/// Removes the pid file of the session running in the background once it has ended.
+pub fn finish_detached(root_path: &Path) {
+  if background_session(root_path) == Some(std::process::id()) {
+    let _ = fs::remove_file(pid_path(root_path));
+  }
+}
+
+/// Starts conductor again in the background with the same arguments and returns its pid. The
+/// new process is marked so it runs the session instead of detaching again. It leads a new
+/// session of its own, or is detached from the console on Windows, so closing the terminal does
+/// not stop it, and its output is written to the daemon log.
+pub fn detach(root_path: &Path) -> io::Result<u32> {
+  let path = log_path(root_path);
+  if let Some(dir) = path.parent() {
+    fs::create_dir_all(dir)?;
+  }
+  let log = File::create(&path)?;
+  let mut command = Command::new(std::env::current_exe()?);
+  command
+    .args(std::env::args_os().skip(1))
+    .env(DETACHED_VAR, "1")
+    .stdin(Stdio::null())
+    .stdout(log.try_clone()?)
+    .stderr(log);
+  #[cfg(unix)]
+  {
+    use std::os::unix::process::CommandExt;
+    unsafe {
+      command.pre_exec(|| {
+        if libc::setsid() < 0 {
+          return Err(io::Error::last_os_error());
+        }
+        Ok(())
+      });
+    }
+  }
+  #[cfg(windows)]
+  {
+    use std::os::windows::process::CommandExt;
+    const DETACHED_PROCESS: u32 = 0x0000_0008;
+    const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+    command.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+  }
+  let pid = command.spawn()?.id();
+  fs::write(pid_path(root_path), format!("{}\n", pid))?;
+  Ok(pid)
+}
+
+/// Prints the output of the session running in the background as it is written, starting with
+/// what it has written so far, until the session ends or the process is interrupted.
+pub fn follow(root_path: &Path) -> io::Result<()> {
+  let pid = background_session(root_path)
+    .ok_or_else(|| io::Error::other("No session is running in the background"))?;
+  let path = log_path(root_path);
+  let contents = fs::read(&path)?;
+  print!("{}", String::from_utf8_lossy(&contents));
+  logs::tail(
+    &path,
+    contents.len() as u64,
+    |line| println!("{}", line),
+    || !process::is_alive(pid),
+  )
+}
@@ -0,0 +1,410 @@
+use crate::control::Request;
+use crate::scrollback::{Buffer, Retention, Weight};
+use crate::state::{ComponentState, ComponentStatus, SessionState};
+use crate::ui::{self, Line, Source};
+use crate::{Command, Project, TerminalColor};
+use crossbeam::channel::{never, tick, unbounded, Receiver, Sender};
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::path::PathBuf;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use termion::event::Key;
+use termion::input::TermRead;
+use termion::raw::IntoRawMode;
+use termion::screen::AlternateScreen;
+use tui::backend::{Backend, TermionBackend};
+use tui::layout::{Constraint, Direction, Layout};
+use tui::style::{Color, Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use tui::{Frame, Terminal};
+
+/// The key bindings shown at the bottom of the screen.
+const HINTS: &str =
+  "↑↓ select  PgUp/PgDn scroll  r restart  s stop  a start  p pause  : command  q quit";
+
+/// A full screen view of the session. Components are listed in a sidebar along with their status
+/// and the output of the selected component is shown in a scrollable pane. All output from the
+/// ui module is captured while the dashboard is shown.
+pub struct Dashboard {
+  stop: Sender<()>,
+  thread: Option<JoinHandle<()>>,
+}
+
+impl Dashboard {
+  /// Takes over the terminal and starts drawing. Keys are turned into commands for the session,
+  /// quitting calls `quit`. The terminal is restored when the dashboard is dropped.
+  pub fn start<F>(project: &Project, commands: Sender<Request>, quit: F) -> io::Result<Dashboard>
+  where
+    F: Fn() + Send + 'static,
+  {
+    let screen = AlternateScreen::from(io::stdout().into_raw_mode()?);
+    let mut terminal = Terminal::new(TermionBackend::new(screen))?;
+    terminal.hide_cursor()?;
+    let (line_sender, lines) = unbounded();
+    ui::capture(Some(line_sender));
+    let (stop, stopped) = unbounded();
+    let mut view = View::new(project);
+    let thread = thread::spawn(move || {
+      let mut keys = read_keys();
+      let refresh = tick(Duration::from_millis(500));
+      view.refresh();
+      loop {
+        let _ = terminal.draw(|f| view.draw(f));
+        crossbeam::select! {
+          recv(stopped) -> _ => break,
+          recv(lines) -> line => {
+            if let Ok(line) = line {
+              view.push(line);
+            }
+            while let Ok(line) = lines.try_recv() {
+              view.push(line);
+            }
+          }
+          recv(keys) -> key => match key {
+            Ok(key) => {
+              if view.key(key, &commands) {
+                quit();
+              }
+            }
+            Err(_) => keys = never(),
+          },
+          recv(refresh) -> _ => view.refresh(),
+        }
+      }
+      ui::capture(None);
+      let _ = terminal.show_cursor();
+    });
+    Ok(Dashboard {
+      stop,
+      thread: Some(thread),
+    })
+  }
+}
+
+impl Drop for Dashboard {
+  fn drop(&mut self) {
+    let _ = self.stop.send(());
+    if let Some(thread) = self.thread.take() {
+      let _ = thread.join();
+    }
+  }
+}
+
+/// Reads keys from the terminal on a separate thread.
+fn read_keys() -> Receiver<Key> {
+  let (sender, keys) = unbounded();
+  thread::spawn(move || {
+    for key in io::stdin().keys().map_while(Result::ok) {
+      if sender.send(key).is_err() {
+        break;
+      }
+    }
+  });
+  keys
+}
+
+/// A line shown in a pane, with the name of its source when shown alongside other sources.
+struct Entry {
+  prefix: Option<(String, Color)>,
+  text: String,
+  style: Style,
+}
+
+impl Entry {
+  fn spans(&self) -> Spans<'_> {
+    let mut spans = vec![];
+    if let Some((name, color)) = &self.prefix {
+      spans.push(Span::styled(
+        format!("[{}] ", name),
+        Style::default().fg(*color).add_modifier(Modifier::BOLD),
+      ));
+    }
+    spans.push(Span::styled(self.text.as_str(), self.style));
+    Spans::from(spans)
+  }
+}
+
+impl Weight for Entry {
+  fn weight(&self) -> usize {
+    self.text.len() + self.prefix.as_ref().map_or(0, |(name, _)| name.len())
+  }
+}
+
+struct View {
+  root_path: PathBuf,
+  colors: HashMap<String, Color>,
+  components: Vec<String>,
+  states: Vec<ComponentState>,
+  /// How much of the output each pane keeps.
+  retention: Retention,
+  all: Buffer<Entry>,
+  panes: HashMap<String, Buffer<Entry>>,
+  /// The selected sidebar entry, the first entry shows the output of everything.
+  selected: usize,
+  /// The number of lines scrolled up from the bottom of the pane.
+  scroll: usize,
+  status: Option<(String, bool)>,
+  /// The command being typed, if any.
+  input: Option<String>,
+}
+
+impl View {
+  fn new(project: &Project) -> Self {
+    let mut colors: HashMap<String, Color> = project
+      .components
+      .iter()
+      .map(|c| (c.name.clone(), color(&c.color)))
+      .collect();
+    colors.extend(
+      project
+        .services
+        .iter()
+        .map(|s| (s.name.clone(), color(&s.color))),
+    );
+    View {
+      root_path: project.root_path.clone(),
+      colors,
+      components: project.components.iter().map(|c| c.name.clone()).collect(),
+      states: vec![],
+      retention: project.tuning.scrollback.clone(),
+      all: Buffer::new(project.tuning.scrollback.clone()),
+      panes: HashMap::new(),
+      selected: 0,
+      scroll: 0,
+      status: None,
+      input: None,
+    }
+  }
+
+  /// Reloads the component states from the session's state file.
+  fn refresh(&mut self) {
+    if let Ok(state) = SessionState::read(&self.root_path) {
+      self.states = state.components;
+    }
+  }
+
+  fn push(&mut self, line: Line) {
+    let prefix = match &line.source {
+      Source::System => {
+        // System messages usually follow a change of state.
+        self.status = Some((line.text.clone(), line.error));
+        self.refresh();
+        None
+      }
+      Source::Component(name) => {
+        let retention = &self.retention;
+        self
+          .panes
+          .entry(name.clone())
+          .or_insert_with(|| Buffer::new(retention.clone()))
+          .push(Entry {
+            prefix: None,
+            text: line.text.clone(),
+            style: match line.error {
+              true => status_style(true),
+              false => Style::default(),
+            },
+          });
+        Some((name.clone(), self.color(name)))
+      }
+      Source::Service(name) => Some((format!("({})", name), self.color(name))),
+      Source::Task(name) => Some((name.clone(), Color::Magenta)),
+    };
+    let style = match (&line.source, line.error) {
+      (_, true) => status_style(true),
+      (Source::System, false) => status_style(false),
+      _ => Style::default(),
+    };
+    self.all.push(Entry {
+      prefix,
+      text: line.text,
+      style,
+    });
+  }
+
+  fn color(&self, name: &str) -> Color {
+    self.colors.get(name).cloned().unwrap_or(Color::White)
+  }
+
+  /// Handles a key press. Returns true if the dashboard should quit.
+  fn key(&mut self, key: Key, commands: &Sender<Request>) -> bool {
+    if let Some(input) = self.input.as_mut() {
+      match key {
+        Key::Char('\n') => {
+          let line = input.clone();
+          self.input = None;
+          match line.parse() {
+            Ok(command) => self.send(command, commands),
+            Err(e) => self.status = Some((e, true)),
+          }
+        }
+        Key::Esc => self.input = None,
+        Key::Backspace => {
+          input.pop();
+        }
+        Key::Char(c) => input.push(c),
+        _ => {}
+      }
+      return false;
+    }
+    let selected = self.selected_component();
+    match key {
+      Key::Char('q') | Key::Ctrl('c') => return true,
+      Key::Up | Key::Char('k') => {
+        self.selected = self.selected.saturating_sub(1);
+        self.scroll = 0;
+      }
+      Key::Down | Key::Char('j') => {
+        self.selected = (self.selected + 1).min(self.components.len());
+        self.scroll = 0;
+      }
+      Key::PageUp => self.scroll += 10,
+      Key::PageDown => self.scroll = self.scroll.saturating_sub(10),
+      Key::End => self.scroll = 0,
+      Key::Char(':') => self.input = Some(String::new()),
+      Key::Char('r') => {
+        if let Some(name) = selected {
+          self.send(Command::Restart(name), commands)
+        }
+      }
+      Key::Char('s') => {
+        if let Some(name) = selected {
+          self.send(Command::Stop(name), commands)
+        }
+      }
+      Key::Char('a') => {
+        if let Some(name) = selected {
+          self.send(Command::Start(name), commands)
+        }
+      }
+      Key::Char('p') => {
+        if let Some(name) = selected {
+          let paused = self.states.iter().any(|s| s.name == name && s.paused);
+          match paused {
+            true => self.send(Command::Resume(name), commands),
+            false => self.send(Command::Pause(name), commands),
+          }
+        }
+      }
+      _ => {}
+    }
+    false
+  }
+
+  fn selected_component(&self) -> Option<String> {
+    self
+      .selected
+      .checked_sub(1)
+      .and_then(|i| self.components.get(i))
+      .cloned()
+  }
+
+  /// Sends a command to the session, failures are shown in the status line.
+  fn send(&mut self, command: Command, commands: &Sender<Request>) {
+    let (request, result) = Request::new(command);
+    if commands.send(request).is_err() {
+      return;
+    }
+    if let Ok(Err(e)) = result.recv_timeout(Duration::from_secs(5)) {
+      self.status = Some((e, true));
+    }
+  }
+
+  fn draw<B: Backend>(&mut self, f: &mut Frame<B>) {
+    let rows = Layout::default()
+      .direction(Direction::Vertical)
+      .constraints([Constraint::Min(1), Constraint::Length(1)])
+      .split(f.size());
+    let columns = Layout::default()
+      .direction(Direction::Horizontal)
+      .constraints([Constraint::Length(24), Constraint::Min(1)])
+      .split(rows[0]);
+
+    let mut items = vec![ListItem::new("all")];
+    for name in self.components.iter() {
+      let state = self.states.iter().find(|s| &s.name == name);
+      let indicator = match state.map(|s| (&s.status, s.ready)) {
+        _ if state.is_some_and(|s| s.paused) => Color::Blue,
+        Some((ComponentStatus::Running, true)) => Color::Green,
+        Some((ComponentStatus::Running, false)) | Some((ComponentStatus::Starting, _)) => {
+          Color::Yellow
+        }
+        Some((ComponentStatus::Failed, _)) => Color::Red,
+        _ => Color::DarkGray,
+      };
+      let mut spans = vec![
+        Span::styled("● ", Style::default().fg(indicator)),
+        Span::raw(name.as_str()),
+      ];
+      if let Some(usage) = state.and_then(|s| s.usage) {
+        spans.push(Span::styled(
+          format!(" {:.0}% {}M", usage.cpu, usage.memory_kb / 1024),
+          Style::default().fg(Color::DarkGray),
+        ));
+      }
+      items.push(ListItem::new(Spans::from(spans)));
+    }
+    let list = List::new(items)
+      .block(Block::default().borders(Borders::ALL).title("Components"))
+      .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut list_state = ListState::default();
+    list_state.select(Some(self.selected));
+    f.render_stateful_widget(list, columns[0], &mut list_state);
+
+    let selected = self.selected_component();
+    let empty = VecDeque::new();
+    let lines = match &selected {
+      Some(name) => self.panes.get(name).map_or(&empty, |pane| pane.entries()),
+      None => self.all.entries(),
+    };
+    let height = columns[1].height.saturating_sub(2) as usize;
+    self.scroll = self.scroll.min(lines.len().saturating_sub(height));
+    let end = lines.len() - self.scroll;
+    let start = end.saturating_sub(height);
+    let text: Vec<Spans> = lines.range(start..end).map(|e| e.spans()).collect();
+    let mut title = selected.unwrap_or_else(|| "all".into());
+    if self.scroll > 0 {
+      title.push_str(&format!(" (scrolled up {} lines)", self.scroll));
+    }
+    f.render_widget(
+      Paragraph::new(text).block(Block::default().borders(Borders::ALL).title(title)),
+      columns[1],
+    );
+
+    let footer = match (&self.input, &self.status) {
+      (Some(input), _) => Spans::from(format!(":{}", input)),
+      (None, Some((status, error))) => Spans::from(vec![
+        Span::styled(status.as_str(), status_style(*error)),
+        Span::styled(format!("  {}", HINTS), Style::default().fg(Color::DarkGray)),
+      ]),
+      (None, None) => Spans::from(Span::styled(HINTS, Style::default().fg(Color::DarkGray))),
+    };
+    f.render_widget(Paragraph::new(footer), rows[1]);
+  }
+}
+
+/// System messages are highlighted, errors in red.
+fn status_style(error: bool) -> Style {
+  let style = Style::default().add_modifier(Modifier::BOLD);
+  if error {
+    style.fg(Color::Red)
+  } else {
+    style
+  }
+}
+
+fn color(color: &TerminalColor) -> Color {
+  match color {
+    TerminalColor::White => Color::White,
+    TerminalColor::Blue => Color::Blue,
+    TerminalColor::Red => Color::Red,
+    TerminalColor::Green => Color::Green,
+    TerminalColor::Purple => Color::Magenta,
+    TerminalColor::Yellow => Color::Yellow,
+    TerminalColor::Cyan => Color::Cyan,
+    TerminalColor::Indexed(n) => Color::Indexed(*n),
+    TerminalColor::Auto => Color::Yellow,
+  }
+}
@@ -0,0 +1,176 @@
+use crate::service::{all_containers, ServiceType};
+use crate::supervisor::expand_env;
+use crate::{git, plugin, process, Project};
+
+/// The outcome of checking one thing the project needs from the machine it runs on.
+pub struct Check {
+  /// What was checked, such as `Path of api`.
+  pub name: String,
+  /// Why the check failed, none if it passed.
+  pub problem: Option<String>,
+  /// What to do about a failed check.
+  pub hint: String,
+}
+
+impl Check {
+  fn new(name: String, result: Result<(), String>, hint: &str) -> Check {
+    Check {
+      name,
+      problem: result.err(),
+      hint: hint.to_string(),
+    }
+  }
+}
+
+/// Words that start shell commands without naming an executable.
+const SHELL_WORDS: &[&str] = &[
+  "case", "cd", "echo", "eval", "exit", "export", "for", "if", "printf", "read", "set", "source",
+  "test", "trap", "until", "while", "[", ".", "{", "(",
+];
+
+/// The executable a shell command starts, if it names one that is looked up on the PATH.
+/// Variable assignments in front of it are skipped, and commands starting with a path or a
+/// shell keyword are left alone.
+fn program(command: &str) -> Option<&str> {
+  let assignment = |w: &&str| match w.split_once('=') {
+    Some((name, _)) => !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_'),
+    None => false,
+  };
+  let mut words = command.split_whitespace().filter(|w| !assignment(w));
+  let mut word = words.next()?;
+  if matches!(word, "env" | "exec" | "nohup" | "sudo") {
+    word = words.next()?;
+  }
+  match word.contains('/') || word.contains('$') || SHELL_WORDS.contains(&word) {
+    true => None,
+    false => Some(word),
+  }
+}
+
+/// Checks the environment a project runs in: the docker daemon and the containers of services,
+/// access to the repositories of components that still need to be cloned, component paths,
+/// ports and the programs components and services start.
+pub fn doctor(project: &Project) -> Vec<Check> {
+  let mut checks = vec![];
+
+  // Containers of services with an image are created when they are missing.
+  let containers: Vec<(&str, bool)> = project
+    .services
+    .iter()
+    .filter(|s| s.service_type == ServiceType::DockerContainer)
+    .map(|s| (s.container.as_deref().unwrap_or(&s.name), s.image.is_some()))
+    .collect();
+  if !containers.is_empty() {
+    let existing = all_containers().map_err(|e| e.to_string());
+    checks.push(Check::new(
+      "Docker daemon".into(),
+      existing.as_ref().map(|_| ()).map_err(Clone::clone),
+      "Start docker, or set docker_socket in the user config if it listens elsewhere",
+    ));
+    if let Ok(existing) = existing {
+      for (container, created) in containers {
+        let found = existing.iter().any(|c| {
+          c.Names
+            .iter()
+            .any(|n| n.trim_start_matches('/') == container)
+        });
+        checks.push(Check::new(
+          format!("Container {}", container),
+          match found || created {
+            true => Ok(()),
+            false => Err("No container with this name exists".into()),
+          },
+          "Create it with docker create or docker run --name, or give the service an image",
+        ));
+      }
+    }
+  }
+
+  for service in project.services.iter() {
+    if let ServiceType::Plugin(service_type) = &service.service_type {
+      let name = plugin::executable(service_type);
+      checks.push(Check::new(
+        format!("Plugin {}", name),
+        match plugin::is_installed(service_type) {
+          true => Ok(()),
+          false => Err("Not found on the PATH".into()),
+        },
+        "Install the plugin or add its directory to the PATH",
+      ));
+    }
+  }
+
+  for component in project.components.iter() {
+    let path = project
+      .root_path
+      .join(expand_env(&component.get_path().to_string_lossy()));
+    let cloned = path.is_dir();
+    match &component.repo {
+      // Cloned repositories are not touched again, so only the others need credentials.
+      Some(repo) if !cloned => {
+        checks.push(Check::new(
+          format!("Repository of {}", component.name),
+          git::check_access(repo).map_err(|e| e.to_string()),
+          "Check that git clone works for the repository, or set GIT_USER and GIT_PAT",
+        ));
+        checks.push(Check::new(
+          format!("Path of {}", component.name),
+          Err(format!("{} has not been cloned", path.display())),
+          "Run conductor setup to clone it",
+        ));
+      }
+      _ => checks.push(Check::new(
+        format!("Path of {}", component.name),
+        match cloned {
+          true => Ok(()),
+          false => Err(format!("{} does not exist", path.display())),
+        },
+        match component.source {
+          Some(_) => "Run conductor setup to download it",
+          None => "Create the directory or fix the component's path",
+        },
+      )),
+    }
+    for port in component.ports.iter() {
+      checks.push(Check::new(
+        format!("Port {} of {}", port, component.name),
+        match component.ports_in_use().contains(port) {
+          false => Ok(()),
+          true => Err(match process::listening_on(*port) {
+            Some(pid) => format!("In use by pid {}", pid),
+            None => "In use by another process".into(),
+          }),
+        },
+        "Stop whatever is listening on it, conductor audit finds leftovers of earlier sessions",
+      ));
+    }
+  }
+
+  let commands = project
+    .components
+    .iter()
+    .flat_map(|c| {
+      c.build
+        .iter()
+        .map(String::as_str)
+        .chain(c.start_command().ok())
+    })
+    .chain(project.services.iter().filter_map(|s| s.command.as_deref()));
+  let mut programs: Vec<&str> = vec![];
+  for program in commands.filter_map(|command| program(command)) {
+    if !programs.contains(&program) {
+      programs.push(program);
+    }
+  }
+  for program in programs {
+    checks.push(Check::new(
+      format!("Program {}", program),
+      match process::on_path(program) {
+        true => Ok(()),
+        false => Err("Not found on the PATH".into()),
+      },
+      "Install it or add its directory to the PATH",
+    ));
+  }
+  checks
+}
@@ -0,0 +1,220 @@
+use crate::plan::MASK;
+use crate::platform;
+use serde_yaml::Value;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+use subprocess::{Exec, Redirection};
+
+/// Marks a config value encrypted with age, followed by the base64 body of the armored
+/// ciphertext on a single line.
+const TAG: &str = "!encrypted";
+
+/// An age identity given in the environment instead of a file.
+const KEY_VAR: &str = "CONDUCTOR_AGE_KEY";
+
+const ARMOR_BEGIN: &str = "-----BEGIN AGE ENCRYPTED FILE-----";
+const ARMOR_END: &str = "-----END AGE ENCRYPTED FILE-----";
+
+/// The plaintext of each value decrypted in this session, by ciphertext, so reloading the config
+/// does not run age again. The plaintexts are also what is masked when the config is shown.
+static DECRYPTED: Mutex<Option<HashMap<String, String>>> = Mutex::new(None);
+
+/// The identity from `CONDUCTOR_AGE_KEY`, taken out of the environment the first time a config is
+/// decrypted so the processes conductor starts do not inherit it.
+static KEY: OnceLock<Option<String>> = OnceLock::new();
+
+/// Numbers the files the ciphertext is written to, which are unique within the process.
+static FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// Quotes the `!encrypted` values of a YAML config. The YAML parser drops tags it does not know,
+/// so they are kept as strings starting with the tag for decrypt to find.
+pub fn quote_tags(config: &str) -> String {
+  let mut quoted = String::with_capacity(config.len());
+  for line in config.split_inclusive('\n') {
+    let mut rest = line;
+    while let Some(start) = rest.find(TAG) {
+      let (before, tagged) = rest.split_at(start);
+      quoted.push_str(before);
+      if before.ends_with(['"', '\'']) {
+        quoted.push_str(TAG);
+        rest = &tagged[TAG.len()..];
+        continue;
+      }
+      let value = tagged[TAG.len()..].trim_start();
+      let end = value
+        .find(|c: char| !(c.is_ascii_alphanumeric() || "+/=".contains(c)))
+        .unwrap_or(value.len());
+      quoted.push_str(&format!("\"{} {}\"", TAG, &value[..end]));
+      rest = &value[end..];
+    }
+    quoted.push_str(rest);
+  }
+  quoted
+}
+
+/// Replaces every string of the config starting with `!encrypted` with its plaintext, using the
+/// identity in `CONDUCTOR_AGE_KEY` or else the identity file from the user config. Returns
+/// whether anything was decrypted.
+pub fn decrypt(config: &mut Value, identity: Option<&Path>) -> io::Result<bool> {
+  key();
+  decrypt_strings(config, identity)
+}
+
+fn decrypt_strings(config: &mut Value, identity: Option<&Path>) -> io::Result<bool> {
+  match config {
+    Value::String(s) => match s.strip_prefix(TAG) {
+      Some(ciphertext) => {
+        *s = decrypt_value(ciphertext.trim(), identity)?;
+        Ok(true)
+      }
+      None => Ok(false),
+    },
+    Value::Sequence(values) => values.iter_mut().try_fold(false, |changed, value| {
+      Ok(decrypt_strings(value, identity)? || changed)
+    }),
+    Value::Mapping(mapping) => mapping.iter_mut().try_fold(false, |changed, (_, value)| {
+      Ok(decrypt_strings(value, identity)? || changed)
+    }),
+    _ => Ok(false),
+  }
+}
+
+/// Masks a value that holds anything decrypted from the config, for printing it or writing it
+/// out.
+pub fn conceal(value: &str) -> String {
+  let decrypted = DECRYPTED.lock().unwrap();
+  let secret = decrypted
+    .iter()
+    .flat_map(|d| d.values())
+    .any(|plaintext| !plaintext.is_empty() && value.contains(plaintext.as_str()));
+  match secret {
+    true => MASK.to_string(),
+    false => value.to_string(),
+  }
+}
+
+/// Masks every string of a config that holds anything decrypted from it.
+pub fn conceal_all(config: &mut Value) {
+  match config {
+    Value::String(s) => *s = conceal(s),
+    Value::Sequence(values) => values.iter_mut().for_each(conceal_all),
+    Value::Mapping(mapping) => mapping.iter_mut().for_each(|(_, value)| conceal_all(value)),
+    _ => {}
+  }
+}
+
+fn key() -> Option<&'static String> {
+  KEY
+    .get_or_init(|| {
+      let key = env::var(KEY_VAR).ok().filter(|k| !k.trim().is_empty());
+      env::remove_var(KEY_VAR);
+      key
+    })
+    .as_ref()
+}
+
+fn decrypt_value(ciphertext: &str, identity: Option<&Path>) -> io::Result<String> {
+  if let Some(plaintext) = DECRYPTED
+    .lock()
+    .unwrap()
+    .as_ref()
+    .and_then(|d| d.get(ciphertext))
+  {
+    return Ok(plaintext.clone());
+  }
+  let mut armored = format!("{}\n", ARMOR_BEGIN);
+  for line in ciphertext.as_bytes().chunks(64) {
+    armored.push_str(&String::from_utf8_lossy(line));
+    armored.push('\n');
+  }
+  armored.push_str(ARMOR_END);
+  armored.push('\n');
+  // The ciphertext is passed on stdin, unless the identity from the environment is, so the
+  // identity is never written to disk. The ciphertext is not secret but its file is created
+  // fresh, never through a link someone else left in its place.
+  let command = Exec::cmd("age").arg("--decrypt");
+  let (command, file) = match key() {
+    Some(key) => {
+      let file = env::temp_dir().join(format!(
+        "conductor-{}-{}.age",
+        std::process::id(),
+        FILES.fetch_add(1, Ordering::SeqCst)
+      ));
+      platform::create_private(&file)?.write_all(armored.as_bytes())?;
+      let command = command
+        .args(&["--identity", "-"])
+        .stdin(key.as_str())
+        .arg(&file);
+      (command, Some(file))
+    }
+    None => {
+      let command = command
+        .arg("--identity")
+        .arg(identity_file(identity)?)
+        .stdin(armored.as_str());
+      (command, None)
+    }
+  };
+  let capture = command
+    .stdout(Redirection::Pipe)
+    .stderr(Redirection::Pipe)
+    .capture();
+  if let Some(file) = file {
+    let _ = fs::remove_file(&file);
+  }
+  let capture = capture
+    .map_err(|e| io::Error::other(format!("Could not run age to decrypt a value: {}", e)))?;
+  if !capture.success() {
+    return Err(io::Error::other(format!(
+      "Could not decrypt a value: {}",
+      capture.stderr_str().trim()
+    )));
+  }
+  let plaintext = capture.stdout_str();
+  DECRYPTED
+    .lock()
+    .unwrap()
+    .get_or_insert_with(HashMap::new)
+    .insert(ciphertext.to_string(), plaintext.clone());
+  Ok(plaintext)
+}
+
+fn identity_file(identity: Option<&Path>) -> io::Result<&Path> {
+  identity.ok_or_else(|| {
+    io::Error::other(format!(
+      "The config has encrypted values, set {} or age_identity in the user config to decrypt them",
+      KEY_VAR
+    ))
+  })
+}
+
+/// Encrypts a value for the recipients, returning it as it is written in a config.
+pub fn encrypt(value: &str, recipients: &[&str]) -> io::Result<String> {
+  let mut command = Exec::cmd("age").args(&["--encrypt", "--armor"]);
+  for recipient in recipients {
+    command = command.args(&["--recipient", recipient]);
+  }
+  let capture = command
+    .stdin(value)
+    .stdout(Redirection::Pipe)
+    .stderr(Redirection::Pipe)
+    .capture()
+    .map_err(|e| io::Error::other(format!("Could not run age to encrypt the value: {}", e)))?;
+  if !capture.success() {
+    return Err(io::Error::other(format!(
+      "Could not encrypt the value: {}",
+      capture.stderr_str().trim()
+    )));
+  }
+  let body: String = capture
+    .stdout_str()
+    .lines()
+    .filter(|line| *line != ARMOR_BEGIN && *line != ARMOR_END)
+    .collect();
+  Ok(format!("{} {}", TAG, body))
+}
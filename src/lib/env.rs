@@ -0,0 +1,272 @@
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use subprocess::{Exec, Redirection};
+
+/// The output of each env command run in this session, by command. Each command has a lock of
+/// its own, so a launch only waits for commands it needs that are already running.
+type CommandOutput = Arc<Mutex<Option<String>>>;
+static COMMAND_OUTPUTS: Mutex<Option<HashMap<String, CommandOutput>>> = Mutex::new(None);
+
+/// Loading the dotenv files found in the directory a component or task runs in, as most app
+/// frameworks do when run locally, so the environment is the same inside and outside conductor.
+/// What they define sits below every env given in the config.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(default)]
+pub struct Dotenv {
+  pub enabled: bool,
+  /// The files looked for, later ones taking precedence. An `.envrc` only contributes its
+  /// `export` lines.
+  pub files: Vec<String>,
+  /// When not empty, only these variables are loaded.
+  pub only: Vec<String>,
+  /// Variables that are never loaded.
+  pub except: Vec<String>,
+}
+
+impl Default for Dotenv {
+  fn default() -> Self {
+    Dotenv {
+      enabled: false,
+      files: vec![".env".into()],
+      only: vec![],
+      except: vec![],
+    }
+  }
+}
+
+impl Dotenv {
+  /// The variables the dotenv files in a directory define, none if discovery is disabled.
+  /// Missing files are skipped. Variables already set in the shell conductor runs in are left
+  /// out, as dotenv libraries do not override them either.
+  pub fn load(&self, dir: &Path) -> io::Result<HashMap<String, String>> {
+    let mut env = HashMap::new();
+    if !self.enabled {
+      return Ok(env);
+    }
+    for file in self.files.iter() {
+      let path = dir.join(file);
+      if !path.is_file() {
+        continue;
+      }
+      match path.file_name().and_then(|n| n.to_str()) {
+        Some(".envrc") => env.extend(parse_envrc(&read(&path)?)),
+        _ => env.extend(read_env_file(&path)?),
+      }
+    }
+    env.retain(|key, _| {
+      (self.only.is_empty() || self.only.contains(key))
+        && !self.except.contains(key)
+        && std::env::var_os(key).is_none()
+    });
+    Ok(env)
+  }
+}
+
+/// Reads a dotenv style file of KEY=VALUE pairs.
+pub fn read_env_file(path: &Path) -> io::Result<HashMap<String, String>> {
+  Ok(parse_env(&read(path)?))
+}
+
+fn read(path: &Path) -> io::Result<String> {
+  fs::read_to_string(path).map_err(|e| {
+    io::Error::new(
+      e.kind(),
+      format!("Could not read env file {}: {}", path.display(), e),
+    )
+  })
+}
+
+/// Parses dotenv style contents. Blank lines and lines starting with # are ignored, an optional
+/// `export` prefix is allowed and values may be wrapped in single or double quotes.
+pub fn parse_env(contents: &str) -> HashMap<String, String> {
+  contents
+    .lines()
+    .map(str::trim)
+    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+    .filter_map(|line| {
+      let line = line.strip_prefix("export ").unwrap_or(line);
+      let split = line.find('=')?;
+      let key = line[..split].trim();
+      let value = line[split + 1..].trim();
+      let value = match (value.chars().next(), value.chars().last()) {
+        (Some('"'), Some('"')) | (Some('\''), Some('\'')) if value.len() > 1 => {
+          &value[1..value.len() - 1]
+        }
+        _ => value,
+      };
+      Some((key.to_string(), value.to_string()))
+    })
+    .collect()
+}
+
+/// Parses the `export` lines of an `.envrc`. The rest of the file is shell code only direnv runs,
+/// and exports whose values use variables or commands are skipped, as only a shell can expand
+/// them.
+pub fn parse_envrc(contents: &str) -> HashMap<String, String> {
+  let exports: Vec<&str> = contents
+    .lines()
+    .map(str::trim)
+    .filter(|line| line.starts_with("export "))
+    .filter(|line| {
+      let value = line.split_once('=').map_or("", |(_, value)| value.trim());
+      let quoted = value.len() > 1 && value.starts_with('\'') && value.ends_with('\'');
+      quoted || !value.contains(['$', '`'])
+    })
+    .collect();
+  parse_env(&exports.join("\n"))
+}
+
+/// Resolves secrets by running the command for each one and using its output, without the
+/// trailing newline, as the value. Output is captured and never logged, errors only name the
+/// secret.
+pub fn resolve_secrets(
+  secrets: &HashMap<String, String>,
+  cwd: &Path,
+) -> io::Result<HashMap<String, String>> {
+  secrets
+    .iter()
+    .map(|(name, cmd)| {
+      let capture = Exec::shell(cmd)
+        .cwd(cwd)
+        .stdout(Redirection::Pipe)
+        .stderr(Redirection::Pipe)
+        .capture()
+        .map_err(|e| io::Error::other(format!("Could not resolve secret {}: {}", name, e)))?;
+      if !capture.success() {
+        return Err(io::Error::other(format!(
+          "Could not resolve secret {}: command exited with {:?}",
+          name, capture.exit_status
+        )));
+      }
+      let value = capture.stdout_str();
+      Ok((
+        name.clone(),
+        value.trim_end_matches(&['\r', '\n'][..]).to_string(),
+      ))
+    })
+    .collect()
+}
+
+/// Moves env entries written as `{ from_command: ... }` into the `env_commands` beside them, for
+/// the project, its components and its templates.
+pub fn move_env_commands(config: &mut Value) {
+  move_from(config);
+  if let Some(components) = config
+    .get_mut("components")
+    .and_then(Value::as_sequence_mut)
+  {
+    components.iter_mut().for_each(move_from);
+  }
+  if let Some(templates) = config.get_mut("templates").and_then(Value::as_mapping_mut) {
+    templates
+      .iter_mut()
+      .for_each(|(_, template)| move_from(template));
+  }
+}
+
+fn move_from(definition: &mut Value) {
+  let definition = match definition.as_mapping_mut() {
+    Some(definition) => definition,
+    None => return,
+  };
+  let env = match definition
+    .get_mut(&Value::from("env"))
+    .and_then(Value::as_mapping_mut)
+  {
+    Some(env) => env,
+    None => return,
+  };
+  let names: Vec<Value> = env
+    .iter()
+    .filter(|(_, value)| value.get("from_command").is_some())
+    .map(|(name, _)| name.clone())
+    .collect();
+  let mut commands = Mapping::new();
+  for name in names {
+    if let Some(command) = env
+      .remove(&name)
+      .and_then(|v| v.get("from_command").cloned())
+    {
+      commands.insert(name, command);
+    }
+  }
+  if commands.is_empty() {
+    return;
+  }
+  let key = Value::from("env_commands");
+  match definition.get_mut(&key).and_then(Value::as_mapping_mut) {
+    Some(existing) => existing.extend(commands),
+    None => {
+      definition.insert(key, Value::Mapping(commands));
+    }
+  }
+}
+
+/// Resolves env commands by running each in the project root and using its trimmed output as
+/// the value. Each command runs once per session, or until the outputs are forgotten, later
+/// lookups share its output.
+pub fn resolve_env_commands(
+  commands: &HashMap<String, String>,
+  root_path: &Path,
+) -> io::Result<HashMap<String, String>> {
+  let mut env = HashMap::new();
+  for (name, command) in commands.iter() {
+    let slot = COMMAND_OUTPUTS
+      .lock()
+      .unwrap()
+      .get_or_insert_with(HashMap::new)
+      .entry(command.clone())
+      .or_default()
+      .clone();
+    // The command's lock is held while it runs, so launches needing the same command wait for
+    // its output rather than running it again.
+    let mut output = slot.lock().unwrap();
+    if let Some(output) = output.as_ref() {
+      env.insert(name.clone(), output.clone());
+      continue;
+    }
+    let capture = Exec::shell(command)
+      .cwd(root_path)
+      .stdout(Redirection::Pipe)
+      .stderr(Redirection::Pipe)
+      .capture()
+      .map_err(|e| io::Error::other(format!("Could not run the command for {}: {}", name, e)))?;
+    if !capture.success() {
+      return Err(io::Error::other(format!(
+        "The command for {} exited with {:?}: {}",
+        name,
+        capture.exit_status,
+        capture.stderr_str().trim()
+      )));
+    }
+    let value = capture.stdout_str().trim().to_string();
+    *output = Some(value.clone());
+    env.insert(name.clone(), value);
+  }
+  Ok(env)
+}
+
+/// Forgets the outputs of the env commands run so far, so they run again the next time they
+/// are needed, as when the config is reloaded.
+pub fn forget_env_commands() {
+  COMMAND_OUTPUTS.lock().unwrap().take();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn reads_only_plain_exports_from_envrc() {
+    let envrc = "use nix\nexport PORT=4000\nexport NAME='$literal'\nexport PATH=$PWD/bin:$PATH\nexport TOKEN=\"$(gh auth token)\"\nexport DEBUG=`date`\nLOCAL=1\n";
+    let env = parse_envrc(envrc);
+    assert_eq!(env.len(), 2);
+    assert_eq!(env["PORT"], "4000");
+    assert_eq!(env["NAME"], "$literal");
+  }
+}
@@ -0,0 +1,41 @@
+/// The exit status of a conductor invocation. These are stable so scripts and CI can branch on
+/// the result instead of parsing output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum ExitCode {
+  /// Everything completed successfully.
+  #[default]
+  Success,
+  /// An error that does not fall into a more specific category.
+  Failure,
+  /// The configuration could not be found, loaded or failed validation.
+  Config,
+  /// A component failed to start or exited with a failure status.
+  Component,
+  /// A service failed to start or stop.
+  Service,
+  /// A task command exited with a failure status.
+  Task,
+  /// The session was interrupted with ctrl-c or stopped by SIGTERM or SIGHUP.
+  Interrupted,
+}
+
+impl ExitCode {
+  /// The numeric code returned to the shell.
+  pub fn code(self) -> i32 {
+    match self {
+      ExitCode::Success => 0,
+      ExitCode::Failure => 1,
+      ExitCode::Config => 2,
+      ExitCode::Component => 3,
+      ExitCode::Service => 4,
+      ExitCode::Task => 5,
+      ExitCode::Interrupted => 130,
+    }
+  }
+
+  /// Combines two results, keeping the more significant one. An interruption outranks failures
+  /// and failures outrank success.
+  pub fn and(self, other: ExitCode) -> ExitCode {
+    self.max(other)
+  }
+}
@@ -0,0 +1,345 @@
+use crate::random;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Replaces references to variables in a string with their values. `$NAME`, `${NAME}` and
+/// `%NAME%` are all understood, `${NAME:-default}` falls back to the default when the variable is
+/// not set and `$$` and `%%` stand for a literal `$` and `%`. References to variables that are
+/// not set are left as written.
+pub(crate) fn expand<F>(text: &str, lookup: F) -> String
+where
+  F: Fn(&str) -> Option<String>,
+{
+  expand_with(text, &lookup, None)
+}
+
+/// Expands a string like expand, also replacing calls such as `${free_port()}` with their result.
+pub(crate) fn expand_calling<F>(text: &str, lookup: F, functions: &CallSite) -> String
+where
+  F: Fn(&str) -> Option<String>,
+{
+  expand_with(text, &lookup, Some(functions))
+}
+
+type Lookup<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+fn expand_with(text: &str, lookup: Lookup, functions: Option<&CallSite>) -> String {
+  let mut out = String::with_capacity(text.len());
+  let mut rest = text;
+  while let Some(start) = rest.find(['$', '%']) {
+    out.push_str(&rest[..start]);
+    let sigil = &rest[start..start + 1];
+    let after = &rest[start + 1..];
+    if let Some(after) = after.strip_prefix(sigil) {
+      out.push_str(sigil);
+      rest = after;
+      continue;
+    }
+    let (reference, replacement) = match sigil {
+      "%" => percent(after, lookup),
+      _ => match after.strip_prefix('{') {
+        Some(braced) => brace(braced, lookup, functions),
+        None => dollar(after, lookup),
+      },
+    };
+    match replacement {
+      Some(value) => out.push_str(&value),
+      None => {
+        out.push_str(sigil);
+        out.push_str(&after[..reference]);
+      }
+    }
+    rest = &after[reference..];
+  }
+  out.push_str(rest);
+  out
+}
+
+/// The length of the variable name at the start of the text.
+fn name_len(text: &str) -> usize {
+  match text.chars().next() {
+    Some(c) if c.is_ascii_alphabetic() || c == '_' => text
+      .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+      .unwrap_or(text.len()),
+    _ => 0,
+  }
+}
+
+/// Expands `$NAME`, returning how much of the text after the `$` the reference spans.
+fn dollar(text: &str, lookup: Lookup) -> (usize, Option<String>) {
+  match name_len(text) {
+    0 => (0, None),
+    len => (len, lookup(&text[..len])),
+  }
+}
+
+/// Expands `${NAME}`, `${NAME:-default}` or `${name(argument)}` given the text after the brace.
+fn brace(text: &str, lookup: Lookup, functions: Option<&CallSite>) -> (usize, Option<String>) {
+  let end = match text.find('}') {
+    Some(end) => end,
+    None => return (0, None),
+  };
+  let inner = &text[..end];
+  // The reference spans the brace, what it holds and the closing brace.
+  if let Some((name, argument)) = call(inner) {
+    return (end + 2, functions.and_then(|f| f.call(name, argument)));
+  }
+  let (name, default) = match inner.split_once(":-") {
+    Some((name, default)) => (name, Some(default)),
+    None => (inner, None),
+  };
+  if name.is_empty() || name_len(name) != name.len() {
+    return (0, None);
+  }
+  let value = lookup(name)
+    .filter(|v| default.is_none() || !v.is_empty())
+    .or_else(|| default.map(|d| expand_with(d, lookup, functions)));
+  (end + 2, value)
+}
+
+/// Expands `%NAME%` given the text after the first `%`.
+fn percent(text: &str, lookup: Lookup) -> (usize, Option<String>) {
+  let len = name_len(text);
+  match text[len..].starts_with('%') {
+    true if len > 0 => (len + 1, lookup(&text[..len])),
+    _ => (0, None),
+  }
+}
+
+/// Splits a function call such as `file(VERSION)` into the function's name and its argument.
+fn call(text: &str) -> Option<(&str, &str)> {
+  let (name, argument) = text.strip_suffix(')')?.split_once('(')?;
+  match !name.is_empty() && name_len(name) == name.len() {
+    true => Some((name, argument.trim())),
+    false => None,
+  }
+}
+
+/// The functions config strings can call, as in `${free_port()}`, for a launch of a component or
+/// task. Each call is evaluated once per launch, and calls made in different places get results
+/// of their own, so two `${free_port()}` calls pick two ports.
+pub(crate) struct Functions {
+  /// What `file()` paths are relative to, the project root.
+  dir: PathBuf,
+  /// The results of the calls of this launch by where they were made.
+  results: Mutex<HashMap<String, String>>,
+  /// The ports picked by `free_port()` calls by where they were made, kept for later launches.
+  ports: Arc<Mutex<HashMap<String, u16>>>,
+}
+
+impl Functions {
+  pub fn new(dir: &Path) -> Self {
+    Functions {
+      dir: dir.to_path_buf(),
+      results: Mutex::new(HashMap::new()),
+      ports: Arc::new(Mutex::new(HashMap::new())),
+    }
+  }
+
+  /// The functions for the next launch of the same component. Calls are evaluated again, except
+  /// that `free_port()` calls keep the ports they picked, so a restarted component can still be
+  /// reached where it was.
+  pub fn relaunch(&self) -> Self {
+    Functions {
+      dir: self.dir.clone(),
+      results: Mutex::new(HashMap::new()),
+      ports: Arc::clone(&self.ports),
+    }
+  }
+
+  /// The calls made in one place, such as an env value or a command, named by `site`.
+  pub fn at(&self, site: &str) -> CallSite<'_> {
+    CallSite {
+      functions: self,
+      site: site.to_string(),
+      calls: Cell::new(0),
+    }
+  }
+
+  /// The result of a call made where `key` says, none for unknown functions and calls that
+  /// failed, which are left as written.
+  fn call(&self, key: &str, name: &str, argument: &str) -> Option<String> {
+    if let Some(result) = self.results.lock().unwrap().get(key) {
+      return Some(result.clone());
+    }
+    let result = match name {
+      "free_port" => {
+        let mut ports = self.ports.lock().unwrap();
+        let port = match ports.get(key) {
+          Some(port) => *port,
+          None => TcpListener::bind(("127.0.0.1", 0))
+            .and_then(|listener| listener.local_addr())
+            .map(|address| address.port())
+            .ok()?,
+        };
+        ports.insert(key.to_string(), port);
+        port.to_string()
+      }
+      "uuid" => uuid().ok()?,
+      "hostname" => crate::process::hostname()?,
+      "file" => {
+        let path = argument.trim_matches(|c| c == '"' || c == '\'');
+        fs::read_to_string(self.dir.join(path))
+          .ok()?
+          .trim_end_matches(&['\r', '\n'][..])
+          .to_string()
+      }
+      _ => return None,
+    };
+    self
+      .results
+      .lock()
+      .unwrap()
+      .insert(key.to_string(), result.clone());
+    Some(result)
+  }
+}
+
+/// The calls made in one place of the config. The calls are told apart by their order, so each
+/// one keeps its result when the place is expanded again in a later launch.
+pub(crate) struct CallSite<'a> {
+  functions: &'a Functions,
+  site: String,
+  calls: Cell<usize>,
+}
+
+impl CallSite<'_> {
+  /// Replaces the function calls in a command and nothing else, the shell running it expands its
+  /// variables.
+  pub fn apply(&self, text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+      out.push_str(&rest[..start]);
+      let after = &rest[start + 2..];
+      let result = after.find('}').and_then(|end| {
+        let (name, argument) = call(&after[..end])?;
+        Some((end, self.call(name, argument)?))
+      });
+      match result {
+        Some((end, value)) => {
+          out.push_str(&value);
+          rest = &after[end + 1..];
+        }
+        None => {
+          out.push_str("${");
+          rest = after;
+        }
+      }
+    }
+    out.push_str(rest);
+    out
+  }
+
+  fn call(&self, name: &str, argument: &str) -> Option<String> {
+    let n = self.calls.get();
+    self.calls.set(n + 1);
+    let key = format!("{} {}", self.site, n);
+    self.functions.call(&key, name, argument)
+  }
+}
+
+/// A random version 4 UUID.
+fn uuid() -> io::Result<String> {
+  let mut bytes = [0u8; 16];
+  random::fill(&mut bytes)?;
+  bytes[6] = (bytes[6] & 0x0f) | 0x40;
+  bytes[8] = (bytes[8] & 0x3f) | 0x80;
+  let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+  Ok(format!(
+    "{}-{}-{}-{}-{}",
+    &hex[..8],
+    &hex[8..12],
+    &hex[12..16],
+    &hex[16..20],
+    &hex[20..]
+  ))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn lookup(name: &str) -> Option<String> {
+    match name {
+      "HOME" => Some("/home/me".into()),
+      "USER" => Some("me".into()),
+      "EMPTY" => Some("".into()),
+      _ => None,
+    }
+  }
+
+  #[test]
+  fn expands_every_style() {
+    assert_eq!(expand("$HOME/src", lookup), "/home/me/src");
+    assert_eq!(expand("${HOME}/src", lookup), "/home/me/src");
+    assert_eq!(expand("%HOME%/src", lookup), "/home/me/src");
+    assert_eq!(expand("${USER}_$USER-%USER%", lookup), "me_me-me");
+  }
+
+  #[test]
+  fn names_end_at_the_first_character_not_allowed_in_them() {
+    assert_eq!(expand("$USER_dir", lookup), "$USER_dir");
+    assert_eq!(expand("${USER}_dir", lookup), "me_dir");
+    assert_eq!(expand("$USER.txt", lookup), "me.txt");
+  }
+
+  #[test]
+  fn escapes_take_precedence_over_references() {
+    assert_eq!(expand("$$HOME", lookup), "$HOME");
+    assert_eq!(expand("%%HOME%%", lookup), "%HOME%");
+    assert_eq!(expand("$$${HOME}", lookup), "$/home/me");
+  }
+
+  #[test]
+  fn leaves_unknown_and_malformed_references_as_written() {
+    assert_eq!(
+      expand("$MISSING and %MISSING%", lookup),
+      "$MISSING and %MISSING%"
+    );
+    assert_eq!(expand("${HOME", lookup), "${HOME");
+    assert_eq!(expand("100% of $5", lookup), "100% of $5");
+    assert_eq!(expand("${not a name}", lookup), "${not a name}");
+  }
+
+  #[test]
+  fn calls_are_only_made_when_functions_are_given() {
+    let functions = Functions::new(Path::new("."));
+    assert_eq!(expand("${hostname()}", lookup), "${hostname()}");
+    let id = expand_calling("${uuid()}", lookup, &functions.at("ID"));
+    assert_eq!(id.len(), 36);
+    assert_eq!(&id[14..15], "4");
+    assert_eq!(expand_calling("${uuid()}", lookup, &functions.at("ID")), id);
+    assert_ne!(
+      expand_calling("${uuid()}", lookup, &functions.at("NEXT_ID")),
+      id
+    );
+    let nope = expand_calling("${nope()}", lookup, &functions.at("NOPE"));
+    assert_eq!(nope, "${nope()}");
+  }
+
+  #[test]
+  fn each_call_picks_a_port_kept_for_later_launches() {
+    let functions = Functions::new(Path::new("."));
+    let command = "serve --port ${free_port()} --admin ${free_port()} $$ ${HOME}";
+    let applied = functions.at("start").apply(command);
+    let words: Vec<&str> = applied.split(' ').collect();
+    assert!(words[2].parse::<u16>().is_ok());
+    assert_ne!(words[2], words[4]);
+    assert_eq!(&words[5..], ["$$", "${HOME}"]);
+    assert_eq!(functions.relaunch().at("start").apply(command), applied);
+  }
+
+  #[test]
+  fn defaults_apply_to_unset_and_empty_variables() {
+    assert_eq!(expand("${MISSING:-/tmp}", lookup), "/tmp");
+    assert_eq!(expand("${EMPTY:-/tmp}", lookup), "/tmp");
+    assert_eq!(expand("${HOME:-/tmp}", lookup), "/home/me");
+    assert_eq!(expand("${MISSING:-$HOME}", lookup), "/home/me");
+  }
+}
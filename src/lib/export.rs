@@ -0,0 +1,531 @@
+use crate::encrypted;
+use crate::process::quote;
+use crate::service::{all_containers, ServiceType};
+use crate::{Component, Project, Service};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// The formats a project can be exported to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+  Compose,
+  Procfile,
+  Systemd,
+  Vscode,
+}
+
+impl FromStr for Format {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "compose" => Ok(Format::Compose),
+      "procfile" => Ok(Format::Procfile),
+      "systemd" => Ok(Format::Systemd),
+      "vscode" => Ok(Format::Vscode),
+      _ => Err(format!("Unknown export format {}", s)),
+    }
+  }
+}
+
+impl Format {
+  /// The file, or directory for formats with a file per component, written when no output is
+  /// given.
+  pub fn default_path(&self) -> &'static str {
+    match self {
+      Format::Compose => "docker-compose.yml",
+      Format::Procfile => "Procfile",
+      Format::Systemd => "systemd",
+      Format::Vscode => ".vscode/tasks.json",
+    }
+  }
+
+  /// Returns true if the format is written as a directory of files.
+  pub fn is_directory(&self) -> bool {
+    *self == Format::Systemd
+  }
+}
+
+/// A file of an export. The first file of formats written as a single file is written to the
+/// output, the rest of their files are written beside it.
+pub struct File {
+  pub name: String,
+  pub contents: String,
+  /// Files holding env values are only readable by their owner, so the values stay out of the
+  /// files that are shared or committed.
+  pub private: bool,
+}
+
+/// A project converted to another format, along with what could not be converted.
+pub struct Export {
+  pub files: Vec<File>,
+  pub warnings: Vec<String>,
+}
+
+impl Export {
+  fn single(format: Format, contents: String, warnings: Vec<String>) -> Self {
+    Export {
+      files: vec![File {
+        name: format.default_path().into(),
+        contents,
+        private: false,
+      }],
+      warnings,
+    }
+  }
+}
+
+/// Converts the project to another format, to be written to the output path. Env values are
+/// written to env files of their own beside the output, named after it, that the export loads.
+pub fn export(project: &Project, format: Format, output: &Path) -> Result<Export, String> {
+  let mut export = match format {
+    Format::Compose => compose(project, &EnvFiles::new(output)),
+    Format::Procfile => Ok(procfile(project, &EnvFiles::new(output))),
+    Format::Systemd => Ok(systemd(project, output)),
+    Format::Vscode => vscode(project),
+  }?;
+  export.warnings.extend(secret_warnings(project));
+  Ok(export)
+}
+
+/// Warns that secrets were left out, no format carries them.
+fn secret_warnings(project: &Project) -> Vec<String> {
+  let mut warnings: Vec<String> = project
+    .components
+    .iter()
+    .filter(|c| !c.secrets.is_empty())
+    .map(|c| format!("The secrets of {} were not exported", c.name))
+    .collect();
+  if !project.secrets.is_empty() {
+    warnings.push("The project's secrets were not exported".into());
+  }
+  warnings
+}
+
+/// Names the env files written beside a single file export, such as `Procfile.api.env`.
+struct EnvFiles {
+  stem: String,
+}
+
+impl EnvFiles {
+  fn new(output: &Path) -> Self {
+    EnvFiles {
+      stem: output
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default(),
+    }
+  }
+
+  /// The env file of a component or service, none when it has no env.
+  fn file(&self, name: &str, env: &BTreeMap<String, String>) -> Option<File> {
+    env_file(format!("{}.{}.env", self.stem, unit_name(name)), env)
+  }
+}
+
+/// An env file holding the values of an env, none when it is empty.
+fn env_file(name: String, env: &BTreeMap<String, String>) -> Option<File> {
+  if env.is_empty() {
+    return None;
+  }
+  let contents = env
+    .iter()
+    .map(|(k, v)| format!("{}={}\n", k, env_quote(v)))
+    .collect();
+  Some(File {
+    name,
+    contents,
+    private: true,
+  })
+}
+
+/// Quotes a value for an env file so the shell, systemd and docker compose all read it as it is.
+/// Single quotes are taken literally by each of them, values containing one are double quoted.
+fn env_quote(value: &str) -> String {
+  if !value.contains('\'') {
+    return format!("'{}'", value);
+  }
+  let mut quoted = String::from("\"");
+  for c in value.chars() {
+    if "\\\"$`".contains(c) {
+      quoted.push('\\');
+    }
+    quoted.push(c);
+  }
+  quoted.push('"');
+  quoted
+}
+
+#[derive(Serialize)]
+struct VscodeTasks {
+  version: &'static str,
+  tasks: Vec<VscodeTask>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct VscodeTask {
+  label: String,
+  #[serde(rename = "type")]
+  kind: &'static str,
+  command: &'static str,
+  args: Vec<String>,
+  is_background: bool,
+  problem_matcher: Vec<String>,
+}
+
+impl VscodeTask {
+  fn new(label: String, args: Vec<String>, is_background: bool) -> Self {
+    VscodeTask {
+      label,
+      kind: "shell",
+      command: "conductor",
+      args,
+      is_background,
+      problem_matcher: vec![],
+    }
+  }
+}
+
+/// Writes a VS Code task for each project and component task and for starting each component.
+/// The tasks run conductor so they behave as they do on the command line.
+fn vscode(project: &Project) -> Result<Export, String> {
+  let mut tasks = vec![];
+  let mut names: Vec<&String> = project.tasks.keys().collect();
+  names.sort();
+  for name in names {
+    tasks.push(VscodeTask::new(name.clone(), vec![name.clone()], false));
+  }
+  for c in project.components.iter() {
+    for name in c.task_names() {
+      let name = format!("{}:{}", c.name, name);
+      tasks.push(VscodeTask::new(name.clone(), vec![name], false));
+    }
+    tasks.push(VscodeTask::new(
+      format!("run {}", c.name),
+      vec!["run".into(), c.name.clone()],
+      true,
+    ));
+  }
+  let file = VscodeTasks {
+    version: "2.0.0",
+    tasks,
+  };
+  let contents = serde_json::to_string_pretty(&file).map_err(|e| e.to_string())? + "\n";
+  Ok(Export::single(Format::Vscode, contents, vec![]))
+}
+
+/// The start command of a component on unix like systems, which all export formats run on.
+fn unix_start(c: &Component) -> &str {
+  c.start_unix.as_deref().unwrap_or(&c.start)
+}
+
+/// Writes a process for each component. The command exports the project's and component's env
+/// from its env file and changes to the component's path before running the start command, so
+/// it behaves as it does under conductor when run from the project root.
+fn procfile(project: &Project, env_files: &EnvFiles) -> Export {
+  let mut contents = String::new();
+  let mut files = vec![];
+  let mut warnings = vec![];
+  for c in project.components.iter() {
+    let mut env = sorted(&project.env);
+    env.extend(sorted(&c.env));
+    let mut steps = vec![];
+    if let Some(file) = env_files.file(&c.name, &env) {
+      steps.push(format!(
+        "set -a && . {} && set +a",
+        quote(&format!("./{}", file.name))
+      ));
+      files.push(file);
+    }
+    steps.push(format!("cd {}", quote(&c.get_path().display().to_string())));
+    steps.push(unix_start(c).to_string());
+    contents.push_str(&format!("{}: {}\n", c.name, steps.join(" && ")));
+    if c.env_file.is_some() {
+      warnings.push(format!(
+        "The env file of {} is not loaded by the Procfile",
+        c.name
+      ));
+    }
+  }
+  let mut export = Export::single(Format::Procfile, contents, warnings);
+  export.files.extend(files);
+  export
+}
+
+/// Writes a user unit for each component. Units run the start command in the component's path
+/// with the project's and component's env, loaded from an env file beside the unit, and
+/// components with `retry` are restarted by systemd when they fail.
+fn systemd(project: &Project, output: &Path) -> Export {
+  let mut files = vec![];
+  let mut warnings = vec![];
+  let prefix = unit_name(&project.name);
+  for c in project.components.iter() {
+    let dir = project.root_path.join(c.get_path());
+    let mut unit = String::new();
+    unit.push_str("[Unit]\n");
+    unit.push_str(&format!(
+      "Description={} {}\n\n",
+      unit_escape(&project.name),
+      unit_escape(&c.name)
+    ));
+    unit.push_str("[Service]\nType=simple\n");
+    unit.push_str(&format!(
+      "WorkingDirectory={}\n",
+      unit_escape(&dir.display().to_string())
+    ));
+    if let Some(env_file) = &c.env_file {
+      unit.push_str(&format!(
+        "EnvironmentFile={}\n",
+        unit_escape(&dir.join(env_file).display().to_string())
+      ));
+    }
+    let mut env = sorted(&project.env);
+    env.extend(sorted(&c.env));
+    let name = format!("{}-{}", prefix, unit_name(&c.name));
+    if let Some(file) = env_file(format!("{}.env", name), &env) {
+      unit.push_str(&format!(
+        "EnvironmentFile={}\n",
+        unit_escape(&output.join(&file.name).display().to_string())
+      ));
+      files.push(file);
+    }
+    // Variables in commands are expanded by systemd unless escaped, the shell should see them.
+    unit.push_str(&format!(
+      "ExecStart=/bin/sh -c {}\n",
+      unit_quote(&unix_start(c).replace('$', "$$"))
+    ));
+    if c.retry {
+      unit.push_str("Restart=on-failure\n");
+      unit.push_str(&format!("RestartSec={}\n", c.restart_delay.unwrap_or(1)));
+    }
+    unit.push_str("\n[Install]\nWantedBy=default.target\n");
+    files.push(File {
+      name: format!("{}.service", name),
+      contents: unit,
+      private: false,
+    });
+    if !c.services.is_empty() {
+      warnings.push(format!(
+        "The services of {} are not started by its unit",
+        c.name
+      ));
+    }
+  }
+  Export { files, warnings }
+}
+
+/// A name that is safe to use in a unit's file name.
+fn unit_name(name: &str) -> String {
+  name
+    .to_lowercase()
+    .chars()
+    .map(|c| match c.is_ascii_alphanumeric() || c == '_' {
+      true => c,
+      false => '-',
+    })
+    .collect()
+}
+
+/// Escapes the specifiers systemd expands in unit settings.
+fn unit_escape(value: &str) -> String {
+  value.replace('%', "%%")
+}
+
+/// Quotes a value for a unit setting.
+fn unit_quote(value: &str) -> String {
+  let escaped = unit_escape(value)
+    .replace('\\', "\\\\")
+    .replace('"', "\\\"");
+  format!("\"{}\"", escaped)
+}
+
+#[derive(Serialize, Default)]
+struct ComposeFile {
+  services: BTreeMap<String, ComposeService>,
+}
+
+#[derive(Serialize, Default)]
+struct ComposeService {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  image: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  build: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  container_name: Option<String>,
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  command: Vec<String>,
+  #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+  environment: BTreeMap<String, String>,
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  env_file: Vec<String>,
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  ports: Vec<String>,
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  volumes: Vec<String>,
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  depends_on: Vec<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  restart: Option<String>,
+}
+
+/// Maps services and components to a docker compose file. Containers keep the image they were
+/// created from, the service's image or else the one looked up from docker. Components and
+/// process services are built from a Dockerfile in their path and run their command with
+/// `sh -c`. Anything else is left out with a warning. Env values are loaded from an env file for
+/// each service and secrets are never exported.
+fn compose(project: &Project, env_files: &EnvFiles) -> Result<Export, String> {
+  let mut file = ComposeFile::default();
+  let mut files = vec![];
+  let mut warnings = vec![];
+  // Docker may not be running, in which case containers are reported as skipped.
+  let containers = all_containers().unwrap_or_default();
+  for s in project.services.iter() {
+    let service = match &s.service_type {
+      ServiceType::DockerContainer => {
+        let name = s.get_container_name();
+        let container = containers
+          .iter()
+          .find(|c| c.Names.iter().any(|n| n.trim_start_matches('/') == name));
+        match container {
+          _ if s.image.is_some() => ComposeService {
+            image: s.image.clone(),
+            container_name: Some(name),
+            environment: sorted(&s.env),
+            ports: s.ports.clone(),
+            volumes: s.volumes.clone(),
+            ..ComposeService::default()
+          },
+          Some(container) => ComposeService {
+            image: Some(container.Image.clone()),
+            container_name: Some(name),
+            environment: sorted(&s.env),
+            ports: container
+              .Ports
+              .iter()
+              .filter_map(|p| {
+                p.PublicPort
+                  .map(|public| format!("{}:{}", public, p.PrivatePort))
+              })
+              .collect(),
+            ..ComposeService::default()
+          },
+          None => {
+            warnings.push(format!(
+              "Skipped service {}, the image of container {} could not be found",
+              s.name, name
+            ));
+            continue;
+          }
+        }
+      }
+      ServiceType::Process => match process_service(project, s) {
+        Ok(service) => service,
+        Err(e) => {
+          warnings.push(e);
+          continue;
+        }
+      },
+      ServiceType::Plugin(name) => {
+        warnings.push(format!(
+          "Skipped service {}, {} services can not be exported",
+          s.name, name
+        ));
+        continue;
+      }
+    };
+    file.services.insert(s.name.clone(), service);
+  }
+  for c in project.components.iter() {
+    match component_service(project, c) {
+      Ok(service) => {
+        file.services.insert(c.name.clone(), service);
+      }
+      Err(e) => warnings.push(e),
+    }
+  }
+  for (name, service) in file.services.iter_mut() {
+    let environment = std::mem::take(&mut service.environment);
+    if let Some(env) = env_files.file(name, &environment) {
+      service.env_file.push(format!("./{}", env.name));
+      files.push(env);
+    }
+  }
+  let contents = serde_yaml::to_string(&file).map_err(|e| e.to_string())? + "\n";
+  let mut export = Export::single(Format::Compose, contents, warnings);
+  export.files.extend(files);
+  Ok(export)
+}
+
+fn component_service(project: &Project, c: &Component) -> Result<ComposeService, String> {
+  let path = c.get_path().display().to_string();
+  if !has_dockerfile(&project.root_path, &path) {
+    return Err(format!(
+      "Skipped component {}, there is no Dockerfile in {}",
+      c.name, path
+    ));
+  }
+  let mut environment = sorted(&project.env);
+  environment.extend(sorted(&c.env));
+  Ok(ComposeService {
+    build: Some(compose_path(Path::new(&path))),
+    command: vec!["sh".into(), "-c".into(), unix_start(c).to_string()],
+    environment,
+    env_file: c
+      .env_file
+      .iter()
+      .map(|f| compose_path(&Path::new(&path).join(f)))
+      .collect(),
+    ports: c.ports.iter().map(|p| format!("{}:{}", p, p)).collect(),
+    depends_on: c.services.clone(),
+    restart: match c.retry {
+      true => Some("on-failure".into()),
+      false => None,
+    },
+    ..ComposeService::default()
+  })
+}
+
+fn process_service(project: &Project, s: &Service) -> Result<ComposeService, String> {
+  let path = s.path.clone().unwrap_or_else(|| ".".into());
+  let command = match &s.command {
+    Some(command) if has_dockerfile(&project.root_path, &path) => command.clone(),
+    _ => {
+      return Err(format!(
+        "Skipped service {}, there is no Dockerfile in {}",
+        s.name, path
+      ))
+    }
+  };
+  Ok(ComposeService {
+    build: Some(compose_path(Path::new(&path))),
+    command: vec!["sh".into(), "-c".into(), command],
+    environment: sorted(&s.env),
+    ..ComposeService::default()
+  })
+}
+
+/// A path as compose expects it, relative paths start with `./` and absolute ones are kept.
+fn compose_path(path: &Path) -> String {
+  match path.is_absolute() {
+    true => path.display().to_string(),
+    false => {
+      let path: PathBuf = path.components().collect();
+      format!("./{}", path.display())
+    }
+  }
+}
+
+fn has_dockerfile(root_path: &Path, path: &str) -> bool {
+  root_path.join(path).join("Dockerfile").is_file()
+}
+
+/// Environment variables in a stable order so exports can be diffed.
+fn sorted(env: &HashMap<String, String>) -> BTreeMap<String, String> {
+  env
+    .iter()
+    .map(|(k, v)| (k.clone(), encrypted::conceal(v)))
+    .collect()
+}
@@ -1,11 +1,15 @@
-use git2::build::RepoBuilder;
+use git2::build::{CheckoutBuilder, RepoBuilder};
 use git2::Repository;
-use git2::{Cred, FetchOptions, RemoteCallbacks};
+use git2::{
+  BranchType, Config, Cred, CredentialType, Direction, FetchOptions, Oid, Remote, RemoteCallbacks,
+  StatusOptions, SubmoduleUpdateOptions,
+};
 use std::env;
 use std::fs;
 use std::io::{Error, ErrorKind};
 use std::path::Path;
 
+#[allow(clippy::io_other_error, clippy::needless_borrow)]
 pub fn clone_repo(repo_url: &str, root_path: &Path) -> Result<Repository, Error> {
   if root_path.exists() {
     return Result::Err(Error::new(
@@ -18,16 +22,8 @@ pub fn clone_repo(repo_url: &str, root_path: &Path) -> Result<Repository, Error>
   }
   fs::create_dir_all(root_path)?;
   let mut builder = RepoBuilder::new();
-  let mut callbacks = RemoteCallbacks::new();
   let mut fetch_options = FetchOptions::new();
-
-  callbacks.credentials(|_, _, _| {
-    let user: String = env::var("GIT_USER").unwrap_or_else(|_| "".into());
-    let pass: String = env::var("GIT_PAT").unwrap_or_else(|_| "".into());
-    Cred::userpass_plaintext(&user, &pass)
-  });
-
-  fetch_options.remote_callbacks(callbacks);
+  fetch_options.remote_callbacks(credentials());
   builder.fetch_options(fetch_options);
 
   builder.clone(repo_url, &root_path).map_err(|e| {
@@ -37,3 +33,228 @@ pub fn clone_repo(repo_url: &str, root_path: &Path) -> Result<Repository, Error>
     )
   })
 }
+
+/// Initializes and updates the submodules of a checkout to the commits it records for them, and
+/// then their submodules in turn.
+pub fn update_submodules(path: &Path) -> Result<(), Error> {
+  let repo = Repository::open(path).map_err(git)?;
+  for mut submodule in repo.submodules().map_err(git)? {
+    let name = submodule.name().unwrap_or_default().to_string();
+    let mut fetch_options = FetchOptions::new();
+    fetch_options.remote_callbacks(credentials());
+    let mut options = SubmoduleUpdateOptions::new();
+    options.fetch(fetch_options);
+    submodule.update(true, Some(&mut options)).map_err(|e| {
+      Error::other(format!(
+        "Could not update submodule {}: {}",
+        name,
+        e.message()
+      ))
+    })?;
+    update_submodules(&path.join(submodule.path()))?;
+  }
+  Ok(())
+}
+
+/// Fetches the upstream of a checkout's current branch and fast-forwards the branch to it.
+/// Checkouts with local changes, or commits that are not upstream, are left for their owner to
+/// sort out. Returns true if the branch moved.
+pub fn sync_repo(path: &Path) -> Result<bool, Error> {
+  let repo = Repository::open(path).map_err(git)?;
+  if repo.head_detached().map_err(git)? {
+    switch_to_default_branch(&repo)?;
+  }
+  let head = repo.head().map_err(git)?;
+  let (reference, branch) = match (head.name(), head.shorthand()) {
+    (Some(reference), Some(branch)) if head.is_branch() => (reference, branch),
+    _ => return Err(Error::other("Not on a branch")),
+  };
+  let remote = repo.branch_upstream_remote(reference).map_err(git)?;
+  let mut remote = repo
+    .find_remote(remote.as_str().unwrap_or_default())
+    .map_err(git)?;
+  fetch(&mut remote)?;
+
+  let upstream = repo
+    .find_branch(branch, BranchType::Local)
+    .and_then(|b| b.upstream())
+    .map_err(git)?;
+  let target = upstream
+    .get()
+    .target()
+    .ok_or_else(|| Error::other("The upstream branch has no commit"))?;
+  let annotated = repo.find_annotated_commit(target).map_err(git)?;
+  let (analysis, _) = repo.merge_analysis(&[&annotated]).map_err(git)?;
+  if analysis.is_up_to_date() {
+    return Ok(false);
+  }
+  if !analysis.is_fast_forward() {
+    return Err(Error::other(format!(
+      "{} has commits that are not upstream, merge or rebase it yourself",
+      branch
+    )));
+  }
+  ensure_clean(&repo)?;
+  let commit = repo.find_object(target, None).map_err(git)?;
+  repo
+    .checkout_tree(&commit, Some(CheckoutBuilder::new().safe()))
+    .map_err(git)?;
+  repo
+    .find_reference(reference)
+    .and_then(|mut r| r.set_target(target, "conductor setup --sync: fast-forward"))
+    .map_err(git)?;
+  Ok(true)
+}
+
+/// Checks out the branch a detached checkout, such as one at a locked commit, was cloned from.
+/// That is origin's HEAD if it is known, or otherwise the only local branch, the one a clone
+/// creates.
+fn switch_to_default_branch(repo: &Repository) -> Result<(), Error> {
+  let origin_head = repo
+    .find_reference("refs/remotes/origin/HEAD")
+    .ok()
+    .and_then(|r| r.symbolic_target().map(String::from))
+    .and_then(|target| {
+      target
+        .strip_prefix("refs/remotes/origin/")
+        .map(String::from)
+    });
+  let only_branch = || {
+    let branches: Vec<String> = repo
+      .branches(Some(BranchType::Local))
+      .ok()?
+      .filter_map(|b| b.ok()?.0.name().ok()?.map(String::from))
+      .collect();
+    match branches.as_slice() {
+      [branch] => Some(branch.clone()),
+      _ => None,
+    }
+  };
+  let default = origin_head
+    .or_else(only_branch)
+    .ok_or_else(|| Error::other("Not on a branch"))?;
+  let reference = format!("refs/heads/{}", default);
+  let commit = repo.revparse_single(&reference).map_err(|_| {
+    Error::other(format!(
+      "Not on a branch and there is no {} branch",
+      default
+    ))
+  })?;
+  ensure_clean(repo)?;
+  repo
+    .checkout_tree(&commit, Some(CheckoutBuilder::new().safe()))
+    .map_err(git)?;
+  repo.set_head(&reference).map_err(git)
+}
+
+/// The commit a checkout is at.
+pub fn head_commit(path: &Path) -> Result<String, Error> {
+  let repo = Repository::open(path).map_err(git)?;
+  let commit = repo
+    .head()
+    .and_then(|head| head.peel_to_commit())
+    .map_err(git)?;
+  Ok(commit.id().to_string())
+}
+
+/// Checks out a commit with a detached HEAD, fetching from origin first if the checkout does not
+/// have it. Returns true if HEAD moved.
+pub fn checkout_commit(path: &Path, commit: &str) -> Result<bool, Error> {
+  let repo = Repository::open(path).map_err(git)?;
+  let oid = Oid::from_str(commit).map_err(git)?;
+  if repo.head().ok().and_then(|head| head.target()) == Some(oid) {
+    return Ok(false);
+  }
+  if repo.find_commit(oid).is_err() {
+    fetch(&mut repo.find_remote("origin").map_err(git)?)?;
+  }
+  let object = repo
+    .find_object(oid, None)
+    .map_err(|_| Error::other(format!("Commit {} does not exist upstream", commit)))?;
+  ensure_clean(&repo)?;
+  repo
+    .checkout_tree(&object, Some(CheckoutBuilder::new().safe()))
+    .map_err(git)?;
+  repo.set_head_detached(oid).map_err(git)?;
+  Ok(true)
+}
+
+fn git(e: git2::Error) -> Error {
+  Error::other(e.message().to_string())
+}
+
+fn fetch(remote: &mut Remote) -> Result<(), Error> {
+  let mut fetch_options = FetchOptions::new();
+  fetch_options.remote_callbacks(credentials());
+  remote
+    .fetch(&[] as &[&str], Some(&mut fetch_options), None)
+    .map_err(|e| Error::other(format!("Could not fetch: {}", e.message())))
+}
+
+/// Fails if tracked files of a checkout were changed, so they are not overwritten.
+fn ensure_clean(repo: &Repository) -> Result<(), Error> {
+  let changes = repo
+    .statuses(Some(StatusOptions::new().include_untracked(false)))
+    .map_err(git)?;
+  match changes.is_empty() {
+    true => Ok(()),
+    false => Err(Error::other("There are uncommitted changes")),
+  }
+}
+
+/// Authenticates with the credentials in GIT_USER and GIT_PAT when they are set, and otherwise
+/// the way git would: keys from the ssh agent, the configured credential.helper and, as a last
+/// resort, the default credentials of the platform. Each is tried once, since libgit2 asks again
+/// as long as the server rejects what it is given.
+fn credentials<'a>() -> RemoteCallbacks<'a> {
+  let mut callbacks = RemoteCallbacks::new();
+  let mut tried_env = false;
+  let mut tried_agent = false;
+  let mut tried_helper = false;
+  let mut tried_default = false;
+  callbacks.credentials(move |url, username, allowed| {
+    if allowed.contains(CredentialType::USERNAME) {
+      return Cred::username(username.unwrap_or("git"));
+    }
+    if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) && !tried_env {
+      tried_env = true;
+      if let (Ok(user), Ok(pass)) = (env::var("GIT_USER"), env::var("GIT_PAT")) {
+        return Cred::userpass_plaintext(&user, &pass);
+      }
+    }
+    if allowed.contains(CredentialType::SSH_KEY) && !tried_agent {
+      tried_agent = true;
+      return Cred::ssh_key_from_agent(username.unwrap_or("git"));
+    }
+    if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) && !tried_helper {
+      tried_helper = true;
+      if let Ok(config) = Config::open_default() {
+        if let Ok(cred) = Cred::credential_helper(&config, url, username) {
+          return Ok(cred);
+        }
+      }
+    }
+    if allowed.contains(CredentialType::DEFAULT) && !tried_default {
+      tried_default = true;
+      return Cred::default();
+    }
+    Err(git2::Error::from_str(
+      "No credentials were accepted, set up ssh-agent or a git credential helper, or set GIT_USER and GIT_PAT",
+    ))
+  });
+  callbacks
+}
+
+/// Connects to a repository with the credentials clone_repo uses, without fetching anything.
+pub fn check_access(repo_url: &str) -> Result<(), Error> {
+  // Remotes belong to a repository, so a throwaway bare one is created for the connection.
+  let dir = env::temp_dir().join(format!("conductor-remote-{}", std::process::id()));
+  let result = Repository::init_bare(&dir).and_then(|repo| {
+    let mut remote = repo.remote_anonymous(repo_url)?;
+    remote
+      .connect_auth(Direction::Fetch, Some(credentials()), None)
+      .map(|_| ())
+  });
+  let _ = fs::remove_dir_all(&dir);
+  result.map_err(|e| Error::other(format!("Could not reach repository: {}", e)))
+}
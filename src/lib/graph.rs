@@ -0,0 +1,178 @@
+use crate::Project;
+use std::fmt::Write;
+use std::str::FromStr;
+
+/// The formats a project's graph can be written in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+  Dot,
+  Mermaid,
+}
+
+impl FromStr for Format {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "dot" => Ok(Format::Dot),
+      "mermaid" => Ok(Format::Mermaid),
+      _ => Err(format!("Unknown graph format {}", s)),
+    }
+  }
+}
+
+#[derive(PartialEq)]
+enum Kind {
+  Component,
+  Service,
+  Group,
+}
+
+struct Node {
+  id: String,
+  label: String,
+  kind: Kind,
+}
+
+enum Relation {
+  /// A component waits for another component to be ready before starting.
+  WaitsFor,
+  /// A component starts a service.
+  Uses,
+  /// A group contains a component or another group.
+  Contains,
+}
+
+struct Edge {
+  from: usize,
+  to: usize,
+  relation: Relation,
+}
+
+/// The components, services and groups of a project and the edges between them. Names that do
+/// not refer to anything are left out, validate reports them.
+fn build(project: &Project) -> (Vec<Node>, Vec<Edge>) {
+  let mut nodes = vec![];
+  let node = |kind: Kind, prefix: &str, i: usize, label: &str| Node {
+    id: format!("{}{}", prefix, i),
+    label: label.to_string(),
+    kind,
+  };
+  for (i, c) in project.components.iter().enumerate() {
+    nodes.push(node(Kind::Component, "c", i, &c.name));
+  }
+  for (i, s) in project.services.iter().enumerate() {
+    nodes.push(node(Kind::Service, "s", i, &s.name));
+  }
+  for (i, g) in project.groups.iter().enumerate() {
+    nodes.push(node(Kind::Group, "g", i, &g.name));
+  }
+  let find = |nodes: &[Node], kinds: &[Kind], name: &str| {
+    nodes
+      .iter()
+      .position(|n| kinds.contains(&n.kind) && n.label.to_lowercase() == name.to_lowercase())
+  };
+
+  let mut edges = vec![];
+  for c in project.components.iter() {
+    let from = find(&nodes, &[Kind::Component], &c.name).unwrap();
+    let waits_for = c.wait_for.as_ref().and_then(|w| w.component.as_ref());
+    if let Some(to) = waits_for.and_then(|name| find(&nodes, &[Kind::Component], name)) {
+      edges.push(Edge {
+        from,
+        to,
+        relation: Relation::WaitsFor,
+      });
+    }
+    for to in c
+      .services
+      .iter()
+      .filter_map(|name| find(&nodes, &[Kind::Service], name))
+    {
+      edges.push(Edge {
+        from,
+        to,
+        relation: Relation::Uses,
+      });
+    }
+  }
+  for g in project.groups.iter() {
+    let from = find(&nodes, &[Kind::Group], &g.name).unwrap();
+    // Components take precedence over groups of the same name, as when a group is run.
+    for to in g
+      .components
+      .iter()
+      .filter_map(|name| find(&nodes, &[Kind::Component, Kind::Group], name))
+    {
+      edges.push(Edge {
+        from,
+        to,
+        relation: Relation::Contains,
+      });
+    }
+  }
+  (nodes, edges)
+}
+
+/// Writes the graph of a project's components, services and groups.
+pub fn graph(project: &Project, format: Format) -> String {
+  let (nodes, edges) = build(project);
+  match format {
+    Format::Dot => dot(&project.name, &nodes, &edges),
+    Format::Mermaid => mermaid(&nodes, &edges),
+  }
+}
+
+fn dot(name: &str, nodes: &[Node], edges: &[Edge]) -> String {
+  let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+  let mut out = format!("digraph {} {{\n  rankdir=LR;\n", quote(name));
+  for n in nodes.iter() {
+    let shape = match n.kind {
+      Kind::Component => "box",
+      Kind::Service => "cylinder",
+      Kind::Group => "folder",
+    };
+    let _ = writeln!(
+      out,
+      "  {} [label={}, shape={}];",
+      n.id,
+      quote(&n.label),
+      shape
+    );
+  }
+  for e in edges.iter() {
+    let style = match e.relation {
+      Relation::WaitsFor => "label=\"waits for\"",
+      Relation::Uses => "label=\"uses\", style=dashed",
+      Relation::Contains => "style=dotted, arrowhead=none",
+    };
+    let _ = writeln!(
+      out,
+      "  {} -> {} [{}];",
+      nodes[e.from].id, nodes[e.to].id, style
+    );
+  }
+  out.push_str("}\n");
+  out
+}
+
+fn mermaid(nodes: &[Node], edges: &[Edge]) -> String {
+  let mut out = String::from("flowchart LR\n");
+  for n in nodes.iter() {
+    let label = n.label.replace('"', "#quot;");
+    let _ = match n.kind {
+      Kind::Component => writeln!(out, "  {}[\"{}\"]", n.id, label),
+      Kind::Service => writeln!(out, "  {}[(\"{}\")]", n.id, label),
+      Kind::Group => writeln!(out, "  {}{{{{\"{}\"}}}}", n.id, label),
+    };
+  }
+  for e in edges.iter() {
+    let arrow = match e.relation {
+      Relation::WaitsFor => "-->|waits for|",
+      Relation::Uses => "-.->|uses|",
+      Relation::Contains => "-.-",
+    };
+    let _ = writeln!(out, "  {} {} {}", nodes[e.from].id, arrow, nodes[e.to].id);
+  }
+  out
+}
@@ -1,10 +1,13 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-#[derive(Deserialize, PartialEq, Clone)]
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
 pub struct Group {
   pub name: String,
   pub components: Vec<String>,
   #[serde(default)]
   pub env: HashMap<String, String>,
+  /// Starts the components one at a time, each once the one before it is ready or has finished.
+  #[serde(default)]
+  pub serial: bool,
 }
@@ -0,0 +1,94 @@
+use crate::service::ServiceType;
+use crate::{Component, Project};
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+/// Limits that guard against launching far more than intended, such as every component of a
+/// large project when only one group was wanted. A limit of 0 disables the check.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(default)]
+pub struct Guardrails {
+  /// The most processes, components and process services, launched at once.
+  pub max_processes: usize,
+  /// The most docker containers launched at once.
+  pub max_containers: usize,
+  /// The memory each process is estimated to use, in megabytes.
+  pub process_memory_mb: u64,
+  /// The memory each container is estimated to use, in megabytes.
+  pub container_memory_mb: u64,
+}
+
+impl Default for Guardrails {
+  fn default() -> Self {
+    Guardrails {
+      max_processes: 20,
+      max_containers: 10,
+      process_memory_mb: 250,
+      container_memory_mb: 500,
+    }
+  }
+}
+
+/// Estimates what launching the components would start and describes every limit it exceeds.
+/// The estimate is compared against the available memory where it can be determined.
+pub fn check(project: &Project, components: &[&Component]) -> Vec<String> {
+  let limits = &project.guardrails;
+  let mut services: Vec<String> = components
+    .iter()
+    .flat_map(|c| c.services.iter().map(|s| s.to_lowercase()))
+    .collect();
+  services.sort();
+  services.dedup();
+  let services: Vec<_> = services
+    .iter()
+    .filter_map(|name| project.service_by_name(name))
+    .collect();
+  let containers = services
+    .iter()
+    .filter(|s| s.service_type == ServiceType::DockerContainer)
+    .count();
+  let processes = components.len() + services.len() - containers;
+
+  let mut problems = vec![];
+  if limits.max_processes > 0 && processes > limits.max_processes {
+    problems.push(format!(
+      "About to launch {} processes, more than the limit of {}",
+      processes, limits.max_processes
+    ));
+  }
+  if limits.max_containers > 0 && containers > limits.max_containers {
+    problems.push(format!(
+      "About to launch {} containers, more than the limit of {}",
+      containers, limits.max_containers
+    ));
+  }
+  let estimate =
+    processes as u64 * limits.process_memory_mb + containers as u64 * limits.container_memory_mb;
+  if let Some(available) = available_memory_mb() {
+    if estimate > available {
+      problems.push(format!(
+        "About {} MB of memory is needed but only {} MB is available",
+        estimate, available
+      ));
+    }
+  }
+  problems
+}
+
+/// The memory available for new processes, read from /proc/meminfo. Other platforms skip the
+/// memory check.
+fn available_memory_mb() -> Option<u64> {
+  let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+  meminfo
+    .lines()
+    .find_map(|line| line.strip_prefix("MemAvailable:"))
+    .and_then(|value| {
+      value
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse::<u64>()
+        .ok()
+    })
+    .map(|kb| kb / 1024)
+}
@@ -0,0 +1,310 @@
+use crate::control::Request;
+use crate::metrics::Metrics;
+use crate::platform;
+use crate::random;
+use crate::scrollback::Scrollback;
+use crate::state::SessionState;
+use crate::supervisor::submit;
+use crate::Command;
+use crossbeam::channel::Sender;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// How long a client may take to send its request before the connection is dropped.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+/// The most bytes read of a request's line and headers, the rest is ignored.
+const MAX_HEAD: u64 = 16 * 1024;
+
+/// A response to a request.
+struct Response {
+  status: &'static str,
+  content_type: &'static str,
+  body: String,
+}
+
+impl Response {
+  fn ok(body: serde_json::Value) -> Self {
+    Response {
+      status: "200 OK",
+      content_type: "application/json",
+      body: body.to_string(),
+    }
+  }
+
+  fn text(body: String) -> Self {
+    Response {
+      status: "200 OK",
+      content_type: "text/plain; version=0.0.4",
+      body,
+    }
+  }
+
+  fn error(status: &'static str, error: impl ToString) -> Self {
+    Response {
+      status,
+      content_type: "application/json",
+      body: serde_json::json!({ "error": error.to_string() }).to_string(),
+    }
+  }
+}
+
+/// The HTTP server of the running session. Its token is removed when it is dropped.
+pub struct Server {
+  /// The address being listened on.
+  pub address: SocketAddr,
+  token_path: PathBuf,
+}
+
+impl Drop for Server {
+  fn drop(&mut self) {
+    let _ = fs::remove_file(&self.token_path);
+  }
+}
+
+/// The location of the token requests to the HTTP server of a project rooted at the given path
+/// must carry. A new token is written each time a session starts listening.
+pub fn token_path(root_path: &Path) -> PathBuf {
+  root_path.join(".conductor").join("http.token")
+}
+
+/// What requests are served from.
+struct Session {
+  token: String,
+  root_path: PathBuf,
+  requests: Sender<Request>,
+  metrics: Arc<Metrics>,
+  scrollback: Arc<Scrollback>,
+}
+
+/// Serves the state of the session over HTTP so tools such as status bars and editor plugins can
+/// show the health of the environment, and accepts commands for it. Each connection carries a
+/// single request, which must carry the session's token as a bearer token. Requests naming a host
+/// other than the loopback interface, or sent from a page of another origin, are refused so web
+/// pages cannot reach the server through DNS rebinding or cross-site requests.
+pub fn listen(
+  address: &str,
+  root_path: &Path,
+  requests: Sender<Request>,
+  metrics: Arc<Metrics>,
+  scrollback: Arc<Scrollback>,
+) -> io::Result<Server> {
+  let listener = TcpListener::bind(address)?;
+  let local = listener.local_addr()?;
+  let token = random::hex(32)?;
+  let token_path = token_path(root_path);
+  if let Some(dir) = token_path.parent() {
+    fs::create_dir_all(dir)?;
+  }
+  // The token of an earlier session is replaced rather than trusted.
+  let _ = fs::remove_file(&token_path);
+  platform::create_private(&token_path)?.write_all(token.as_bytes())?;
+  let session = Arc::new(Session {
+    token,
+    root_path: root_path.to_path_buf(),
+    requests,
+    metrics,
+    scrollback,
+  });
+  thread::spawn(move || {
+    for stream in listener.incoming().filter_map(Result::ok) {
+      let session = session.clone();
+      thread::spawn(move || {
+        if let Err(e) = serve(stream, &session) {
+          log::warn!("Could not serve HTTP request: {}", e);
+        }
+      });
+    }
+  });
+  Ok(Server {
+    address: local,
+    token_path,
+  })
+}
+
+fn serve(stream: TcpStream, session: &Session) -> io::Result<()> {
+  stream.set_read_timeout(Some(READ_TIMEOUT))?;
+  let mut reader = BufReader::new((&stream).take(MAX_HEAD));
+  let mut request = String::new();
+  reader.read_line(&mut request)?;
+  let mut headers = Headers::default();
+  let mut header = String::new();
+  while reader.read_line(&mut header)? > 2 {
+    headers.add(&header);
+    header.clear();
+  }
+  let mut parts = request.split_whitespace();
+  let method = parts.next().unwrap_or_default();
+  let path = parts.next().unwrap_or_default();
+  let response = match headers.refusal(&session.token) {
+    Some(refusal) => refusal,
+    None => route(method, path, session),
+  };
+  write!(
+    &stream,
+    "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+    response.status,
+    response.content_type,
+    response.body.len(),
+    response.body
+  )
+}
+
+/// The headers of a request that decide whether it is served.
+#[derive(Default)]
+struct Headers {
+  host: Option<String>,
+  origin: Option<String>,
+  authorization: Option<String>,
+}
+
+impl Headers {
+  fn add(&mut self, line: &str) {
+    let (name, value) = match line.split_once(':') {
+      Some(header) => header,
+      None => return,
+    };
+    let value = Some(value.trim().to_string());
+    match name.trim().to_ascii_lowercase().as_str() {
+      "host" => self.host = value,
+      "origin" => self.origin = value,
+      "authorization" => self.authorization = value,
+      _ => {}
+    }
+  }
+
+  /// The response refusing the request, if it should not be served.
+  fn refusal(&self, token: &str) -> Option<Response> {
+    let host = self.host.as_deref().unwrap_or_default();
+    if !is_loopback(host) {
+      return Some(Response::error(
+        "403 Forbidden",
+        format!("Requests for host '{}' are not served", host),
+      ));
+    }
+    if let Some(origin) = &self.origin {
+      if origin.strip_prefix("http://") != Some(host) {
+        return Some(Response::error(
+          "403 Forbidden",
+          format!("Requests from '{}' are not served", origin),
+        ));
+      }
+    }
+    let given = self
+      .authorization
+      .as_deref()
+      .and_then(|value| value.strip_prefix("Bearer "))
+      .unwrap_or_default();
+    if !same_token(given.trim(), token) {
+      return Some(Response::error(
+        "401 Unauthorized",
+        "Expected the token in .conductor/http.token as a bearer token",
+      ));
+    }
+    None
+  }
+}
+
+/// Whether a Host header names the loopback interface, such as `localhost:3939`, `127.0.0.1` or
+/// `[::1]:3939`.
+fn is_loopback(host: &str) -> bool {
+  let name = host_name(host);
+  name.eq_ignore_ascii_case("localhost")
+    || name
+      .parse::<IpAddr>()
+      .map(|ip| ip.is_loopback())
+      .unwrap_or(false)
+}
+
+/// The host named by a Host header without its port. IPv6 addresses lose their brackets, so
+/// `[::1]:3939` names `::1`.
+pub(crate) fn host_name(host: &str) -> &str {
+  match host.strip_prefix('[') {
+    Some(rest) => rest.split(']').next().unwrap_or_default(),
+    None => host.split(':').next().unwrap_or_default(),
+  }
+}
+
+/// Compares tokens in time that does not depend on where they differ.
+fn same_token(given: &str, token: &str) -> bool {
+  given.len() == token.len()
+    && given
+      .bytes()
+      .zip(token.bytes())
+      .fold(0, |diff, (a, b)| diff | (a ^ b))
+      == 0
+}
+
+fn route(method: &str, path: &str, session: &Session) -> Response {
+  let (path, query) = path.split_once('?').unwrap_or((path, ""));
+  let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+  let command = match segments.as_slice() {
+    ["status"] if method == "GET" => return status(&session.root_path),
+    ["metrics"] if method == "GET" => return metrics(session),
+    ["components", name, "output"] if method == "GET" => return output(name, query, session),
+    ["components", name, "start"] => Command::Start(name.to_string()),
+    ["components", name, "stop"] => Command::Stop(name.to_string()),
+    ["components", name, "restart"] => Command::Restart(name.to_string()),
+    ["components", name, "pause"] => Command::Pause(name.to_string()),
+    ["components", name, "resume"] => Command::Resume(name.to_string()),
+    ["tasks", name, "run"] => Command::RunTask(name.to_string()),
+    ["status"] | ["metrics"] | ["components", _, "output"] => {
+      return Response::error("405 Method Not Allowed", "Only GET is supported")
+    }
+    _ => return Response::error("404 Not Found", format!("Nothing at {}", path)),
+  };
+  if method != "POST" {
+    return Response::error("405 Method Not Allowed", "Only POST is supported");
+  }
+  match submit(&session.requests, command) {
+    Ok(()) => Response::ok(serde_json::json!({ "ok": true })),
+    Err(e) => Response::error("409 Conflict", e),
+  }
+}
+
+/// The recent output of a component kept in memory, all of it or the last `lines` lines.
+fn output(name: &str, query: &str, session: &Session) -> Response {
+  let lines = query
+    .split('&')
+    .find_map(|pair| pair.strip_prefix("lines="))
+    .map(|n| n.parse::<usize>());
+  let count = match lines.transpose() {
+    Ok(count) => count,
+    Err(_) => return Response::error("400 Bad Request", "Expected a number of lines"),
+  };
+  match session.scrollback.tail(name, count) {
+    Some(lines) => Response::ok(serde_json::json!({ "component": name, "lines": lines })),
+    None => Response::error("404 Not Found", format!("No output of {}", name)),
+  }
+}
+
+/// The session state along with how long each running component has been up.
+fn status(root_path: &Path) -> Response {
+  let state = match SessionState::read(&PathBuf::from(root_path)) {
+    Ok(state) => state,
+    Err(e) => return Response::error("503 Service Unavailable", e),
+  };
+  let now = SystemTime::now();
+  let mut body = match serde_json::to_value(&state) {
+    Ok(body) => body,
+    Err(e) => return Response::error("500 Internal Server Error", e),
+  };
+  if let Some(components) = body["components"].as_array_mut() {
+    for (value, component) in components.iter_mut().zip(state.components.iter()) {
+      value["uptime_secs"] = serde_json::json!(component.uptime(now));
+    }
+  }
+  Response::ok(body)
+}
+
+/// The session's metrics in the Prometheus text format.
+fn metrics(session: &Session) -> Response {
+  match SessionState::read(&session.root_path) {
+    Ok(state) => Response::text(session.metrics.render(&state, SystemTime::now())),
+    Err(e) => Response::error("503 Service Unavailable", e),
+  }
+}
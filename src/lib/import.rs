@@ -0,0 +1,298 @@
+use crate::scaffold::quote;
+use crate::Project;
+use serde_yaml::{Mapping, Value};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// What importing did to a component.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change {
+  Added(String),
+  Updated(String),
+}
+
+/// Reads the processes of a Procfile as pairs of names and commands. Blank lines and comments
+/// are skipped.
+pub fn read_procfile(contents: &str) -> io::Result<Vec<(String, String)>> {
+  let mut processes = vec![];
+  for (n, line) in contents.lines().enumerate() {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+    let (name, command) = line
+      .split_once(':')
+      .map(|(name, command)| (name.trim(), command.trim()))
+      .filter(|(name, command)| !name.is_empty() && !command.is_empty())
+      .ok_or_else(|| {
+        io::Error::new(
+          io::ErrorKind::InvalidData,
+          format!("Line {} is not a process, expected name: command", n + 1),
+        )
+      })?;
+    processes.push((name.to_string(), command.to_string()));
+  }
+  Ok(processes)
+}
+
+/// Adds a component to the config file for each process in a Procfile. Components that already
+/// exist get the process' command as their start command. The components run in the Procfile's
+/// directory. The file is rewritten in its own format, which does not keep comments.
+pub fn procfile(project: &Project, config: &Path, procfile: &Path) -> io::Result<Vec<Change>> {
+  let processes = read_procfile(&fs::read_to_string(procfile)?)?;
+  let dir = procfile
+    .parent()
+    .and_then(|dir| fs::canonicalize(dir).ok())
+    .unwrap_or_default();
+  let root = fs::canonicalize(&project.root_path)?;
+  let path = match dir.strip_prefix(&root) {
+    Ok(relative) if relative.as_os_str().is_empty() => ".".to_string(),
+    Ok(relative) => relative.display().to_string(),
+    Err(_) => dir.display().to_string(),
+  };
+
+  let contents = fs::read_to_string(config)?;
+  let mut value = read(config, &contents)?;
+  let config_map = value
+    .as_mapping_mut()
+    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "The config is not a map"))?;
+  let key = Value::from("components");
+  if !config_map.contains_key(&key) {
+    config_map.insert(key.clone(), Value::Sequence(vec![]));
+  }
+  let components = config_map
+    .get_mut(&key)
+    .and_then(Value::as_sequence_mut)
+    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "components is not a list"))?;
+
+  let mut changes = vec![];
+  for (name, command) in processes {
+    let existing = components
+      .iter_mut()
+      .filter_map(Value::as_mapping_mut)
+      .find(|c| field(c, "name").map(|n| n.to_lowercase()) == Some(name.to_lowercase()));
+    match existing {
+      Some(component) => {
+        if field(component, "start") != Some(command.as_str()) {
+          component.insert("start".into(), command.into());
+          changes.push(Change::Updated(name));
+        }
+      }
+      // Components from included files are left alone rather than defined a second time.
+      None if project.find_component(&name).is_some() => {}
+      None => {
+        let mut component = Mapping::new();
+        component.insert("name".into(), name.clone().into());
+        component.insert("path".into(), path.clone().into());
+        component.insert("start".into(), command.into());
+        components.push(Value::Mapping(component));
+        changes.push(Change::Added(name));
+      }
+    }
+  }
+  if !changes.is_empty() {
+    fs::write(config, write(config, &value)?)?;
+  }
+  Ok(changes)
+}
+
+/// A component added with `conductor add component`. Empty values are left out.
+pub struct NewComponent {
+  pub name: String,
+  pub repo: String,
+  pub path: String,
+  pub start: String,
+}
+
+impl NewComponent {
+  fn fields(&self) -> Vec<(&'static str, &str)> {
+    [
+      ("name", self.name.as_str()),
+      ("repo", self.repo.as_str()),
+      ("path", self.path.as_str()),
+      ("start", self.start.as_str()),
+    ]
+    .iter()
+    .copied()
+    .filter(|(_, value)| !value.is_empty())
+    .collect()
+  }
+}
+
+/// Adds a component to the end of the components in a config file. YAML configs are edited as
+/// text so their comments and layout are kept. Other formats are rewritten as with procfile.
+pub fn component(config: &Path, component: &NewComponent) -> io::Result<()> {
+  let contents = fs::read_to_string(config)?;
+  let yaml = !matches!(
+    config.extension().and_then(|e| e.to_str()),
+    Some("toml" | "json")
+  );
+  let edited = match yaml {
+    true => append_yaml(&contents, component).filter(|edited| {
+      // The edit is only kept if the component ends up where it was meant to.
+      let value = read(config, edited).ok();
+      let components = value
+        .as_ref()
+        .and_then(|v| v.get("components"))
+        .and_then(Value::as_sequence);
+      components
+        .and_then(|c| c.last())
+        .and_then(Value::as_mapping)
+        .and_then(|c| field(c, "name"))
+        == Some(component.name.as_str())
+    }),
+    false => None,
+  };
+  let edited = match edited {
+    Some(edited) => edited,
+    None => {
+      let mut value = read(config, &contents)?;
+      let config_map = value
+        .as_mapping_mut()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "The config is not a map"))?;
+      let key = Value::from("components");
+      if !config_map.contains_key(&key) {
+        config_map.insert(key.clone(), Value::Sequence(vec![]));
+      }
+      let components = config_map
+        .get_mut(&key)
+        .and_then(Value::as_sequence_mut)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "components is not a list"))?;
+      let mut map = Mapping::new();
+      for (key, value) in component.fields() {
+        map.insert(key.into(), value.into());
+      }
+      components.push(Value::Mapping(map));
+      write(config, &value)?
+    }
+  };
+  fs::write(config, edited)
+}
+
+/// Adds a component block after the last line of the top level components list, in the list's
+/// indentation. Returns None if the list is not written as a block the component can be added
+/// to.
+fn append_yaml(contents: &str, component: &NewComponent) -> Option<String> {
+  let lines: Vec<&str> = contents.lines().collect();
+  let top_level = |line: &str| !line.starts_with([' ', '\t', '#']) && !line.trim().is_empty();
+  let start = lines.iter().position(|l| l.starts_with("components:"));
+  let (mut out, indent, rest) = match start {
+    None => {
+      let mut out = contents.trim_end().to_string();
+      out.push_str("\n\ncomponents:\n");
+      (out, String::new(), vec![])
+    }
+    Some(start) => {
+      let value = lines[start]["components:".len()..].trim();
+      if !(value.is_empty() || value == "[]" || value.starts_with('#')) {
+        return None;
+      }
+      // The list ends at the next top level key, comments and blank lines before it belong to
+      // that key.
+      let mut end = start + 1;
+      let mut last = start;
+      while end < lines.len() && (!top_level(lines[end]) || lines[end].starts_with('-')) {
+        if !lines[end].trim().is_empty() && !lines[end].trim_start().starts_with('#') {
+          last = end;
+        }
+        end += 1;
+      }
+      let indent = lines[start + 1..=last]
+        .iter()
+        .find(|l| l.trim_start().starts_with('-'))
+        .map(|l| l[..l.len() - l.trim_start().len()].to_string())
+        .unwrap_or_default();
+      let mut out: Vec<String> = lines[..=last].iter().map(|l| l.to_string()).collect();
+      if value == "[]" {
+        out[start] = "components:".into();
+      }
+      let rest = lines[last + 1..].iter().map(|l| l.to_string()).collect();
+      (out.join("\n") + "\n", indent, rest)
+    }
+  };
+  for (n, (key, value)) in component.fields().into_iter().enumerate() {
+    let marker = if n == 0 { "- " } else { "  " };
+    out.push_str(&format!("{}{}{}: {}\n", indent, marker, key, quote(value)));
+  }
+  for line in rest {
+    out.push_str(&line);
+    out.push('\n');
+  }
+  Some(out)
+}
+
+fn field<'a>(map: &'a Mapping, key: &str) -> Option<&'a str> {
+  map.get(&Value::from(key)).and_then(Value::as_str)
+}
+
+/// Reads a config file into a generic value so it can be changed without losing settings this
+/// version does not know about. The order of keys is kept.
+fn read(path: &Path, contents: &str) -> io::Result<Value> {
+  let value = match path.extension().and_then(|e| e.to_str()) {
+    Some("toml") => toml::from_str(contents).map_err(io::Error::other)?,
+    Some("json") => serde_json::from_str(contents)?,
+    _ => serde_yaml::from_str(contents).map_err(io::Error::other)?,
+  };
+  Ok(value)
+}
+
+fn write(path: &Path, value: &Value) -> io::Result<String> {
+  let contents = match path.extension().and_then(|e| e.to_str()) {
+    Some("toml") => {
+      let value = toml::Value::try_from(value).map_err(io::Error::other)?;
+      toml::to_string_pretty(&value).map_err(io::Error::other)?
+    }
+    Some("json") => serde_json::to_string_pretty(value)? + "\n",
+    _ => serde_yaml::to_string(value).map_err(io::Error::other)? + "\n",
+  };
+  Ok(contents)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn new_component(name: &str, start: &str) -> NewComponent {
+    NewComponent {
+      name: name.into(),
+      repo: String::new(),
+      path: String::new(),
+      start: start.into(),
+    }
+  }
+
+  #[test]
+  fn appends_to_the_components_list() {
+    let config = "name: app\n\ncomponents:\n  # The api\n  - name: api\n    start: make\n\n# Tasks\ntasks: {}\n";
+    let edited = append_yaml(config, &new_component("web", "npm start")).unwrap();
+    assert_eq!(
+      edited,
+      "name: app\n\ncomponents:\n  # The api\n  - name: api\n    start: make\n  - name: 'web'\n    start: 'npm start'\n\n# Tasks\ntasks: {}\n"
+    );
+  }
+
+  #[test]
+  fn adds_a_components_list() {
+    let web = new_component("web", "npm start");
+    let edited = append_yaml("name: app\n", &web).unwrap();
+    assert_eq!(
+      edited,
+      "name: app\n\ncomponents:\n- name: 'web'\n  start: 'npm start'\n"
+    );
+    let edited = append_yaml("name: app\ncomponents: []\n", &web).unwrap();
+    assert_eq!(
+      edited,
+      "name: app\ncomponents:\n- name: 'web'\n  start: 'npm start'\n"
+    );
+  }
+
+  #[test]
+  fn leaves_lists_that_are_not_blocks_alone() {
+    let config = "components: [{name: api, start: make}]\n";
+    assert_eq!(
+      append_yaml(config, &new_component("web", "npm start")),
+      None
+    );
+  }
+}
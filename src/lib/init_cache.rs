@@ -0,0 +1,81 @@
+use crate::Component;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Files whose changes call for running init commands again when a component does not list its
+/// own: the manifests and lockfiles of common package managers.
+const MANIFESTS: &[&str] = &[
+  "package.json",
+  "package-lock.json",
+  "yarn.lock",
+  "pnpm-lock.yaml",
+  "Cargo.toml",
+  "Cargo.lock",
+  "Gemfile",
+  "Gemfile.lock",
+  "requirements.txt",
+  "Pipfile.lock",
+  "poetry.lock",
+  "go.mod",
+  "go.sum",
+  "composer.json",
+  "composer.lock",
+  "mix.exs",
+  "mix.lock",
+];
+
+/// Where the checksum of a component's last successful init is kept.
+fn record_path(root_path: &Path, component: &Component) -> PathBuf {
+  root_path
+    .join(".conductor")
+    .join("init")
+    .join(&component.name)
+}
+
+/// 64 bit FNV-1a, which stays the same across builds unlike the hashers of the standard library.
+fn fnv(hash: u64, bytes: &[u8]) -> u64 {
+  bytes.iter().fold(hash, |hash, byte| {
+    (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+  })
+}
+
+/// A checksum of a component's init commands and the contents of its init inputs, missing
+/// files included.
+fn checksum(component: &Component, path: &Path) -> String {
+  let mut hash = 0xcbf29ce484222325;
+  for command in component.init_commands().into_iter().flatten() {
+    hash = fnv(hash, command.as_bytes());
+    hash = fnv(hash, &[0]);
+  }
+  let inputs: Vec<&str> = match component.init_inputs.is_empty() {
+    true => MANIFESTS.to_vec(),
+    false => component.init_inputs.iter().map(String::as_str).collect(),
+  };
+  for input in inputs {
+    hash = fnv(hash, input.as_bytes());
+    if let Ok(contents) = fs::read(path.join(input)) {
+      hash = fnv(hash, &[1]);
+      hash = fnv(hash, &contents);
+    }
+    hash = fnv(hash, &[0]);
+  }
+  format!("{:016x}", hash)
+}
+
+/// Returns true if a component's init commands and inputs are the same as when its init last
+/// succeeded, so there is no need to run it again.
+pub fn unchanged(root_path: &Path, component: &Component, path: &Path) -> bool {
+  fs::read_to_string(record_path(root_path, component))
+    .map(|recorded| recorded.trim() == checksum(component, path))
+    .unwrap_or(false)
+}
+
+/// Remembers the inputs of a successful init.
+pub fn record(root_path: &Path, component: &Component, path: &Path) -> io::Result<()> {
+  let record = record_path(root_path, component);
+  if let Some(dir) = record.parent() {
+    fs::create_dir_all(dir)?;
+  }
+  fs::write(record, checksum(component, path) + "\n")
+}
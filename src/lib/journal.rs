@@ -0,0 +1,387 @@
+use crate::record;
+use crate::supervisor::{ComponentEvent, ComponentEventBody};
+use crate::{Message, TerminalColor};
+use crossbeam::channel::{unbounded, Sender};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+use std::thread::{self, JoinHandle};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// When a component's logs are rotated and how many of the rotated files are kept. Rotated files
+/// are numbered from `<log>.1`, the most recent, upward.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(default)]
+pub struct LogRotation {
+  /// The size a log grows to before it is rotated. The logs under `.conductor` use the project's
+  /// `log_max_bytes` when unset, log files chosen with `log_file` or `--log-dir` are not rotated.
+  pub max_bytes: Option<u64>,
+  /// The number of rotated files kept. With none a full log is started over.
+  pub keep: usize,
+}
+
+impl Default for LogRotation {
+  fn default() -> Self {
+    LogRotation {
+      max_bytes: None,
+      keep: 1,
+    }
+  }
+}
+
+/// Records a session under `.conductor` so it can be inspected after the fact. Lifecycle events
+/// are written to `events.log` and every component event and message to `events.jsonl` in the
+/// format of a recording, both truncated at the start of each session. With `--record` the same
+/// lines are written to the recording. Each component's output is written to
+/// `logs/<component>.log` with timestamps and kept across sessions. Once a log grows past its
+/// limit it is rotated, see LogRotation.
+///
+/// The files are written on a thread of their own so the session never waits for the disk. The
+/// thread finishes writing what it was given when the journal is dropped.
+pub struct Journal {
+  root_path: PathBuf,
+  max_log_bytes: u64,
+  started: Instant,
+  writes: Option<Sender<Write>>,
+  writer: Option<JoinHandle<()>>,
+}
+
+/// What the writing thread is asked to do.
+enum Write {
+  /// Appends a line to a file, rotating it once it grows past the limit.
+  Line {
+    path: PathBuf,
+    line: String,
+    keep: bool,
+    limit: Option<u64>,
+    rotated: usize,
+  },
+  /// Writes a line of `events.jsonl` and the recording.
+  Event(String),
+  /// Starts writing the events to a recording as well.
+  Record(File),
+}
+
+struct OpenFile {
+  file: File,
+  len: u64,
+}
+
+impl Journal {
+  pub fn new(root_path: &Path, max_log_bytes: u64) -> Self {
+    let (writes, received) = unbounded();
+    let events = Journal::json_events_path(root_path);
+    let writer = thread::spawn(move || {
+      let mut files = HashMap::new();
+      let mut recording: Option<File> = None;
+      for write in received {
+        match write {
+          Write::Line {
+            path,
+            line,
+            keep,
+            limit,
+            rotated,
+          } => {
+            if append(&mut files, &path, &line, keep, limit) {
+              rotate(&path, rotated);
+            }
+          }
+          Write::Event(line) => {
+            if append(&mut files, &events, &line, false, Some(max_log_bytes)) {
+              rotate(&events, 1);
+            }
+            if let Some(file) = recording.as_mut() {
+              let _ = writeln!(file, "{}", line);
+            }
+          }
+          Write::Record(file) => recording = Some(file),
+        }
+      }
+    });
+    Journal {
+      root_path: root_path.to_path_buf(),
+      max_log_bytes,
+      started: Instant::now(),
+      writes: Some(writes),
+      writer: Some(writer),
+    }
+  }
+
+  /// The location of the event log for a project rooted at the given path.
+  pub fn events_path(root_path: &Path) -> PathBuf {
+    root_path.join(".conductor").join("events.log")
+  }
+
+  /// The location of the component events of a project rooted at the given path, one line of
+  /// JSON each.
+  pub fn json_events_path(root_path: &Path) -> PathBuf {
+    root_path.join(".conductor").join("events.jsonl")
+  }
+
+  /// The directory holding the output of each component.
+  pub fn log_dir(root_path: &Path) -> PathBuf {
+    root_path.join(".conductor").join("logs")
+  }
+
+  /// The log a component's output is written to.
+  pub fn log_path(root_path: &Path, component: &str) -> PathBuf {
+    Journal::log_dir(root_path).join(format!("{}.log", component))
+  }
+
+  /// The older parts of a component's log that were moved aside when it reached its limit,
+  /// oldest first.
+  pub fn rotated_log_paths(root_path: &Path, component: &str) -> Vec<PathBuf> {
+    let path = Journal::log_path(root_path, component);
+    let mut rotated: Vec<PathBuf> = (1..)
+      .map(|n| rotated_path(&path, n))
+      .take_while(|p| p.exists())
+      .collect();
+    rotated.reverse();
+    rotated
+  }
+
+  /// Writes the events of the session to a recording as well, from now on.
+  pub fn record(&self, path: &Path) -> io::Result<()> {
+    self.write(Write::Record(File::create(path)?));
+    Ok(())
+  }
+
+  /// Records a lifecycle event with a timestamp.
+  pub fn event(&self, msg: &str) {
+    self.write(Write::Line {
+      path: Journal::events_path(&self.root_path),
+      line: format!("{} {}", timestamp(), msg),
+      keep: false,
+      limit: None,
+      rotated: 0,
+    });
+  }
+
+  /// Records a component event. The color is the one the output is shown in, which for service
+  /// output is the service's. As the events include every line of output `events.jsonl` is
+  /// rotated like a component's log.
+  pub fn component(&self, event: &ComponentEvent, color: &TerminalColor) {
+    let (name, body) = event.body.describe();
+    let source = match &event.body {
+      ComponentEventBody::ServiceOutput { service_name, .. } => service_name,
+      _ => &event.component.name,
+    };
+    self.json_event(source, Some(color.clone()), name, body);
+  }
+
+  /// Records a message of the supervisor, such as an error.
+  pub fn message(&self, msg: &Message) {
+    let event = if msg.is_error() { "error" } else { "message" };
+    self.json_event("", None, event, msg.to_string().into());
+  }
+
+  fn json_event(
+    &self,
+    name: &str,
+    color: Option<TerminalColor>,
+    event: &str,
+    body: serde_json::Value,
+  ) {
+    let line = record::entry(self.started.elapsed(), name, color, event, body);
+    if let Some(line) = line {
+      self.write(Write::Event(line));
+    }
+  }
+
+  /// Records a line of output from a component, prefixed with the time.
+  pub fn output(&self, component: &str, line: &str, rotation: &LogRotation) {
+    self.write(Write::Line {
+      path: Journal::log_path(&self.root_path, component),
+      line: format!("{} {}", utc_timestamp(SystemTime::now()), line),
+      keep: true,
+      limit: Some(rotation.max_bytes.unwrap_or(self.max_log_bytes)),
+      rotated: rotation.keep,
+    });
+  }
+
+  /// Records a line in a log file chosen by the user, prefixed with the time. These files are
+  /// kept across sessions rather than truncated.
+  pub fn log(&self, path: &Path, line: &str, rotation: &LogRotation) {
+    self.write(Write::Line {
+      path: path.to_path_buf(),
+      line: format!("{} {}", utc_timestamp(SystemTime::now()), line),
+      keep: true,
+      limit: rotation.max_bytes,
+      rotated: rotation.keep,
+    });
+  }
+
+  fn write(&self, write: Write) {
+    if let Some(writes) = &self.writes {
+      let _ = writes.send(write);
+    }
+  }
+}
+
+impl Drop for Journal {
+  fn drop(&mut self) {
+    // Closing the channel ends the writing thread once it has written everything.
+    self.writes.take();
+    if let Some(writer) = self.writer.take() {
+      let _ = writer.join();
+    }
+  }
+}
+
+/// Writes a line to a file, opening it the first time. Returns true if the file has grown past
+/// the limit, in which case it is closed so the next line opens a new one.
+fn append(
+  files: &mut HashMap<PathBuf, OpenFile>,
+  path: &Path,
+  line: &str,
+  keep: bool,
+  limit: Option<u64>,
+) -> bool {
+  let result = match files.get_mut(path) {
+    Some(open) => writeln!(open.file, "{}", line).map(|_| open),
+    None => create(path, keep).and_then(|mut file| {
+      let len = file.metadata()?.len();
+      writeln!(file, "{}", line)?;
+      Ok(
+        files
+          .entry(path.to_path_buf())
+          .or_insert(OpenFile { file, len }),
+      )
+    }),
+  };
+  let full = match result {
+    Ok(open) => {
+      open.len += line.len() as u64 + 1;
+      limit.is_some_and(|limit| open.len >= limit)
+    }
+    Err(e) => {
+      warn!("Could not write to {}: {}", path.display(), e);
+      false
+    }
+  };
+  if full {
+    files.remove(path);
+  }
+  full
+}
+
+/// The path of the nth rotated file of a log.
+fn rotated_path(path: &Path, n: usize) -> PathBuf {
+  let mut name = path.as_os_str().to_owned();
+  name.push(format!(".{}", n));
+  PathBuf::from(name)
+}
+
+/// Moves a full log aside as `<log>.1`, shifting the files rotated before it up by one and
+/// removing those beyond the number kept.
+fn rotate(path: &Path, keep: usize) {
+  let result = match keep {
+    0 => fs::remove_file(path),
+    _ => {
+      let _ = fs::remove_file(rotated_path(path, keep));
+      for n in (1..keep).rev() {
+        let from = rotated_path(path, n);
+        if from.exists() {
+          let _ = fs::rename(&from, rotated_path(path, n + 1));
+        }
+      }
+      fs::rename(path, rotated_path(path, 1))
+    }
+  };
+  if let Err(e) = result {
+    warn!("Could not rotate {}: {}", path.display(), e);
+  }
+}
+
+/// Opens a file for writing, either truncating it or appending to what is already there.
+fn create(path: &Path, keep: bool) -> io::Result<File> {
+  if let Some(dir) = path.parent() {
+    fs::create_dir_all(dir)?;
+  }
+  OpenOptions::new()
+    .create(true)
+    .write(true)
+    .append(keep)
+    .truncate(!keep)
+    .open(path)
+}
+
+/// Formats a time as an RFC 3339 UTC timestamp with milliseconds.
+pub(crate) fn utc_timestamp(time: SystemTime) -> String {
+  let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+  let secs = since_epoch.as_secs();
+  let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+  // Converts days since the epoch to a civil date, see
+  // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+  let z = days as i64 + 719_468;
+  let era = z.div_euclid(146_097);
+  let doe = z - era * 146_097;
+  let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = doy - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+  format!(
+    "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+    year,
+    month,
+    day,
+    secs_of_day / 3600,
+    secs_of_day % 3600 / 60,
+    secs_of_day % 60,
+    since_epoch.subsec_millis()
+  )
+}
+
+/// Formats a time in the local timezone with a strftime format. `%3f` is replaced with the
+/// milliseconds.
+#[cfg(unix)]
+pub(crate) fn local_timestamp(format: &str, time: SystemTime) -> String {
+  let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+  let format = format.replace("%3f", &format!("{:03}", since_epoch.subsec_millis()));
+  let format = match std::ffi::CString::new(format) {
+    Ok(format) => format,
+    Err(_) => return String::new(),
+  };
+  let secs = since_epoch.as_secs() as libc::time_t;
+  let mut buf = [0u8; 256];
+  let len = unsafe {
+    let mut tm: libc::tm = std::mem::zeroed();
+    libc::localtime_r(&secs, &mut tm);
+    strftime(
+      buf.as_mut_ptr() as *mut libc::c_char,
+      buf.len(),
+      format.as_ptr(),
+      &tm,
+    )
+  };
+  String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+#[cfg(unix)]
+extern "C" {
+  fn strftime(
+    s: *mut libc::c_char,
+    max: libc::size_t,
+    format: *const libc::c_char,
+    tm: *const libc::tm,
+  ) -> libc::size_t;
+}
+
+#[cfg(not(unix))]
+pub(crate) fn local_timestamp(_format: &str, time: SystemTime) -> String {
+  utc_timestamp(time)
+}
+
+/// Seconds since the unix epoch.
+pub(crate) fn timestamp() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_secs())
+    .unwrap_or(0)
+}
@@ -0,0 +1,106 @@
+use crate::control::Request;
+use crate::supervisor::submit;
+use crate::{ui, Command, Message};
+use crossbeam::channel::Sender;
+use std::io::{self, BufRead, Read, Write};
+use std::thread;
+
+/// Reads single key commands from the terminal while a session is running. Keys are read without
+/// waiting for enter, `r`, `s`, `p`, `c`, `f` and `i` then ask for the component to restart, stop,
+/// pause, continue, filter the output to or attach to and `:` opens the command palette. The
+/// terminal's settings are restored when dropped.
+pub struct Keys {
+  original: libc::termios,
+}
+
+/// Puts the terminal in cbreak mode and starts reading keys.
+pub fn listen(sender: Sender<Request>) -> io::Result<Keys> {
+  let original = attributes()?;
+  let mut cbreak = original;
+  cbreak.c_lflag &= !(libc::ICANON | libc::ECHO);
+  cbreak.c_cc[libc::VMIN] = 1;
+  cbreak.c_cc[libc::VTIME] = 0;
+  set_attributes(&cbreak)?;
+  ui::message(Message::KeysHint);
+  thread::spawn(move || read_keys(sender, original, cbreak));
+  Ok(Keys { original })
+}
+
+impl Drop for Keys {
+  fn drop(&mut self) {
+    let _ = set_attributes(&self.original);
+  }
+}
+
+fn read_keys(sender: Sender<Request>, line_mode: libc::termios, key_mode: libc::termios) {
+  // Lines are read with the terminal's own editing and echo turned back on.
+  let read_line = |prompt: &str| {
+    let _ = set_attributes(&line_mode);
+    print!("{}", prompt);
+    let _ = io::stdout().flush();
+    let mut line = String::new();
+    let read = io::stdin().lock().read_line(&mut line);
+    let _ = set_attributes(&key_mode);
+    read
+      .ok()
+      .filter(|n| *n > 0)
+      .map(|_| line.trim().to_string())
+  };
+  let mut key = [0u8; 1];
+  loop {
+    // The lock on stdin has to be released before a line is read.
+    let read = io::stdin().lock().read(&mut key);
+    if !matches!(read, Ok(1)) {
+      break;
+    }
+    let command = match key[0] {
+      b':' => read_line(":").map(|line| line.parse()),
+      b'r' => read_line("restart: ").map(|name| Ok(Command::Restart(name))),
+      b's' => read_line("stop: ").map(|name| Ok(Command::Stop(name))),
+      b'p' => read_line("pause: ").map(|name| Ok(Command::Pause(name))),
+      b'c' => read_line("resume: ").map(|name| Ok(Command::Resume(name))),
+      b'f' => read_line("filter: ").map(|name| match name.is_empty() {
+        true => Ok(Command::Filter(None)),
+        false => Ok(Command::Filter(Some(name))),
+      }),
+      b'/' => read_line("grep: ").map(|pattern| match pattern.is_empty() {
+        true => Ok(Command::Grep(None)),
+        false => Ok(Command::Grep(Some(pattern))),
+      }),
+      b'i' => {
+        if let Some(name) = read_line("attach: ") {
+          let _ = set_attributes(&line_mode);
+          ui::attach(&name, |text| {
+            submit(&sender, Command::Input(name.clone(), text))
+          });
+          let _ = set_attributes(&key_mode);
+        }
+        continue;
+      }
+      _ => continue,
+    };
+    match command {
+      Some(command) => {
+        if let Err(e) = command.and_then(|command| submit(&sender, command)) {
+          ui::message(Message::Error { error: e });
+        }
+      }
+      None => break,
+    }
+  }
+}
+
+fn attributes() -> io::Result<libc::termios> {
+  let mut attributes = unsafe { std::mem::zeroed() };
+  if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut attributes) } != 0 {
+    return Err(io::Error::last_os_error());
+  }
+  Ok(attributes)
+}
+
+fn set_attributes(attributes: &libc::termios) -> io::Result<()> {
+  if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, attributes) } != 0 {
+    return Err(io::Error::last_os_error());
+  }
+  Ok(())
+}
@@ -0,0 +1,279 @@
+#[cfg(windows)]
+use crate::process::Job;
+use serde::{Deserialize, Serialize};
+#[cfg(target_os = "linux")]
+use std::fs::{self, File, OpenOptions};
+use std::io;
+#[cfg(target_os = "linux")]
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+#[cfg(target_os = "linux")]
+use std::thread;
+#[cfg(target_os = "linux")]
+use std::time::{Duration, Instant};
+
+/// Caps on the resources a component's processes may use together.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug, Default)]
+#[serde(default)]
+pub struct Limits {
+  /// Memory such as `512M` or `1.5G`. The processes are killed when they use more.
+  pub memory: Option<String>,
+  /// CPU cores, such as 1.5. The processes are slowed down rather than killed.
+  pub cpu: Option<f64>,
+}
+
+impl Limits {
+  /// The memory limit in bytes.
+  pub fn memory_bytes(&self) -> Result<Option<u64>, String> {
+    let memory = match &self.memory {
+      Some(memory) => memory.trim(),
+      None => return Ok(None),
+    };
+    let invalid = || format!("Invalid memory limit {}", memory);
+    let (number, unit) = match memory.char_indices().last() {
+      Some((i, c)) if c.is_ascii_alphabetic() => (&memory[..i], c.to_ascii_uppercase()),
+      _ => (memory, 'B'),
+    };
+    let scale: u64 = match unit {
+      'B' => 1,
+      'K' => 1 << 10,
+      'M' => 1 << 20,
+      'G' => 1 << 30,
+      'T' => 1 << 40,
+      _ => return Err(invalid()),
+    };
+    match number.parse::<f64>() {
+      Ok(n) if n > 0.0 => Ok(Some((n * scale as f64) as u64)),
+      _ => Err(invalid()),
+    }
+  }
+
+  /// Describes what is wrong with the limits, if anything.
+  pub fn problem(&self) -> Option<String> {
+    if let Err(e) = self.memory_bytes() {
+      return Some(e);
+    }
+    match self.cpu {
+      Some(cpu) if cpu <= 0.0 => Some(format!("Invalid CPU limit {}", cpu)),
+      _ => None,
+    }
+  }
+}
+
+/// What holds the processes of a component to its limits: a cgroup, created next to the one
+/// conductor runs in, on Linux and a Job Object on Windows. Processes still in it are killed
+/// when it is dropped.
+pub struct Confinement {
+  #[cfg(target_os = "linux")]
+  path: PathBuf,
+  #[cfg(windows)]
+  job: Job,
+  #[cfg(windows)]
+  memory: Option<u64>,
+}
+
+impl Confinement {
+  /// Creates a cgroup with the limits applied. This needs cgroups v2 with the memory and cpu
+  /// controllers delegated to the user, as systemd does for user sessions.
+  #[cfg(target_os = "linux")]
+  pub fn create(component: &str, limits: &Limits) -> io::Result<Confinement> {
+    let root = PathBuf::from("/sys/fs/cgroup");
+    if !root.join("cgroup.controllers").exists() {
+      return Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "cgroups v2 are not available",
+      ));
+    }
+    let own = fs::read_to_string("/proc/self/cgroup")?;
+    let own = own
+      .lines()
+      .find_map(|line| line.strip_prefix("0::"))
+      .ok_or_else(|| io::Error::other("Could not find the cgroup conductor runs in"))?;
+    // Processes can only be placed in cgroups without children of their own, so the component's
+    // cgroup is a sibling of conductor's rather than a child.
+    let own = root.join(own.trim_start_matches('/'));
+    let parent = own.parent().unwrap_or(&root);
+    let memory = limits.memory_bytes().map_err(io::Error::other)?;
+    let mut controllers = vec![];
+    if memory.is_some() {
+      controllers.push("memory");
+    }
+    if limits.cpu.is_some() {
+      controllers.push("cpu");
+    }
+    enable_controllers(parent, &controllers)?;
+    let name = format!(
+      "conductor-{}-{}",
+      std::process::id(),
+      component.replace(|c: char| !c.is_ascii_alphanumeric(), "-")
+    );
+    let cgroup = Confinement {
+      path: parent.join(name),
+    };
+    fs::create_dir_all(&cgroup.path)?;
+    if let Some(bytes) = memory {
+      cgroup.write("memory.max", &bytes.to_string())?;
+      // Swapping would only delay the kill while slowing everything else down.
+      let _ = cgroup.write("memory.swap.max", "0");
+    }
+    if let Some(cpu) = limits.cpu {
+      let period = 100_000;
+      let quota = (cpu * period as f64) as u64;
+      cgroup.write("cpu.max", &format!("{} {}", quota.max(1000), period))?;
+    }
+    Ok(cgroup)
+  }
+
+  /// Creates a Job Object with the limits applied. Windows refuses allocations past the memory
+  /// limit rather than killing the processes.
+  #[cfg(windows)]
+  pub fn create(_component: &str, limits: &Limits) -> io::Result<Confinement> {
+    let job = Job::new()?;
+    let memory = limits.memory_bytes().map_err(io::Error::other)?;
+    if let Some(bytes) = memory {
+      job.limit_memory(bytes)?;
+    }
+    if let Some(cpu) = limits.cpu {
+      job.limit_cpu(cpu)?;
+    }
+    Ok(Confinement { job, memory })
+  }
+
+  #[cfg(not(any(target_os = "linux", windows)))]
+  pub fn create(_component: &str, _limits: &Limits) -> io::Result<Confinement> {
+    Err(io::Error::new(
+      io::ErrorKind::Unsupported,
+      "Resource limits are only supported on Linux and Windows",
+    ))
+  }
+
+  #[cfg(target_os = "linux")]
+  fn write(&self, file: &str, value: &str) -> io::Result<()> {
+    fs::write(self.path.join(file), value)
+      .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", file, e)))
+  }
+
+  /// Makes the command's process join the cgroup before the command is executed, so nothing it
+  /// starts escapes the limits.
+  #[cfg(target_os = "linux")]
+  pub fn add(&self, command: &mut Command) -> io::Result<()> {
+    let procs: File = OpenOptions::new()
+      .write(true)
+      .open(self.path.join("cgroup.procs"))?;
+    crate::process::join_cgroup(command, procs);
+    Ok(())
+  }
+
+  /// A Job Object can only be given a process once it has been started.
+  #[cfg(not(target_os = "linux"))]
+  pub fn add(&self, _command: &mut Command) -> io::Result<()> {
+    Ok(())
+  }
+
+  /// Adds the started process of a component to the Job Object, along with the processes it
+  /// starts from then on. On Linux the process already joined the cgroup.
+  pub fn assign(&self, _child: &Child) -> io::Result<()> {
+    #[cfg(windows)]
+    self.job.assign(_child)?;
+    Ok(())
+  }
+
+  /// Returns true if processes in the cgroup were killed for using more memory than allowed.
+  #[cfg(target_os = "linux")]
+  pub fn memory_exceeded(&self) -> bool {
+    fs::read_to_string(self.path.join("memory.events"))
+      .map(|events| {
+        events.lines().any(|line| {
+          line
+            .strip_prefix("oom_kill ")
+            .is_some_and(|n| n.trim() != "0")
+        })
+      })
+      .unwrap_or(false)
+  }
+
+  /// Returns true if the processes in the job ran into the memory limit. As allocations past it
+  /// fail, coming within a megabyte of it counts.
+  #[cfg(windows)]
+  pub fn memory_exceeded(&self) -> bool {
+    match (self.memory, self.job.peak_memory()) {
+      (Some(limit), Ok(peak)) => peak + (1 << 20) >= limit,
+      _ => false,
+    }
+  }
+
+  #[cfg(not(any(target_os = "linux", windows)))]
+  pub fn memory_exceeded(&self) -> bool {
+    false
+  }
+
+  /// Returns true while processes are left in the cgroup.
+  #[cfg(target_os = "linux")]
+  fn populated(&self) -> bool {
+    fs::read_to_string(self.path.join("cgroup.events"))
+      .map(|events| events.lines().any(|line| line == "populated 1"))
+      .unwrap_or(false)
+  }
+
+  /// Kills every process in the cgroup. Kernels without `cgroup.kill` have each process killed
+  /// instead, which misses processes started in the meantime.
+  #[cfg(target_os = "linux")]
+  fn kill(&self) -> bool {
+    if self.write("cgroup.kill", "1").is_ok() {
+      return true;
+    }
+    let procs = fs::read_to_string(self.path.join("cgroup.procs")).unwrap_or_default();
+    for pid in procs.lines().filter_map(|pid| pid.trim().parse().ok()) {
+      let _ = crate::process::kill(pid);
+    }
+    false
+  }
+}
+
+/// Enables the controllers for the children of a cgroup, which they need to be limited by them.
+#[cfg(target_os = "linux")]
+fn enable_controllers(cgroup: &Path, controllers: &[&str]) -> io::Result<()> {
+  let available = fs::read_to_string(cgroup.join("cgroup.controllers"))?;
+  let enabled = fs::read_to_string(cgroup.join("cgroup.subtree_control"))?;
+  for controller in controllers {
+    if !available.split_whitespace().any(|c| c == *controller) {
+      return Err(io::Error::other(format!(
+        "The {} controller is not delegated to {}",
+        controller,
+        cgroup.display()
+      )));
+    }
+    if !enabled.split_whitespace().any(|c| c == *controller) {
+      fs::write(
+        cgroup.join("cgroup.subtree_control"),
+        format!("+{}", controller),
+      )
+      .map_err(|e| {
+        io::Error::new(
+          e.kind(),
+          format!("Could not enable the {} controller: {}", controller, e),
+        )
+      })?;
+    }
+  }
+  Ok(())
+}
+
+/// A cgroup can only be removed once it is empty, so whatever is left of the component is killed
+/// and waited for first.
+#[cfg(target_os = "linux")]
+impl Drop for Confinement {
+  fn drop(&mut self) {
+    if self.populated() {
+      let deadline = Instant::now() + Duration::from_secs(5);
+      let mut killed = self.kill();
+      while self.populated() && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(10));
+        if !killed {
+          killed = self.kill();
+        }
+      }
+    }
+    let _ = fs::remove_dir(&self.path);
+  }
+}
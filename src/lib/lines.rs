@@ -0,0 +1,203 @@
+use crossbeam::channel::{bounded, RecvTimeoutError};
+use std::io::{ErrorKind, Read};
+use std::thread;
+use std::time::Duration;
+
+/// The buffer size used to read output when none is configured.
+pub const DEFAULT_CAPACITY: usize = 8 * 1024;
+
+/// How long output has to be idle before a line without an ending is passed on, so prompts and
+/// progress bars are shown while the process waits.
+const PARTIAL_LINE_IDLE: Duration = Duration::from_millis(100);
+
+/// The longest line held back while waiting for its ending, longer lines are passed on in parts
+/// of this size so output without any line endings does not use up memory.
+const MAX_LINE: usize = 64 * 1024;
+
+/// Reads a stream of process output as lines until it is closed. Bytes that are not valid UTF-8
+/// are replaced rather than losing the line, and line endings are removed. A carriage return on
+/// its own ends a line as well, as progress bars redraw themselves with one.
+pub fn read_lines<R, F>(mut stream: R, capacity: usize, mut line: F)
+where
+  R: Read + Send + 'static,
+  F: FnMut(String),
+{
+  // The stream is read on a thread of its own so a partial line can be passed on while the
+  // read blocks.
+  let (sender, chunks) = bounded::<Vec<u8>>(16);
+  thread::spawn(move || {
+    let mut buf = vec![0; capacity.max(1)];
+    loop {
+      match stream.read(&mut buf) {
+        Ok(0) => break,
+        Ok(n) => {
+          if sender.send(buf[..n].to_vec()).is_err() {
+            break;
+          }
+        }
+        Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+        // A pseudo-terminal reports an error rather than the end of the output once the
+        // process has exited.
+        Err(_) => break,
+      }
+    }
+  });
+
+  let mut pending: Vec<u8> = vec![];
+  loop {
+    let chunk = match pending.is_empty() {
+      true => chunks.recv().map_err(|_| RecvTimeoutError::Disconnected),
+      false => chunks.recv_timeout(PARTIAL_LINE_IDLE),
+    };
+    match chunk {
+      Ok(chunk) => {
+        pending.extend(chunk);
+        while let Some(complete) = next_line(&mut pending) {
+          line(decode(&complete));
+        }
+      }
+      Err(RecvTimeoutError::Timeout) => line(decode(&std::mem::take(&mut pending))),
+      Err(RecvTimeoutError::Disconnected) => break,
+    }
+  }
+  if !pending.is_empty() {
+    line(decode(&pending));
+  }
+}
+
+/// Takes the first complete line off the pending output, with its ending. A carriage return at
+/// the very end is left for the next chunk, which may start with the newline that belongs to it.
+fn next_line(pending: &mut Vec<u8>) -> Option<Vec<u8>> {
+  let end = match pending.iter().position(|b| *b == b'\n' || *b == b'\r') {
+    Some(end) if pending[end] == b'\n' => end + 1,
+    Some(end) if end + 1 < pending.len() => match pending[end + 1] {
+      b'\n' => end + 2,
+      _ => end + 1,
+    },
+    _ if pending.len() >= MAX_LINE => MAX_LINE,
+    _ => return None,
+  };
+  let rest = pending.split_off(end);
+  Some(std::mem::replace(pending, rest))
+}
+
+fn decode(bytes: &[u8]) -> String {
+  String::from_utf8_lossy(bytes)
+    .trim_end_matches(['\n', '\r'])
+    .to_string()
+}
+
+/// Removes terminal escape sequences from a line of output, such as colors, cursor movement and
+/// window titles.
+pub fn strip_ansi(line: &str) -> String {
+  let mut stripped = String::with_capacity(line.len());
+  let mut chars = line.chars().peekable();
+  while let Some(c) = chars.next() {
+    match c {
+      '\u{1b}' => match chars.next() {
+        // Control sequences end with a character from @ to ~.
+        Some('[') => {
+          for c in chars.by_ref() {
+            if ('@'..='~').contains(&c) {
+              break;
+            }
+          }
+        }
+        // Strings, such as a window title, end with BEL or ESC \.
+        Some(']') | Some('P') | Some('X') | Some('^') | Some('_') => {
+          while let Some(c) = chars.next() {
+            if c == '\u{7}' {
+              break;
+            }
+            if c == '\u{1b}' && chars.peek() == Some(&'\\') {
+              chars.next();
+              break;
+            }
+          }
+        }
+        // Other escapes are a character long after any intermediate characters.
+        Some(c) if (' '..='/').contains(&c) => {
+          while chars.next_if(|c| (' '..='/').contains(c)).is_some() {}
+          chars.next();
+        }
+        _ => {}
+      },
+      '\u{7}' => {}
+      c => stripped.push(c),
+    }
+  }
+  stripped
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::io::Cursor;
+
+  /// The lines of some output, read a few bytes at a time to split the line endings across reads.
+  fn lines_of(output: &[u8]) -> Vec<String> {
+    read_in(output, 4)
+  }
+
+  fn read_in(output: &[u8], capacity: usize) -> Vec<String> {
+    let mut lines = vec![];
+    read_lines(Cursor::new(output.to_vec()), capacity, |line| {
+      lines.push(line)
+    });
+    lines
+  }
+
+  #[test]
+  fn splits_on_every_kind_of_line_ending() {
+    assert_eq!(
+      lines_of(b"one\ntwo\r\nthree\rfour"),
+      ["one", "two", "three", "four"]
+    );
+    assert_eq!(lines_of(b"10%\r50%\r100%\r\n"), ["10%", "50%", "100%"]);
+  }
+
+  #[test]
+  fn replaces_invalid_utf8() {
+    assert_eq!(lines_of(b"a\xffb\n"), ["a\u{fffd}b"]);
+  }
+
+  #[test]
+  fn keeps_a_carriage_return_until_its_newline_arrives() {
+    let mut pending = b"one\r".to_vec();
+    assert_eq!(next_line(&mut pending), None);
+    pending.extend(b"\ntwo");
+    assert_eq!(next_line(&mut pending).unwrap(), b"one\r\n");
+    assert_eq!(pending, b"two");
+  }
+
+  #[test]
+  fn strips_colors_and_cursor_movement() {
+    assert_eq!(
+      strip_ansi("\u{1b}[1;31merror\u{1b}[0m: failed"),
+      "error: failed"
+    );
+    assert_eq!(strip_ansi("\u{1b}[2K\u{1b}[1Gbuilding"), "building");
+    assert_eq!(strip_ansi("plain text"), "plain text");
+  }
+
+  #[test]
+  fn strips_titles_and_other_escapes() {
+    assert_eq!(strip_ansi("\u{1b}]0;title\u{7}shell"), "shell");
+    assert_eq!(strip_ansi("\u{1b}]8;;http://x\u{1b}\\link"), "link");
+    assert_eq!(strip_ansi("\u{1b}(Bcharset\u{1b}7saved"), "charsetsaved");
+    assert_eq!(strip_ansi("bell\u{7}"), "bell");
+  }
+
+  #[test]
+  fn strips_sequences_cut_off_at_the_end() {
+    assert_eq!(strip_ansi("done\u{1b}[1;3"), "done");
+    assert_eq!(strip_ansi("done\u{1b}"), "done");
+  }
+
+  #[test]
+  fn passes_on_long_lines_in_parts() {
+    let lines = read_in(&vec![b'a'; MAX_LINE * 2 + 10], DEFAULT_CAPACITY);
+    let lengths: Vec<usize> = lines.iter().map(String::len).collect();
+    assert_eq!(lengths, [MAX_LINE, MAX_LINE, 10]);
+  }
+}
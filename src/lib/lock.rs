@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The file the commits of component repositories are recorded in, next to the config.
+pub const LOCKFILE: &str = "conductor.lock";
+
+/// The commit each component repository was at when the project was locked, so setup can check
+/// out the same code on every machine.
+#[derive(Serialize, Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct Lockfile {
+  pub components: BTreeMap<String, LockedRepo>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[serde(default)]
+pub struct LockedRepo {
+  /// The repository the commit belongs to. A commit locked for another repository is ignored.
+  pub repo: String,
+  pub commit: String,
+}
+
+impl Lockfile {
+  pub fn path(root_path: &Path) -> PathBuf {
+    root_path.join(LOCKFILE)
+  }
+
+  /// Reads the lockfile of a project, if it has one.
+  pub fn load(root_path: &Path) -> io::Result<Option<Lockfile>> {
+    let path = Lockfile::path(root_path);
+    match fs::read_to_string(&path) {
+      Ok(contents) => serde_yaml::from_str(&contents)
+        .map(Some)
+        .map_err(|e| io::Error::other(format!("{}: {}", path.display(), e))),
+      Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+      Err(e) => Err(e),
+    }
+  }
+
+  /// Writes the lockfile, returning where it was written.
+  pub fn save(&self, root_path: &Path) -> io::Result<PathBuf> {
+    let path = Lockfile::path(root_path);
+    let contents = serde_yaml::to_string(self).map_err(io::Error::other)?;
+    fs::write(
+      &path,
+      format!(
+        "# Written by conductor lock, commit it to share the exact commits.\n{}\n",
+        contents.trim_end()
+      ),
+    )?;
+    Ok(path)
+  }
+}
@@ -0,0 +1,99 @@
+use crate::journal::{utc_timestamp, Journal};
+use crate::schedule::parse_interval;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// Turns the value of `--since` into the timestamp lines are compared with. It is either an
+/// interval such as `10m` back from now or a UTC time such as `2024-05-01T14:30`.
+pub fn since(value: &str) -> Result<String, String> {
+  if let Some(interval) = parse_interval(value) {
+    let time = SystemTime::now()
+      .checked_sub(interval)
+      .unwrap_or(SystemTime::UNIX_EPOCH);
+    return Ok(utc_timestamp(time));
+  }
+  let date = value.split('T').next().unwrap_or_default();
+  match date.len() == 10 && date.chars().all(|c| c.is_ascii_digit() || c == '-') {
+    true => Ok(value.to_string()),
+    false => Err(format!(
+      "Expected an interval such as 10m or a time such as 2024-05-01T14:30 for --since, got {}",
+      value
+    )),
+  }
+}
+
+/// Prints the recorded output of a component, oldest first, starting at `since` if given. When
+/// following, new lines are printed as they are written until the process is interrupted.
+/// Returns false if nothing has been recorded for the component.
+pub fn show(
+  root_path: &Path,
+  component: &str,
+  since: Option<&str>,
+  follow: bool,
+) -> io::Result<bool> {
+  let path = Journal::log_path(root_path, component);
+  let mut logs = Journal::rotated_log_paths(root_path, component);
+  if !path.exists() && logs.is_empty() && !follow {
+    return Ok(false);
+  }
+  logs.push(path.clone());
+  let print = |line: &str| {
+    if since.is_none_or(|since| line >= since) {
+      println!("{}", line);
+    }
+  };
+  for log in logs.iter() {
+    match fs::read_to_string(log) {
+      Ok(contents) => contents.lines().for_each(print),
+      Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+      Err(e) => return Err(e),
+    }
+  }
+  if !follow {
+    return Ok(true);
+  }
+  let position = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+  tail(&path, position, print, || false)?;
+  Ok(true)
+}
+
+/// Prints the lines written to a file from a position on, until `done` returns true. The file is
+/// polled for growth. A file that got shorter was rotated and is read from the start.
+pub fn tail(
+  path: &Path,
+  mut position: u64,
+  print: impl Fn(&str),
+  done: impl Fn() -> bool,
+) -> io::Result<()> {
+  let mut partial = String::new();
+  while !done() {
+    thread::sleep(Duration::from_millis(250));
+    let len = match fs::metadata(path) {
+      Ok(metadata) => metadata.len(),
+      Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+      Err(e) => return Err(e),
+    };
+    if len < position {
+      position = 0;
+      partial.clear();
+    }
+    if len == position {
+      continue;
+    }
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(position))?;
+    let mut buf = vec![];
+    file.take(len - position).read_to_end(&mut buf)?;
+    position = len;
+    partial.push_str(&String::from_utf8_lossy(&buf));
+    // A line is only printed once its end has been written.
+    while let Some(end) = partial.find('\n') {
+      let line: String = partial.drain(..=end).collect();
+      print(line.trim_end_matches('\n'));
+    }
+  }
+  Ok(())
+}
@@ -0,0 +1,1149 @@
+use crate::process::Usage;
+use std::fmt;
+
+/// The text of every message keyed by its id. Arguments are substituted for their `{name}`
+/// placeholders when a message is displayed.
+const CATALOG: &[(&str, &str)] = &[
+  ("session.shutting_down", "shutting down"),
+  (
+    "session.running",
+    "A session is currently running (pid {pid})",
+  ),
+  (
+    "session.detached",
+    "Running in the background (pid {pid}), output is written to {log}. Use conductor attach to follow it",
+  ),
+  ("environment.up", "Environment up"),
+  ("environment.report", "Environment report"),
+  (
+    "environment.timeout",
+    "The environment was not ready after {seconds}s",
+  ),
+  ("session.summary", "Summary"),
+  ("component.started", "Component {component} started"),
+  ("component.ready", "Component {component} ready"),
+  ("component.shutdown", "Component {component} shutdown"),
+  ("component.exited", "Component {component} exited with code {code}"),
+  ("component.error", "Component error [{component}]: {error}"),
+  ("component.dropped", "... {lines} lines dropped"),
+  (
+    "component.status",
+    "Component {component} {status} (pid {pid}, ready: {ready})",
+  ),
+  ("component.cloned", "{component} cloned"),
+  ("component.synced", "{component} updated"),
+  ("component.up_to_date", "{component} is up to date"),
+  ("component.sync_failed", "Could not update {component}: {error}"),
+  ("component.lock_failed", "Could not lock {component}: {error}"),
+  ("component.downloaded", "{component} downloaded"),
+  (
+    "component.excluded",
+    "{component} is left out by its {condition} condition",
+  ),
+  (
+    "component.download_failed",
+    "Could not download {component}: {error}",
+  ),
+  (
+    "component.init_skipped",
+    "Skipped the init commands of {component}, its manifests did not change",
+  ),
+  (
+    "component.usage",
+    "{component} is using {cpu}% CPU and {memory} of memory",
+  ),
+  ("component.none", "No components to run"),
+  ("component.restarting", "Restarting component {component}"),
+  ("component.stopping", "Stopping component {component}"),
+  ("component.paused", "Paused component {component}"),
+  ("component.resumed", "Resumed component {component}"),
+  ("component.starting", "Starting component {component}"),
+  ("chaos.killed", "Chaos: killed component {component}"),
+  (
+    "component.shell",
+    "Starting {shell} with the environment of {component}, exit to return",
+  ),
+  (
+    "component.port_in_use",
+    "Port {port} is already in use by {process}",
+  ),
+  ("component.added", "Component {component} added"),
+  (
+    "component.wait_timeout",
+    "Gave up waiting for {condition} after {seconds}s",
+  ),
+  ("wait.ready", "{components} ready"),
+  ("wait.failed", "Component {component} failed before it was ready"),
+  ("wait.ended", "The session ended before the components were ready"),
+  (
+    "component.previous_failed",
+    "Not starting {component}, {previous} failed before it",
+  ),
+  ("component.removed", "Component {component} removed"),
+  (
+    "component.start_timeout",
+    "No output or readiness after {seconds}s, stopping it",
+  ),
+  (
+    "component.limits_not_applied",
+    "Could not apply resource limits, starting without them: {error}",
+  ),
+  (
+    "component.nice_not_applied",
+    "Could not set the nice level to {nice}, starting at the default level",
+  ),
+  (
+    "component.memory_exceeded",
+    "Killed for using more than {limit} of memory",
+  ),
+  (
+    "component.unhealthy",
+    "Component {component} failed {failures} health checks in a row, restarting it",
+  ),
+  (
+    "component.fail_fast",
+    "Component {component} failed, stopping the session",
+  ),
+  (
+    "component.failed",
+    "Component {component} of {project} failed",
+  ),
+  (
+    "component.crash_looping",
+    "Component {component} of {project} failed {failures} times within {seconds}s, it is no longer restarted",
+  ),
+  ("output.muted", "Muted {name}"),
+  ("output.unmuted", "Unmuted {name}"),
+  ("output.filtered", "Showing output from {name} only"),
+  ("output.unfiltered", "Showing all output"),
+  ("output.matching", "Showing lines matching {pattern}"),
+  ("output.unmatched", "Showing lines with any content"),
+  (
+    "console.hint",
+    "Type a command for the session, tab completes, help lists the commands and exit leaves",
+  ),
+  (
+    "keys.hint",
+    "Press r to restart, s to stop, p to pause, c to resume, f to filter or i to attach to a component, / to match lines, : for commands",
+  ),
+  (
+    "attach.started",
+    "Attached to {component}, type ~. on its own line to detach",
+  ),
+  ("attach.ended", "Detached from {component}"),
+  ("service.started", "Service started {service}"),
+  ("service.stopped", "Service stopped {service}"),
+  ("service.status", "Service {service} {status}"),
+  (
+    "service.creating",
+    "Creating container {container} from {image}",
+  ),
+  ("service.removed", "Removed container {container}"),
+  (
+    "service.kept",
+    "Container {container} was not created by conductor, it is stopped but not removed",
+  ),
+  ("service.volume_removed", "Removed volume {volume}"),
+  (
+    "service.start_failed",
+    "Could not start service [{service}]: {error}",
+  ),
+  (
+    "service.stop_failed",
+    "Could not stop service [{service}]: {error}",
+  ),
+  (
+    "service.follow_failed",
+    "Could not follow logs for service [{service}]: {error}",
+  ),
+  ("task.command", "{command}"),
+  ("task.scheduled", "Running scheduled task {task}"),
+  (
+    "task.schedule_skipped",
+    "Skipped scheduled task {task}, its last run has not finished",
+  ),
+  ("task.sent", "Running task {task} in the session"),
+  ("task.timed_out", "{command} timed out after {seconds}s"),
+  ("task.finished", "Task {task} finished after {seconds}s"),
+  ("task.failed", "Task {task} failed after {seconds}s"),
+  (
+    "task.foreach_succeeded",
+    "Task {task} succeeded in every component ({count})",
+  ),
+  (
+    "task.foreach_failed",
+    "Task {task} failed in {failed} of {count} components: {components}",
+  ),
+  ("platform.no_commands", "{name} has no commands for {platform}"),
+  ("build.succeeded", "Built {component} in {seconds}s"),
+  (
+    "build.failed",
+    "Build of {component} failed after {seconds}s",
+  ),
+  (
+    "build.skipped",
+    "Skipped building {component}, {dependency} did not build",
+  ),
+  ("build.none", "No components to build"),
+  ("test.passed", "Tests of {component} passed in {seconds}s"),
+  (
+    "test.failed",
+    "Tests of {component} failed after {seconds}s",
+  ),
+  ("test.none", "No components to test"),
+  ("clone.skipped", "Skipping clone: {error}"),
+  ("report.written", "Report written to {path}"),
+  ("plan.task", "Would run task {task} in {path}"),
+  ("plan.group", "Would start group {group}: {components}"),
+  ("plan.component", "Would start component {component} in {path}"),
+  ("plan.service", "Would start service {service}"),
+  ("plan.build", "  build: {command}"),
+  ("plan.command", "  $ {command}"),
+  ("plan.env", "  {key}={value}"),
+  ("logs.none", "No output has been recorded for {component}"),
+  ("spec.applied", "Applied {path}"),
+  ("config.reloaded", "Reloaded {path}"),
+  ("proxy.listening", "Proxying requests on http://{address}"),
+  (
+    "http.listening",
+    "Serving the session status on http://{address}",
+  ),
+  ("report.failed", "Could not write report: {error}"),
+  ("record.failed", "Could not record the session to {path}: {error}"),
+  (
+    "config.remote_cached",
+    "Using the copy of {url} downloaded earlier: {error}",
+  ),
+  ("audit.clean", "Nothing to clean up"),
+  (
+    "audit.leftovers",
+    "{count} processes or containers of a previous session are still running, conductor cleanup stops them",
+  ),
+  ("audit.cleaned", "Cleaned up {finding}"),
+  (
+    "audit.clean_failed",
+    "Could not clean up {finding}: {error}",
+  ),
+  ("config.valid", "The configuration is valid"),
+  ("doctor.passed", "ok    {check}"),
+  ("doctor.failed", "FAIL  {check}: {problem}"),
+  ("doctor.hint", "      {hint}"),
+  ("doctor.healthy", "All {checks} checks passed"),
+  ("doctor.unhealthy", "{failed} of {checks} checks failed"),
+  ("config.error", "{problem}"),
+  ("config.warning", "Warning: {problem}"),
+  ("config.written", "Wrote {path}"),
+  ("guardrail.exceeded", "Warning: {problem}"),
+  (
+    "guardrail.stop",
+    "Not starting, use --force to start anyway",
+  ),
+  ("config.exists", "{path} already exists"),
+  (
+    "project.created",
+    "Created {path}, run conductor setup in it to clone the components",
+  ),
+  ("import.added", "Added component {component}"),
+  ("import.updated", "Updated the start command of {component}"),
+  ("import.unchanged", "Every component is up to date"),
+  ("command.help", ":{usage} - {description}"),
+  ("error", "{error}"),
+];
+
+/// A message shown to the user. Each message has a stable id and its text comes from the
+/// catalog so every place that shows it formats it the same way.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Message {
+  ShuttingDown,
+  SessionRunning {
+    pid: u32,
+  },
+  SessionDetached {
+    pid: u32,
+    log: String,
+  },
+  EnvironmentUp,
+  EnvironmentReport,
+  EnvironmentTimedOut {
+    seconds: u64,
+  },
+  Summary,
+  ComponentStarted {
+    component: String,
+  },
+  ComponentReady {
+    component: String,
+  },
+  ComponentShutdown {
+    component: String,
+  },
+  ComponentExited {
+    component: String,
+    code: i32,
+  },
+  OutputDropped {
+    lines: usize,
+  },
+  ComponentError {
+    component: String,
+    error: String,
+  },
+  ComponentStatus {
+    component: String,
+    status: String,
+    pid: Option<u32>,
+    ready: bool,
+  },
+  ComponentCloned {
+    component: String,
+  },
+  ComponentSynced {
+    component: String,
+  },
+  ComponentUpToDate {
+    component: String,
+  },
+  SyncFailed {
+    component: String,
+    error: String,
+  },
+  LockFailed {
+    component: String,
+    error: String,
+  },
+  InitSkipped {
+    component: String,
+  },
+  ComponentDownloaded {
+    component: String,
+  },
+  ComponentExcluded {
+    component: String,
+    condition: String,
+  },
+  DownloadFailed {
+    component: String,
+    error: String,
+  },
+  ComponentUsage {
+    component: String,
+    cpu: String,
+    memory: String,
+  },
+  NoComponents,
+  ComponentRestarting {
+    component: String,
+  },
+  ComponentStopping {
+    component: String,
+  },
+  ComponentPaused {
+    component: String,
+  },
+  ComponentResumed {
+    component: String,
+  },
+  ChaosKilled {
+    component: String,
+  },
+  ComponentStarting {
+    component: String,
+  },
+  ShellStarted {
+    component: String,
+    shell: String,
+  },
+  ComponentAdded {
+    component: String,
+  },
+  WaitTimedOut {
+    condition: String,
+    seconds: u64,
+  },
+  WaitReady {
+    components: String,
+  },
+  WaitFailed {
+    component: String,
+  },
+  WaitSessionEnded,
+  PreviousFailed {
+    component: String,
+    previous: String,
+  },
+  PortInUse {
+    port: u16,
+    pid: Option<u32>,
+  },
+  ComponentRemoved {
+    component: String,
+  },
+  StartTimedOut {
+    seconds: u64,
+  },
+  ComponentUnhealthy {
+    component: String,
+    failures: u32,
+  },
+  LimitsNotApplied {
+    error: String,
+  },
+  NiceNotApplied {
+    nice: i32,
+  },
+  MemoryExceeded {
+    limit: String,
+  },
+  FailFast {
+    component: String,
+  },
+  ComponentFailed {
+    project: String,
+    component: String,
+  },
+  ComponentCrashLooping {
+    project: String,
+    component: String,
+    failures: usize,
+    seconds: u64,
+  },
+  OutputMuted {
+    name: String,
+  },
+  OutputUnmuted {
+    name: String,
+  },
+  OutputFiltered {
+    name: String,
+  },
+  OutputUnfiltered,
+  OutputMatching {
+    pattern: String,
+  },
+  OutputUnmatched,
+  KeysHint,
+  ConsoleHint,
+  ProxyListening {
+    address: String,
+  },
+  Listening {
+    address: String,
+  },
+  Attached {
+    component: String,
+  },
+  Detached {
+    component: String,
+  },
+  ServiceStarted {
+    service: String,
+  },
+  ServiceStopped {
+    service: String,
+  },
+  ServiceStatus {
+    service: String,
+    status: String,
+  },
+  ContainerCreating {
+    container: String,
+    image: String,
+  },
+  ContainerRemoved {
+    container: String,
+  },
+  /// A container that was asked to be removed was made by hand and is only stopped.
+  ContainerKept {
+    container: String,
+  },
+  VolumeRemoved {
+    volume: String,
+  },
+  ServiceStartFailed {
+    service: String,
+    error: String,
+  },
+  ServiceStopFailed {
+    service: String,
+    error: String,
+  },
+  ServiceFollowFailed {
+    service: String,
+    error: String,
+  },
+  TaskCommand {
+    command: String,
+  },
+  TaskSent {
+    task: String,
+  },
+  TaskScheduled {
+    task: String,
+  },
+  ScheduleSkipped {
+    task: String,
+  },
+  NoPlatformCommands {
+    name: String,
+    platform: String,
+  },
+  TaskTimedOut {
+    command: String,
+    seconds: u64,
+  },
+  TaskFinished {
+    task: String,
+    seconds: u64,
+  },
+  ForeachSucceeded {
+    task: String,
+    count: usize,
+  },
+  ForeachFailed {
+    task: String,
+    count: usize,
+    components: Vec<String>,
+  },
+  TaskFailed {
+    task: String,
+    seconds: u64,
+  },
+  BuildSucceeded {
+    component: String,
+    seconds: u64,
+  },
+  BuildFailed {
+    component: String,
+    seconds: u64,
+  },
+  BuildSkipped {
+    component: String,
+    dependency: String,
+  },
+  NothingToBuild,
+  TestsPassed {
+    component: String,
+    seconds: u64,
+  },
+  TestsFailed {
+    component: String,
+    seconds: u64,
+  },
+  NothingToTest,
+  CloneSkipped {
+    error: String,
+  },
+  ReportWritten {
+    path: String,
+  },
+  PlanTask {
+    task: String,
+    path: String,
+  },
+  PlanGroup {
+    group: String,
+    components: String,
+  },
+  PlanComponent {
+    component: String,
+    path: String,
+  },
+  PlanService {
+    service: String,
+  },
+  PlanBuild {
+    command: String,
+  },
+  PlanCommand {
+    command: String,
+  },
+  PlanEnv {
+    key: String,
+    value: String,
+  },
+  NoLogs {
+    component: String,
+  },
+  SpecApplied {
+    path: String,
+  },
+  ConfigReloaded {
+    path: String,
+  },
+  ReportFailed {
+    error: String,
+  },
+  RecordingFailed {
+    path: String,
+    error: String,
+  },
+  RemoteConfigCached {
+    url: String,
+    error: String,
+  },
+  NothingToClean,
+  LeftoversFound {
+    count: usize,
+  },
+  CleanedUp {
+    finding: String,
+  },
+  CleanUpFailed {
+    finding: String,
+    error: String,
+  },
+  ConfigValid,
+  CheckPassed {
+    check: String,
+  },
+  CheckFailed {
+    check: String,
+    problem: String,
+  },
+  CheckHint {
+    hint: String,
+  },
+  ChecksPassed {
+    checks: usize,
+  },
+  ChecksFailed {
+    failed: usize,
+    checks: usize,
+  },
+  ConfigError {
+    problem: String,
+  },
+  ConfigWarning {
+    problem: String,
+  },
+  ConfigWritten {
+    path: String,
+  },
+  GuardrailExceeded {
+    problem: String,
+  },
+  GuardrailStop,
+  ConfigExists {
+    path: String,
+  },
+  ProjectCreated {
+    path: String,
+  },
+  ComponentImported {
+    component: String,
+  },
+  ComponentUpdated {
+    component: String,
+  },
+  ImportUnchanged,
+  CommandHelp {
+    usage: String,
+    description: String,
+  },
+  Error {
+    error: String,
+  },
+}
+
+impl Message {
+  /// A generic error message.
+  pub fn error<E: fmt::Display>(error: E) -> Self {
+    Message::Error {
+      error: error.to_string(),
+    }
+  }
+
+  /// The CPU and memory used by a component.
+  pub fn usage(component: &str, usage: Usage) -> Self {
+    Message::ComponentUsage {
+      component: component.to_string(),
+      cpu: format!("{:.1}", usage.cpu),
+      memory: format!("{:.1} MB", usage.memory_kb as f64 / 1024.0),
+    }
+  }
+
+  /// The stable identifier of the message.
+  pub fn id(&self) -> &'static str {
+    match self {
+      Message::ShuttingDown => "session.shutting_down",
+      Message::SessionRunning { .. } => "session.running",
+      Message::SessionDetached { .. } => "session.detached",
+      Message::EnvironmentUp => "environment.up",
+      Message::EnvironmentReport => "environment.report",
+      Message::EnvironmentTimedOut { .. } => "environment.timeout",
+      Message::Summary => "session.summary",
+      Message::ComponentStarted { .. } => "component.started",
+      Message::ComponentReady { .. } => "component.ready",
+      Message::ComponentShutdown { .. } => "component.shutdown",
+      Message::ComponentExited { .. } => "component.exited",
+      Message::ComponentError { .. } => "component.error",
+      Message::OutputDropped { .. } => "component.dropped",
+      Message::ComponentStatus { .. } => "component.status",
+      Message::ComponentUsage { .. } => "component.usage",
+      Message::ComponentCloned { .. } => "component.cloned",
+      Message::ComponentSynced { .. } => "component.synced",
+      Message::ComponentUpToDate { .. } => "component.up_to_date",
+      Message::SyncFailed { .. } => "component.sync_failed",
+      Message::LockFailed { .. } => "component.lock_failed",
+      Message::InitSkipped { .. } => "component.init_skipped",
+      Message::ComponentExcluded { .. } => "component.excluded",
+      Message::ComponentDownloaded { .. } => "component.downloaded",
+      Message::DownloadFailed { .. } => "component.download_failed",
+      Message::NoComponents => "component.none",
+      Message::ComponentRestarting { .. } => "component.restarting",
+      Message::ComponentStopping { .. } => "component.stopping",
+      Message::ComponentPaused { .. } => "component.paused",
+      Message::ComponentResumed { .. } => "component.resumed",
+      Message::ComponentStarting { .. } => "component.starting",
+      Message::ChaosKilled { .. } => "chaos.killed",
+      Message::ShellStarted { .. } => "component.shell",
+      Message::ComponentAdded { .. } => "component.added",
+      Message::PortInUse { .. } => "component.port_in_use",
+      Message::WaitTimedOut { .. } => "component.wait_timeout",
+      Message::WaitReady { .. } => "wait.ready",
+      Message::WaitFailed { .. } => "wait.failed",
+      Message::WaitSessionEnded => "wait.ended",
+      Message::PreviousFailed { .. } => "component.previous_failed",
+      Message::ComponentRemoved { .. } => "component.removed",
+      Message::StartTimedOut { .. } => "component.start_timeout",
+      Message::ComponentUnhealthy { .. } => "component.unhealthy",
+      Message::LimitsNotApplied { .. } => "component.limits_not_applied",
+      Message::NiceNotApplied { .. } => "component.nice_not_applied",
+      Message::MemoryExceeded { .. } => "component.memory_exceeded",
+      Message::FailFast { .. } => "component.fail_fast",
+      Message::ComponentFailed { .. } => "component.failed",
+      Message::ComponentCrashLooping { .. } => "component.crash_looping",
+      Message::OutputMuted { .. } => "output.muted",
+      Message::OutputUnmuted { .. } => "output.unmuted",
+      Message::OutputFiltered { .. } => "output.filtered",
+      Message::OutputUnfiltered => "output.unfiltered",
+      Message::OutputMatching { .. } => "output.matching",
+      Message::OutputUnmatched => "output.unmatched",
+      Message::KeysHint => "keys.hint",
+      Message::ConsoleHint => "console.hint",
+      Message::Listening { .. } => "http.listening",
+      Message::ProxyListening { .. } => "proxy.listening",
+      Message::Attached { .. } => "attach.started",
+      Message::Detached { .. } => "attach.ended",
+      Message::ServiceStarted { .. } => "service.started",
+      Message::ServiceStopped { .. } => "service.stopped",
+      Message::ServiceStatus { .. } => "service.status",
+      Message::ContainerCreating { .. } => "service.creating",
+      Message::ContainerRemoved { .. } => "service.removed",
+      Message::ContainerKept { .. } => "service.kept",
+      Message::VolumeRemoved { .. } => "service.volume_removed",
+      Message::ServiceStartFailed { .. } => "service.start_failed",
+      Message::ServiceStopFailed { .. } => "service.stop_failed",
+      Message::ServiceFollowFailed { .. } => "service.follow_failed",
+      Message::TaskCommand { .. } => "task.command",
+      Message::TaskScheduled { .. } => "task.scheduled",
+      Message::ScheduleSkipped { .. } => "task.schedule_skipped",
+      Message::TaskSent { .. } => "task.sent",
+      Message::NoPlatformCommands { .. } => "platform.no_commands",
+      Message::TaskTimedOut { .. } => "task.timed_out",
+      Message::TaskFinished { .. } => "task.finished",
+      Message::TaskFailed { .. } => "task.failed",
+      Message::ForeachSucceeded { .. } => "task.foreach_succeeded",
+      Message::ForeachFailed { .. } => "task.foreach_failed",
+      Message::BuildSucceeded { .. } => "build.succeeded",
+      Message::BuildFailed { .. } => "build.failed",
+      Message::BuildSkipped { .. } => "build.skipped",
+      Message::NothingToBuild => "build.none",
+      Message::TestsPassed { .. } => "test.passed",
+      Message::TestsFailed { .. } => "test.failed",
+      Message::NothingToTest => "test.none",
+      Message::CloneSkipped { .. } => "clone.skipped",
+      Message::ReportWritten { .. } => "report.written",
+      Message::PlanTask { .. } => "plan.task",
+      Message::PlanGroup { .. } => "plan.group",
+      Message::PlanComponent { .. } => "plan.component",
+      Message::PlanService { .. } => "plan.service",
+      Message::PlanBuild { .. } => "plan.build",
+      Message::PlanCommand { .. } => "plan.command",
+      Message::PlanEnv { .. } => "plan.env",
+      Message::NoLogs { .. } => "logs.none",
+      Message::SpecApplied { .. } => "spec.applied",
+      Message::ConfigReloaded { .. } => "config.reloaded",
+      Message::ReportFailed { .. } => "report.failed",
+      Message::RecordingFailed { .. } => "record.failed",
+      Message::RemoteConfigCached { .. } => "config.remote_cached",
+      Message::NothingToClean => "audit.clean",
+      Message::LeftoversFound { .. } => "audit.leftovers",
+      Message::CleanedUp { .. } => "audit.cleaned",
+      Message::CleanUpFailed { .. } => "audit.clean_failed",
+      Message::ConfigValid => "config.valid",
+      Message::CheckPassed { .. } => "doctor.passed",
+      Message::CheckFailed { .. } => "doctor.failed",
+      Message::CheckHint { .. } => "doctor.hint",
+      Message::ChecksPassed { .. } => "doctor.healthy",
+      Message::ChecksFailed { .. } => "doctor.unhealthy",
+      Message::ConfigError { .. } => "config.error",
+      Message::ConfigWarning { .. } => "config.warning",
+      Message::ConfigWritten { .. } => "config.written",
+      Message::GuardrailExceeded { .. } => "guardrail.exceeded",
+      Message::GuardrailStop => "guardrail.stop",
+      Message::ConfigExists { .. } => "config.exists",
+      Message::ProjectCreated { .. } => "project.created",
+      Message::ComponentImported { .. } => "import.added",
+      Message::ComponentUpdated { .. } => "import.updated",
+      Message::ImportUnchanged => "import.unchanged",
+      Message::CommandHelp { .. } => "command.help",
+      Message::Error { .. } => "error",
+    }
+  }
+
+  /// The named arguments of the message.
+  pub fn args(&self) -> Vec<(&'static str, String)> {
+    match self {
+      Message::ShuttingDown
+      | Message::EnvironmentUp
+      | Message::EnvironmentReport
+      | Message::Summary
+      | Message::NoComponents
+      | Message::NothingToClean
+      | Message::NothingToBuild
+      | Message::NothingToTest
+      | Message::ConfigValid
+      | Message::GuardrailStop
+      | Message::OutputUnfiltered
+      | Message::OutputUnmatched
+      | Message::ImportUnchanged
+      | Message::KeysHint
+      | Message::WaitSessionEnded
+      | Message::ConsoleHint => vec![],
+      Message::SessionRunning { pid } => vec![("pid", pid.to_string())],
+      Message::SessionDetached { pid, log } => vec![("pid", pid.to_string()), ("log", log.clone())],
+      Message::OutputMatching { pattern } => vec![("pattern", pattern.clone())],
+      Message::StartTimedOut { seconds } | Message::EnvironmentTimedOut { seconds } => {
+        vec![("seconds", seconds.to_string())]
+      }
+      Message::LimitsNotApplied { error } => vec![("error", error.clone())],
+      Message::NiceNotApplied { nice } => vec![("nice", nice.to_string())],
+      Message::MemoryExceeded { limit } => vec![("limit", limit.clone())],
+      Message::ComponentUsage {
+        component,
+        cpu,
+        memory,
+      } => vec![
+        ("component", component.clone()),
+        ("cpu", cpu.clone()),
+        ("memory", memory.clone()),
+      ],
+      Message::ComponentUnhealthy {
+        component,
+        failures,
+      } => vec![
+        ("component", component.clone()),
+        ("failures", failures.to_string()),
+      ],
+      Message::ComponentStarted { component }
+      | Message::ComponentReady { component }
+      | Message::ComponentShutdown { component }
+      | Message::ComponentCloned { component }
+      | Message::ComponentSynced { component }
+      | Message::ComponentUpToDate { component }
+      | Message::InitSkipped { component }
+      | Message::ComponentDownloaded { component }
+      | Message::ComponentRestarting { component }
+      | Message::ComponentStopping { component }
+      | Message::ComponentPaused { component }
+      | Message::ComponentResumed { component }
+      | Message::ComponentStarting { component }
+      | Message::ChaosKilled { component }
+      | Message::WaitFailed { component }
+      | Message::ComponentAdded { component }
+      | Message::ComponentRemoved { component }
+      | Message::FailFast { component }
+      | Message::ComponentImported { component }
+      | Message::ComponentUpdated { component }
+      | Message::NoLogs { component }
+      | Message::Attached { component }
+      | Message::Detached { component } => vec![("component", component.clone())],
+      Message::OutputMuted { name }
+      | Message::OutputUnmuted { name }
+      | Message::OutputFiltered { name } => {
+        vec![("name", name.clone())]
+      }
+      Message::ComponentFailed { project, component } => {
+        vec![
+          ("project", project.clone()),
+          ("component", component.clone()),
+        ]
+      }
+      Message::ComponentCrashLooping {
+        project,
+        component,
+        failures,
+        seconds,
+      } => vec![
+        ("project", project.clone()),
+        ("component", component.clone()),
+        ("failures", failures.to_string()),
+        ("seconds", seconds.to_string()),
+      ],
+      Message::ComponentError { component, error } => {
+        vec![("component", component.clone()), ("error", error.clone())]
+      }
+      Message::ComponentStatus {
+        component,
+        status,
+        pid,
+        ready,
+      } => vec![
+        ("component", component.clone()),
+        ("status", status.clone()),
+        (
+          "pid",
+          pid.map(|p| p.to_string()).unwrap_or_else(|| "-".into()),
+        ),
+        ("ready", if *ready { "yes" } else { "no" }.into()),
+      ],
+      Message::WaitTimedOut { condition, seconds } => vec![
+        ("condition", condition.clone()),
+        ("seconds", seconds.to_string()),
+      ],
+      Message::PreviousFailed {
+        component,
+        previous,
+      } => vec![
+        ("component", component.clone()),
+        ("previous", previous.clone()),
+      ],
+      Message::ShellStarted { component, shell } => {
+        vec![("component", component.clone()), ("shell", shell.clone())]
+      }
+      Message::ComponentExited { component, code } => vec![
+        ("component", component.clone()),
+        ("code", crate::process::describe_exit_code(*code)),
+      ],
+      Message::OutputDropped { lines } => vec![("lines", lines.to_string())],
+      Message::PortInUse { port, pid } => vec![
+        ("port", port.to_string()),
+        (
+          "process",
+          pid
+            .map(|p| format!("pid {}", p))
+            .unwrap_or_else(|| "another process".into()),
+        ),
+      ],
+      Message::ServiceStarted { service } | Message::ServiceStopped { service } => {
+        vec![("service", service.clone())]
+      }
+      Message::ServiceStatus { service, status } => {
+        vec![("service", service.clone()), ("status", status.clone())]
+      }
+      Message::ContainerRemoved { container } | Message::ContainerKept { container } => {
+        vec![("container", container.clone())]
+      }
+      Message::VolumeRemoved { volume } => vec![("volume", volume.clone())],
+      Message::ContainerCreating { container, image } => {
+        vec![("container", container.clone()), ("image", image.clone())]
+      }
+      Message::ServiceStartFailed { service, error }
+      | Message::ServiceStopFailed { service, error }
+      | Message::ServiceFollowFailed { service, error } => {
+        vec![("service", service.clone()), ("error", error.clone())]
+      }
+      Message::ComponentExcluded {
+        component,
+        condition,
+      } => vec![
+        ("component", component.clone()),
+        ("condition", condition.clone()),
+      ],
+      Message::SyncFailed { component, error }
+      | Message::LockFailed { component, error }
+      | Message::DownloadFailed { component, error } => {
+        vec![("component", component.clone()), ("error", error.clone())]
+      }
+      Message::ConfigError { problem }
+      | Message::ConfigWarning { problem }
+      | Message::GuardrailExceeded { problem } => {
+        vec![("problem", problem.clone())]
+      }
+      Message::CommandHelp { usage, description } => {
+        vec![
+          ("usage", usage.clone()),
+          ("description", description.clone()),
+        ]
+      }
+      Message::TaskCommand { command } => vec![("command", command.clone())],
+      Message::TaskScheduled { task }
+      | Message::ScheduleSkipped { task }
+      | Message::TaskSent { task } => {
+        vec![("task", task.clone())]
+      }
+      Message::NoPlatformCommands { name, platform } => {
+        vec![("name", name.clone()), ("platform", platform.clone())]
+      }
+      Message::TaskTimedOut { command, seconds } => {
+        vec![
+          ("command", command.clone()),
+          ("seconds", seconds.to_string()),
+        ]
+      }
+      Message::TaskFinished { task, seconds } | Message::TaskFailed { task, seconds } => {
+        vec![("task", task.clone()), ("seconds", seconds.to_string())]
+      }
+      Message::ForeachSucceeded { task, count } => {
+        vec![("task", task.clone()), ("count", count.to_string())]
+      }
+      Message::ForeachFailed {
+        task,
+        count,
+        components,
+      } => vec![
+        ("task", task.clone()),
+        ("count", count.to_string()),
+        ("failed", components.len().to_string()),
+        ("components", components.join(", ")),
+      ],
+      Message::BuildSucceeded { component, seconds }
+      | Message::BuildFailed { component, seconds }
+      | Message::TestsPassed { component, seconds }
+      | Message::TestsFailed { component, seconds } => {
+        vec![
+          ("component", component.clone()),
+          ("seconds", seconds.to_string()),
+        ]
+      }
+      Message::BuildSkipped {
+        component,
+        dependency,
+      } => vec![
+        ("component", component.clone()),
+        ("dependency", dependency.clone()),
+      ],
+      Message::ReportWritten { path }
+      | Message::SpecApplied { path }
+      | Message::ConfigReloaded { path }
+      | Message::ConfigWritten { path }
+      | Message::ConfigExists { path }
+      | Message::ProjectCreated { path } => vec![("path", path.clone())],
+      Message::CleanedUp { finding } => vec![("finding", finding.clone())],
+      Message::CheckPassed { check } => vec![("check", check.clone())],
+      Message::CheckFailed { check, problem } => {
+        vec![("check", check.clone()), ("problem", problem.clone())]
+      }
+      Message::CheckHint { hint } => vec![("hint", hint.clone())],
+      Message::WaitReady { components } => vec![("components", components.clone())],
+      Message::LeftoversFound { count } => vec![("count", count.to_string())],
+      Message::ChecksPassed { checks } => vec![("checks", checks.to_string())],
+      Message::ChecksFailed { failed, checks } => vec![
+        ("failed", failed.to_string()),
+        ("checks", checks.to_string()),
+      ],
+      Message::PlanTask { task, path } => vec![("task", task.clone()), ("path", path.clone())],
+      Message::PlanGroup { group, components } => {
+        vec![("group", group.clone()), ("components", components.clone())]
+      }
+      Message::PlanComponent { component, path } => {
+        vec![("component", component.clone()), ("path", path.clone())]
+      }
+      Message::PlanService { service } => vec![("service", service.clone())],
+      Message::PlanBuild { command } | Message::PlanCommand { command } => {
+        vec![("command", command.clone())]
+      }
+      Message::PlanEnv { key, value } => vec![("key", key.clone()), ("value", value.clone())],
+      Message::Listening { address } | Message::ProxyListening { address } => {
+        vec![("address", address.clone())]
+      }
+      Message::CleanUpFailed { finding, error } => {
+        vec![("finding", finding.clone()), ("error", error.clone())]
+      }
+      Message::CloneSkipped { error }
+      | Message::ReportFailed { error }
+      | Message::Error { error } => {
+        vec![("error", error.clone())]
+      }
+      Message::RecordingFailed { path, error } => {
+        vec![("path", path.clone()), ("error", error.clone())]
+      }
+      Message::RemoteConfigCached { url, error } => {
+        vec![("url", url.clone()), ("error", error.clone())]
+      }
+    }
+  }
+
+  /// Returns true if the message reports a failure.
+  pub fn is_error(&self) -> bool {
+    matches!(
+      self,
+      Message::ComponentError { .. }
+        | Message::FailFast { .. }
+        | Message::ComponentFailed { .. }
+        | Message::PortInUse { .. }
+        | Message::WaitTimedOut { .. }
+        | Message::WaitFailed { .. }
+        | Message::WaitSessionEnded
+        | Message::PreviousFailed { .. }
+        | Message::TaskTimedOut { .. }
+        | Message::ForeachFailed { .. }
+        | Message::NoPlatformCommands { .. }
+        | Message::StartTimedOut { .. }
+        | Message::EnvironmentTimedOut { .. }
+        | Message::ComponentUnhealthy { .. }
+        | Message::LimitsNotApplied { .. }
+        | Message::NiceNotApplied { .. }
+        | Message::MemoryExceeded { .. }
+        | Message::ComponentCrashLooping { .. }
+        | Message::ServiceStartFailed { .. }
+        | Message::ServiceStopFailed { .. }
+        | Message::ServiceFollowFailed { .. }
+        | Message::BuildFailed { .. }
+        | Message::BuildSkipped { .. }
+        | Message::TestsFailed { .. }
+        | Message::CloneSkipped { .. }
+        | Message::SyncFailed { .. }
+        | Message::LockFailed { .. }
+        | Message::DownloadFailed { .. }
+        | Message::ReportFailed { .. }
+        | Message::RecordingFailed { .. }
+        | Message::CleanUpFailed { .. }
+        | Message::ConfigError { .. }
+        | Message::CheckFailed { .. }
+        | Message::ChecksFailed { .. }
+        | Message::ConfigExists { .. }
+        | Message::GuardrailStop
+        | Message::Error { .. }
+    )
+  }
+}
+
+impl fmt::Display for Message {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let id = self.id();
+    let template = CATALOG
+      .iter()
+      .find(|(key, _)| *key == id)
+      .map(|(_, text)| *text)
+      .unwrap_or(id);
+    write!(f, "{}", render(template, &self.args()))
+  }
+}
+
+/// Substitutes arguments for their placeholders in a single pass so values that happen to
+/// contain braces are left alone.
+fn render(template: &str, args: &[(&str, String)]) -> String {
+  let mut text = String::new();
+  let mut rest = template;
+  while let Some(start) = rest.find('{') {
+    text.push_str(&rest[..start]);
+    let arg = rest[start + 1..]
+      .find('}')
+      .map(|end| &rest[start + 1..start + 1 + end])
+      .and_then(|name| args.iter().find(|(n, _)| *n == name));
+    match arg {
+      Some((name, value)) => {
+        text.push_str(value);
+        rest = &rest[start + name.len() + 2..];
+      }
+      None => {
+        text.push('{');
+        rest = &rest[start + 1..];
+      }
+    }
+  }
+  text.push_str(rest);
+  text
+}
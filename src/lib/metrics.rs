@@ -0,0 +1,182 @@
+use crate::state::{ComponentState, SessionState};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/// A metric family with its name, type, help text and the value for each component.
+type Family<'a> = (
+  &'static str,
+  &'static str,
+  &'static str,
+  Box<dyn Fn(&ComponentState) -> u64 + 'a>,
+);
+
+/// How a component did over a session, shown in the summary printed when the session ends.
+pub struct ComponentSummary {
+  pub state: ComponentState,
+  /// How long the component ran for, over every time it was started.
+  pub uptime: Duration,
+  pub output_lines: u64,
+}
+
+/// Counters for a session that are not part of its state file, exposed in the Prometheus text
+/// format along with the state.
+#[derive(Default)]
+pub struct Metrics {
+  output_lines: Mutex<HashMap<String, u64>>,
+  service_failures: Mutex<HashMap<String, u64>>,
+  run_times: Mutex<HashMap<String, Duration>>,
+  /// The services started or stopped during the session and whether they were stopped.
+  services: Mutex<BTreeMap<String, bool>>,
+}
+
+impl Metrics {
+  /// Counts a line of output from a component.
+  pub fn output_line(&self, component: &str) {
+    *self
+      .output_lines
+      .lock()
+      .unwrap()
+      .entry(component.to_string())
+      .or_insert(0) += 1;
+  }
+
+  /// Counts a service that failed to start.
+  pub fn service_failure(&self, service: &str) {
+    *self
+      .service_failures
+      .lock()
+      .unwrap()
+      .entry(service.to_string())
+      .or_insert(0) += 1;
+  }
+
+  /// Adds the time a component's process ran for once it has exited.
+  pub fn component_ran(&self, component: &str, time: Duration) {
+    *self
+      .run_times
+      .lock()
+      .unwrap()
+      .entry(component.to_string())
+      .or_default() += time;
+  }
+
+  /// Records a service that was started.
+  pub fn service_started(&self, service: &str) {
+    self
+      .services
+      .lock()
+      .unwrap()
+      .insert(service.to_string(), false);
+  }
+
+  /// Records a service that was stopped.
+  pub fn service_stopped(&self, service: &str) {
+    self
+      .services
+      .lock()
+      .unwrap()
+      .insert(service.to_string(), true);
+  }
+
+  /// The state of each component along with how long it ran and how much it wrote.
+  pub fn summary(&self, states: Vec<ComponentState>) -> Vec<ComponentSummary> {
+    let run_times = self.run_times.lock().unwrap();
+    let output_lines = self.output_lines.lock().unwrap();
+    states
+      .into_iter()
+      .map(|state| ComponentSummary {
+        uptime: run_times.get(&state.name).copied().unwrap_or_default(),
+        output_lines: output_lines.get(&state.name).copied().unwrap_or(0),
+        state,
+      })
+      .collect()
+  }
+
+  /// The services started or stopped during the session, by name, and whether they were
+  /// stopped.
+  pub fn services(&self) -> Vec<(String, bool)> {
+    self
+      .services
+      .lock()
+      .unwrap()
+      .iter()
+      .map(|(name, stopped)| (name.clone(), *stopped))
+      .collect()
+  }
+
+  /// Renders the metrics for the session in the Prometheus text format.
+  pub fn render(&self, state: &SessionState, now: SystemTime) -> String {
+    let mut out = String::new();
+    let output_lines = self.output_lines.lock().unwrap();
+    let families: [Family; 4] = [
+      (
+        "conductor_component_up",
+        "gauge",
+        "Whether the component is running.",
+        Box::new(|c| c.uptime(now).is_some() as u64),
+      ),
+      (
+        "conductor_component_uptime_seconds",
+        "gauge",
+        "Seconds since the component was started, 0 if it is not running.",
+        Box::new(|c| c.uptime(now).unwrap_or(0)),
+      ),
+      (
+        "conductor_component_restarts_total",
+        "counter",
+        "Times the component has been restarted.",
+        Box::new(|c| c.restarts as u64),
+      ),
+      (
+        "conductor_component_output_lines_total",
+        "counter",
+        "Lines of output written by the component.",
+        Box::new(|c| output_lines.get(&c.name).copied().unwrap_or(0)),
+      ),
+    ];
+    for (name, kind, help, value) in families.iter() {
+      header(&mut out, name, kind, help);
+      for c in state.components.iter() {
+        let _ = writeln!(
+          out,
+          "{}{{component=\"{}\"}} {}",
+          name,
+          escape(&c.name),
+          value(c)
+        );
+      }
+    }
+
+    // Running services that have never failed are listed with a count of 0.
+    let mut failures: BTreeMap<String, u64> =
+      state.services.iter().map(|s| (s.name.clone(), 0)).collect();
+    failures.extend(self.service_failures.lock().unwrap().clone());
+    let name = "conductor_service_start_failures_total";
+    header(
+      &mut out,
+      name,
+      "counter",
+      "Times the service failed to start.",
+    );
+    for (service, count) in failures.iter() {
+      let _ = writeln!(out, "{}{{service=\"{}\"}} {}", name, escape(service), count);
+    }
+    out
+  }
+}
+
+/// Writes the help and type lines that start a metric family.
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+  let _ = writeln!(out, "# HELP {} {}", name, help);
+  let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// Escapes a label value.
+fn escape(value: &str) -> String {
+  value
+    .replace('\\', "\\\\")
+    .replace('"', "\\\"")
+    .replace('\n', "\\n")
+}
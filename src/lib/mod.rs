@@ -1,13 +1,89 @@
+mod audit;
+mod cancel;
+mod chaos;
+mod command;
 mod component;
+mod console;
+mod control;
+mod daemon;
+#[cfg(unix)]
+mod dashboard;
+mod doctor;
+mod encrypted;
+mod env;
+mod exit;
+mod expand;
+mod export;
 mod git;
+mod graph;
 mod group;
+mod guardrails;
+mod http;
+mod import;
+mod init_cache;
+mod journal;
+#[cfg(unix)]
+mod keys;
+mod limits;
+mod lines;
+mod lock;
+mod logs;
+mod message;
+mod metrics;
+mod notify;
+mod pattern;
+mod plan;
+mod platform;
+mod plugin;
+mod process;
 mod project;
+mod proxy;
+mod random;
+mod record;
+mod reload;
+mod remote;
+mod report;
+mod scaffold;
+mod schedule;
+mod scrollback;
 mod service;
+#[cfg(unix)]
+mod signals;
+mod source;
+mod state;
 mod supervisor;
 mod task;
+mod telemetry;
+mod terminal;
+mod top;
+mod user;
+mod validate;
+mod vars;
 
+pub use cancel::{CancellationToken, Completion};
+pub use chaos::Chaos;
+pub use command::Command;
 use component::*;
+pub use daemon::{finish_detached, take_detached};
+pub use encrypted::encrypt;
+pub use exit::ExitCode;
 use group::*;
-pub use project::Project;
+pub use import::NewComponent;
+pub use message::Message;
+pub use notify::wait_for_notifications;
+pub use pattern::Pattern;
+pub use project::{Project, SetupOptions};
+pub use record::replay;
+pub use reload::ConfigWatch;
+pub use remote::{config_location, fetch_config, is_remote};
+pub use scaffold::{create, scaffold};
+pub use scrollback::Retention;
+pub use service::use_docker_socket;
 use service::*;
+pub use supervisor::{
+  ComponentEvent, ComponentEventBody, ComponentReport, RunOptions, SessionEvent, Supervisor,
+  SupervisorHandle, Tuning,
+};
 pub mod ui;
+pub use telemetry::{export_trace, trace_invocation, Telemetry};
+pub use user::UserConfig;
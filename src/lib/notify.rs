@@ -0,0 +1,146 @@
+use serde::{Deserialize, Serialize};
+use std::io::{self, IsTerminal, Write};
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Notifications still being sent.
+static PENDING: Mutex<Vec<JoinHandle<()>>> = Mutex::new(vec![]);
+
+/// Waits for notifications that are still being sent, so they are not lost when conductor exits
+/// right after sending them.
+pub fn wait_for_notifications() {
+  let pending: Vec<_> = PENDING.lock().unwrap().drain(..).collect();
+  for handle in pending {
+    let _ = handle.join();
+  }
+}
+
+fn background<F: FnOnce() + Send + 'static>(f: F) {
+  let mut pending = PENDING.lock().unwrap();
+  pending.retain(|h| !h.is_finished());
+  pending.push(thread::spawn(f));
+}
+
+/// Something that happened in a session that can be notified about.
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationEvent {
+  /// A component without `retry` exited with an error.
+  Failure,
+  /// A retried component failed repeatedly within a short time.
+  CrashLoop,
+  /// A task or setup that ran for at least `long_task_secs` finished.
+  TaskFinished,
+}
+
+/// How notifications are shown in the terminal conductor is running in.
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TerminalAlert {
+  /// Rings the terminal bell.
+  Bell,
+  /// Shows the notification with the OSC 9 escape sequence, which iTerm2, Windows Terminal, kitty
+  /// and others turn into a desktop notification. Terminals without support ignore it.
+  Osc,
+}
+
+/// Where and when notifications about a session are posted.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(default)]
+pub struct Notifications {
+  /// A URL that notifications are posted to as JSON. The body has a `text` field so Slack
+  /// incoming webhooks can be used directly.
+  pub webhook: Option<String>,
+  /// Shows notifications on the desktop, through the notification service on Linux, Notification
+  /// Center on macOS and toast notifications on Windows.
+  pub desktop: bool,
+  /// Alerts the terminal, which is only done when stderr is a terminal.
+  pub terminal: Option<TerminalAlert>,
+  /// The events that are notified.
+  pub events: Vec<NotificationEvent>,
+  /// How long a task has to run before its completion is notified, in seconds.
+  pub long_task_secs: u64,
+}
+
+impl Default for Notifications {
+  fn default() -> Self {
+    Notifications {
+      webhook: None,
+      desktop: false,
+      terminal: None,
+      events: vec![
+        NotificationEvent::Failure,
+        NotificationEvent::CrashLoop,
+        NotificationEvent::TaskFinished,
+      ],
+      long_task_secs: 30,
+    }
+  }
+}
+
+impl Notifications {
+  /// Sends a notification for an event if it is wanted. `name` is the component or task the
+  /// event is about. Notifications are sent in the background and failures are only logged so a
+  /// broken webhook never disturbs the session.
+  pub fn send(&self, event: NotificationEvent, project: &str, name: &str, text: String) {
+    if !self.events.contains(&event) {
+      return;
+    }
+    if let Some(url) = self.webhook.clone() {
+      let key = match event {
+        NotificationEvent::TaskFinished => "task",
+        _ => "component",
+      };
+      let mut body = serde_json::json!({
+        "text": text,
+        "project": project,
+        "event": event,
+      });
+      body[key] = name.into();
+      background(move || {
+        // Responses that are not successful are an error as well.
+        let posted = ureq::post(&url)
+          .timeout(Duration::from_secs(10))
+          .send_json(&body);
+        if let Err(e) = posted {
+          log::warn!("Could not post notification: {}", e);
+        }
+      });
+    }
+    if let Some(alert) = self.terminal {
+      terminal(alert, &text);
+    }
+    if self.desktop {
+      let title = format!("conductor: {}", project);
+      background(move || {
+        if let Err(e) = desktop(&title, &text) {
+          log::warn!("Could not show notification: {}", e);
+        }
+      });
+    }
+  }
+}
+
+/// Writes the alert to stderr, so it is not mixed into output that is piped elsewhere.
+fn terminal(alert: TerminalAlert, text: &str) {
+  let mut stderr = io::stderr();
+  if !stderr.is_terminal() {
+    return;
+  }
+  // Control characters would end the escape sequence early.
+  let text: String = text.chars().filter(|c| !c.is_control()).collect();
+  let _ = match alert {
+    TerminalAlert::Bell => write!(stderr, "\x07"),
+    TerminalAlert::Osc => write!(stderr, "\x1b]9;{}\x07", text),
+  };
+  let _ = stderr.flush();
+}
+
+fn desktop(title: &str, text: &str) -> Result<(), notify_rust::error::Error> {
+  notify_rust::Notification::new()
+    .summary(title)
+    .body(text)
+    .show()
+    .map(|_| ())
+}
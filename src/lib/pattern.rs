@@ -0,0 +1,105 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+/// A regular expression that output lines are matched against, in the syntax of the regex crate.
+/// Matching takes time linear in the length of the line whatever the pattern, so patterns can be
+/// checked against every line of output. A leading `(?i)` makes the match case insensitive. A
+/// line matches if the pattern is found anywhere in it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Pattern {
+  regex: Regex,
+}
+
+impl FromStr for Pattern {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Regex::new(s)
+      .map(|regex| Pattern { regex })
+      .map_err(|e| format!("Invalid pattern {}: {}", s, e))
+  }
+}
+
+impl TryFrom<String> for Pattern {
+  type Error = String;
+
+  fn try_from(s: String) -> Result<Self, Self::Error> {
+    s.parse()
+  }
+}
+
+impl From<Pattern> for String {
+  fn from(pattern: Pattern) -> Self {
+    pattern.regex.as_str().to_string()
+  }
+}
+
+impl PartialEq for Pattern {
+  fn eq(&self, other: &Self) -> bool {
+    self.regex.as_str() == other.regex.as_str()
+  }
+}
+
+impl fmt::Display for Pattern {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.regex.as_str())
+  }
+}
+
+impl Pattern {
+  /// Returns true if the pattern is found in the line.
+  pub fn is_match(&self, line: &str) -> bool {
+    self.regex.is_match(line)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::time::{Duration, Instant};
+
+  fn pattern(s: &str) -> Pattern {
+    s.parse().unwrap()
+  }
+
+  #[test]
+  fn matches_anywhere_in_the_line() {
+    assert!(pattern("error").is_match("an error occurred"));
+    assert!(!pattern("^error").is_match("an error occurred"));
+    assert!(pattern("ERROR|panic").is_match("thread 'main' panicked"));
+    assert!(pattern(r"status=\d{3}$").is_match("GET / status=500"));
+  }
+
+  #[test]
+  fn ignores_case_when_asked() {
+    assert!(pattern("(?i)warn").is_match("WARNING: disk almost full"));
+    assert!(!pattern("warn").is_match("WARNING: disk almost full"));
+  }
+
+  #[test]
+  fn rejects_invalid_patterns() {
+    let error = "(unclosed".parse::<Pattern>().unwrap_err();
+    assert!(error.starts_with("Invalid pattern (unclosed"), "{}", error);
+  }
+
+  #[test]
+  fn matches_ambiguous_repetitions_quickly() {
+    let line = "a".repeat(10_000);
+    let started = Instant::now();
+    assert!(!pattern("(a|a)*b").is_match(&line));
+    assert!(!pattern("(a*)*b").is_match(&line));
+    assert!(started.elapsed() < Duration::from_secs(1));
+  }
+
+  #[test]
+  fn round_trips_through_yaml() {
+    let p: Pattern = serde_yaml::from_str("'(?i)warn|error'").unwrap();
+    assert_eq!(p, pattern("(?i)warn|error"));
+    let written = serde_yaml::to_string(&p).unwrap();
+    assert_eq!(serde_yaml::from_str::<Pattern>(&written).unwrap(), p);
+  }
+}
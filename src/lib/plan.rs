@@ -0,0 +1,259 @@
+use crate::encrypted;
+use crate::env::read_env_file;
+use crate::supervisor::expand_env;
+use crate::task::Task;
+use crate::ui::{self, Table};
+use crate::{Component, Message, Project};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// Shown in place of secrets, env commands, which are not run in a dry run or when printing an
+/// environment, and decrypted values.
+pub(crate) const MASK: &str = "***";
+
+/// The environment a command would get from the configuration, sorted by name. The variables
+/// conductor itself was started with are left out.
+fn env(
+  project: &Project,
+  dir: &Path,
+  env_file: Option<&String>,
+  layers: &[&HashMap<String, String>],
+  masked: &[&HashMap<String, String>],
+) -> BTreeMap<String, String> {
+  let mut env = match project.dotenv.load(dir) {
+    Ok(vars) => vars,
+    Err(e) => {
+      ui::message(Message::error(e));
+      HashMap::new()
+    }
+  };
+  env.extend(project.env.clone());
+  if let Some(env_file) = env_file {
+    match read_env_file(&dir.join(expand_env(env_file))) {
+      Ok(vars) => env.extend(vars),
+      Err(e) => ui::message(Message::error(e)),
+    }
+  }
+  for layer in layers {
+    env.extend((*layer).clone());
+  }
+  let mut env: BTreeMap<_, _> = env
+    .into_iter()
+    .map(|(k, v)| (k, encrypted::conceal(&expand_env(&v))))
+    .collect();
+  let project_masked = [&project.env_commands, &project.secrets];
+  for name in project_masked.iter().chain(masked).flat_map(|m| m.keys()) {
+    env.insert(name.clone(), MASK.into());
+  }
+  env
+}
+
+/// The whole environment a task's commands get, the variables conductor was started with
+/// included. Secrets and env commands are masked.
+pub(crate) fn task_env(
+  project: &Project,
+  task: &Task,
+  overrides: &HashMap<String, String>,
+) -> BTreeMap<String, String> {
+  let dir = project
+    .root_path
+    .join(expand_env(&task.path.to_string_lossy()));
+  let mut vars: BTreeMap<_, _> = std::env::vars().collect();
+  vars.extend(env(
+    project,
+    &dir,
+    task.env_file.as_ref(),
+    &[&task.env, overrides],
+    &[&task.env_commands, &task.secrets],
+  ));
+  vars
+}
+
+/// The whole environment a component is started with, the variables conductor was started with
+/// included. `extra` is the env it gets from its group and the command line. Secrets and env
+/// commands are masked.
+pub(crate) fn component_env(
+  project: &Project,
+  component: &Component,
+  extra: &HashMap<String, String>,
+) -> BTreeMap<String, String> {
+  let dir = project
+    .root_path
+    .join(expand_env(&component.get_path().to_string_lossy()));
+  let mut vars: BTreeMap<_, _> = std::env::vars().collect();
+  vars.extend(env(
+    project,
+    &dir,
+    component.env_file.as_ref(),
+    &[&project.service_env(component), &component.env, extra],
+    &[&component.env_commands, &component.secrets],
+  ));
+  vars
+}
+
+fn print_env(env: BTreeMap<String, String>) {
+  for (key, value) in env {
+    ui::detail(Message::PlanEnv { key, value });
+  }
+}
+
+/// Prints the commands a task would run, where and with which environment.
+pub(crate) fn task(project: &Project, task: &Task, overrides: &HashMap<String, String>) {
+  let dir = project
+    .root_path
+    .join(expand_env(&task.path.to_string_lossy()));
+  ui::message(Message::PlanTask {
+    task: task.name.clone(),
+    path: dir.display().to_string(),
+  });
+  if let Some(e) = &task.unavailable {
+    ui::message(Message::error(e));
+  }
+  for command in task.commands.iter() {
+    ui::detail(Message::PlanCommand {
+      command: command.clone(),
+    });
+  }
+  print_env(env(
+    project,
+    &dir,
+    task.env_file.as_ref(),
+    &[&task.env, overrides],
+    &[&task.env_commands, &task.secrets],
+  ));
+}
+
+/// Prints how a component would be started. `extra` is the env it gets from its group and the
+/// command line.
+pub(crate) fn component(
+  project: &Project,
+  component: &Component,
+  extra: &HashMap<String, String>,
+  build: bool,
+) {
+  let dir = project
+    .root_path
+    .join(expand_env(&component.get_path().to_string_lossy()));
+  ui::message(Message::PlanComponent {
+    component: component.name.clone(),
+    path: dir.display().to_string(),
+  });
+  if build {
+    for command in component.build.iter() {
+      ui::detail(Message::PlanBuild {
+        command: command.clone(),
+      });
+    }
+  }
+  match component.start_command() {
+    Ok(command) => ui::detail(Message::PlanCommand {
+      command: command.to_string(),
+    }),
+    Err(e) => ui::message(Message::error(e)),
+  }
+  print_env(env(
+    project,
+    &dir,
+    component.env_file.as_ref(),
+    &[&project.service_env(component), &component.env, extra],
+    &[&component.env_commands, &component.secrets],
+  ));
+}
+
+/// Prints the services the components depend on, each once.
+pub(crate) fn services(project: &Project, components: &[&Component]) {
+  let mut planned: Vec<String> = vec![];
+  for name in components.iter().flat_map(|c| c.services.iter()) {
+    if let Some(service) = project.service_by_name(name) {
+      if !planned.contains(&service.name) {
+        ui::message(Message::PlanService {
+          service: service.name.clone(),
+        });
+        planned.push(service.name);
+      }
+    }
+  }
+}
+
+/// The table printed before an invocation starts anything, listing the components it launches
+/// with their groups, paths, ports and services, and the tasks it runs.
+pub(crate) struct Banner<'a> {
+  project: &'a Project,
+  table: Table,
+  launches: bool,
+}
+
+impl<'a> Banner<'a> {
+  pub fn new(project: &'a Project) -> Self {
+    Banner {
+      project,
+      table: Table::new(&["Name", "Kind", "Group", "Path", "Ports", "Services"]),
+      launches: false,
+    }
+  }
+
+  pub fn component(&mut self, component: &Component, group: Option<&str>) {
+    let ports = component
+      .ports
+      .iter()
+      .map(|p| p.to_string())
+      .chain(component.auto_ports.iter().map(|p| format!("{} (auto)", p)))
+      .collect::<Vec<_>>();
+    self.launches = true;
+    self.table.row(vec![
+      component.name.as_str().into(),
+      "component".into(),
+      group.unwrap_or("-").into(),
+      self.path(&component.get_path()).into(),
+      list(&ports).into(),
+      list(&component.services).into(),
+    ]);
+  }
+
+  /// Adds a task, with the services of the component it belongs to if any.
+  pub fn task(&mut self, name: &str, task: &Task, component: Option<&Component>) {
+    self.table.row(vec![
+      name.into(),
+      "task".into(),
+      "-".into(),
+      self.path(&task.path).into(),
+      "-".into(),
+      list(component.map_or(&[][..], |c| &c.services)).into(),
+    ]);
+  }
+
+  /// Whether any components were added, as opposed to only tasks.
+  pub fn launches(&self) -> bool {
+    self.launches
+  }
+
+  pub fn print(&self) {
+    if !self.table.is_empty() {
+      ui::table(&self.table);
+    }
+  }
+
+  /// A path relative to the project root, `.` for the root itself.
+  fn path(&self, path: &Path) -> String {
+    let dir = self
+      .project
+      .root_path
+      .join(expand_env(&path.to_string_lossy()));
+    match dir.strip_prefix(&self.project.root_path) {
+      Ok(relative) if relative.as_os_str().is_empty() => ".".into(),
+      Ok(relative) => relative.display().to_string(),
+      Err(_) => dir.display().to_string(),
+    }
+  }
+}
+
+fn list<T: AsRef<str>>(items: &[T]) -> String {
+  match items.is_empty() {
+    true => "-".into(),
+    false => items
+      .iter()
+      .map(|i| i.as_ref())
+      .collect::<Vec<_>>()
+      .join(", "),
+  }
+}
@@ -0,0 +1,46 @@
+use crate::Message;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// The platform conductor runs on, as named by the suffix of command variants such as
+/// `start_windows` and `start_unix`.
+pub const PLATFORM: &str = if cfg!(windows) { "windows" } else { "unix" };
+
+/// Picks the commands to run on this platform: its own variant when one is given, otherwise the
+/// commands shared by all platforms. Fails when there are no shared commands and only another
+/// platform has a variant, rather than quietly running nothing.
+pub fn select<'a, T: Default + PartialEq>(
+  name: &str,
+  shared: &'a T,
+  windows: Option<&'a T>,
+  unix: Option<&'a T>,
+) -> Result<&'a T, String> {
+  let (own, other) = match cfg!(windows) {
+    true => (windows, unix),
+    false => (unix, windows),
+  };
+  match own {
+    Some(own) => Ok(own),
+    None if other.is_some() && *shared == T::default() => Err(
+      Message::NoPlatformCommands {
+        name: name.to_string(),
+        platform: PLATFORM.to_string(),
+      }
+      .to_string(),
+    ),
+    None => Ok(shared),
+  }
+}
+
+/// Creates a file only the current user can read, failing if anything exists at the path.
+pub(crate) fn create_private(path: &Path) -> io::Result<fs::File> {
+  let mut options = fs::OpenOptions::new();
+  options.write(true).create_new(true);
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::OpenOptionsExt;
+    options.mode(0o600);
+  }
+  options.open(path)
+}
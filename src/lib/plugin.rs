@@ -0,0 +1,119 @@
+use crate::service::runtime_file;
+use crate::supervisor::expand_env;
+use crate::Service;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use subprocess::{Exec, Redirection};
+
+/// A request sent to a service plugin as a line of JSON on its stdin. For `start` and `stop`
+/// the plugin answers with a Reply on stdout. For `logs` it writes the service's output to
+/// stdout until it is killed.
+#[derive(Serialize)]
+struct Request<'a> {
+  action: &'a str,
+  name: &'a str,
+  container: Option<&'a str>,
+  command: Option<&'a str>,
+  /// The service's working directory.
+  path: PathBuf,
+  env: HashMap<&'a str, String>,
+}
+
+/// The answer of a plugin, the last line it writes to stdout.
+#[derive(Deserialize)]
+struct Reply {
+  ok: bool,
+  /// Identifies what was started, such as a container or process id.
+  #[serde(default)]
+  id: String,
+  #[serde(default)]
+  error: Option<String>,
+}
+
+/// The executable that provides services of a type.
+pub fn executable(service_type: &str) -> String {
+  format!("conductor-service-{}", service_type)
+}
+
+/// Returns true if the plugin for a service type is on the PATH.
+pub fn is_installed(service_type: &str) -> bool {
+  crate::process::on_path(&executable(service_type))
+}
+
+fn request(service: &Service, root_path: &Path, action: &str) -> io::Result<String> {
+  let request = Request {
+    action,
+    name: &service.name,
+    container: service.container.as_deref(),
+    command: service.command.as_deref(),
+    path: match &service.path {
+      Some(path) => root_path.join(expand_env(path)),
+      None => root_path.to_path_buf(),
+    },
+    env: service
+      .env
+      .iter()
+      .map(|(k, v)| (k.as_str(), expand_env(v)))
+      .collect(),
+  };
+  serde_json::to_string(&request).map_err(io::Error::other)
+}
+
+/// Asks the plugin of a service type to start or stop a service. Returns the id the plugin
+/// reported.
+pub fn call(
+  service_type: &str,
+  service: &Service,
+  root_path: &Path,
+  action: &str,
+) -> io::Result<String> {
+  let name = executable(service_type);
+  let capture = Exec::cmd(&name)
+    .stdin((request(service, root_path, action)? + "\n").into_bytes())
+    .stdout(Redirection::Pipe)
+    .stderr(Redirection::Pipe)
+    .capture()
+    .map_err(|e| match e {
+      subprocess::PopenError::IoError(e) if e.kind() == io::ErrorKind::NotFound => io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("Service type {} needs {} on the PATH", service_type, name),
+      ),
+      e => io::Error::other(format!("{}: {}", name, e)),
+    })?;
+  let stdout = capture.stdout_str();
+  let reply: Reply = match stdout.lines().rev().find(|l| !l.trim().is_empty()) {
+    Some(line) => serde_json::from_str(line)
+      .map_err(|e| io::Error::other(format!("{} replied with invalid JSON: {}", name, e)))?,
+    None => {
+      let stderr = capture.stderr_str();
+      return Err(io::Error::other(format!(
+        "{} did not reply{}",
+        name,
+        match stderr.trim() {
+          "" => String::new(),
+          stderr => format!(": {}", stderr),
+        }
+      )));
+    }
+  };
+  match reply.ok {
+    true => Ok(reply.id),
+    false => Err(io::Error::other(reply.error.unwrap_or_else(|| {
+      format!("{} failed to {} {}", name, action, service.name)
+    }))),
+  }
+}
+
+/// Returns the command that follows the output of a plugin service. The request is kept in a
+/// runtime file that is given to the plugin as its stdin, as the output is streamed.
+pub fn follow_logs(service_type: &str, service: &Service, root_path: &Path) -> io::Result<Exec> {
+  let path = runtime_file(root_path, &service.name, "request");
+  if let Some(dir) = path.parent() {
+    fs::create_dir_all(dir)?;
+  }
+  fs::write(&path, request(service, root_path, "logs")? + "\n")?;
+  Ok(Exec::cmd(executable(service_type)).stdin(File::open(&path)?))
+}
@@ -0,0 +1,757 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::process::{Command, ExitStatus};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use subprocess::{Exec, Redirection};
+use sysinfo::{Pid, ProcessRefreshKind, System};
+
+/// Builds a command that runs a command line through the shell. On unix the shell leads a new
+/// process group so everything it starts can be signalled at once. Other platforms fall back to
+/// stopping the descendants that can be found.
+pub fn shell(command_line: &str) -> Command {
+  #[cfg(unix)]
+  {
+    use std::os::unix::process::CommandExt;
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(command_line).process_group(0);
+    command
+  }
+  #[cfg(not(unix))]
+  {
+    let mut command = Command::new("cmd.exe");
+    command.arg("/c").arg(command_line);
+    command
+  }
+}
+
+/// Quotes a value for the shell when it contains anything but plain characters.
+pub fn quote(value: &str) -> String {
+  let plain = |c: char| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c);
+  if !value.is_empty() && value.chars().all(plain) {
+    value.to_string()
+  } else {
+    format!("'{}'", value.replace('\'', "'\\''"))
+  }
+}
+
+/// Runs a command connected to the terminal and waits for it. Conductor ignores ctrl-c while it
+/// runs, as a shell does, so the keys reach the command instead of leaving it behind.
+#[cfg(unix)]
+pub fn run_attached(command: &mut Command) -> io::Result<ExitStatus> {
+  use std::os::unix::process::CommandExt;
+  const SIGNALS: [libc::c_int; 2] = [libc::SIGINT, libc::SIGQUIT];
+  unsafe {
+    command.pre_exec(|| {
+      for signal in SIGNALS {
+        libc::signal(signal, libc::SIG_DFL);
+      }
+      Ok(())
+    });
+  }
+  let previous: Vec<_> = SIGNALS
+    .iter()
+    .map(|s| unsafe { libc::signal(*s, libc::SIG_IGN) })
+    .collect();
+  let status = command.status();
+  for (signal, handler) in SIGNALS.iter().zip(previous) {
+    unsafe { libc::signal(*signal, handler) };
+  }
+  status
+}
+
+#[cfg(not(unix))]
+pub fn run_attached(command: &mut Command) -> io::Result<ExitStatus> {
+  command.status()
+}
+
+/// Makes a command's process write itself to a cgroup's `cgroup.procs` before it is executed.
+#[cfg(unix)]
+pub fn join_cgroup(command: &mut Command, procs: File) {
+  use std::os::unix::io::AsRawFd;
+  use std::os::unix::process::CommandExt;
+  // Writing 0 moves the writing process. Only a plain write is made between fork and exec.
+  unsafe {
+    command.pre_exec(move || {
+      if libc::write(procs.as_raw_fd(), b"0".as_ptr() as *const libc::c_void, 1) < 0 {
+        return Err(io::Error::last_os_error());
+      }
+      Ok(())
+    });
+  }
+}
+
+#[cfg(not(unix))]
+pub fn join_cgroup(_command: &mut Command, _procs: File) {}
+
+/// Runs a command's process, and what it starts, at a nice level. Higher levels get less CPU
+/// time, from -20 to 19. A level that can not be set, such as a negative one for a user without
+/// the privilege, does not keep the process from starting, nice_of tells whether it was set.
+#[cfg(unix)]
+pub fn set_nice(command: &mut Command, nice: i32) {
+  use std::os::unix::process::CommandExt;
+  unsafe {
+    command.pre_exec(move || {
+      libc::setpriority(libc::PRIO_PROCESS, 0, nice);
+      Ok(())
+    });
+  }
+}
+
+#[cfg(not(unix))]
+pub fn set_nice(_command: &mut Command, _nice: i32) {}
+
+/// The nice level a process runs at.
+#[cfg(unix)]
+pub fn nice_of(pid: u32) -> Option<i32> {
+  Some(unsafe { libc::getpriority(libc::PRIO_PROCESS, pid as libc::id_t) })
+}
+
+#[cfg(not(unix))]
+pub fn nice_of(_pid: u32) -> Option<i32> {
+  None
+}
+
+/// The exit code of a process. A process stopped by a signal is given 128 plus the signal's
+/// number, the code a shell reports for it.
+pub fn exit_code(status: &ExitStatus) -> Option<i32> {
+  #[cfg(unix)]
+  {
+    use std::os::unix::process::ExitStatusExt;
+    status.code().or_else(|| status.signal().map(|s| 128 + s))
+  }
+  #[cfg(not(unix))]
+  {
+    status.code()
+  }
+}
+
+/// Describes an exit code, naming the signal of codes that mean a process was stopped by one,
+/// such as `137 (SIGKILL)`.
+pub fn describe_exit_code(code: i32) -> String {
+  let signal = match code - 128 {
+    1 => "SIGHUP",
+    2 => "SIGINT",
+    3 => "SIGQUIT",
+    4 => "SIGILL",
+    6 => "SIGABRT",
+    7 => "SIGBUS",
+    8 => "SIGFPE",
+    9 => "SIGKILL",
+    11 => "SIGSEGV",
+    13 => "SIGPIPE",
+    14 => "SIGALRM",
+    15 => "SIGTERM",
+    _ => return code.to_string(),
+  };
+  format!("{} ({})", code, signal)
+}
+
+/// Sends a termination signal to every process in the group led by the given process.
+#[cfg(unix)]
+pub fn terminate_group(pid: u32) -> io::Result<()> {
+  signal_group(pid, libc::SIGTERM)
+}
+
+/// Sends a signal to every process in the group led by the given process.
+#[cfg(unix)]
+pub fn signal_group(pid: u32, signal: libc::c_int) -> io::Result<()> {
+  if unsafe { libc::kill(-(pid as libc::pid_t), signal) } != 0 {
+    return Err(io::Error::last_os_error());
+  }
+  Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn terminate_group(_pid: u32) -> io::Result<()> {
+  Err(io::Error::other(
+    "Signalling processes is not supported on this platform",
+  ))
+}
+
+/// A Job Object a component's process is assigned to. The processes it starts join the job as
+/// well, and everything still in the job is killed once it is dropped, so on Windows nothing a
+/// component started outlives it.
+#[cfg(windows)]
+pub struct Job {
+  handle: windows_sys::Win32::Foundation::HANDLE,
+}
+
+// The handle is only used through the job's own methods.
+#[cfg(windows)]
+unsafe impl Send for Job {}
+
+#[cfg(windows)]
+impl Job {
+  pub fn new() -> io::Result<Job> {
+    use windows_sys::Win32::System::JobObjects::{
+      CreateJobObjectW, JobObjectExtendedLimitInformation, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+      JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+    };
+    let handle = unsafe { CreateJobObjectW(std::ptr::null(), std::ptr::null()) };
+    if handle.is_null() {
+      return Err(io::Error::last_os_error());
+    }
+    let job = Job { handle };
+    let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = unsafe { std::mem::zeroed() };
+    info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+    job.set(JobObjectExtendedLimitInformation, &info)?;
+    Ok(job)
+  }
+
+  fn set<T>(
+    &self,
+    class: windows_sys::Win32::System::JobObjects::JOBOBJECTINFOCLASS,
+    info: &T,
+  ) -> io::Result<()> {
+    let set = unsafe {
+      windows_sys::Win32::System::JobObjects::SetInformationJobObject(
+        self.handle,
+        class,
+        info as *const T as *const std::ffi::c_void,
+        std::mem::size_of::<T>() as u32,
+      )
+    };
+    match set {
+      0 => Err(io::Error::last_os_error()),
+      _ => Ok(()),
+    }
+  }
+
+  fn query<T>(
+    &self,
+    class: windows_sys::Win32::System::JobObjects::JOBOBJECTINFOCLASS,
+  ) -> io::Result<T> {
+    let mut info: T = unsafe { std::mem::zeroed() };
+    let queried = unsafe {
+      windows_sys::Win32::System::JobObjects::QueryInformationJobObject(
+        self.handle,
+        class,
+        &mut info as *mut T as *mut std::ffi::c_void,
+        std::mem::size_of::<T>() as u32,
+        std::ptr::null_mut(),
+      )
+    };
+    match queried {
+      0 => Err(io::Error::last_os_error()),
+      _ => Ok(info),
+    }
+  }
+
+  /// Caps the memory committed by the processes in the job together, in bytes.
+  pub fn limit_memory(&self, bytes: u64) -> io::Result<()> {
+    use windows_sys::Win32::System::JobObjects::{
+      JobObjectExtendedLimitInformation, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+      JOB_OBJECT_LIMIT_JOB_MEMORY,
+    };
+    let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION =
+      self.query(JobObjectExtendedLimitInformation)?;
+    info.BasicLimitInformation.LimitFlags |= JOB_OBJECT_LIMIT_JOB_MEMORY;
+    info.JobMemoryLimit = bytes as usize;
+    self.set(JobObjectExtendedLimitInformation, &info)
+  }
+
+  /// Caps the CPU time of the processes in the job to a number of cores.
+  pub fn limit_cpu(&self, cores: f64) -> io::Result<()> {
+    use windows_sys::Win32::System::JobObjects::{
+      JobObjectCpuRateControlInformation, JOBOBJECT_CPU_RATE_CONTROL_INFORMATION,
+      JOBOBJECT_CPU_RATE_CONTROL_INFORMATION_0, JOB_OBJECT_CPU_RATE_CONTROL_ENABLE,
+      JOB_OBJECT_CPU_RATE_CONTROL_HARD_CAP,
+    };
+    // The rate is in hundredths of a percent of all the processors.
+    let processors = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let rate = (cores / processors as f64 * 10_000.0) as u32;
+    let info = JOBOBJECT_CPU_RATE_CONTROL_INFORMATION {
+      ControlFlags: JOB_OBJECT_CPU_RATE_CONTROL_ENABLE | JOB_OBJECT_CPU_RATE_CONTROL_HARD_CAP,
+      Anonymous: JOBOBJECT_CPU_RATE_CONTROL_INFORMATION_0 {
+        CpuRate: rate.clamp(1, 10_000),
+      },
+    };
+    self.set(JobObjectCpuRateControlInformation, &info)
+  }
+
+  /// The most memory the processes in the job have committed together, in bytes.
+  pub fn peak_memory(&self) -> io::Result<u64> {
+    use windows_sys::Win32::System::JobObjects::{
+      JobObjectExtendedLimitInformation, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+    };
+    let info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION =
+      self.query(JobObjectExtendedLimitInformation)?;
+    Ok(info.PeakJobMemoryUsed as u64)
+  }
+
+  /// Adds a process to the job, along with the processes it starts from then on.
+  pub fn assign(&self, child: &std::process::Child) -> io::Result<()> {
+    use std::os::windows::io::AsRawHandle;
+    let assigned = unsafe {
+      windows_sys::Win32::System::JobObjects::AssignProcessToJobObject(
+        self.handle,
+        child.as_raw_handle() as windows_sys::Win32::Foundation::HANDLE,
+      )
+    };
+    match assigned {
+      0 => Err(io::Error::last_os_error()),
+      _ => Ok(()),
+    }
+  }
+}
+
+#[cfg(windows)]
+impl Drop for Job {
+  fn drop(&mut self) {
+    unsafe { windows_sys::Win32::Foundation::CloseHandle(self.handle) };
+  }
+}
+
+/// Other platforms stop what a component started through its process group instead.
+#[cfg(not(windows))]
+pub struct Job;
+
+#[cfg(not(windows))]
+impl Job {
+  pub fn new() -> io::Result<Job> {
+    Ok(Job)
+  }
+
+  pub fn assign(&self, _child: &std::process::Child) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+/// Opens a pseudo-terminal and returns its controlling side and the side handed to a child
+/// process. The terminal has the size of stdout when that is a terminal.
+#[cfg(unix)]
+pub fn open_pty() -> io::Result<(File, File)> {
+  use std::os::unix::io::FromRawFd;
+  let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+  if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } != 0
+    || size.ws_col == 0
+  {
+    size.ws_col = 80;
+    size.ws_row = 24;
+  }
+  let (mut master, mut slave) = (0, 0);
+  let opened = unsafe {
+    libc::openpty(
+      &mut master,
+      &mut slave,
+      std::ptr::null_mut(),
+      std::ptr::null(),
+      &size,
+    )
+  };
+  if opened != 0 {
+    return Err(io::Error::last_os_error());
+  }
+  // Other children must not inherit either side, the controlling side only sees the end of the
+  // output once every copy of the child's side is closed.
+  for fd in [master, slave].iter() {
+    unsafe { libc::fcntl(*fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+  }
+  Ok(unsafe { (File::from_raw_fd(master), File::from_raw_fd(slave)) })
+}
+
+#[cfg(not(unix))]
+pub fn open_pty() -> io::Result<(File, File)> {
+  Err(io::Error::other(
+    "Pseudo-terminals are not supported on this platform",
+  ))
+}
+
+/// The name of the machine conductor runs on.
+#[cfg(unix)]
+pub fn hostname() -> Option<String> {
+  let mut name = [0u8; 256];
+  if unsafe { libc::gethostname(name.as_mut_ptr() as *mut libc::c_char, name.len()) } != 0 {
+    return None;
+  }
+  let len = name.iter().position(|b| *b == 0).unwrap_or(name.len());
+  Some(String::from_utf8_lossy(&name[..len]).into_owned())
+}
+
+#[cfg(not(unix))]
+pub fn hostname() -> Option<String> {
+  std::env::var("COMPUTERNAME").ok()
+}
+
+/// Returns true if a process with the given pid exists.
+#[cfg(unix)]
+pub fn is_alive(pid: u32) -> bool {
+  unsafe { libc::kill(pid as libc::pid_t, 0) == 0 }
+}
+
+#[cfg(not(unix))]
+pub fn is_alive(_pid: u32) -> bool {
+  false
+}
+
+/// Sends a termination signal to a process.
+#[cfg(unix)]
+pub fn terminate(pid: u32) -> io::Result<()> {
+  if unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) } != 0 {
+    return Err(io::Error::last_os_error());
+  }
+  Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn terminate(_pid: u32) -> io::Result<()> {
+  Err(io::Error::other(
+    "Signalling processes is not supported on this platform",
+  ))
+}
+
+/// Kills every process in the group led by the given process outright.
+#[cfg(unix)]
+pub fn kill_group(pid: u32) -> io::Result<()> {
+  signal_group(pid, libc::SIGKILL)
+}
+
+/// Kills a process and the processes it started outright.
+#[cfg(not(unix))]
+pub fn kill_group(pid: u32) -> io::Result<()> {
+  kill(pid)
+}
+
+/// Stops every process in the group led by the given process until it is resumed, along with
+/// the descendants that moved to a group of their own.
+#[cfg(unix)]
+pub fn pause_group(pid: u32) -> io::Result<()> {
+  signal_tree(pid, libc::SIGSTOP)
+}
+
+/// Continues every process stopped by pause_group.
+#[cfg(unix)]
+pub fn resume_group(pid: u32) -> io::Result<()> {
+  signal_tree(pid, libc::SIGCONT)
+}
+
+#[cfg(unix)]
+fn signal_tree(pid: u32, signal: libc::c_int) -> io::Result<()> {
+  signal_group(pid, signal)?;
+  // Descendants may have exited since they were listed.
+  for child in descendants(pid) {
+    unsafe { libc::kill(child as libc::pid_t, signal) };
+  }
+  Ok(())
+}
+
+/// Suspends every thread of a process and of its descendants until it is resumed.
+#[cfg(windows)]
+pub fn pause_group(pid: u32) -> io::Result<()> {
+  suspend_tree(pid, true)
+}
+
+/// Resumes the threads suspended by pause_group.
+#[cfg(windows)]
+pub fn resume_group(pid: u32) -> io::Result<()> {
+  suspend_tree(pid, false)
+}
+
+/// Suspends or resumes the threads of a process and its descendants, as listed by a Toolhelp
+/// snapshot.
+#[cfg(windows)]
+fn suspend_tree(pid: u32, suspend: bool) -> io::Result<()> {
+  use windows_sys::Win32::Foundation::{CloseHandle, INVALID_HANDLE_VALUE};
+  use windows_sys::Win32::System::Diagnostics::ToolHelp::{
+    CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, Thread32First, Thread32Next,
+    PROCESSENTRY32W, TH32CS_SNAPPROCESS, TH32CS_SNAPTHREAD, THREADENTRY32,
+  };
+  use windows_sys::Win32::System::Threading::{
+    OpenThread, ResumeThread, SuspendThread, THREAD_SUSPEND_RESUME,
+  };
+  let snapshot = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS | TH32CS_SNAPTHREAD, 0) };
+  if snapshot == INVALID_HANDLE_VALUE {
+    return Err(io::Error::last_os_error());
+  }
+  let mut parents = vec![];
+  let mut process: PROCESSENTRY32W = unsafe { std::mem::zeroed() };
+  process.dwSize = std::mem::size_of::<PROCESSENTRY32W>() as u32;
+  let mut more = unsafe { Process32FirstW(snapshot, &mut process) } != 0;
+  while more {
+    parents.push((process.th32ProcessID, process.th32ParentProcessID));
+    more = unsafe { Process32NextW(snapshot, &mut process) } != 0;
+  }
+  let mut tree = tree_of(pid, &parents);
+  tree.push(pid);
+  let mut result = Ok(());
+  let mut thread: THREADENTRY32 = unsafe { std::mem::zeroed() };
+  thread.dwSize = std::mem::size_of::<THREADENTRY32>() as u32;
+  let mut more = unsafe { Thread32First(snapshot, &mut thread) } != 0;
+  while more {
+    if tree.contains(&thread.th32OwnerProcessID) {
+      // The thread may have exited since the snapshot.
+      let handle = unsafe { OpenThread(THREAD_SUSPEND_RESUME, 0, thread.th32ThreadID) };
+      if !handle.is_null() {
+        let count = match suspend {
+          true => unsafe { SuspendThread(handle) },
+          false => unsafe { ResumeThread(handle) },
+        };
+        if count == u32::MAX {
+          result = Err(io::Error::last_os_error());
+        }
+        unsafe { CloseHandle(handle) };
+      }
+    }
+    more = unsafe { Thread32Next(snapshot, &mut thread) } != 0;
+  }
+  unsafe { CloseHandle(snapshot) };
+  result
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn pause_group(_pid: u32) -> io::Result<()> {
+  Err(io::Error::other(
+    "Pausing processes is not supported on this platform",
+  ))
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn resume_group(_pid: u32) -> io::Result<()> {
+  Err(io::Error::other(
+    "Pausing processes is not supported on this platform",
+  ))
+}
+
+/// Kills a process outright.
+#[cfg(unix)]
+pub fn kill(pid: u32) -> io::Result<()> {
+  if unsafe { libc::kill(pid as libc::pid_t, libc::SIGKILL) } != 0 {
+    return Err(io::Error::last_os_error());
+  }
+  Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn kill(pid: u32) -> io::Result<()> {
+  let status = Exec::cmd("taskkill")
+    .args(&["/F", "/T", "/PID"])
+    .arg(pid.to_string())
+    .stdout(Redirection::Pipe)
+    .stderr(Redirection::Merge)
+    .join()
+    .map_err(io::Error::other)?;
+  match status.success() {
+    true => Ok(()),
+    false => Err(io::Error::other(format!("Could not kill process {}", pid))),
+  }
+}
+
+/// What tells a process apart from one started later with the same pid: when it started and its
+/// command line.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct Identity {
+  pub started: String,
+  pub command: String,
+}
+
+/// Reads the identity of a running process. The start time is the boot and the clock tick the
+/// process started at, so a process from before a reboot never matches.
+#[cfg(target_os = "linux")]
+pub fn identity(pid: u32) -> Option<Identity> {
+  let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+  // The name in parentheses may hold spaces, the start time is the 20th field after it.
+  let ticks = stat[stat.rfind(')')? + 1..].split_whitespace().nth(19)?;
+  let boot = std::fs::read_to_string("/proc/sys/kernel/random/boot_id").ok()?;
+  let argv = std::fs::read(format!("/proc/{}/cmdline", pid)).ok()?;
+  let command: Vec<_> = argv
+    .split(|b| *b == 0)
+    .filter(|arg| !arg.is_empty())
+    .map(String::from_utf8_lossy)
+    .collect();
+  Some(Identity {
+    started: format!("{}/{}", boot.trim(), ticks),
+    command: command.join(" "),
+  })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn identity(pid: u32) -> Option<Identity> {
+  let output = Exec::cmd("ps")
+    .arg("-o")
+    .arg("lstart=,args=")
+    .arg("-p")
+    .arg(pid.to_string())
+    .env("LC_ALL", "C")
+    .stdout(Redirection::Pipe)
+    .stderr(Redirection::None)
+    .capture()
+    .ok()?
+    .stdout_str();
+  // The start time is always five fields, such as `Mon Oct 16 10:02:03 2026`.
+  let fields: Vec<&str> = output.split_whitespace().collect();
+  match fields.len() {
+    0..=5 => None,
+    _ => Some(Identity {
+      started: fields[..5].join(" "),
+      command: fields[5..].join(" "),
+    }),
+  }
+}
+
+/// Returns true if the pid still belongs to the process with the identity, rather than having
+/// been handed to another process since. A shell started with `sh -c` may have replaced itself
+/// with the command it ran, which keeps its start time.
+pub fn is_same(pid: u32, identity: &Identity) -> bool {
+  match self::identity(pid).filter(|_| is_alive(pid)) {
+    Some(current) => {
+      current.started == identity.started
+        && (current.command == identity.command
+          || identity.command.strip_prefix("sh -c ") == Some(&current.command))
+    }
+    None => false,
+  }
+}
+
+/// Returns true if an executable with the name is in one of the directories on the PATH.
+pub fn on_path(name: &str) -> bool {
+  std::env::var_os("PATH")
+    .map(|path| std::env::split_paths(&path).any(|dir| dir.join(name).is_file()))
+    .unwrap_or(false)
+}
+
+/// Returns the pid of the process listening on a local TCP port, if it can be found.
+pub fn listening_on(port: u16) -> Option<u32> {
+  Exec::cmd("lsof")
+    .arg("-nP")
+    .arg(format!("-iTCP:{}", port))
+    .arg("-sTCP:LISTEN")
+    .arg("-t")
+    .stdout(Redirection::Pipe)
+    .stderr(Redirection::None)
+    .capture()
+    .ok()?
+    .stdout_str()
+    .lines()
+    .find_map(|line| line.trim().parse().ok())
+}
+
+/// Returns the pids of every process descended from the given process, children first.
+pub fn descendants(pid: u32) -> Vec<u32> {
+  let output = Exec::cmd("ps")
+    .arg("-axo")
+    .arg("pid=,ppid=")
+    .stdout(Redirection::Pipe)
+    .stderr(Redirection::None)
+    .capture()
+    .map(|c| c.stdout_str())
+    .unwrap_or_default();
+  let parents: Vec<(u32, u32)> = output
+    .lines()
+    .filter_map(|line| {
+      let mut fields = line.split_whitespace().map(|f| f.parse().ok());
+      Some((fields.next()??, fields.next()??))
+    })
+    .collect();
+  tree_of(pid, &parents)
+}
+
+/// The descendants of a process, children first, from the pids and parent pids of every
+/// process. A pid that was reused by a process started later is only followed once.
+fn tree_of(pid: u32, parents: &[(u32, u32)]) -> Vec<u32> {
+  let mut found = vec![];
+  let mut pending = vec![pid];
+  while let Some(parent) = pending.pop() {
+    for (child, _) in parents.iter().filter(|(_, ppid)| *ppid == parent) {
+      if *child != pid && !found.contains(child) {
+        found.push(*child);
+        pending.push(*child);
+      }
+    }
+  }
+  found
+}
+
+/// The CPU and memory used by a process and its descendants.
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Debug, Default)]
+pub struct Usage {
+  /// Percent of a CPU core used since the previous measurement.
+  pub cpu: f32,
+  /// Resident memory in kilobytes.
+  pub memory_kb: u64,
+}
+
+/// Measures the usage of process trees from the process table. CPU use is the share of a core
+/// used between two measurements, so the first measurement of a process reports none.
+pub struct UsageSampler {
+  system: System,
+}
+
+impl UsageSampler {
+  /// How long to wait between two measurements for their CPU use to be accurate.
+  pub const MIN_INTERVAL: Duration = sysinfo::MINIMUM_CPU_UPDATE_INTERVAL;
+
+  pub fn new() -> Self {
+    UsageSampler {
+      system: System::new(),
+    }
+  }
+
+  /// Measures the usage of each of the given processes along with their descendants.
+  pub fn measure(&mut self, pids: &[u32]) -> HashMap<u32, Usage> {
+    self
+      .system
+      .refresh_processes_specifics(ProcessRefreshKind::new().with_cpu().with_memory());
+    let processes = self.system.processes();
+    let mut usage = HashMap::new();
+    for pid in pids {
+      let mut total = Usage::default();
+      let mut pending = vec![Pid::from_u32(*pid)];
+      while let Some(parent) = pending.pop() {
+        if let Some(process) = processes.get(&parent) {
+          total.cpu += process.cpu_usage();
+          total.memory_kb += process.memory() / 1024;
+        }
+        let children = processes.values().filter(|p| p.parent() == Some(parent));
+        pending.extend(children.map(|p| p.pid()));
+      }
+      usage.insert(*pid, total);
+    }
+    usage
+  }
+}
+
+/// Measures the usage of a changing set of process trees on a thread of its own, so that
+/// reading the process table never holds up the caller. The thread ends with the monitor.
+pub struct UsageMonitor {
+  watched: Arc<Mutex<Vec<u32>>>,
+  measured: Arc<Mutex<HashMap<u32, Usage>>>,
+}
+
+impl UsageMonitor {
+  /// Starts measuring the watched processes every interval.
+  pub fn start(interval: Duration) -> Self {
+    let watched = Arc::new(Mutex::new(vec![]));
+    let measured = Arc::new(Mutex::new(HashMap::new()));
+    let monitor = UsageMonitor {
+      watched: watched.clone(),
+      measured: measured.clone(),
+    };
+    let watched = Arc::downgrade(&watched);
+    thread::spawn(move || {
+      let mut sampler = UsageSampler::new();
+      while let Some(pids) = watched.upgrade().map(|w| w.lock().unwrap().clone()) {
+        if !pids.is_empty() {
+          let usage = sampler.measure(&pids);
+          *measured.lock().unwrap() = usage;
+        }
+        thread::sleep(interval);
+      }
+    });
+    monitor
+  }
+
+  /// Sets the processes to measure, returning the usage of those already measured.
+  pub fn watch(&self, pids: Vec<u32>) -> HashMap<u32, Usage> {
+    let measured = self.measured.lock().unwrap();
+    let usage = pids
+      .iter()
+      .filter_map(|pid| Some((*pid, *measured.get(pid)?)))
+      .collect();
+    *self.watched.lock().unwrap() = pids;
+    usage
+  }
+}
@@ -1,44 +1,472 @@
-use crate::supervisor::Supervisor;
-use crate::task::Task;
+use crate::audit::Finding;
+use crate::command::USAGE;
+use crate::console::LineEditor;
+use crate::control;
+use crate::daemon;
+use crate::encrypted;
+use crate::env::{self, Dotenv};
+use crate::expand::Functions;
+use crate::export;
+use crate::graph;
+use crate::guardrails::{self, Guardrails};
+use crate::import;
+use crate::init_cache;
+use crate::lock::{LockedRepo, Lockfile};
+use crate::notify::{NotificationEvent, Notifications};
+use crate::plan::{self, Banner};
+use crate::platform;
+use crate::process;
+use crate::proxy::Proxy;
+use crate::source::Source;
+use crate::state::{ComponentState, ComponentStatus, SessionState};
+use crate::supervisor::{expand_env, RunOptions, Supervisor, Tuning};
+use crate::task::{Foreach, ProjectTask, Task};
+use crate::telemetry::Telemetry;
+use crate::terminal::TerminalIntegration;
+use crate::ui::{self, Cell, Table};
+use crate::validate::Issue;
+use crate::vars;
 use crate::Component;
 use crate::Group;
-use crate::Service;
-use serde::Deserialize;
+use crate::TerminalColor;
+use crate::{Command, ExitCode, Message};
+use crate::{Service, ServiceLauncher, ServiceStatus, ServiceTerminator, UserConfig};
+use crossbeam::channel::RecvTimeoutError;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
 use std::collections::HashMap;
 use std::fs;
-use std::io::{Error, ErrorKind};
-use std::path::PathBuf;
+use std::io::{Error, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
 
-#[derive(Deserialize, PartialEq, Clone)]
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
 #[serde(default)]
 pub struct Project {
   pub name: String,
   pub components: Vec<Component>,
   pub groups: Vec<Group>,
   pub services: Vec<Service>,
-  pub tasks: HashMap<String, Vec<String>>,
+  pub tasks: HashMap<String, ProjectTask>,
+  /// Environment variables shared by every component, task and service. Values defined at
+  /// those levels take precedence.
+  pub env: HashMap<String, String>,
+  /// Environment variables set to the output of a command, written in env as
+  /// `{ from_command: ... }`. Each command runs once per session, from the project root, and
+  /// everything using it shares its output. They take precedence over other env values.
+  pub env_commands: HashMap<String, String>,
+  /// Commands producing environment values shared by every component and task. They are run at
+  /// launch and take precedence over env values.
+  pub secrets: HashMap<String, String>,
+  /// Values shared by several definitions, such as ports and hostnames, referenced as
+  /// `${vars.name}` in any string of the config.
+  pub vars: HashMap<String, String>,
+  /// Loading the `.env` files found where components and tasks run.
+  pub dotenv: Dotenv,
+  /// Additional config files merged into the project, relative to the file including them.
+  pub include: Vec<String>,
+  /// Other projects that make up a workspace with this one, by namespace. Each is the path of
+  /// its config file, or of the directory holding its conductor.yml, relative to the project
+  /// root. Their components, services, groups and tasks are named `<namespace>/<name>`.
+  pub projects: HashMap<String, String>,
+  #[serde(skip_serializing)]
   pub root_path: PathBuf,
+  pub tuning: Tuning,
+  /// Runs every component under a pseudo-terminal, see Component::pty.
+  pub pty: bool,
+  /// Escapes that show the health of the environment in the terminal's title or tab.
+  pub terminal: TerminalIntegration,
+  /// Limits checked before launching components.
+  pub guardrails: Guardrails,
+  /// How output is shown.
+  pub output: ui::Settings,
+  /// Where failures are posted so they are noticed on long running environments.
+  pub notifications: Notifications,
+  /// Where the trace of each invocation is sent.
+  pub telemetry: Telemetry,
+  /// Commands run from the project root before any component is started. Components are not
+  /// started if one of them fails.
+  pub pre_run: Vec<String>,
+  /// Commands run from the project root once the session has ended, including when it was
+  /// interrupted.
+  pub post_run: Vec<String>,
+  /// Routes hostnames and paths to the components' ports.
+  pub proxy: Proxy,
+  /// Partial component definitions that components build on by naming one in `extends`.
+  /// Templates can extend other templates, see deep_merge for how definitions are combined.
+  pub templates: HashMap<String, Value>,
+}
+
+/// How `conductor setup` treats components that are already checked out.
+#[derive(Clone, Debug, Default)]
+pub struct SetupOptions {
+  /// Updates checkouts from their upstream, or to their locked commit, instead of skipping them.
+  pub sync: bool,
+  /// Runs the init commands of updated checkouts again if their manifests changed.
+  pub reinit: bool,
+  /// Runs the init commands of updated checkouts again even if their manifests did not change.
+  pub force_init: bool,
+  /// Checks out the latest commits instead of those in the lockfile and records them in it.
+  pub latest: bool,
+}
+
+/// A component of a group and the env the group gives it.
+type Member<'a> = (&'a Component, HashMap<String, String>);
+
+/// The components of a config file as written, before defaults are filled in.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct RawComponents {
+  components: Vec<Value>,
 }
 
 impl Project {
-  pub fn load(path: &PathBuf) -> Result<Self, std::io::Error> {
-    let config = fs::read_to_string(path)?;
-    let mut p =
-      serde_yaml::from_str::<Project>(&config).map_err(|e| Error::new(ErrorKind::Other, e))?;
-    let mut root_path = path.clone();
+  /// Loads a config with the settings of the user running it applied.
+  pub fn load(path: &Path, user: &UserConfig) -> Result<Self, std::io::Error> {
+    Project::load_with_vars(path, &HashMap::new(), user)
+  }
+
+  /// Loads a config with variables that take precedence over those it defines, such as those
+  /// given with `--var`.
+  pub fn load_with_vars(
+    path: &Path,
+    vars: &HashMap<String, String>,
+    user: &UserConfig,
+  ) -> Result<Self, std::io::Error> {
+    Project::load_at(path, path, vars, user)
+  }
+
+  /// Loads a config read from `path` as if it were the file at `location`, such as a config
+  /// downloaded to the cache. Includes, workspace members and the local override are found next
+  /// to `location`, which is also where the project root is.
+  pub fn load_at(
+    path: &Path,
+    location: &Path,
+    vars: &HashMap<String, String>,
+    user: &UserConfig,
+  ) -> Result<Self, std::io::Error> {
+    let mut p = Project::load_workspace(path, location, vars, user, &mut vec![])?;
+    p.vars.extend(vars.clone());
+    user.apply(&mut p);
+    p.assign_colors();
+    Ok(p)
+  }
+
+  /// Loads a config and the projects it has as workspace members. `loading` holds the configs
+  /// whose members are being loaded, so a workspace can not end up containing itself.
+  fn load_workspace(
+    path: &Path,
+    location: &Path,
+    vars: &HashMap<String, String>,
+    user: &UserConfig,
+    loading: &mut Vec<PathBuf>,
+  ) -> Result<Self, std::io::Error> {
+    let canonical = fs::canonicalize(path)
+      .map_err(|e| Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+    if loading.contains(&canonical) {
+      return Err(Error::other(format!(
+        "{} is a member of its own workspace",
+        path.display()
+      )));
+    }
+    let mut extending = vec![];
+    let mut p = Project::load_file(path, location, vars, user, &mut vec![], &mut extending)?;
+    // Templates can come from any of the files, so they are applied once everything is merged.
+    for raw in extending {
+      let component = p.extend(raw)?;
+      if let Some(c) = p.components.iter_mut().find(|c| c.name == component.name) {
+        *c = component;
+      }
+    }
+    let mut root_path = location.to_path_buf();
     root_path.pop();
     p.root_path = root_path;
+
+    loading.push(canonical);
+    let mut namespaces: Vec<String> = p.projects.keys().cloned().collect();
+    namespaces.sort();
+    let mut members = vec![];
+    for namespace in namespaces {
+      let dir = PathBuf::from(&p.projects[&namespace]);
+      let config = p.root_path.join(&dir);
+      let dir = match config.is_dir() {
+        true => dir,
+        false => dir.parent().map(Path::to_path_buf).unwrap_or_default(),
+      };
+      members.push((namespace, config, dir));
+    }
+    // The projects of components that have not been set up yet are left out until they are.
+    for c in p.components.iter().filter(|c| c.includes_project) {
+      let dir = c.get_path();
+      if p.root_path.join(&dir).join("conductor.yml").is_file() {
+        members.push((c.name.clone(), p.root_path.join(&dir), dir));
+      }
+    }
+    for (namespace, mut config, dir) in members {
+      if config.is_dir() {
+        config.push("conductor.yml");
+      }
+      let member = Project::load_workspace(&config, &config, vars, user, loading)?;
+      p.merge(member.into_member(&namespace, &dir))
+        .map_err(|e| Error::other(format!("{}: {}", path.display(), e)))?;
+    }
+    loading.pop();
+    Ok(p)
+  }
+
+  /// Turns a project into a workspace member. Names are prefixed with the namespace, and paths
+  /// are rebased on the member's directory. The member's env and secrets are handed to what it
+  /// defines rather than to the whole workspace. Its other settings do not apply.
+  fn into_member(self, namespace: &str, dir: &Path) -> Project {
+    let name = |n: &str| format!("{}/{}", namespace, n);
+    let rebase = |p: &Path| dir.join(p).to_string_lossy().into_owned();
+    let with_env = |env: &mut HashMap<String, String>, defaults: &HashMap<String, String>| {
+      for (k, v) in defaults {
+        env.entry(k.clone()).or_insert_with(|| v.clone());
+      }
+    };
+    let mut member = Project::default();
+    for mut c in self.components {
+      c.path = Some(rebase(&c.get_path()));
+      c.name = name(&c.name);
+      c.log_file = c.log_file.map(|f| rebase(Path::new(&f)));
+      c.services = c.services.iter().map(|s| name(s)).collect();
+      if let Some(wait_for) = c.wait_for.as_mut() {
+        wait_for.component = wait_for.component.as_deref().map(name);
+      }
+      with_env(&mut c.env, &self.env);
+      with_env(&mut c.env_commands, &self.env_commands);
+      with_env(&mut c.secrets, &self.secrets);
+      member.components.push(c);
+    }
+    for mut s in self.services {
+      // Containers keep the name they are known by to docker.
+      s.container = Some(s.get_container_name());
+      s.path = Some(rebase(Path::new(s.path.as_deref().unwrap_or("."))));
+      s.name = name(&s.name);
+      with_env(&mut s.env, &self.env);
+      member.services.push(s);
+    }
+    for mut g in self.groups {
+      g.name = name(&g.name);
+      g.components = g.components.iter().map(|c| name(c)).collect();
+      member.groups.push(g);
+    }
+    for (task_name, mut task) in self.tasks {
+      task.path = Some(rebase(Path::new(task.path.as_deref().unwrap_or("."))));
+      with_env(&mut task.env, &self.env);
+      member.tasks.insert(name(&task_name), task);
+    }
+    member
+  }
+
+  /// Gives each component without a color its own from the palette. Colors are handed out in
+  /// the order components are defined so neighbouring components never share one.
+  fn assign_colors(&mut self) {
+    let auto = self
+      .components
+      .iter_mut()
+      .filter(|c| c.color == TerminalColor::Auto);
+    for (n, c) in auto.enumerate() {
+      c.color = TerminalColor::palette(n);
+    }
+  }
+
+  /// Reads a config file and merges in the files it includes. Paths inside included files are
+  /// still relative to the project root. References to variables are replaced in the strings of
+  /// the parsed file, with the variables of the including files taking precedence over its own.
+  /// The override and the included files are found next to `location`.
+  fn load_file(
+    path: &Path,
+    location: &Path,
+    vars: &HashMap<String, String>,
+    user: &UserConfig,
+    visited: &mut Vec<PathBuf>,
+    extending: &mut Vec<Value>,
+  ) -> Result<Self, std::io::Error> {
+    let with_path = |e: Error| Error::new(e.kind(), format!("{}: {}", path.display(), e));
+    let canonical = fs::canonicalize(path).map_err(with_path)?;
+    if visited.contains(&canonical) {
+      return Err(Error::other(format!(
+        "{} is included more than once",
+        path.display()
+      )));
+    }
+    // Only the file a project is loaded from has a local override, not the files it includes.
+    let root_file = visited.is_empty();
+    visited.push(canonical);
+    let in_file = |e: String| Error::other(format!("{}: {}", path.display(), e));
+    let config = encrypted::quote_tags(&fs::read_to_string(path).map_err(with_path)?);
+    let mut raw: Value = parse(path, &config).map_err(|e| in_file(e.to_string()))?;
+    let mut vars = vars.clone();
+    for (name, value) in vars::defined(&raw).map_err(in_file)? {
+      vars.entry(name).or_insert(value);
+    }
+    vars::interpolate(&mut raw, &vars).map_err(in_file)?;
+    env::move_env_commands(&mut raw);
+    let identity = user.age_identity.as_deref();
+    encrypted::decrypt(&mut raw, identity).map_err(with_path)?;
+    let override_path = override_path(location);
+    if root_file && override_path.is_file() {
+      let with_override_path =
+        |e: String| Error::other(format!("{}: {}", override_path.display(), e));
+      let overrides = encrypted::quote_tags(
+        &fs::read_to_string(&override_path).map_err(|e| with_override_path(e.to_string()))?,
+      );
+      let mut overrides: Value =
+        parse(&override_path, &overrides).map_err(|e| with_override_path(e.to_string()))?;
+      vars::interpolate(&mut overrides, &vars).map_err(with_override_path)?;
+      env::move_env_commands(&mut overrides);
+      encrypted::decrypt(&mut overrides, identity)
+        .map_err(|e| with_override_path(e.to_string()))?;
+      raw = overlay(raw, overrides);
+    }
+    // The definitions are read back from text so values are interpreted as they are in a config
+    // file, such as numbers in env.
+    let config = unparse(path, &raw).map_err(|e| in_file(e.to_string()))?;
+    let mut p: Project = parse(path, &config).map_err(|e| in_file(e.to_string()))?;
+    let raw: RawComponents = serde_yaml::from_value(raw).map_err(|e| in_file(e.to_string()))?;
+    extending.extend(
+      raw
+        .components
+        .into_iter()
+        .filter(|c| c.get("extends").is_some()),
+    );
+    let dir = location.parent().unwrap_or_else(|| Path::new(""));
+    for include in p.include.clone() {
+      let include = dir.join(include);
+      let fragment = Project::load_file(&include, &include, &vars, user, visited, extending)?;
+      p.merge(fragment).map_err(with_path)?;
+    }
     Ok(p)
   }
+
+  /// Merges an included config into the project. Components, services, groups and tasks may
+  /// only be defined once across all files. Env values and secrets already set take precedence
+  /// over the included ones.
+  fn merge(&mut self, other: Project) -> Result<(), std::io::Error> {
+    let duplicate =
+      |kind: &str, name: &str| Error::other(format!("{} {} is defined more than once", kind, name));
+    for c in other.components {
+      if self.find_component(&c.name).is_some() {
+        return Err(duplicate("Component", &c.name));
+      }
+      self.components.push(c);
+    }
+    for s in other.services {
+      if self.service_by_name(&s.name).is_some() {
+        return Err(duplicate("Service", &s.name));
+      }
+      self.services.push(s);
+    }
+    for g in other.groups {
+      if self.find_group(&g.name).is_some() {
+        return Err(duplicate("Group", &g.name));
+      }
+      self.groups.push(g);
+    }
+    for (name, template) in other.templates {
+      if self.templates.contains_key(&name) {
+        return Err(duplicate("Template", &name));
+      }
+      self.templates.insert(name, template);
+    }
+    for (name, cmds) in other.tasks {
+      if self.find_project_task(&name).is_some() {
+        return Err(duplicate("Task", &name));
+      }
+      self.tasks.insert(name, cmds);
+    }
+    for (namespace, path) in other.projects {
+      if self.projects.contains_key(&namespace) {
+        return Err(duplicate("Project", &namespace));
+      }
+      self.projects.insert(namespace, path);
+    }
+    for (k, v) in other.env {
+      self.env.entry(k).or_insert(v);
+    }
+    for (k, v) in other.env_commands {
+      self.env_commands.entry(k).or_insert(v);
+    }
+    for (k, v) in other.secrets {
+      self.secrets.entry(k).or_insert(v);
+    }
+    for (k, v) in other.vars {
+      self.vars.entry(k).or_insert(v);
+    }
+    Ok(())
+  }
+  /// Builds a component from its definition as written and the templates it extends.
+  fn extend(&self, raw: Value) -> Result<Component, std::io::Error> {
+    let name = raw
+      .get("name")
+      .and_then(Value::as_str)
+      .unwrap_or("without a name")
+      .to_string();
+    let in_component = |e: String| Error::other(format!("Component {}: {}", name, e));
+    let merged = self.expand(raw, &mut vec![]).map_err(in_component)?;
+    // The merged definition is read back as YAML so values are interpreted as they are in a
+    // config file, such as numbers in env.
+    serde_yaml::to_string(&merged)
+      .and_then(|yaml| serde_yaml::from_str(&yaml))
+      .map_err(|e| in_component(e.to_string()))
+  }
+
+  /// Merges the template a definition extends beneath it, following templates that extend
+  /// others.
+  fn expand(&self, mut definition: Value, seen: &mut Vec<String>) -> Result<Value, String> {
+    let extends = definition
+      .as_mapping_mut()
+      .and_then(|m| m.remove(&Value::from("extends")));
+    let name = match extends {
+      None => return Ok(definition),
+      Some(Value::String(name)) => name,
+      Some(_) => return Err("extends must be the name of a template".into()),
+    };
+    if let Some(start) = seen.iter().position(|s| *s == name) {
+      let mut cycle = seen[start..].to_vec();
+      cycle.push(name);
+      return Err(format!(
+        "Templates extend each other in a cycle: {}",
+        cycle.join(" -> ")
+      ));
+    }
+    let template = self
+      .templates
+      .get(&name)
+      .cloned()
+      .ok_or_else(|| format!("Unknown template {}", name))?;
+    seen.push(name);
+    Ok(deep_merge(self.expand(template, seen)?, definition))
+  }
+
+  /// Finds a service by name. The returned service has the project environment applied below
+  /// its own env.
   pub fn service_by_name(&self, name: &str) -> Option<Service> {
-    match self
+    self
       .services
       .iter()
       .find(|s| s.name.to_lowercase() == *name.to_lowercase())
-    {
-      Some(s) => Some(s.clone()),
-      None => None,
-    }
+      .map(|s| {
+        let mut service = s.clone();
+        service.env = self.env.clone();
+        service.env.extend(s.env.clone());
+        service
+      })
+  }
+
+  /// The connection env of the services a component uses, see Service::connection_env.
+  pub(crate) fn service_env(&self, component: &Component) -> HashMap<String, String> {
+    component
+      .services
+      .iter()
+      .filter_map(|name| self.service_by_name(name))
+      .flat_map(|s| s.connection_env())
+      .collect()
   }
 
   pub fn filter_names(&mut self, names: Vec<String>) {
@@ -72,7 +500,75 @@ impl Project {
       .collect();
   }
 
-  fn find_component(&self, name: &str) -> Option<&Component> {
+  /// Limits the project to the components and services enabled by the given profiles. Anything
+  /// assigned to a profile is disabled unless that profile is active. Components no longer use
+  /// services that are disabled.
+  pub fn filter_profiles(&mut self, profiles: &[&str]) {
+    let disabled: Vec<String> = self
+      .services
+      .iter()
+      .filter(|s| !s.in_profiles(profiles))
+      .map(|s| s.name.to_lowercase())
+      .collect();
+    self.services.retain(|s| s.in_profiles(profiles));
+    self.components.retain(|c| c.in_profiles(profiles));
+    for c in self.components.iter_mut() {
+      c.services
+        .retain(|name| !disabled.contains(&name.to_lowercase()));
+    }
+  }
+
+  /// Leaves out the components whose `only_if` condition does not hold or whose `unless`
+  /// condition does. Conditions see the environment conductor runs in along with the project's
+  /// and the component's env. Components that include a project are left out as well, they are
+  /// never run.
+  pub fn filter_conditions(&mut self) {
+    let root_path = self.root_path.clone();
+    let project_env = self.env.clone();
+    self.components.retain(|c| {
+      if c.includes_project {
+        return false;
+      }
+      if c.only_if.is_none() && c.unless.is_none() {
+        return true;
+      }
+      let mut env = project_env.clone();
+      env.extend(c.env.clone());
+      let env: HashMap<_, _> = std::env::vars()
+        .chain(env.into_iter().map(|(k, v)| (k, expand_env(&v))))
+        .collect();
+      let env: Vec<(String, String)> = env.into_iter().collect();
+      match c.excluded_by(&root_path, &env) {
+        Some(condition) => {
+          ui::message(Message::ComponentExcluded {
+            component: c.name.clone(),
+            condition: condition.into(),
+          });
+          false
+        }
+        None => true,
+      }
+    });
+  }
+
+  /// Leaves out the named components and the members of the named groups.
+  pub fn filter_except(&mut self, names: &[&str]) -> Result<(), String> {
+    let mut excluded = vec![];
+    for name in names.iter() {
+      if let Some(component) = self.find_component(name) {
+        excluded.push(component.name.clone());
+      } else if let Some(group) = self.find_group(name) {
+        let members = self.group_components(group)?;
+        excluded.extend(members.iter().map(|(c, _)| c.name.clone()));
+      } else {
+        return Err(format!("Unknown component or group {}", name));
+      }
+    }
+    self.components.retain(|c| !excluded.contains(&c.name));
+    Ok(())
+  }
+
+  pub(crate) fn find_component(&self, name: &str) -> Option<&Component> {
     self
       .components
       .iter()
@@ -86,14 +582,57 @@ impl Project {
       .find(|g| g.name.to_lowercase() == name.to_lowercase())
   }
 
+  /// The components of a group along with the env they get from it. Members that name another
+  /// group bring in that group's components, and a group's env takes precedence over the env of
+  /// the groups containing it. Names that are neither are skipped.
+  pub(crate) fn group_components(&self, group: &Group) -> Result<Vec<Member<'_>>, String> {
+    let mut members = vec![];
+    self.collect_group(group, &HashMap::new(), &mut vec![], &mut members)?;
+    Ok(members)
+  }
+
+  fn collect_group<'a>(
+    &'a self,
+    group: &Group,
+    env: &HashMap<String, String>,
+    containing: &mut Vec<String>,
+    members: &mut Vec<Member<'a>>,
+  ) -> Result<(), String> {
+    if containing.contains(&group.name.to_lowercase()) {
+      return Err(format!("Group {} contains itself", group.name));
+    }
+    containing.push(group.name.to_lowercase());
+    let mut env = env.clone();
+    env.extend(group.env.clone());
+    for name in group.components.iter() {
+      if let Some(component) = self.find_component(name) {
+        if !members.iter().any(|(c, _)| c.name == component.name) {
+          members.push((component, env.clone()));
+        }
+      } else if let Some(inner) = self.find_group(name) {
+        self.collect_group(inner, &env, containing, members)?;
+      }
+    }
+    containing.pop();
+    Ok(())
+  }
+
   fn find_component_task(&self, name: &str) -> Option<(Component, Task)> {
     for c in self.components.iter() {
-      for (task_name, cmds) in c.tasks.clone().into_iter() {
+      for task_name in c.task_names() {
         if name.to_lowercase() == format!("{}:{}", c.name, task_name).to_lowercase() {
-          return Some((
-            c.clone(),
-            Task::new(name, &c.get_path(), cmds, c.env.clone()),
-          ));
+          let commands = c.task_commands(task_name)?.cloned();
+          let mut task = Task::new(
+            name,
+            &c.get_path(),
+            commands.clone().unwrap_or_default(),
+            c.env.clone(),
+          );
+          task.env_file = c.env_file.clone();
+          task.secrets = c.secrets.clone();
+          task.env_commands = c.env_commands.clone();
+          task.unavailable = commands.err();
+          return Some((c.clone(), task));
         }
       }
     }
@@ -101,65 +640,249 @@ impl Project {
   }
 
   fn find_project_task(&self, name: &str) -> Option<Task> {
-    for (task_name, cmds) in self.tasks.clone().into_iter() {
+    for (task_name, task) in self.tasks.clone().into_iter() {
       if name.to_lowercase() == task_name.to_lowercase() {
-        return Some(Task::new(name, &self.root_path, cmds, HashMap::new()));
+        let timeout = task.timeout.map(Duration::from_secs);
+        let path = match &task.path {
+          Some(path) => self.root_path.join(path),
+          None => self.root_path.clone(),
+        };
+        let commands = task.platform_commands(name).cloned();
+        let mut task = Task::new(name, &path, commands.clone().unwrap_or_default(), task.env);
+        task.timeout = timeout;
+        task.unavailable = commands.err();
+        return Some(task);
       }
     }
     None
   }
 
-  pub fn run(&self) {
-    let supr = Supervisor::new(self);
+  /// The task as it runs in each of the components named by its `foreach_components`, in the
+  /// component's directory with its environment and the task's env above that. None when the
+  /// task does not run in components.
+  pub(crate) fn foreach_tasks(&self, name: &str) -> Option<Result<Vec<Task>, String>> {
+    let (_, task) = self
+      .tasks
+      .iter()
+      .find(|(task_name, _)| task_name.to_lowercase() == name.to_lowercase())?;
+    let components: Result<Vec<&Component>, String> = match task.foreach_components.as_ref()? {
+      Foreach::All(false) => return None,
+      Foreach::All(true) => Ok(self.components.iter().collect()),
+      Foreach::Only(names) => names
+        .iter()
+        .map(|c| {
+          self
+            .find_component(c)
+            .ok_or_else(|| format!("Unknown component {}", c))
+        })
+        .collect(),
+    };
+    let tasks = components.and_then(|components| {
+      let commands = task.platform_commands(name)?;
+      Ok(
+        components
+          .into_iter()
+          .map(|component| {
+            let mut t = component_task(component, commands.clone());
+            t.env.extend(task.env.clone());
+            t.timeout = task.timeout.map(Duration::from_secs);
+            t
+          })
+          .collect(),
+      )
+    });
+    Some(tasks)
+  }
+
+  /// Finds a project task, or a component task named as `component:task`.
+  pub(crate) fn find_task(&self, name: &str) -> Option<Task> {
+    self
+      .find_project_task(name)
+      .or_else(|| self.find_component_task(name).map(|(_, t)| t))
+  }
+
+  /// The names that can be given on the command line, for shell completion. `kind` is one of
+  /// `components`, `groups` or `tasks`, every name is returned without one.
+  pub fn completions(&self, kind: Option<&str>) -> Result<Vec<String>, String> {
+    let components = || self.components.iter().map(|c| c.name.clone());
+    let groups = || self.groups.iter().map(|g| g.name.clone());
+    let tasks = || {
+      let component_tasks = self.components.iter().flat_map(|c| {
+        c.task_names()
+          .into_iter()
+          .map(move |t| format!("{}:{}", c.name, t))
+      });
+      self.tasks.keys().cloned().chain(component_tasks)
+    };
+    let mut names: Vec<String> = match kind {
+      None => components().chain(groups()).chain(tasks()).collect(),
+      Some("components") => components().collect(),
+      Some("groups") => groups().collect(),
+      Some("tasks") => tasks().collect(),
+      Some(kind) => {
+        return Err(format!(
+          "Expected components, groups or tasks to complete, got {}",
+          kind
+        ))
+      }
+    };
+    names.sort();
+    names.dedup();
+    Ok(names)
+  }
+
+  /// Warns when launching the components would exceed the guardrails. Returns true if they
+  /// should be launched, which requires --force or confirmation when a limit is exceeded.
+  fn within_guardrails(&self, components: &[&Component], options: &RunOptions) -> bool {
+    let problems = guardrails::check(self, components);
+    if problems.is_empty() {
+      return true;
+    }
+    for problem in problems {
+      ui::message(Message::GuardrailExceeded { problem });
+    }
+    if options.force || (options.interactive && ui::confirm("Start anyway?")) {
+      return true;
+    }
+    ui::message(Message::GuardrailStop);
+    false
+  }
+
+  pub fn run(&self, options: &RunOptions) -> ExitCode {
+    let mut banner = Banner::new(self);
+    for c in self.components.iter() {
+      banner.component(c, None);
+    }
+    banner.print();
+    if options.dry_run {
+      let members: Vec<Member> = self
+        .components
+        .iter()
+        .map(|c| (c, HashMap::new()))
+        .collect();
+      self.plan_launch(&members, options);
+      return ExitCode::Success;
+    }
+    if !self.within_guardrails(&self.components.iter().collect::<Vec<_>>(), options) {
+      return ExitCode::Failure;
+    }
+    self.check_leftovers(options);
+    let supr = Supervisor::with_options(self, options.clone());
+    if !self.run_hooks(&supr, "pre_run", &self.pre_run) {
+      supr.record(ExitCode::Task);
+      return supr.exit_code();
+    }
     for c in self.components.iter() {
-      supr.spawn_component(&c, HashMap::new());
+      supr.spawn_component(c, HashMap::new());
     }
     supr.init();
+    if !self.run_hooks(&supr, "post_run", &self.post_run) {
+      supr.record(ExitCode::Task);
+    }
+    supr.exit_code()
+  }
+
+  /// Runs the project's pre_run or post_run commands in order. Returns false if one of them
+  /// failed, the rest are skipped.
+  fn run_hooks(&self, supr: &Supervisor, name: &str, hooks: &[String]) -> bool {
+    let task = Task::new(name, &self.root_path, hooks.to_vec(), HashMap::new());
+    let t = task.clone();
+    task.into_iter().all(|cmd| supr.run_task_command(&t, cmd))
   }
 
-  pub fn run_names(&self, names: Vec<String>) -> Result<(), String> {
+  pub fn run_names(&self, names: Vec<String>, options: &RunOptions) -> Result<ExitCode, String> {
+    if options.dry_run {
+      return self.plan(&names, options);
+    }
+    let banner = self.banner(&names);
+    if banner.launches() {
+      banner.print();
+    }
     // If a component was ran we need to invoke Supervisor::init at the end
     let mut cmp_running = false;
     // If a task has was ran we wont invoke Supervisor::init but we will still respond
     // that we have handled the operation so that we dont default to running everything in the project
     let mut task_running = false;
-    let supr = Supervisor::new(self);
+    let supr = Supervisor::with_options(self, options.clone());
 
     for name in names.iter() {
+      if let Some(tasks) = self.foreach_tasks(name) {
+        task_running = true;
+        let started = Instant::now();
+        let succeeded = match tasks {
+          Ok(tasks) => run_foreach(&supr, name, tasks, false),
+          Err(e) => {
+            ui::message(Message::error(e));
+            false
+          }
+        };
+        if !succeeded {
+          supr.record(ExitCode::Task);
+        }
+        self.notify_task(name, started, succeeded);
+        continue;
+      }
       if let Some(task) = self.find_project_task(name) {
+        task_running = true;
+        if let Some(e) = &task.unavailable {
+          ui::message(Message::error(e));
+          supr.record(ExitCode::Task);
+          continue;
+        }
         let t = task.clone();
+        let started = Instant::now();
+        let mut succeeded = true;
         for cmd in task {
-          supr.run_task_command(&t, cmd.clone());
+          if !supr.run_task_command(&t, cmd.clone()) {
+            supr.record(ExitCode::Task);
+            succeeded = false;
+          }
         }
-        task_running = true;
+        self.notify_task(name, started, succeeded);
         continue;
       }
     }
 
     for name in names.iter() {
       if let Some((component, task)) = self.find_component_task(name) {
+        if let Some(e) = &task.unavailable {
+          ui::message(Message::error(e));
+          supr.record(ExitCode::Task);
+          task_running = true;
+          continue;
+        }
         let t = task.clone();
         supr
           .run_component_services(&component)
           .for_each(|result| match result {
-            Ok(s) => {
-              crate::ui::system_message(format!("Started service: {}", s.name));
-            }
+            Ok(s) => ui::message(Message::ServiceStarted { service: s.name }),
             Err((s, e)) => {
-              crate::ui::system_message(format!("Could not start service [{}]: {}", s.name, e));
+              supr.record(ExitCode::Service);
+              ui::message(Message::ServiceStartFailed {
+                service: s.name,
+                error: e.to_string(),
+              })
             }
           });
+        let started = Instant::now();
+        let mut succeeded = true;
         for cmd in task {
-          supr.run_task_command(&t, cmd.clone());
+          if !supr.run_task_command(&t, cmd.clone()) {
+            supr.record(ExitCode::Task);
+            succeeded = false;
+          }
         }
+        self.notify_task(name, started, succeeded);
         supr
           .shutdown_component_services(&component)
           .for_each(|result| match result {
-            Ok(s) => {
-              crate::ui::system_message(format!("Shutdown service: {}", s.name));
-            }
+            Ok(s) => ui::message(Message::ServiceStopped { service: s.name }),
             Err((s, e)) => {
-              crate::ui::system_message(format!("Could not stop service [{}]: {}", s.name, e));
+              supr.record(ExitCode::Service);
+              ui::message(Message::ServiceStopFailed {
+                service: s.name,
+                error: e.to_string(),
+              })
             }
           });
         task_running = true;
@@ -167,66 +890,2144 @@ impl Project {
       }
     }
 
+    let mut launching: Vec<&Component> = names
+      .iter()
+      .filter_map(|name| self.find_component(name))
+      .filter(|c| !c.includes_project)
+      .collect();
+    let mut groups = vec![];
+    for group in names.iter().filter_map(|name| self.find_group(name)) {
+      match self.group_components(group) {
+        Ok(members) => groups.push((group, members)),
+        Err(problem) => {
+          ui::message(Message::ConfigError { problem });
+          supr.record(ExitCode::Config);
+          return Ok(supr.exit_code());
+        }
+      }
+    }
+    for (_, members) in groups.iter() {
+      launching.extend(members.iter().map(|(c, _)| *c));
+    }
+    if !launching.is_empty() && !self.within_guardrails(&launching, options) {
+      supr.record(ExitCode::Failure);
+      return Ok(supr.exit_code());
+    }
+    if !launching.is_empty() {
+      self.check_leftovers(options);
+    }
+    if !launching.is_empty() && !self.run_hooks(&supr, "pre_run", &self.pre_run) {
+      supr.record(ExitCode::Task);
+      return Ok(supr.exit_code());
+    }
+
     for name in names.iter() {
-      if let Some(component) = self.find_component(name) {
+      if let Some(component) = self.find_component(name).filter(|c| !c.includes_project) {
         supr.spawn_component(component, HashMap::new());
         cmp_running = true;
         continue;
       }
     }
-    for name in names.iter() {
-      if let Some(group) = self.find_group(name) {
-        for component_name in group.components.iter() {
-          if let Some(component) = self.find_component(component_name) {
-            cmp_running = true;
-            supr.spawn_component(component, group.env.clone());
-            continue;
-          }
+    for (group, members) in groups {
+      let mut previous: Option<&str> = None;
+      for (component, env) in members {
+        cmp_running = true;
+        supr.spawn_group_component(&group.name, component, env, previous);
+        if group.serial {
+          previous = Some(&component.name);
         }
       }
     }
     if cmp_running {
       supr.init();
+      if !self.run_hooks(&supr, "post_run", &self.post_run) {
+        supr.record(ExitCode::Task);
+      }
     }
 
     if cmp_running || task_running {
-      Ok(())
+      Ok(supr.exit_code())
     } else {
       Err("Nothing to run".into())
     }
   }
 
-  pub fn setup(&self) {
-    let supr = Supervisor::new(self);
-    for cmp in self.components.iter() {
-      if cmp.repo.is_none() {
-        continue;
+  /// Prints what running the names would do, matching them the way run_names does, without
+  /// running anything.
+  fn plan(&self, names: &[String], options: &RunOptions) -> Result<ExitCode, String> {
+    self.banner(names).print();
+    let mut planned = false;
+    for name in names.iter() {
+      let tasks = match self.foreach_tasks(name) {
+        Some(tasks) => tasks?,
+        None => match self.find_project_task(name) {
+          Some(task) => vec![task],
+          None => continue,
+        },
+      };
+      for task in tasks.iter() {
+        plan::task(self, task, &options.env);
+      }
+      planned = true;
+    }
+    for (component, task) in names
+      .iter()
+      .filter_map(|name| self.find_component_task(name))
+    {
+      plan::services(self, &[&component]);
+      plan::task(self, &task, &options.env);
+      planned = true;
+    }
+    let mut members: Vec<Member> = names
+      .iter()
+      .filter_map(|name| self.find_component(name))
+      .map(|c| (c, HashMap::new()))
+      .collect();
+    for group in names.iter().filter_map(|name| self.find_group(name)) {
+      match self.group_components(group) {
+        Ok(group_members) => {
+          ui::message(Message::PlanGroup {
+            group: group.name.clone(),
+            components: group_members
+              .iter()
+              .map(|(c, _)| c.name.as_str())
+              .collect::<Vec<_>>()
+              .join(", "),
+          });
+          members.extend(group_members);
+        }
+        Err(problem) => {
+          ui::message(Message::ConfigError { problem });
+          return Ok(ExitCode::Config);
+        }
+      }
+    }
+    if !members.is_empty() {
+      self.plan_launch(&members, options);
+      planned = true;
+    }
+    match planned {
+      true => Ok(ExitCode::Success),
+      false => Err("Nothing to run".into()),
+    }
+  }
+
+  /// The table of what running the names starts, matching them the way run_names does. Names
+  /// that match nothing are left for run_names to report.
+  fn banner(&self, names: &[String]) -> Banner<'_> {
+    let mut banner = Banner::new(self);
+    for name in names.iter() {
+      match self.foreach_tasks(name) {
+        Some(tasks) => {
+          for task in tasks.iter().flatten() {
+            banner.task(name, task, self.find_component(&task.name));
+          }
+        }
+        None => {
+          if let Some(task) = self.find_project_task(name) {
+            banner.task(name, &task, None);
+          }
+        }
+      }
+      if let Some((component, task)) = self.find_component_task(name) {
+        banner.task(name, &task, Some(&component));
+      }
+      if let Some(component) = self.find_component(name).filter(|c| !c.includes_project) {
+        banner.component(component, None);
+      }
+      if let Some(group) = self.find_group(name) {
+        for (component, _) in self.group_components(group).unwrap_or_default() {
+          banner.component(component, Some(&group.name));
+        }
+      }
+    }
+    banner
+  }
+
+  /// Prints how the components would be started along with the services and hooks that would
+  /// run with them.
+  fn plan_launch(&self, members: &[Member], options: &RunOptions) {
+    let hook = |name: &str, hooks: &[String]| {
+      if !hooks.is_empty() {
+        let task = Task::new(name, &self.root_path, hooks.to_vec(), HashMap::new());
+        plan::task(self, &task, &options.env);
+      }
+    };
+    hook("pre_run", &self.pre_run);
+    plan::services(self, &members.iter().map(|(c, _)| *c).collect::<Vec<_>>());
+    for (component, env) in members.iter() {
+      let mut env = env.clone();
+      env.extend(options.env.clone());
+      plan::component(self, component, &env, !options.no_build);
+    }
+    hook("post_run", &self.post_run);
+  }
+
+  /// Brings up the components, waits up to the timeout for them to be ready and runs a task
+  /// against them. An environment report is printed if anything fails. Everything is torn down
+  /// before returning.
+  pub fn e2e(&self, task_name: &str, timeout: Duration) -> Result<ExitCode, String> {
+    let task = self
+      .find_task(task_name)
+      .ok_or_else(|| format!("Unknown task {}", task_name))?;
+    if let Some(e) = task.unavailable {
+      return Err(e);
+    }
+    if self.components.is_empty() {
+      return Err(Message::NoComponents.to_string());
+    }
+    let supr = Supervisor::with_options(
+      self,
+      RunOptions {
+        wait: true,
+        ..RunOptions::default()
+      },
+    );
+    if !self.run_hooks(&supr, "pre_run", &self.pre_run) {
+      supr.record(ExitCode::Task);
+      return Ok(supr.exit_code());
+    }
+    for c in self.components.iter() {
+      supr.spawn_component(c, HashMap::new());
+    }
+    let up = supr.environment_up();
+    thread::scope(|s| {
+      s.spawn(|| supr.init());
+      let t = task.clone();
+      match up.recv_timeout(timeout) {
+        Err(RecvTimeoutError::Timeout) => {
+          ui::message(Message::EnvironmentTimedOut {
+            seconds: timeout.as_secs(),
+          });
+          supr.record(ExitCode::Component);
+        }
+        Err(RecvTimeoutError::Disconnected) => supr.record(ExitCode::Component),
+        Ok(()) if !task.into_iter().all(|cmd| supr.run_task_command(&t, cmd)) => {
+          supr.record(ExitCode::Task);
+        }
+        Ok(()) => {}
+      }
+      if supr.exit_code() != ExitCode::Success {
+        ui::environment_report(&supr.report());
+      }
+      supr.cancel_all().wait();
+    });
+    if !self.run_hooks(&supr, "post_run", &self.post_run) {
+      supr.record(ExitCode::Task);
+    }
+    Ok(supr.exit_code())
+  }
+
+  /// Bundles the resolved config together with the logs and state of the last session for
+  /// attaching to bug reports.
+  pub fn report(&self, output: Option<&str>) -> ExitCode {
+    match crate::report::bundle(self, output.map(Path::new)) {
+      Ok(path) => {
+        ui::message(Message::ReportWritten {
+          path: path.display().to_string(),
+        });
+        ExitCode::Success
+      }
+      Err(e) => {
+        ui::message(Message::ReportFailed {
+          error: e.to_string(),
+        });
+        ExitCode::Failure
+      }
+    }
+  }
+
+  /// Prints the output a component has written to its log, in this session or earlier ones.
+  pub fn logs(&self, name: &str, since: Option<&str>, follow: bool) -> ExitCode {
+    let component = match self.find_component(name) {
+      Some(component) => component,
+      None => {
+        ui::message(Message::ConfigError {
+          problem: format!("Unknown component {}", name),
+        });
+        return ExitCode::Config;
+      }
+    };
+    let since = match since.map(crate::logs::since).transpose() {
+      Ok(since) => since,
+      Err(problem) => {
+        ui::message(Message::ConfigError { problem });
+        return ExitCode::Config;
+      }
+    };
+    match crate::logs::show(&self.root_path, &component.name, since.as_deref(), follow) {
+      Ok(true) => ExitCode::Success,
+      Ok(false) => {
+        ui::message(Message::NoLogs {
+          component: component.name.clone(),
+        });
+        ExitCode::Success
+      }
+      Err(e) => {
+        ui::message(Message::error(e));
+        ExitCode::Failure
+      }
+    }
+  }
+
+  /// Writes the project in another format, such as a docker compose file. The export is written
+  /// to the project root unless an output is given and existing files are never overwritten.
+  pub fn export(&self, format: &str, output: Option<&str>) -> ExitCode {
+    let format: export::Format = match format.parse() {
+      Ok(format) => format,
+      Err(e) => {
+        ui::message(Message::error(e));
+        return ExitCode::Failure;
+      }
+    };
+    let path = match output {
+      Some(path) => std::env::current_dir().unwrap_or_default().join(path),
+      None => self.root_path.join(format.default_path()),
+    };
+    let export = match export::export(self, format, &path) {
+      Ok(export) => export,
+      Err(e) => {
+        ui::message(Message::error(e));
+        return ExitCode::Failure;
+      }
+    };
+    let files: Vec<(PathBuf, export::File)> = export
+      .files
+      .into_iter()
+      .enumerate()
+      .map(|(i, f)| match format.is_directory() {
+        true => (path.join(&f.name), f),
+        false if i == 0 => (path.clone(), f),
+        false => (path.with_file_name(&f.name), f),
+      })
+      .collect();
+    if let Some((existing, _)) = files.iter().find(|(path, _)| path.exists()) {
+      ui::message(Message::ConfigExists {
+        path: existing.display().to_string(),
+      });
+      return ExitCode::Failure;
+    }
+    for problem in export.warnings {
+      ui::message(Message::ConfigWarning { problem });
+    }
+    let dir = match format.is_directory() {
+      true => Some(path.as_path()),
+      false => path.parent(),
+    };
+    if let Some(dir) = dir {
+      if let Err(e) = fs::create_dir_all(dir) {
+        ui::message(Message::error(e));
+        return ExitCode::Failure;
+      }
+    }
+    for (path, file) in files {
+      let written = if file.private {
+        platform::create_private(&path).and_then(|mut f| f.write_all(file.contents.as_bytes()))
+      } else {
+        fs::write(&path, file.contents)
+      };
+      if let Err(e) = written {
+        ui::message(Message::error(e));
+        return ExitCode::Failure;
+      }
+      ui::message(Message::ConfigWritten {
+        path: path.display().to_string(),
+      });
+    }
+    ExitCode::Success
+  }
+
+  /// Adds components to the config file from another tool's definitions, such as a Procfile.
+  /// The file defaults to the tool's usual name in the project root.
+  pub fn import(&self, config: &Path, format: &str, file: Option<&str>) -> ExitCode {
+    let result = match format {
+      "procfile" => {
+        let path = match file {
+          Some(path) => std::env::current_dir().unwrap_or_default().join(path),
+          None => self.root_path.join("Procfile"),
+        };
+        import::procfile(self, config, &path)
+      }
+      _ => Err(Error::other(format!("Unknown import format {}", format))),
+    };
+    let changes = match result {
+      Ok(changes) => changes,
+      Err(e) => {
+        ui::message(Message::error(e));
+        return ExitCode::Failure;
+      }
+    };
+    if changes.is_empty() {
+      ui::message(Message::ImportUnchanged);
+    }
+    for change in changes {
+      ui::message(match change {
+        import::Change::Added(component) => Message::ComponentImported { component },
+        import::Change::Updated(component) => Message::ComponentUpdated { component },
+      });
+    }
+    ExitCode::Success
+  }
+
+  /// Adds a component to the end of the components in the config file.
+  pub fn add_component(&self, config: &Path, component: &import::NewComponent) -> ExitCode {
+    if component.name.is_empty() {
+      ui::message(Message::ConfigError {
+        problem: "A component needs a name".into(),
+      });
+      return ExitCode::Config;
+    }
+    if self.find_component(&component.name).is_some() {
+      ui::message(Message::ConfigError {
+        problem: format!("Component {} is already defined", component.name),
+      });
+      return ExitCode::Config;
+    }
+    match import::component(config, component) {
+      Ok(()) => {
+        ui::message(Message::ComponentImported {
+          component: component.name.clone(),
+        });
+        ExitCode::Success
       }
+      Err(e) => {
+        ui::message(Message::error(e));
+        ExitCode::Failure
+      }
+    }
+  }
+
+  /// Clones or downloads the named components and the components of the named groups, or every
+  /// component, and runs their init commands. Components are checked out at the commits in the
+  /// lockfile, see SetupOptions for what happens to existing checkouts.
+  pub fn setup(&self, names: &[&str], options: &SetupOptions) -> ExitCode {
+    let components = match self.select(names, |c| c.repo.is_some() || c.source.is_some()) {
+      Ok(components) => components,
+      Err(code) => return code,
+    };
+    let lockfile = match Lockfile::load(&self.root_path) {
+      Ok(lockfile) => lockfile,
+      Err(e) => {
+        ui::message(Message::ConfigError {
+          problem: e.to_string(),
+        });
+        return ExitCode::Config;
+      }
+    };
+    let supr = Supervisor::new(self);
+    let started = Instant::now();
+    for cmp in components.iter() {
       let mut cmp_path = self.root_path.clone();
       cmp_path.push(cmp.get_path());
-      let task = Task::new(&cmp.name, &cmp_path, cmp.init.clone(), cmp.env.clone());
-      match cmp.clone_repo(&cmp_path) {
-        Ok(_) => {
-          crate::ui::system_message(format!("{} cloned", cmp.clone().name));
-          for cmd in &cmp.init {
-            supr.run_task_command(&task, cmd.clone());
+      let init_commands = cmp.init_commands().cloned();
+      let mut task = Task::new(
+        &cmp.name,
+        &cmp_path,
+        init_commands.clone().unwrap_or_default(),
+        cmp.env.clone(),
+      );
+      task.env_file = cmp.env_file.clone();
+      task.secrets = cmp.secrets.clone();
+      task.env_commands = cmp.env_commands.clone();
+      let init = match &cmp.source {
+        Some(source) => self.setup_source(cmp, source, &cmp_path, options),
+        None => self.setup_repo(cmp, &cmp_path, lockfile.as_ref(), options),
+      };
+      if init {
+        let mut succeeded = true;
+        if let Err(e) = &init_commands {
+          ui::message(Message::error(e));
+          supr.record(ExitCode::Task);
+          succeeded = false;
+        }
+        for cmd in init_commands.iter().flatten() {
+          if !supr.run_task_command(&task, cmd.clone()) {
+            supr.record(ExitCode::Task);
+            succeeded = false;
           }
         }
-        Err(e) => crate::ui::system_error(format!("Skipping clone: {}", e)),
+        if succeeded {
+          if let Err(e) = init_cache::record(&self.root_path, cmp, &cmp_path) {
+            ui::message(Message::error(e));
+          }
+        }
+      }
+    }
+    if let Some(lockfile) = lockfile.filter(|_| options.latest) {
+      let repos: Vec<&Component> = components
+        .into_iter()
+        .filter(|c| c.repo.is_some())
+        .collect();
+      if self.lock_components(&repos, lockfile) != ExitCode::Success {
+        supr.record(ExitCode::Failure);
       }
     }
+    self.notify_task("setup", started, supr.exit_code() == ExitCode::Success);
+    supr.exit_code()
   }
-}
 
-impl Default for Project {
-  fn default() -> Self {
-    Project {
+  /// Clones a component's repo, or updates an existing checkout when syncing, at its locked
+  /// commit if it has one. Returns true if its init commands should run.
+  fn setup_repo(
+    &self,
+    cmp: &Component,
+    cmp_path: &Path,
+    lockfile: Option<&Lockfile>,
+    options: &SetupOptions,
+  ) -> bool {
+    let locked = match lockfile.and_then(|l| l.components.get(&cmp.name)) {
+      Some(_) if options.latest => None,
+      Some(locked) if cmp.repo.as_deref() != Some(&locked.repo) => {
+        ui::message(Message::ConfigWarning {
+          problem: format!(
+            "The locked commit of {} is for another repository, using the latest commit",
+            cmp.name
+          ),
+        });
+        None
+      }
+      locked => locked.map(|l| l.commit.as_str()),
+    };
+    match options.sync && cmp_path.exists() {
+      true => match locked.map_or_else(
+        || cmp.sync_repo(cmp_path),
+        |commit| cmp.checkout(cmp_path, commit),
+      ) {
+        Ok(updated) => {
+          ui::message(match updated {
+            true => Message::ComponentSynced {
+              component: cmp.name.clone(),
+            },
+            false => Message::ComponentUpToDate {
+              component: cmp.name.clone(),
+            },
+          });
+          self.needs_init(cmp, cmp_path, options)
+        }
+        Err(e) => {
+          ui::message(Message::SyncFailed {
+            component: cmp.name.clone(),
+            error: e.to_string(),
+          });
+          false
+        }
+      },
+      false => match cmp.clone_repo(cmp_path).and_then(|_| match locked {
+        Some(commit) => cmp.checkout(cmp_path, commit).map(|_| ()),
+        None => Ok(()),
+      }) {
+        Ok(_) => {
+          ui::message(Message::ComponentCloned {
+            component: cmp.name.clone(),
+          });
+          true
+        }
+        Err(e) => {
+          ui::message(Message::CloneSkipped {
+            error: e.to_string(),
+          });
+          false
+        }
+      },
+    }
+  }
+
+  /// Downloads and unpacks a component's archive. An existing path is only replaced when syncing
+  /// and the source changed since it was unpacked. Returns true if its init commands should run.
+  fn setup_source(
+    &self,
+    cmp: &Component,
+    source: &Source,
+    cmp_path: &Path,
+    options: &SetupOptions,
+  ) -> bool {
+    let record = self
+      .root_path
+      .join(".conductor")
+      .join("sources")
+      .join(&cmp.name);
+    let exists = cmp_path.exists();
+    if exists && !options.sync {
+      ui::message(Message::CloneSkipped {
+        error: format!("Directory already exists at {}", cmp_path.display()),
+      });
+      return false;
+    }
+    let unpacked = fs::read_to_string(&record).unwrap_or_default();
+    if exists && unpacked.trim() == source.fingerprint() {
+      ui::message(Message::ComponentUpToDate {
+        component: cmp.name.clone(),
+      });
+      return self.needs_init(cmp, cmp_path, options);
+    }
+    let fetched = source.fetch(cmp_path).and_then(|_| {
+      fs::create_dir_all(record.parent().unwrap_or(&self.root_path))?;
+      fs::write(&record, source.fingerprint() + "\n")
+    });
+    match fetched {
+      Ok(_) => {
+        ui::message(match exists {
+          true => Message::ComponentSynced {
+            component: cmp.name.clone(),
+          },
+          false => Message::ComponentDownloaded {
+            component: cmp.name.clone(),
+          },
+        });
+        true
+      }
+      Err(e) => {
+        ui::message(Message::DownloadFailed {
+          component: cmp.name.clone(),
+          error: e.to_string(),
+        });
+        false
+      }
+    }
+  }
+
+  /// Whether the init commands of an updated component are run again. Unless forced they are
+  /// skipped when its manifests are the same as when its init last succeeded.
+  fn needs_init(&self, cmp: &Component, cmp_path: &Path, options: &SetupOptions) -> bool {
+    let current = !options.force_init && init_cache::unchanged(&self.root_path, cmp, cmp_path);
+    if options.reinit && current {
+      ui::message(Message::InitSkipped {
+        component: cmp.name.clone(),
+      });
+    }
+    options.reinit && !current
+  }
+
+  /// Records the commit the named components and the components of the named groups, or every
+  /// component, are checked out at in the lockfile.
+  pub fn lock(&self, names: &[&str]) -> ExitCode {
+    let components = match self.select(names, |c| c.repo.is_some()) {
+      Ok(components) => components,
+      Err(code) => return code,
+    };
+    match Lockfile::load(&self.root_path) {
+      Ok(lockfile) => self.lock_components(&components, lockfile.unwrap_or_default()),
+      Err(e) => {
+        ui::message(Message::ConfigError {
+          problem: e.to_string(),
+        });
+        ExitCode::Config
+      }
+    }
+  }
+
+  fn lock_components(&self, components: &[&Component], mut lockfile: Lockfile) -> ExitCode {
+    let mut code = ExitCode::Success;
+    for cmp in components.iter() {
+      let path = self.root_path.join(cmp.get_path());
+      let commit = match path.exists() {
+        true => crate::git::head_commit(&path),
+        false => Err(Error::other(
+          "It has not been cloned, run conductor setup first",
+        )),
+      };
+      match commit {
+        Ok(commit) => {
+          lockfile.components.insert(
+            cmp.name.clone(),
+            LockedRepo {
+              repo: cmp.repo.clone().unwrap_or_default(),
+              commit,
+            },
+          );
+        }
+        Err(e) => {
+          code = ExitCode::Failure;
+          ui::message(Message::LockFailed {
+            component: cmp.name.clone(),
+            error: e.to_string(),
+          });
+        }
+      }
+    }
+    match lockfile.save(&self.root_path) {
+      Ok(path) => ui::message(Message::ConfigWritten {
+        path: path.display().to_string(),
+      }),
+      Err(e) => {
+        code = ExitCode::Failure;
+        ui::message(Message::error(e));
+      }
+    }
+    code
+  }
+
+  /// Runs the build commands of the named components and groups, or of every component, with up
+  /// to `jobs` builds at once. A component that waits for another component is built after it
+  /// and skipped if it did not build. A summary of the builds is shown at the end.
+  pub fn build(&self, names: &[&str], jobs: usize, env: &HashMap<String, String>) -> ExitCode {
+    let components = match self.select(names, |c| !c.build.is_empty()) {
+      Ok(components) => components,
+      Err(code) => return code,
+    };
+    if components.is_empty() {
+      ui::message(Message::NothingToBuild);
+      return ExitCode::Success;
+    }
+    let started = Instant::now();
+    let outcomes = self.run_suites(&components, jobs, env, true, |c| &c.build);
+    ui::message(Message::Summary);
+    for (component, outcome) in outcomes.iter() {
+      let component = component.clone();
+      let seconds = outcome.seconds;
+      ui::message(match (&outcome.skipped_for, outcome.succeeded) {
+        (Some(dependency), _) => Message::BuildSkipped {
+          component,
+          dependency: dependency.clone(),
+        },
+        (None, true) => Message::BuildSucceeded { component, seconds },
+        (None, false) => Message::BuildFailed { component, seconds },
+      });
+    }
+    let succeeded = outcomes.iter().all(|(_, o)| o.succeeded);
+    self.notify_task("build", started, succeeded);
+    match succeeded {
+      true => ExitCode::Success,
+      false => ExitCode::Task,
+    }
+  }
+
+  /// Runs the test commands of the named components and groups, or of every component, with up
+  /// to `jobs` suites at once. A summary of the suites is shown at the end.
+  pub fn test(&self, names: &[&str], jobs: usize, env: &HashMap<String, String>) -> ExitCode {
+    let components = match self.select(names, |c| !c.test.is_empty()) {
+      Ok(components) => components,
+      Err(code) => return code,
+    };
+    if components.is_empty() {
+      ui::message(Message::NothingToTest);
+      return ExitCode::Success;
+    }
+    let started = Instant::now();
+    let outcomes = self.run_suites(&components, jobs, env, false, |c| &c.test);
+    ui::message(Message::Summary);
+    for (component, outcome) in outcomes.iter() {
+      let component = component.clone();
+      let seconds = outcome.seconds;
+      ui::message(match outcome.succeeded {
+        true => Message::TestsPassed { component, seconds },
+        false => Message::TestsFailed { component, seconds },
+      });
+    }
+    let succeeded = outcomes.iter().all(|(_, o)| o.succeeded);
+    self.notify_task("test", started, succeeded);
+    match succeeded {
+      true => ExitCode::Success,
+      false => ExitCode::Task,
+    }
+  }
+
+  /// Runs a one-off command in a component's directory with the environment its start command
+  /// gets. A single argument is run as a shell command line, several are quoted and run as one
+  /// command.
+  pub fn exec(&self, name: &str, args: &[&str], env: &HashMap<String, String>) -> ExitCode {
+    let component = match self.find_component(name) {
+      Some(component) => component,
+      None => {
+        ui::message(Message::ConfigError {
+          problem: format!("Unknown component {}", name),
+        });
+        return ExitCode::Config;
+      }
+    };
+    let command = command_line(args);
+    let task = component_task(component, vec![command.clone()]);
+    let supr = Supervisor::with_options(
+      self,
+      RunOptions {
+        env: env.clone(),
+        ..RunOptions::default()
+      },
+    );
+    match supr.run_task_command(&task, command) {
+      true => ExitCode::Success,
+      false => ExitCode::Task,
+    }
+  }
+
+  /// Runs a one-off command in the directory of every component at once, each with the
+  /// environment its start command gets. Given a group, only its members run the command, with
+  /// the group's env.
+  pub fn each(
+    &self,
+    args: &[&str],
+    group: Option<&str>,
+    env: &HashMap<String, String>,
+  ) -> ExitCode {
+    let members: Result<Vec<Member>, String> = match group {
+      Some(name) => match self.find_group(name) {
+        Some(group) => self.group_components(group),
+        None => Err(format!("Unknown group {}", name)),
+      },
+      None => Ok(
+        self
+          .components
+          .iter()
+          .map(|c| (c, HashMap::new()))
+          .collect(),
+      ),
+    };
+    let members = match members {
+      Ok(members) => members,
+      Err(problem) => {
+        ui::message(Message::ConfigError { problem });
+        return ExitCode::Config;
+      }
+    };
+    if members.is_empty() {
+      ui::message(Message::NoComponents);
+      return ExitCode::Config;
+    }
+    let command = command_line(args);
+    let tasks = members
+      .into_iter()
+      .map(|(component, env)| {
+        let mut task = component_task(component, vec![command.clone()]);
+        task.env.extend(env);
+        task
+      })
+      .collect();
+    let supr = Supervisor::with_options(
+      self,
+      RunOptions {
+        env: env.clone(),
+        ..RunOptions::default()
+      },
+    );
+    match run_foreach(&supr, &command, tasks, true) {
+      true => ExitCode::Success,
+      false => ExitCode::Task,
+    }
+  }
+
+  /// Prints the environment a component or task gets, one `KEY=value` line per variable or as
+  /// shell `export` statements. A component can be given the env of a group it belongs to.
+  pub fn env(
+    &self,
+    name: &str,
+    group: Option<&str>,
+    overrides: &HashMap<String, String>,
+    export: bool,
+  ) -> ExitCode {
+    let vars = match (self.find_component(name), self.find_task(name)) {
+      (Some(component), _) => {
+        let mut extra = HashMap::new();
+        if let Some(group) = group {
+          let members = match self.find_group(group) {
+            Some(group) => self.group_components(group),
+            None => Err(format!("Unknown group {}", group)),
+          };
+          match members.map(|m| m.into_iter().find(|(c, _)| c.name == component.name)) {
+            Ok(Some((_, env))) => extra = env,
+            Ok(None) => {
+              ui::message(Message::ConfigError {
+                problem: format!("{} is not in the group {}", component.name, group),
+              });
+              return ExitCode::Config;
+            }
+            Err(problem) => {
+              ui::message(Message::ConfigError { problem });
+              return ExitCode::Config;
+            }
+          }
+        }
+        extra.extend(overrides.clone());
+        plan::component_env(self, component, &extra)
+      }
+      (None, Some(task)) => plan::task_env(self, &task, overrides),
+      (None, None) => {
+        ui::message(Message::ConfigError {
+          problem: format!("Unknown component or task {}", name),
+        });
+        return ExitCode::Config;
+      }
+    };
+    for (key, value) in vars {
+      match export {
+        true => println!("export {}={}", key, process::quote(&value)),
+        false => println!("{}={}", key, value),
+      }
+    }
+    ExitCode::Success
+  }
+
+  /// Starts an interactive shell in a component's directory with the environment its start
+  /// command gets. The shell is connected to the terminal directly and its exit code is returned.
+  pub fn shell(&self, name: &str, env: &HashMap<String, String>) -> ExitCode {
+    let component = match self.find_component(name) {
+      Some(component) => component,
+      None => {
+        ui::message(Message::ConfigError {
+          problem: format!("Unknown component {}", name),
+        });
+        return ExitCode::Config;
+      }
+    };
+    let supr = Supervisor::with_options(
+      self,
+      RunOptions {
+        env: env.clone(),
+        ..RunOptions::default()
+      },
+    );
+    let functions = Functions::new(&self.root_path);
+    let (path, env) = match supr.task_environment(&component_task(component, vec![]), &functions) {
+      Some(environment) => environment,
+      None => return ExitCode::Task,
+    };
+    let program = match cfg!(windows) {
+      true => std::env::var("COMSPEC").unwrap_or_else(|_| "cmd.exe".into()),
+      false => std::env::var("SHELL").unwrap_or_else(|_| "sh".into()),
+    };
+    ui::message(Message::ShellStarted {
+      component: component.name.clone(),
+      shell: program.clone(),
+    });
+    let mut command = std::process::Command::new(&program);
+    command.current_dir(path).env_clear().envs(env);
+    let status = process::run_attached(&mut command);
+    match status {
+      Ok(status) if status.success() => ExitCode::Success,
+      Ok(_) => ExitCode::Task,
+      Err(e) => {
+        ui::message(Message::error(format!(
+          "Could not start {}: {}",
+          program, e
+        )));
+        ExitCode::Failure
+      }
+    }
+  }
+
+  /// The named components and the components of the named groups, or every component if no
+  /// names are given, limited to those a suite applies to. Unknown names are reported.
+  fn select<F: Fn(&Component) -> bool>(
+    &self,
+    names: &[&str],
+    applies: F,
+  ) -> Result<Vec<&Component>, ExitCode> {
+    let mut selected: Vec<&Component> = vec![];
+    if names.is_empty() {
+      selected = self.components.iter().collect();
+    }
+    for name in names {
+      if let Some(component) = self.find_component(name) {
+        selected.push(component);
+      } else if let Some(group) = self.find_group(name) {
+        match self.group_components(group) {
+          Ok(members) => selected.extend(members.into_iter().map(|(c, _)| c)),
+          Err(problem) => {
+            ui::message(Message::ConfigError { problem });
+            return Err(ExitCode::Config);
+          }
+        }
+      } else {
+        ui::message(Message::ConfigError {
+          problem: format!("Unknown component or group {}", name),
+        });
+        return Err(ExitCode::Config);
+      }
+    }
+    let mut components: Vec<&Component> = vec![];
+    for c in selected {
+      if applies(c) && !components.iter().any(|s| s.name == c.name) {
+        components.push(c);
+      }
+    }
+    Ok(components)
+  }
+
+  /// Runs a list of commands for each component with up to `jobs` components at once, streaming
+  /// their output. When `ordered` is set a component that waits for another component runs
+  /// after it and is skipped if it did not succeed. Outcomes are given in component order.
+  fn run_suites<F: Fn(&Component) -> &Vec<String> + Sync>(
+    &self,
+    components: &[&Component],
+    jobs: usize,
+    env: &HashMap<String, String>,
+    ordered: bool,
+    commands: F,
+  ) -> Vec<(String, Outcome)> {
+    // Only dependencies that are part of the run hold a component back.
+    let dependency = |c: &Component| -> Option<String> {
+      let name = c.wait_for.as_ref()?.component.as_ref()?;
+      components
+        .iter()
+        .find(|s| ordered && s.name.to_lowercase() == name.to_lowercase())
+        .map(|s| s.name.clone())
+    };
+
+    let supr = Supervisor::with_options(
+      self,
+      RunOptions {
+        env: env.clone(),
+        ..RunOptions::default()
+      },
+    );
+    let mut outcomes: HashMap<String, Outcome> = HashMap::new();
+    let mut waiting = components.to_vec();
+    thread::scope(|s| {
+      let (sender, receiver) = mpsc::channel();
+      let mut running = 0;
+      loop {
+        let mut skipped = false;
+        waiting.retain(|c| {
+          let dependency = match dependency(c) {
+            Some(dependency) => dependency,
+            None => return true,
+          };
+          match outcomes.get(&dependency) {
+            None => true,
+            Some(outcome) if outcome.succeeded => true,
+            Some(_) => {
+              outcomes.insert(c.name.clone(), Outcome::skipped(dependency));
+              skipped = true;
+              false
+            }
+          }
+        });
+        while running < jobs {
+          let ready = waiting.iter().position(|c| match dependency(c) {
+            Some(dependency) => outcomes.contains_key(&dependency),
+            None => true,
+          });
+          let c = match ready {
+            Some(i) => waiting.remove(i),
+            None => break,
+          };
+          let mut path = self.root_path.clone();
+          path.push(c.get_path());
+          let cmds = commands(c).clone();
+          let mut task = Task::new(&c.name, &path, cmds.clone(), c.env.clone());
+          task.env_file = c.env_file.clone();
+          task.secrets = c.secrets.clone();
+          task.env_commands = c.env_commands.clone();
+          let sender = sender.clone();
+          let supr = &supr;
+          s.spawn(move || {
+            let started = Instant::now();
+            let succeeded = cmds
+              .into_iter()
+              .all(|cmd| supr.run_task_command(&task, cmd));
+            let outcome = Outcome {
+              succeeded,
+              seconds: started.elapsed().as_secs(),
+              skipped_for: None,
+            };
+            let _ = sender.send((task.name.clone(), outcome));
+          });
+          running += 1;
+        }
+        if running == 0 {
+          if skipped {
+            continue;
+          }
+          break;
+        }
+        if let Ok((component, outcome)) = receiver.recv() {
+          running -= 1;
+          outcomes.insert(component, outcome);
+        }
+      }
+    });
+
+    // Whatever is still waiting waits on a component that waits on it in turn.
+    if !waiting.is_empty() {
+      let names: Vec<&str> = waiting.iter().map(|c| c.name.as_str()).collect();
+      ui::message(Message::ConfigError {
+        problem: format!("{} wait for each other", names.join(", ")),
+      });
+      for c in waiting.iter() {
+        if let Some(dependency) = dependency(c) {
+          outcomes.insert(c.name.clone(), Outcome::skipped(dependency));
+        }
+      }
+    }
+    components
+      .iter()
+      .filter_map(|c| outcomes.remove(&c.name).map(|o| (c.name.clone(), o)))
+      .collect()
+  }
+
+  /// Notifies that a task finished if it ran long enough to have been left unattended.
+  fn notify_task(&self, name: &str, started: Instant, succeeded: bool) {
+    let seconds = started.elapsed().as_secs();
+    if seconds < self.notifications.long_task_secs {
+      return;
+    }
+    let task = name.to_string();
+    let msg = match succeeded {
+      true => Message::TaskFinished { task, seconds },
+      false => Message::TaskFailed { task, seconds },
+    };
+    self.notifications.send(
+      NotificationEvent::TaskFinished,
+      &self.name,
+      name,
+      msg.to_string(),
+    );
+  }
+}
+
+impl Project {
+  /// Forwards stdin to a component of the running session until detached.
+  pub fn attach(&self, name: Option<&str>) -> ExitCode {
+    let name = match name {
+      Some(name) => name,
+      None => {
+        return match daemon::follow(&self.root_path) {
+          Ok(()) => ExitCode::Success,
+          Err(e) => {
+            ui::message(Message::error(e));
+            ExitCode::Failure
+          }
+        }
+      }
+    };
+    let component = match self.find_component(name) {
+      Some(component) => component.name.clone(),
+      None => {
+        ui::message(Message::ConfigError {
+          problem: format!("Unknown component {}", name),
+        });
+        return ExitCode::Config;
+      }
+    };
+    let sent = ui::attach(&component, |text| {
+      control::send(&self.root_path, &Command::Input(component.clone(), text))
+        .map_err(|e| e.to_string())
+        .and_then(|result| result.map(|_| ()))
+    });
+    match sent {
+      true => ExitCode::Success,
+      false => ExitCode::Failure,
+    }
+  }
+
+  /// Runs the session in the background, refusing to if one is already running.
+  pub fn detach(&self) -> ExitCode {
+    if let Some(pid) = daemon::live_session(&self.root_path) {
+      ui::message(Message::SessionRunning { pid });
+      return ExitCode::Failure;
+    }
+    match daemon::detach(&self.root_path) {
+      Ok(pid) => {
+        ui::message(Message::SessionDetached {
+          pid,
+          log: daemon::log_path(&self.root_path).display().to_string(),
+        });
+        ExitCode::Success
+      }
+      Err(e) => {
+        ui::message(Message::error(e));
+        ExitCode::Failure
+      }
+    }
+  }
+
+  /// Restarts a component of the running session with its definition as it is in the config
+  /// now.
+  pub fn restart(&self, name: &str) -> ExitCode {
+    let component = match self.find_component(name) {
+      Some(component) => component.clone(),
+      None => {
+        ui::message(Message::ConfigError {
+          problem: format!("Unknown component {}", name),
+        });
+        return ExitCode::Config;
+      }
+    };
+    let name = component.name.clone();
+    self.send(
+      Command::Replace(Box::new(component)),
+      Message::ComponentRestarting { component: name },
+    )
+  }
+
+  /// Stops a component of the running session.
+  pub fn stop(&self, name: &str) -> ExitCode {
+    self.send(
+      Command::Stop(name.to_string()),
+      Message::ComponentStopping {
+        component: name.to_string(),
+      },
+    )
+  }
+
+  /// Freezes a component of the running session without stopping it, or lets it continue.
+  pub fn pause(&self, name: &str, pause: bool) -> ExitCode {
+    let component = name.to_string();
+    match pause {
+      true => self.send(
+        Command::Pause(component.clone()),
+        Message::ComponentPaused { component },
+      ),
+      false => self.send(
+        Command::Resume(component.clone()),
+        Message::ComponentResumed { component },
+      ),
+    }
+  }
+
+  /// Shows the recent output of a component of the running session, as kept in its scrollback.
+  pub fn tail(&self, name: &str, lines: Option<usize>) -> ExitCode {
+    match control::send(&self.root_path, &Command::Tail(name.to_string(), lines))
+      .map_err(|e| e.to_string())
+      .and_then(|result| result)
+    {
+      Ok(output) => {
+        for line in output {
+          println!("{}", line);
+        }
+        ExitCode::Success
+      }
+      Err(e) => {
+        ui::message(Message::error(e));
+        ExitCode::Failure
+      }
+    }
+  }
+
+  /// Hides or shows again the output of a component or service of the running session.
+  pub fn mute(&self, name: &str, mute: bool) -> ExitCode {
+    let name = name.to_string();
+    match mute {
+      true => self.send(Command::Mute(name.clone()), Message::OutputMuted { name }),
+      false => self.send(
+        Command::Unmute(name.clone()),
+        Message::OutputUnmuted { name },
+      ),
+    }
+  }
+
+  /// Reads commands for the running session at a prompt until exit or ctrl-d. The commands are
+  /// those of the command palette. `logs`, `status` and `help` are shown here, the rest are sent
+  /// over the control socket.
+  pub fn console(&self) -> ExitCode {
+    if daemon::live_session(&self.root_path).is_none() {
+      ui::message(Message::error("No session is running"));
+      return ExitCode::Failure;
+    }
+    ui::message(Message::ConsoleHint);
+    let mut editor = LineEditor::default();
+    while let Some(line) = editor.read("conductor> ", |before| self.console_completions(before)) {
+      let line = line.trim().trim_start_matches(':');
+      match line {
+        "" => continue,
+        "exit" | "quit" => break,
+        _ => {}
+      }
+      match line.parse() {
+        Ok(Command::Logs(name)) => self.logs(&name, None, false),
+        Ok(Command::Tail(name, lines)) => self.tail(&name, lines),
+        Ok(Command::Status) => self.status(),
+        Ok(Command::Help) => {
+          for (usage, description) in USAGE.iter() {
+            ui::message(Message::CommandHelp {
+              usage: usage.to_string(),
+              description: description.to_string(),
+            });
+          }
+          ExitCode::Success
+        }
+        Ok(Command::Start(component)) => self.send(
+          Command::Start(component.clone()),
+          Message::ComponentStarting { component },
+        ),
+        Ok(Command::Stop(name)) => self.stop(&name),
+        Ok(Command::Restart(name)) => self.restart(&name),
+        Ok(Command::Pause(name)) => self.pause(&name, true),
+        Ok(Command::Resume(name)) => self.pause(&name, false),
+        Ok(Command::Mute(name)) => self.mute(&name, true),
+        Ok(Command::Unmute(name)) => self.mute(&name, false),
+        Ok(Command::RunTask(task)) => {
+          self.send(Command::RunTask(task.clone()), Message::TaskSent { task })
+        }
+        Ok(Command::Filter(name)) => {
+          let done = match &name {
+            Some(name) => Message::OutputFiltered { name: name.clone() },
+            None => Message::OutputUnfiltered,
+          };
+          self.send(Command::Filter(name), done)
+        }
+        Ok(Command::Grep(pattern)) => {
+          let done = match &pattern {
+            Some(pattern) => Message::OutputMatching {
+              pattern: pattern.clone(),
+            },
+            None => Message::OutputUnmatched,
+          };
+          self.send(Command::Grep(pattern), done)
+        }
+        Ok(_) => {
+          ui::message(Message::error(format!(
+            ":{} can not be used at the console, type :help to list the commands",
+            line
+          )));
+          ExitCode::Failure
+        }
+        Err(e) => {
+          ui::message(Message::error(e));
+          ExitCode::Failure
+        }
+      };
+    }
+    ExitCode::Success
+  }
+
+  /// The words the word being typed at the console could be, given the line before it.
+  fn console_completions(&self, before: &str) -> Vec<String> {
+    let components = || self.completions(Some("components")).unwrap_or_default();
+    match before
+      .trim_start_matches(':')
+      .split_whitespace()
+      .collect::<Vec<_>>()
+      .as_slice()
+    {
+      [] => USAGE
+        .iter()
+        .filter_map(|(usage, _)| usage.split_whitespace().next())
+        .chain(["exit"].iter().copied())
+        .map(String::from)
+        .collect(),
+      ["run"] => self.completions(Some("tasks")).unwrap_or_default(),
+      ["start"] | ["stop"] | ["restart"] | ["pause"] | ["resume"] | ["logs"] | ["tail"] => {
+        components()
+      }
+      ["mute"] | ["unmute"] | ["filter"] => components()
+        .into_iter()
+        .chain(self.services.iter().map(|s| s.name.clone()))
+        .collect(),
+      _ => vec![],
+    }
+  }
+
+  /// Sends a command to the running session over the control socket, showing the message once
+  /// it has been carried out.
+  fn send(&self, command: Command, done: Message) -> ExitCode {
+    match control::send(&self.root_path, &command)
+      .map_err(|e| e.to_string())
+      .and_then(|result| result)
+    {
+      Ok(_) => {
+        ui::message(done);
+        ExitCode::Success
+      }
+      Err(e) => {
+        ui::message(Message::error(e));
+        ExitCode::Failure
+      }
+    }
+  }
+
+  /// Shows the state of each component of the running session, as recorded in its state file.
+  pub fn status(&self) -> ExitCode {
+    let state = match daemon::live_session(&self.root_path)
+      .and_then(|_| SessionState::read(&self.root_path).ok())
+    {
+      Some(state) => state,
+      None => {
+        ui::message(Message::error("No session is running"));
+        return ExitCode::Failure;
+      }
+    };
+    ui::component_states(&state.components);
+    ExitCode::Success
+  }
+
+  /// Shows the components of the running session in a live table of their CPU, memory,
+  /// restarts and state, sorted by the named column and refreshed every interval in seconds.
+  pub fn top(&self, sort: &str, interval: u64) -> ExitCode {
+    let result = sort.parse().map_err(Error::other).and_then(|sort| {
+      crate::top::run(&self.root_path, sort, Duration::from_secs(interval.max(1)))
+    });
+    match result {
+      Ok(()) => ExitCode::Success,
+      Err(e) => {
+        ui::message(Message::error(e));
+        ExitCode::Failure
+      }
+    }
+  }
+
+  /// Waits until the named components and groups of the running session, or all of its
+  /// components, are ready. Fails as soon as one of them fails, unless it is retried, or the
+  /// session ends, and gives up after the timeout in seconds.
+  pub fn wait(&self, names: &[&str], timeout: u64) -> ExitCode {
+    let mut selected: Vec<String> = vec![];
+    for name in names.iter() {
+      if let Some(group) = self.find_group(name) {
+        match self.group_components(group) {
+          Ok(members) => selected.extend(members.iter().map(|(c, _)| c.name.clone())),
+          Err(problem) => {
+            ui::message(Message::ConfigError { problem });
+            return ExitCode::Config;
+          }
+        }
+      } else if let Some(component) = self.find_component(name) {
+        selected.push(component.name.clone());
+      } else {
+        ui::message(Message::ConfigError {
+          problem: format!("Unknown component or group {}", name),
+        });
+        return ExitCode::Config;
+      }
+    }
+    let is_selected = |name: &str| selected.is_empty() || selected.iter().any(|s| s == name);
+    let retried = |name: &str| self.find_component(name).is_some_and(|c| c.retry);
+    let started = Instant::now();
+    let mut seen = false;
+    loop {
+      let pid = match daemon::live_session(&self.root_path) {
+        Some(pid) => pid,
+        None if seen => {
+          ui::message(Message::WaitSessionEnded);
+          return ExitCode::Component;
+        }
+        None => {
+          ui::message(Message::error("No session is running"));
+          return ExitCode::Failure;
+        }
+      };
+      seen = true;
+      // The state file of a session started in the background is only written once it starts
+      // its components, until then it can be left from an earlier session.
+      let components: Vec<_> = SessionState::read(&self.root_path)
+        .ok()
+        .filter(|state| state.pid == pid)
+        .map(|state| state.components)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|c| is_selected(&c.name))
+        .collect();
+      if let Some(failed) = components
+        .iter()
+        .find(|c| c.status == ComponentStatus::Failed && !retried(&c.name))
+      {
+        ui::message(Message::WaitFailed {
+          component: failed.name.clone(),
+        });
+        return ExitCode::Component;
+      }
+      // Components that finished on their own count as ready, as they do for the session.
+      let ready = |c: &ComponentState| match c.status {
+        ComponentStatus::Running => c.ready,
+        ComponentStatus::Stopped => true,
+        _ => false,
+      };
+      let present = selected
+        .iter()
+        .all(|s| components.iter().any(|c| &c.name == s));
+      if !components.is_empty() && present && components.iter().all(ready) {
+        ui::message(Message::WaitReady {
+          components: components
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", "),
+        });
+        return ExitCode::Success;
+      }
+      if started.elapsed() >= Duration::from_secs(timeout) {
+        ui::message(Message::WaitTimedOut {
+          condition: match names.is_empty() {
+            true => "the environment to be ready".into(),
+            false => format!("{} to be ready", names.join(", ")),
+          },
+          seconds: timeout,
+        });
+        return ExitCode::Component;
+      }
+      thread::sleep(Duration::from_millis(250));
+    }
+  }
+
+  /// Resolves the services named on the command line, every service if none are named.
+  fn named_services(&self, names: &[&str]) -> Result<Vec<Service>, String> {
+    if names.is_empty() {
+      return Ok(
+        self
+          .services
+          .iter()
+          .filter_map(|s| self.service_by_name(&s.name))
+          .collect(),
+      );
+    }
+    names
+      .iter()
+      .map(|name| {
+        self
+          .service_by_name(name)
+          .ok_or_else(|| format!("Unknown service {}", name))
+      })
+      .collect()
+  }
+
+  /// Starts services without any components, waiting for each to be ready.
+  pub fn services_up(&self, names: &[&str]) -> ExitCode {
+    let mut services = match self.named_services(names) {
+      Ok(services) => services,
+      Err(problem) => {
+        ui::message(Message::ConfigError { problem });
+        return ExitCode::Config;
+      }
+    };
+    // The launcher takes services from the back, this starts them in the order they are listed.
+    services.reverse();
+    let mut code = ExitCode::Success;
+    for result in ServiceLauncher::new(services, &self.root_path) {
+      match result {
+        Ok(s) => ui::message(Message::ServiceStarted { service: s.name }),
+        Err((s, e)) => {
+          code = ExitCode::Service;
+          ui::message(Message::ServiceStartFailed {
+            service: s.name,
+            error: e.to_string(),
+          })
+        }
+      }
+    }
+    code
+  }
+
+  /// Stops services, last listed first. With remove_volumes their containers and named volumes
+  /// are removed as well, as if each service had remove_volumes set.
+  pub fn services_down(&self, names: &[&str], remove_volumes: bool) -> ExitCode {
+    let mut services = match self.named_services(names) {
+      Ok(services) => services,
+      Err(problem) => {
+        ui::message(Message::ConfigError { problem });
+        return ExitCode::Config;
+      }
+    };
+    for service in services.iter_mut() {
+      service.remove_volumes |= remove_volumes;
+    }
+    let mut code = ExitCode::Success;
+    for result in ServiceTerminator::new(services, &self.root_path) {
+      match result {
+        Ok(s) => ui::message(Message::ServiceStopped { service: s.name }),
+        Err((s, e)) => {
+          code = ExitCode::Service;
+          ui::message(Message::ServiceStopFailed {
+            service: s.name,
+            error: e.to_string(),
+          })
+        }
+      }
+    }
+    code
+  }
+
+  /// Shows whether each service is running, whether or not a session is.
+  pub fn services_status(&self, names: &[&str]) -> ExitCode {
+    let services = match self.named_services(names) {
+      Ok(services) => services,
+      Err(problem) => {
+        ui::message(Message::ConfigError { problem });
+        return ExitCode::Config;
+      }
+    };
+    let mut code = ExitCode::Success;
+    let mut table = Table::new(&["Service", "Status"]);
+    for service in services {
+      match service.status(&self.root_path) {
+        Ok(status) if ui::json() => ui::message(Message::ServiceStatus {
+          service: service.name,
+          status: status.to_string(),
+        }),
+        Ok(status) => {
+          let cell = Cell::new(status);
+          let cell = match status {
+            ServiceStatus::Running => cell.colored(TerminalColor::Green),
+            _ => cell,
+          };
+          table.row(vec![service.name.into(), cell]);
+        }
+        Err(e) => {
+          code = ExitCode::Failure;
+          ui::message(Message::error(format!("{}: {}", service.name, e)))
+        }
+      }
+    }
+    if !table.is_empty() {
+      ui::table(&table);
+    }
+    code
+  }
+
+  /// Reconciles the running session toward the components listed in a spec file.
+  pub fn apply(&self, spec_path: &Path) -> ExitCode {
+    let components = match fs::read_to_string(spec_path)
+      .map_err(|e| e.to_string())
+      .and_then(|spec| parse::<Spec>(spec_path, &spec).map_err(|e| e.to_string()))
+      .and_then(|spec| self.desired_components(&spec))
+    {
+      Ok(components) => components,
+      Err(e) => {
+        ui::message(Message::ConfigError {
+          problem: format!("{}: {}", spec_path.display(), e),
+        });
+        return ExitCode::Config;
+      }
+    };
+    self.send(
+      Command::Apply(components),
+      Message::SpecApplied {
+        path: spec_path.display().to_string(),
+      },
+    )
+  }
+
+  /// Resolves the components and groups named in a spec to component definitions.
+  fn desired_components(&self, spec: &Spec) -> Result<Vec<Component>, String> {
+    let mut names = spec.components.clone();
+    for name in spec.groups.iter() {
+      let group = self
+        .find_group(name)
+        .ok_or_else(|| format!("Unknown group {}", name))?;
+      let members = self.group_components(group)?;
+      names.extend(members.iter().map(|(c, _)| c.name.clone()));
+    }
+    let mut components: Vec<Component> = vec![];
+    for name in names.iter() {
+      let component = self
+        .find_component(name)
+        .ok_or_else(|| format!("Unknown component {}", name))?;
+      if !components.iter().any(|c| c.name == component.name) {
+        components.push(component.clone());
+      }
+    }
+    Ok(components)
+  }
+
+  /// Prints a graph of the components, the services they use, the components they wait for and
+  /// the groups they are in, as DOT or a mermaid flowchart.
+  pub fn graph(&self, format: &str) -> ExitCode {
+    match format.parse() {
+      Ok(format) => {
+        print!("{}", graph::graph(self, format));
+        ExitCode::Success
+      }
+      Err(e) => {
+        ui::message(Message::error(e));
+        ExitCode::Failure
+      }
+    }
+  }
+
+  /// Prints the configuration as it was loaded, after includes, templates, profiles and
+  /// defaults have been applied.
+  pub fn dump(&self) -> ExitCode {
+    match self.concealed_config() {
+      Ok(config) => {
+        print!("{}", config);
+        ExitCode::Success
+      }
+      Err(e) => {
+        ui::message(Message::error(e));
+        ExitCode::Failure
+      }
+    }
+  }
+
+  /// The resolved config as YAML, with the values decrypted from it masked.
+  fn concealed_config(&self) -> Result<String, serde_yaml::Error> {
+    let mut config = serde_yaml::to_value(self)?;
+    encrypted::conceal_all(&mut config);
+    serde_yaml::to_string(&config)
+  }
+
+  /// Checks the configuration for broken references and other mistakes.
+  pub fn validate(&self) -> ExitCode {
+    let issues = crate::validate::validate(self);
+    if issues.is_empty() {
+      ui::message(Message::ConfigValid);
+      return ExitCode::Success;
+    }
+    let mut code = ExitCode::Success;
+    for issue in issues {
+      match issue {
+        Issue::Error(problem) => {
+          code = ExitCode::Config;
+          ui::message(Message::ConfigError { problem })
+        }
+        Issue::Warning(problem) => ui::message(Message::ConfigWarning { problem }),
+      }
+    }
+    code
+  }
+
+  /// Checks that the machine has what the project needs to run, printing a report with hints
+  /// for the checks that failed.
+  pub fn doctor(&self) -> ExitCode {
+    let checks = crate::doctor::doctor(self);
+    let mut failed = 0;
+    let mut table = Table::new(&["Check", "Result", "Hint"]);
+    for check in checks.iter() {
+      if check.problem.is_some() {
+        failed += 1;
+      }
+      if ui::json() {
+        match &check.problem {
+          None => ui::message(Message::CheckPassed {
+            check: check.name.clone(),
+          }),
+          Some(problem) => {
+            ui::message(Message::CheckFailed {
+              check: check.name.clone(),
+              problem: problem.clone(),
+            });
+            ui::detail(Message::CheckHint {
+              hint: check.hint.clone(),
+            });
+          }
+        }
+        continue;
+      }
+      match &check.problem {
+        None => table.row(vec![
+          check.name.as_str().into(),
+          Cell::new("ok").colored(TerminalColor::Green),
+        ]),
+        Some(problem) => table.row(vec![
+          check.name.as_str().into(),
+          Cell::new(problem).colored(TerminalColor::Red),
+          check.hint.as_str().into(),
+        ]),
+      }
+    }
+    ui::table(&table);
+    match failed {
+      0 => {
+        ui::message(Message::ChecksPassed {
+          checks: checks.len(),
+        });
+        ExitCode::Success
+      }
+      _ => {
+        ui::message(Message::ChecksFailed {
+          failed,
+          checks: checks.len(),
+        });
+        ExitCode::Failure
+      }
+    }
+  }
+
+  /// Reports processes, containers and files left behind by crashed sessions. Each finding is
+  /// cleaned up after confirmation, or immediately if kill is set.
+  pub fn audit(&self, kill: bool) -> ExitCode {
+    let report = crate::audit::audit(self);
+    if let Some(pid) = report.live_session {
+      ui::message(Message::SessionRunning { pid });
+      return ExitCode::Success;
+    }
+    clean_up(&report.findings, kill)
+  }
+
+  /// Stops the processes and containers a session that ended without stopping them left
+  /// running, as recorded in its state file. Each is cleaned up after confirmation, or
+  /// immediately if kill is set.
+  pub fn cleanup(&self, kill: bool) -> ExitCode {
+    if let Some(pid) = daemon::live_session(&self.root_path) {
+      ui::message(Message::SessionRunning { pid });
+      return ExitCode::Success;
+    }
+    clean_up(&crate::audit::leftovers(&self.root_path), kill)
+  }
+
+  /// Checks for processes and containers a crashed session left running before starting new
+  /// ones, since they tend to hold on to the ports the new ones need. They are stopped after
+  /// confirmation when someone is there to answer and only reported otherwise.
+  fn check_leftovers(&self, options: &RunOptions) {
+    let findings = crate::audit::leftovers(&self.root_path);
+    let running = findings
+      .iter()
+      .filter(|f| !matches!(f, Finding::StaleFile { .. }))
+      .count();
+    if running == 0 {
+      return;
+    }
+    ui::message(Message::LeftoversFound { count: running });
+    if options.interactive && ui::confirm("Stop them?") {
+      clean_up(&findings, true);
+    }
+  }
+}
+
+/// Cleans up the findings of an audit, each after confirmation unless kill is set.
+fn clean_up(findings: &[Finding], kill: bool) -> ExitCode {
+  if findings.is_empty() {
+    ui::message(Message::NothingToClean);
+    return ExitCode::Success;
+  }
+  let mut code = ExitCode::Success;
+  for finding in findings.iter() {
+    let description = finding.describe();
+    if !kill && !ui::confirm(&format!("{} - clean up?", description)) {
+      continue;
+    }
+    match finding.clean() {
+      Ok(_) => ui::message(Message::CleanedUp {
+        finding: description,
+      }),
+      Err(e) => {
+        code = ExitCode::Failure;
+        ui::message(Message::CleanUpFailed {
+          finding: description,
+          error: e.to_string(),
+        })
+      }
+    }
+  }
+  code
+}
+
+/// A desired set of components for `conductor apply`.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Spec {
+  components: Vec<String>,
+  groups: Vec<String>,
+}
+
+/// Merges a definition over the one it extends. Mappings, such as env and tasks, are merged key
+/// by key with the definition's values taking precedence. Anything else, including lists such as
+/// tags, is replaced by the definition's value.
+fn deep_merge(base: Value, definition: Value) -> Value {
+  match (base, definition) {
+    (Value::Mapping(mut base), Value::Mapping(definition)) => {
+      for (key, value) in definition {
+        match base.get_mut(&key) {
+          Some(below) => *below = deep_merge(std::mem::replace(below, Value::Null), value),
+          None => {
+            base.insert(key, value);
+          }
+        }
+      }
+      Value::Mapping(base)
+    }
+    (_, definition) => definition,
+  }
+}
+
+/// The local override of a config file, such as `conductor.override.yml` for `conductor.yml`.
+pub(crate) fn override_path(path: &Path) -> PathBuf {
+  let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+  let name = match path.extension() {
+    Some(ext) => format!("{}.override.{}", stem, ext.to_string_lossy()),
+    None => format!("{}.override", stem),
+  };
+  path.with_file_name(name)
+}
+
+/// Lays a local override over a config as written. Mappings are merged key by key, as with
+/// deep_merge. Lists of named entries, such as components, are merged entry by entry: entries
+/// with a new name are added and those with `disabled: true` are removed. Other values are
+/// replaced.
+fn overlay(base: Value, overrides: Value) -> Value {
+  let named = |list: &Vec<Value>| !list.is_empty() && list.iter().all(|v| v.get("name").is_some());
+  match (base, overrides) {
+    (Value::Mapping(mut base), Value::Mapping(overrides)) => {
+      for (key, value) in overrides {
+        match base.get_mut(&key) {
+          Some(below) => *below = overlay(std::mem::replace(below, Value::Null), value),
+          None => {
+            base.insert(key, value);
+          }
+        }
+      }
+      Value::Mapping(base)
+    }
+    (Value::Sequence(mut base), Value::Sequence(overrides)) if named(&overrides) => {
+      for entry in overrides {
+        let disabled = entry.get("disabled") == Some(&Value::Bool(true));
+        let position = base.iter().position(|b| b.get("name") == entry.get("name"));
+        match (position, disabled) {
+          (Some(i), true) => {
+            base.remove(i);
+          }
+          (Some(i), false) => {
+            base[i] = overlay(std::mem::replace(&mut base[i], Value::Null), entry);
+          }
+          (None, true) => {}
+          (None, false) => base.push(entry),
+        }
+      }
+      Value::Sequence(base)
+    }
+    (_, overrides) => overrides,
+  }
+}
+
+/// Runs a task in each component it names, one after the other or all at once, and reports the
+/// components it failed in. Returns true if it succeeded in all of them.
+pub(crate) fn run_foreach(supr: &Supervisor, name: &str, tasks: Vec<Task>, parallel: bool) -> bool {
+  let count = tasks.len();
+  // The name of the component the task failed in, if it did.
+  let run = |task: Task| {
+    let t = task.clone();
+    match task.into_iter().all(|cmd| supr.run_task_command(&t, cmd)) {
+      true => None,
+      false => Some(t.name),
+    }
+  };
+  let failed: Vec<String> = match parallel {
+    true => thread::scope(|s| {
+      let run = &run;
+      let running: Vec<_> = tasks
+        .into_iter()
+        .map(|task| s.spawn(move || run(task)))
+        .collect();
+      running
+        .into_iter()
+        .filter_map(|r| r.join().ok().flatten())
+        .collect()
+    }),
+    false => tasks.into_iter().filter_map(run).collect(),
+  };
+  let succeeded = failed.is_empty();
+  let task = name.to_string();
+  match succeeded {
+    true => ui::message(Message::ForeachSucceeded { task, count }),
+    false => ui::message(Message::ForeachFailed {
+      task,
+      count,
+      components: failed,
+    }),
+  }
+  succeeded
+}
+
+/// A command line from the arguments given for one. A single argument is used as it is, several
+/// are quoted and joined.
+fn command_line(args: &[&str]) -> String {
+  match args {
+    [command] => command.to_string(),
+    _ => args
+      .iter()
+      .map(|a| process::quote(a))
+      .collect::<Vec<_>>()
+      .join(" "),
+  }
+}
+
+/// A task running commands for a component, in its directory and with its environment.
+fn component_task(component: &Component, commands: Vec<String>) -> Task {
+  let mut task = Task::new(
+    &component.name,
+    &component.get_path(),
+    commands,
+    component.env.clone(),
+  );
+  task.env_file = component.env_file.clone();
+  task.secrets = component.secrets.clone();
+  task.env_commands = component.env_commands.clone();
+  task
+}
+
+/// Parses a config as TOML or JSON depending on the file extension, anything else is parsed as
+/// YAML.
+fn parse<T: DeserializeOwned>(path: &Path, config: &str) -> Result<T, Box<dyn std::error::Error>> {
+  Ok(match path.extension().and_then(|e| e.to_str()) {
+    Some("toml") => toml::from_str(config)?,
+    Some("json") => serde_json::from_str(config)?,
+    _ => serde_yaml::from_str(config)?,
+  })
+}
+
+/// Writes a config in the format parse reads it in.
+fn unparse(path: &Path, config: &Value) -> Result<String, Box<dyn std::error::Error>> {
+  Ok(match path.extension().and_then(|e| e.to_str()) {
+    Some("toml") => toml::to_string(&toml::Value::try_from(config)?)?,
+    Some("json") => serde_json::to_string(config)?,
+    _ => serde_yaml::to_string(config)?,
+  })
+}
+
+impl Default for Project {
+  fn default() -> Self {
+    Project {
       name: "Unnamed Project".into(),
       components: vec![],
       services: vec![],
       groups: vec![],
       root_path: "".into(),
       tasks: HashMap::new(),
+      env: HashMap::new(),
+      env_commands: HashMap::new(),
+      secrets: HashMap::new(),
+      vars: HashMap::new(),
+      dotenv: Dotenv::default(),
+      include: vec![],
+      projects: HashMap::new(),
+      tuning: Tuning::default(),
+      pty: false,
+      terminal: TerminalIntegration::default(),
+      guardrails: Guardrails::default(),
+      output: ui::Settings::default(),
+      notifications: Notifications::default(),
+      telemetry: Telemetry::default(),
+      pre_run: vec![],
+      post_run: vec![],
+      proxy: Proxy::default(),
+      templates: HashMap::new(),
+    }
+  }
+}
+
+/// How the commands of a suite went for a component.
+struct Outcome {
+  succeeded: bool,
+  seconds: u64,
+  /// The component it waits for, if it was skipped because that did not succeed.
+  skipped_for: Option<String>,
+}
+
+impl Outcome {
+  fn skipped(dependency: String) -> Self {
+    Outcome {
+      succeeded: false,
+      seconds: 0,
+      skipped_for: Some(dependency),
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn yaml(text: &str) -> Value {
+    serde_yaml::from_str(text).unwrap()
+  }
+
+  fn project(templates: &str) -> Project {
+    Project {
+      templates: serde_yaml::from_str(templates).unwrap(),
+      ..Project::default()
+    }
+  }
+
+  #[test]
+  fn deep_merge_merges_mappings_key_by_key() {
+    let base = yaml("env: {A: '1', B: '2'}\ntasks: {test: [cargo test]}");
+    let definition = yaml("env: {B: '3', C: '4'}");
+    let merged = deep_merge(base, definition);
+    assert_eq!(
+      merged,
+      yaml("env: {A: '1', B: '3', C: '4'}\ntasks: {test: [cargo test]}")
+    );
+  }
+
+  #[test]
+  fn deep_merge_replaces_lists_and_scalars() {
+    let base = yaml("tags: [web, api]\nstart: npm start\nretry: true");
+    let definition = yaml("tags: [worker]\nstart: npm run worker");
+    let merged = deep_merge(base, definition);
+    assert_eq!(
+      merged,
+      yaml("tags: [worker]\nstart: npm run worker\nretry: true")
+    );
+  }
+
+  #[test]
+  fn overlay_merges_named_entries_by_name() {
+    let base = yaml(
+      "env: {A: '1'}
+components:
+- {name: api, start: ./api, env: {PORT: '80'}}
+- {name: worker, start: ./worker}",
+    );
+    let overrides = yaml(
+      "env: {B: '2'}
+components:
+- {name: api, env: {PORT: '8080'}}
+- {name: worker, disabled: true}
+- {name: docs, start: ./docs}",
+    );
+    assert_eq!(
+      overlay(base, overrides),
+      yaml(
+        "env: {A: '1', B: '2'}
+components:
+- {name: api, start: ./api, env: {PORT: '8080'}}
+- {name: docs, start: ./docs}"
+      )
+    );
+  }
+
+  #[test]
+  fn extend_applies_template_chains() {
+    let p = project(
+      "service: {start: ./run, env: {LOG: info, PORT: '80'}, tags: [svc]}
+rust: {extends: service, start: cargo run, env: {RUST_BACKTRACE: 1}}",
+    );
+    let component = p
+      .extend(yaml("name: users\nextends: rust\nenv: {PORT: 3001}"))
+      .unwrap();
+    assert_eq!(component.name, "users");
+    assert_eq!(component.start, "cargo run");
+    assert_eq!(component.tags, vec!["svc"]);
+    assert_eq!(component.env["LOG"], "info");
+    assert_eq!(component.env["PORT"], "3001");
+    assert_eq!(component.env["RUST_BACKTRACE"], "1");
+  }
+
+  #[test]
+  fn extend_reports_unknown_and_cyclic_templates() {
+    let p = project("a: {extends: b}\nb: {extends: a}\nc: {extends: c}");
+    let unknown = p.extend(yaml("name: x\nextends: d")).unwrap_err();
+    assert_eq!(unknown.to_string(), "Component x: Unknown template d");
+    let cycle = p.extend(yaml("name: x\nextends: a")).unwrap_err();
+    assert_eq!(
+      cycle.to_string(),
+      "Component x: Templates extend each other in a cycle: a -> b -> a"
+    );
+    let own = p.extend(yaml("name: x\nextends: c")).unwrap_err();
+    assert_eq!(
+      own.to_string(),
+      "Component x: Templates extend each other in a cycle: c -> c"
+    );
+  }
+
+  #[test]
+  fn load_applies_templates_from_included_files() {
+    let dir = std::env::temp_dir().join(format!("conductor-templates-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+      dir.join("conductor.yml"),
+      "include: [more.yml]
+templates:
+  node: {start: npm start, env: {NODE_ENV: development}}
+components:
+- name: web
+  extends: node
+- name: plain
+  start: ./plain
+",
+    )
+    .unwrap();
+    fs::write(
+      dir.join("more.yml"),
+      "components:\n- {name: admin, extends: node, env: {PORT: 4000}}\n",
+    )
+    .unwrap();
+    let p = Project::load(&dir.join("conductor.yml"), &UserConfig::default());
+    fs::remove_dir_all(&dir).unwrap();
+    let p = p.unwrap();
+    let web = p.find_component("web").unwrap();
+    assert_eq!(web.start, "npm start");
+    let admin = p.find_component("admin").unwrap();
+    assert_eq!(admin.start, "npm start");
+    assert_eq!(admin.env["NODE_ENV"], "development");
+    assert_eq!(admin.env["PORT"], "4000");
+    assert_eq!(p.find_component("plain").unwrap().start, "./plain");
+  }
+}
@@ -0,0 +1,202 @@
+use crate::http;
+use crate::Component;
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+
+/// A reverse proxy in front of the components, so they can be reached by hostname or path such
+/// as `api.localhost` instead of by port. The proxy is only started when it has routes.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(default)]
+pub struct Proxy {
+  /// The address the proxy listens on.
+  pub listen: String,
+  pub routes: Vec<Route>,
+}
+
+impl Default for Proxy {
+  fn default() -> Self {
+    Proxy {
+      listen: "127.0.0.1:8080".into(),
+      routes: vec![],
+    }
+  }
+}
+
+/// Sends requests for a hostname, a path prefix or both to a local port. When several routes
+/// match the one with a host and the longest path wins.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug, Default)]
+#[serde(default)]
+pub struct Route {
+  /// The hostname requests are sent for, such as `api.localhost`.
+  pub host: Option<String>,
+  /// The path prefix requests are sent for, such as `/api`. The path is passed on unchanged.
+  pub path: Option<String>,
+  /// The component requests are sent to, on the first of its ports.
+  pub component: Option<String>,
+  /// The local port requests are sent to, used instead of the component's.
+  pub port: Option<u16>,
+}
+
+impl Route {
+  /// The port requests are sent to.
+  pub fn target(&self, components: &[Component]) -> Result<u16, String> {
+    if let Some(port) = self.port {
+      return Ok(port);
+    }
+    let name = self
+      .component
+      .as_ref()
+      .ok_or_else(|| format!("Proxy route {} has no component or port", self))?;
+    let component = components
+      .iter()
+      .find(|c| c.name.to_lowercase() == name.to_lowercase())
+      .ok_or_else(|| format!("Proxy route {} uses unknown component {}", self, name))?;
+    component
+      .ports
+      .first()
+      .cloned()
+      .ok_or_else(|| format!("Proxy route {} uses component {} without ports", self, name))
+  }
+
+  fn matches(&self, host: &str, path: &str) -> bool {
+    let host_matches = match &self.host {
+      Some(h) => h.eq_ignore_ascii_case(host),
+      None => true,
+    };
+    let path_matches = match &self.path {
+      Some(prefix) => {
+        let prefix = prefix.trim_end_matches('/');
+        path.starts_with(prefix)
+          && matches!(
+            path[prefix.len()..].chars().next(),
+            None | Some('/') | Some('?')
+          )
+      }
+      None => true,
+    };
+    host_matches && path_matches
+  }
+}
+
+impl std::fmt::Display for Route {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "{}{}",
+      self.host.as_deref().unwrap_or_default(),
+      self.path.as_deref().unwrap_or("/")
+    )
+  }
+}
+
+/// Accepts connections and forwards each request to the port of the route it matches. Returns the
+/// address being listened on.
+pub fn listen(address: &str, routes: Vec<(Route, u16)>) -> io::Result<SocketAddr> {
+  let listener = TcpListener::bind(address)?;
+  let local = listener.local_addr()?;
+  let routes = Arc::new(routes);
+  thread::spawn(move || {
+    for stream in listener.incoming().filter_map(Result::ok) {
+      let routes = Arc::clone(&routes);
+      thread::spawn(move || {
+        if let Err(e) = forward(stream, &routes) {
+          log::warn!("Could not proxy request: {}", e);
+        }
+      });
+    }
+  });
+  Ok(local)
+}
+
+/// Finds the port for a request.
+fn route(routes: &[(Route, u16)], host: &str, path: &str) -> Option<u16> {
+  routes
+    .iter()
+    .filter(|(r, _)| r.matches(host, path))
+    .max_by_key(|(r, _)| (r.host.is_some(), r.path.as_ref().map(|p| p.len())))
+    .map(|(_, port)| *port)
+}
+
+fn forward(client: TcpStream, routes: &[(Route, u16)]) -> io::Result<()> {
+  let mut reader = BufReader::new(client.try_clone()?);
+  let mut request = String::new();
+  reader.read_line(&mut request)?;
+  let mut headers = vec![];
+  loop {
+    let mut header = String::new();
+    if reader.read_line(&mut header)? == 0 || header.trim_end().is_empty() {
+      break;
+    }
+    headers.push(header);
+  }
+  let header = |name: &str| {
+    headers.iter().find_map(|h| {
+      let (key, value) = h.split_once(':')?;
+      match key.trim().eq_ignore_ascii_case(name) {
+        true => Some(value.trim().to_string()),
+        false => None,
+      }
+    })
+  };
+  let host = header("host").unwrap_or_default();
+  let host = http::host_name(&host);
+  let path = request.split_whitespace().nth(1).unwrap_or("/");
+  let port = match route(routes, host, path) {
+    Some(port) => port,
+    None => {
+      return respond(
+        &client,
+        "404 Not Found",
+        &format!("No route for {}{}", host, path),
+      )
+    }
+  };
+  let mut upstream = match TcpStream::connect(("127.0.0.1", port)) {
+    Ok(upstream) => upstream,
+    Err(e) => {
+      let error = format!("Could not connect to port {}: {}", port, e);
+      return respond(&client, "502 Bad Gateway", &error);
+    }
+  };
+
+  // Each connection carries a single request so that the next one is routed again. Upgraded
+  // connections, such as websockets, are passed through as they are.
+  let upgrade = header("upgrade").is_some();
+  upstream.write_all(request.as_bytes())?;
+  for h in headers.iter() {
+    let key = h.split(':').next().unwrap_or_default().trim();
+    if !upgrade && key.eq_ignore_ascii_case("connection") {
+      continue;
+    }
+    upstream.write_all(h.as_bytes())?;
+  }
+  if !upgrade {
+    upstream.write_all(b"Connection: close\r\n")?;
+  }
+  upstream.write_all(b"\r\n")?;
+
+  // The rest of the request, starting with whatever was read past the headers, is copied on its
+  // own thread while the response is copied back.
+  let mut to_upstream = upstream.try_clone()?;
+  thread::spawn(move || {
+    let _ = io::copy(&mut reader, &mut to_upstream);
+    let _ = to_upstream.shutdown(Shutdown::Write);
+  });
+  let _ = io::copy(&mut upstream, &mut &client);
+  let _ = client.shutdown(Shutdown::Both);
+  Ok(())
+}
+
+fn respond(client: &TcpStream, status: &str, body: &str) -> io::Result<()> {
+  let mut client = client;
+  write!(
+    client,
+    "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}\n",
+    status,
+    body.len() + 1,
+    body
+  )
+}
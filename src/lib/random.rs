@@ -0,0 +1,20 @@
+use std::io;
+
+/// Fills a buffer with random bytes from the operating system.
+pub(crate) fn fill(bytes: &mut [u8]) -> io::Result<()> {
+  getrandom::getrandom(bytes).map_err(io::Error::from)
+}
+
+/// A random number from 0 up to 1.
+pub(crate) fn fraction() -> io::Result<f64> {
+  let mut bytes = [0u8; 8];
+  fill(&mut bytes)?;
+  Ok((u64::from_le_bytes(bytes) >> 11) as f64 / (1u64 << 53) as f64)
+}
+
+/// A number of random bytes written as lowercase hex.
+pub(crate) fn hex(bytes: usize) -> io::Result<String> {
+  let mut buffer = vec![0u8; bytes];
+  fill(&mut buffer)?;
+  Ok(buffer.iter().map(|b| format!("{:02x}", b)).collect())
+}
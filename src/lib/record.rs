@@ -0,0 +1,119 @@
+use crate::journal::utc_timestamp;
+use crate::ui::{self, Origin};
+use crate::{Component, Service, TerminalColor};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// A line of a recording. Component events are written as in the json output format, system
+/// messages with the `message` or `error` event and their text as the body.
+#[derive(Serialize, Deserialize)]
+struct Entry {
+  /// Milliseconds since the recording started.
+  at: u64,
+  time: String,
+  /// The component or service the event belongs to, empty for system messages.
+  #[serde(default)]
+  name: String,
+  #[serde(default)]
+  color: Option<TerminalColor>,
+  event: String,
+  #[serde(default)]
+  body: serde_json::Value,
+}
+
+/// A line of a recording for an event that happened the given time after the session started.
+/// The journal writes these to `events.jsonl` and, with `--record`, to the recording, so either
+/// can be played back with `conductor replay`.
+pub(crate) fn entry(
+  at: Duration,
+  name: &str,
+  color: Option<TerminalColor>,
+  event: &str,
+  body: serde_json::Value,
+) -> Option<String> {
+  let entry = Entry {
+    at: at.as_millis() as u64,
+    time: utc_timestamp(SystemTime::now()),
+    name: name.to_string(),
+    color,
+    event: event.to_string(),
+    body,
+  };
+  serde_json::to_string(&entry).ok()
+}
+
+/// Plays back a recording with the same output the session had, waiting between events as long
+/// as they were apart divided by the speed.
+pub fn replay(path: &Path, speed: f64) -> io::Result<()> {
+  let mut entries = vec![];
+  for (n, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+    let line = line?;
+    if line.trim().is_empty() {
+      continue;
+    }
+    let entry: Entry = serde_json::from_str(&line).map_err(|e| {
+      io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+          "Line {} of {} is not a recorded event: {}",
+          n + 1,
+          path.display(),
+          e
+        ),
+      )
+    })?;
+    entries.push(entry);
+  }
+  ui::align(
+    entries
+      .iter()
+      .filter(|e| !e.name.is_empty())
+      .map(|e| e.name.as_str()),
+  );
+  let started = Instant::now();
+  let mut origins: HashMap<String, Origin> = HashMap::new();
+  for entry in entries {
+    // A speed slow enough to wait longer than a duration can hold waits as long as it can.
+    let due =
+      Duration::try_from_secs_f64(entry.at as f64 / 1000.0 / speed).unwrap_or(Duration::MAX);
+    if let Some(wait) = due.checked_sub(started.elapsed()) {
+      thread::sleep(wait);
+    }
+    let text = entry.body.as_str().unwrap_or_default().to_string();
+    let component = Component {
+      name: entry.name.clone(),
+      color: entry.color.clone().unwrap_or(TerminalColor::Auto),
+      ..Component::default()
+    };
+    let origin = origins.entry(entry.name.clone()).or_default();
+    // Lifecycle events were also announced as messages, which are replayed instead.
+    match entry.event.as_str() {
+      "message" => ui::system_message(text),
+      "error" if entry.name.is_empty() => ui::system_error(text),
+      "output" => ui::component_message(&component, *origin, text),
+      "stderr" => ui::component_stderr(&component, *origin, text),
+      "start" => {
+        if origin.pid.is_some() {
+          origin.restarts += 1;
+        }
+        origin.pid = entry.body["pid"].as_u64().map(|pid| pid as u32);
+      }
+      "service_output" => {
+        let service = Service {
+          name: entry.name.clone(),
+          color: component.color,
+          ..Service::default()
+        };
+        let output = entry.body["output"].as_str().unwrap_or_default();
+        ui::service_message(&service, output.to_string());
+      }
+      _ => {}
+    }
+  }
+  Ok(())
+}
@@ -0,0 +1,83 @@
+use crate::project::override_path;
+use crate::service::use_docker_socket;
+use crate::{Component, Project, UserConfig};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// The config file of a session along with the tags and profiles the session was started with,
+/// so a changed file is filtered the same way it was at launch.
+#[derive(Clone, Debug)]
+pub struct ConfigWatch {
+  pub path: PathBuf,
+  pub tags: Vec<String>,
+  pub profiles: Vec<String>,
+  /// Components and groups left out with `--except`.
+  pub except: Vec<String>,
+  /// Variables given with `--var`.
+  pub vars: HashMap<String, String>,
+}
+
+impl ConfigWatch {
+  /// When the config file or its local override was last written.
+  pub fn modified(&self) -> Option<SystemTime> {
+    let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+    modified(&self.path).max(modified(&override_path(&self.path)))
+  }
+
+  /// Loads the config file and limits it to the session's tags and profiles and to the
+  /// components whose conditions hold. The user config is read again along with it, so a
+  /// reload also picks up changed user settings.
+  pub fn load(&self) -> io::Result<Project> {
+    let user = UserConfig::load()?;
+    use_docker_socket(user.docker_socket.as_deref());
+    let mut project = Project::load_with_vars(&self.path, &self.vars, &user)?;
+    project.filter_tags(&self.tags.iter().map(String::as_str).collect::<Vec<_>>());
+    project.filter_profiles(&self.profiles.iter().map(String::as_str).collect::<Vec<_>>());
+    project.filter_conditions();
+    project
+      .filter_except(&self.except.iter().map(String::as_str).collect::<Vec<_>>())
+      .map_err(io::Error::other)?;
+    Ok(project)
+  }
+}
+
+/// Gives components that were already defined the color they had, so colors handed out from
+/// the palette do not shift when components are added or removed.
+pub fn keep_colors(old: &Project, new: &mut Project) {
+  for c in new.components.iter_mut() {
+    if let Some(previous) = old.find_component(&c.name) {
+      c.color = previous.color.clone();
+    }
+  }
+}
+
+/// How the components of a project differ from those of the one it replaces.
+#[derive(Default)]
+pub struct Changes {
+  pub added: Vec<Component>,
+  pub removed: Vec<Component>,
+  pub changed: Vec<Component>,
+}
+
+/// Compares the components of two versions of a project. Changed components are given with
+/// their new definition.
+pub fn changes(old: &Project, new: &Project) -> Changes {
+  let mut changes = Changes::default();
+  for c in new.components.iter() {
+    match old.find_component(&c.name) {
+      None => changes.added.push(c.clone()),
+      Some(previous) if previous != c => changes.changed.push(c.clone()),
+      Some(_) => {}
+    }
+  }
+  changes.removed = old
+    .components
+    .iter()
+    .filter(|c| new.find_component(&c.name).is_none())
+    .cloned()
+    .collect();
+  changes
+}
@@ -0,0 +1,117 @@
+use crate::source::sha256;
+use crate::{ui, Message};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Returns true if a config is given as a URL rather than a path.
+pub fn is_remote(config: &str) -> bool {
+  config.starts_with("https://") || config.starts_with("http://")
+}
+
+/// Where a config from a URL is treated as living: the current directory, under the name at the
+/// end of the URL. Paths in the config, its includes and its local override are relative to it,
+/// not to the cache the config is kept in.
+pub fn config_location(url: &str) -> io::Result<PathBuf> {
+  let address = url.split(['?', '#']).next().unwrap_or_default();
+  let name = match address.rsplit_once('/') {
+    Some((_, name)) if Path::new(name).extension().is_some() => name,
+    _ => "conductor.yml",
+  };
+  Ok(env::current_dir()?.join(name))
+}
+
+/// Downloads a config from a URL and returns the copy cached under `$XDG_CACHE_HOME` or
+/// `~/.cache`. When the download fails the copy cached by an earlier run is used, so a session
+/// can still be started offline. A URL ending in `#sha256=<checksum>` is only used if the config
+/// matches the checksum. Configs are only fetched over plain http when they have a checksum.
+pub fn fetch_config(url: &str) -> io::Result<PathBuf> {
+  let (url, expected) = match url.split_once("#sha256=") {
+    Some((url, checksum)) => (url, Some(checksum.trim())),
+    None => (url, None),
+  };
+  if expected.is_none() && !url.starts_with("https://") {
+    return Err(io::Error::other(format!(
+      "{} is not fetched over https, add #sha256=<checksum> to the URL to use it",
+      url
+    )));
+  }
+  let cached = cache_path(url)
+    .ok_or_else(|| io::Error::other("Could not find a directory to cache configs in"))?;
+  let dir = cached.parent().unwrap_or(Path::new("."));
+  fs::create_dir_all(dir)?;
+  // The download only replaces the cached copy once it has been verified.
+  let download = dir.join(format!(".download-{}", std::process::id()));
+  let result = download_into(url, &download).and_then(|_| verify(url, &download, expected));
+  match result {
+    Ok(()) => fs::rename(&download, &cached)?,
+    Err(e) => {
+      let _ = fs::remove_file(&download);
+      if !cached.is_file() {
+        return Err(e);
+      }
+      verify(url, &cached, expected)?;
+      ui::message(Message::RemoteConfigCached {
+        url: url.to_string(),
+        error: e.to_string(),
+      });
+    }
+  }
+  Ok(cached)
+}
+
+/// Where the config at a URL is cached. The file keeps the URL's extension so it is read in the
+/// same format.
+fn cache_path(url: &str) -> Option<PathBuf> {
+  let dir = match env::var_os("XDG_CACHE_HOME").filter(|d| !d.is_empty()) {
+    Some(dir) => PathBuf::from(dir),
+    None => PathBuf::from(env::var_os("HOME")?).join(".cache"),
+  };
+  let address = url.split_once("://").map_or(url, |(_, rest)| rest);
+  let address = address.split(['?', '#']).next().unwrap_or_default();
+  let mut name: String = address
+    .chars()
+    .map(
+      |c| match c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+        true => c,
+        false => '_',
+      },
+    )
+    .collect();
+  match Path::new(address).extension().and_then(|e| e.to_str()) {
+    Some("yml" | "yaml" | "toml" | "json") => {}
+    _ => name.push_str(".yml"),
+  }
+  Some(dir.join("conductor").join("configs").join(name))
+}
+
+/// Downloads a URL into a file, following redirects. Responses that are not successful are an
+/// error.
+fn download_into(url: &str, path: &Path) -> io::Result<()> {
+  // The errors of requests start with the URL.
+  let response = ureq::get(url)
+    .timeout(Duration::from_secs(30))
+    .call()
+    .map_err(|e| io::Error::other(format!("Could not download {}", e)))?;
+  let mut file = fs::File::create(path)?;
+  io::copy(&mut response.into_reader(), &mut file)
+    .map_err(|e| io::Error::new(e.kind(), format!("Could not download {}: {}", url, e)))?;
+  Ok(())
+}
+
+fn verify(url: &str, path: &Path, expected: Option<&str>) -> io::Result<()> {
+  let expected = match expected {
+    Some(expected) => expected,
+    None => return Ok(()),
+  };
+  let actual = sha256(path)?;
+  match actual.eq_ignore_ascii_case(expected) {
+    true => Ok(()),
+    false => Err(io::Error::other(format!(
+      "The checksum of {} is {}, expected {}",
+      url, actual, expected
+    ))),
+  }
+}
@@ -0,0 +1,136 @@
+use crate::encrypted;
+use crate::journal::{timestamp, Journal};
+use crate::plan::MASK;
+use crate::service::{runtime_file, ServiceType};
+use crate::state::SessionState;
+use crate::Project;
+use serde_yaml::Value;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use subprocess::{Exec, NullFile, Redirection};
+
+/// The number of lines kept from the end of each log.
+const TAIL_LINES: usize = 200;
+
+/// Bundles the resolved config, its env values and secrets redacted, the event log, the recent
+/// output of each component and process service, the session state and version information into
+/// a gzipped tarball that can be attached to bug reports. Returns the location of the bundle.
+pub fn bundle(project: &Project, output: Option<&Path>) -> io::Result<PathBuf> {
+  let name = format!("conductor-report-{}", timestamp());
+  let staging_root = env::temp_dir();
+  let staging = staging_root.join(&name);
+  let output = match output {
+    Some(path) => env::current_dir()?.join(path),
+    None => project.root_path.join(format!("{}.tar.gz", name)),
+  };
+  let result = stage(project, &staging).and_then(|_| {
+    let status = Exec::cmd("tar")
+      .arg("-czf")
+      .arg(&output)
+      .arg("-C")
+      .arg(&staging_root)
+      .arg(&name)
+      .join()
+      .map_err(io::Error::other)?;
+    if !status.success() {
+      return Err(io::Error::other("tar could not create the archive"));
+    }
+    Ok(output)
+  });
+  let _ = fs::remove_dir_all(&staging);
+  result
+}
+
+fn stage(project: &Project, dir: &Path) -> io::Result<()> {
+  let root_path = &project.root_path;
+  let log_dir = dir.join("logs");
+  fs::create_dir_all(&log_dir)?;
+  let mut config = serde_yaml::to_value(project).map_err(io::Error::other)?;
+  encrypted::conceal_all(&mut config);
+  redact(&mut config);
+  let config = serde_yaml::to_string(&config).map_err(io::Error::other)?;
+  fs::write(dir.join("config.yml"), config)?;
+  fs::write(dir.join("versions.txt"), versions())?;
+  copy_tail(&Journal::events_path(root_path), &dir.join("events.log"))?;
+  copy_tail(
+    &Journal::json_events_path(root_path),
+    &dir.join("events.jsonl"),
+  )?;
+  copy_tail(&SessionState::path(root_path), &dir.join("state.json"))?;
+  for c in project.components.iter() {
+    let name = format!("{}.log", c.name);
+    copy_tail(
+      &Journal::log_dir(root_path).join(&name),
+      &log_dir.join(&name),
+    )?;
+  }
+  for s in project
+    .services
+    .iter()
+    .filter(|s| s.service_type == ServiceType::Process)
+  {
+    copy_tail(
+      &runtime_file(root_path, &s.name, "log"),
+      &log_dir.join(format!("service-{}.log", s.name)),
+    )?;
+  }
+  Ok(())
+}
+
+/// Masks the values of every `env` and `secrets` map in the config, keeping their names so the
+/// report still shows what is set.
+fn redact(config: &mut Value) {
+  match config {
+    Value::Mapping(mapping) => {
+      for (key, value) in mapping.iter_mut() {
+        match (key.as_str(), value) {
+          (Some("env") | Some("secrets"), Value::Mapping(vars)) => {
+            for (_, var) in vars.iter_mut() {
+              *var = Value::String(MASK.into());
+            }
+          }
+          (_, value) => redact(value),
+        }
+      }
+    }
+    Value::Sequence(values) => values.iter_mut().for_each(redact),
+    _ => {}
+  }
+}
+
+/// Copies the last lines of a file. Files that do not exist are skipped.
+fn copy_tail(from: &Path, to: &Path) -> io::Result<()> {
+  let contents = match fs::read_to_string(from) {
+    Ok(contents) => contents,
+    Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+    Err(e) => return Err(e),
+  };
+  let lines: Vec<&str> = contents.lines().collect();
+  let start = lines.len().saturating_sub(TAIL_LINES);
+  fs::write(to, lines[start..].join("\n") + "\n")
+}
+
+fn versions() -> String {
+  [
+    format!("conductor {}", env!("CARGO_PKG_VERSION")),
+    command_output("docker --version"),
+    command_output("git --version"),
+    format!("{} {}", env::consts::OS, env::consts::ARCH),
+    command_output("uname -a"),
+  ]
+  .join("\n")
+    + "\n"
+}
+
+fn command_output(cmd: &str) -> String {
+  Exec::shell(cmd)
+    .stdout(Redirection::Pipe)
+    .stderr(NullFile)
+    .capture()
+    .ok()
+    .filter(|c| c.success())
+    .map(|c| c.stdout_str().trim().to_string())
+    .unwrap_or_else(|| format!("{}: not available", cmd))
+}
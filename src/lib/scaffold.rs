@@ -0,0 +1,155 @@
+use crate::git;
+use crate::{ui, ExitCode, Message};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// A component entered while scaffolding a project.
+struct Starter {
+  name: String,
+  start: String,
+  repo: String,
+}
+
+/// Writes a starter conductor.yml into a directory. When interactive the project name and
+/// components are prompted for, otherwise an example component is written to be edited by hand.
+/// An existing config is never overwritten.
+pub fn scaffold(dir: &Path, interactive: bool) -> ExitCode {
+  let path = dir.join("conductor.yml");
+  if path.exists() {
+    ui::message(Message::ConfigExists {
+      path: path.display().to_string(),
+    });
+    return ExitCode::Failure;
+  }
+  let default_name = dir
+    .file_name()
+    .map(|n| n.to_string_lossy().to_string())
+    .unwrap_or_else(|| "MyApp".into());
+  let config = if interactive {
+    let name = ui::prompt("Project name", &default_name);
+    let mut components = vec![];
+    loop {
+      let name = ui::prompt("Component name (leave empty to finish)", "");
+      if name.is_empty() {
+        break;
+      }
+      let start = ui::prompt("Start command", "");
+      let repo = ui::prompt("Git repository (optional)", "");
+      components.push(Starter { name, start, repo });
+    }
+    render(&name, &components)
+  } else {
+    render(
+      &default_name,
+      &[Starter {
+        name: "web".into(),
+        start: "npm start".into(),
+        repo: "".into(),
+      }],
+    )
+  };
+  match fs::write(&path, config) {
+    Ok(_) => {
+      ui::message(Message::ConfigWritten {
+        path: path.display().to_string(),
+      });
+      ExitCode::Success
+    }
+    Err(e) => {
+      ui::message(Message::error(e));
+      ExitCode::Failure
+    }
+  }
+}
+
+/// Creates a project directory from a template repository. The template is cloned without its
+/// history and `{{name}}` and `{{org}}` are replaced with the project name and organization in
+/// the text files it holds.
+pub fn create(dir: &Path, template: &str, name: &str, org: &str) -> ExitCode {
+  if dir.exists() {
+    ui::message(Message::ConfigExists {
+      path: dir.display().to_string(),
+    });
+    return ExitCode::Failure;
+  }
+  let created = git::clone_repo(template, dir)
+    .and_then(|_| fs::remove_dir_all(dir.join(".git")))
+    .and_then(|_| substitute(dir, &[("{{name}}", name), ("{{org}}", org)]));
+  if let Err(e) = created {
+    ui::message(Message::error(e));
+    let _ = fs::remove_dir_all(dir);
+    return ExitCode::Failure;
+  }
+  if !["conductor.yml", "conductor.toml", "conductor.json"]
+    .iter()
+    .any(|f| dir.join(f).is_file())
+  {
+    ui::message(Message::ConfigWarning {
+      problem: format!("The template {} has no conductor.yml", template),
+    });
+  }
+  ui::message(Message::ProjectCreated {
+    path: dir.display().to_string(),
+  });
+  ExitCode::Success
+}
+
+/// Replaces placeholders in every text file under a directory. Files that are not UTF-8, such as
+/// images, are left alone. So are symlinks, which could point out of the project.
+fn substitute(dir: &Path, placeholders: &[(&str, &str)]) -> io::Result<()> {
+  for entry in fs::read_dir(dir)? {
+    let entry = entry?;
+    let file_type = entry.file_type()?;
+    let path = entry.path();
+    if file_type.is_symlink() {
+      continue;
+    }
+    if file_type.is_dir() {
+      substitute(&path, placeholders)?;
+      continue;
+    }
+    let contents = match fs::read_to_string(&path) {
+      Ok(contents) => contents,
+      Err(e) if e.kind() == io::ErrorKind::InvalidData => continue,
+      Err(e) => return Err(e),
+    };
+    let replaced = placeholders
+      .iter()
+      .fold(contents.clone(), |text, (placeholder, value)| {
+        text.replace(placeholder, value)
+      });
+    if replaced != contents {
+      fs::write(&path, replaced)?;
+    }
+  }
+  Ok(())
+}
+
+fn render(name: &str, components: &[Starter]) -> String {
+  let mut config = format!(
+    "name: {}\n\n# Environment variables shared by every component and task.\nenv: {{}}\n\n",
+    quote(name)
+  );
+  if components.is_empty() {
+    config.push_str("components: []\n");
+  } else {
+    config.push_str("# Each component runs from a directory named after it unless a path is given.\ncomponents:\n");
+  }
+  for c in components.iter() {
+    config.push_str(&format!("- name: {}\n", quote(&c.name)));
+    if c.repo.is_empty() {
+      config.push_str("  # repo: https://github.com/me/project.git\n");
+    } else {
+      config.push_str(&format!("  repo: {}\n", quote(&c.repo)));
+    }
+    config.push_str(&format!("  start: {}\n", quote(&c.start)));
+  }
+  config.push_str("\n# Commands that can be run with `conductor <task>`.\ntasks: {}\n");
+  config
+}
+
+/// Quotes a value so any characters that are special to YAML are kept as they are.
+pub(crate) fn quote(value: &str) -> String {
+  format!("'{}'", value.replace('\'', "''"))
+}
@@ -0,0 +1,303 @@
+use crate::task::ProjectTask;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// When a task runs on its own while the environment is up.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Schedule {
+  /// Runs once per interval, the first time one interval after the environment is up.
+  Every(Duration),
+  /// Runs in each minute matching a cron expression, in local time.
+  Cron(Cron),
+}
+
+impl std::str::FromStr for Schedule {
+  type Err = String;
+
+  /// Parses an interval such as `90s`, `30m`, `2h` or `1d`, or a five field cron expression such
+  /// as `*/15 9-17 * * 1-5`.
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let s = s.trim();
+    if s.split_whitespace().count() == 5 {
+      return Cron::parse(s).map(Schedule::Cron);
+    }
+    parse_interval(s)
+      .map(Schedule::Every)
+      .ok_or_else(|| format!("Invalid schedule {}", s))
+  }
+}
+
+/// Parses a positive interval such as `90s`, `30m`, `2h` or `1d`.
+pub(crate) fn parse_interval(s: &str) -> Option<Duration> {
+  let (count, unit) = match s.char_indices().last() {
+    Some((i, 's')) => (&s[..i], 1),
+    Some((i, 'm')) => (&s[..i], 60),
+    Some((i, 'h')) => (&s[..i], 3600),
+    Some((i, 'd')) => (&s[..i], 86400),
+    _ => return None,
+  };
+  match count.parse::<u64>() {
+    Ok(n) if n > 0 => n.checked_mul(unit).map(Duration::from_secs),
+    _ => None,
+  }
+}
+
+/// The minutes, hours, days of the month, months and days of the week a cron expression matches.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cron {
+  fields: [Vec<bool>; 5],
+  /// Whether the day of the month and day of the week were restricted. When both are, a day
+  /// matching either one matches, as in cron.
+  days_restricted: (bool, bool),
+}
+
+/// The lowest and highest value of each field. Sunday is both 0 and 7 in the day of the week.
+const RANGES: [(usize, usize); 5] = [(0, 59), (0, 23), (1, 31), (1, 12), (0, 7)];
+
+impl Cron {
+  fn parse(expression: &str) -> Result<Self, String> {
+    let invalid = || format!("Invalid cron expression {}", expression);
+    let mut fields: [Vec<bool>; 5] = Default::default();
+    for (i, field) in expression.split_whitespace().enumerate() {
+      let (low, high) = RANGES[i];
+      fields[i] = vec![false; high + 1];
+      for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+          Some((range, step)) => (range, step.parse::<usize>().map_err(|_| invalid())?),
+          None => (part, 1),
+        };
+        let (start, end) = match range {
+          "*" => (low, high),
+          _ => match range.split_once('-') {
+            Some((a, b)) => (
+              a.parse().map_err(|_| invalid())?,
+              b.parse().map_err(|_| invalid())?,
+            ),
+            None => {
+              let n = range.parse().map_err(|_| invalid())?;
+              // A single value with a step runs from it to the end of the range.
+              (n, if part.contains('/') { high } else { n })
+            }
+          },
+        };
+        if step == 0 || start < low || end > high || start > end {
+          return Err(invalid());
+        }
+        for n in (start..=end).step_by(step) {
+          fields[i][n] = true;
+        }
+      }
+    }
+    if fields[4][7] {
+      fields[4][0] = true;
+    }
+    let all = |i: usize| expression.split_whitespace().nth(i) == Some("*");
+    Ok(Cron {
+      fields,
+      days_restricted: (!all(2), !all(4)),
+    })
+  }
+
+  /// Returns true if the minute of the given time matches.
+  pub fn matches(&self, time: SystemTime) -> bool {
+    let [minute, hour, day, month, weekday] = match local_fields(time) {
+      Some(fields) => fields,
+      None => return false,
+    };
+    let day_matches = match self.days_restricted {
+      (true, true) => self.fields[2][day] || self.fields[4][weekday],
+      _ => self.fields[2][day] && self.fields[4][weekday],
+    };
+    self.fields[0][minute] && self.fields[1][hour] && self.fields[3][month] && day_matches
+  }
+}
+
+/// The minute, hour, day of the month, month and day of the week of a time.
+fn local_fields(time: SystemTime) -> Option<[usize; 5]> {
+  let text = crate::journal::local_timestamp("%M %H %d %m %w", time);
+  let mut fields = [0; 5];
+  let mut parts = text.split_whitespace();
+  for field in fields.iter_mut() {
+    *field = parts.next()?.parse().ok()?;
+  }
+  Some(fields)
+}
+
+/// Keeps track of when the scheduled tasks of a project are due.
+pub struct Scheduler {
+  tasks: Vec<Scheduled>,
+}
+
+struct Scheduled {
+  name: String,
+  schedule: Schedule,
+  /// When an interval task is next due, never when that is too far ahead to represent.
+  next: Option<SystemTime>,
+  /// The minute a cron task last ran in, counted from the epoch.
+  last_minute: Option<u64>,
+}
+
+impl Scheduler {
+  /// Schedules the tasks that have a schedule, counting intervals from `now`. Schedules that do
+  /// not parse are left out and returned as problems.
+  pub fn new(tasks: &HashMap<String, ProjectTask>, now: SystemTime) -> (Self, Vec<String>) {
+    let mut scheduled = vec![];
+    let mut problems = vec![];
+    let mut names: Vec<&String> = tasks.keys().collect();
+    names.sort();
+    for name in names {
+      let schedule = match &tasks[name].schedule {
+        Some(schedule) => schedule,
+        None => continue,
+      };
+      match schedule.parse::<Schedule>() {
+        Ok(schedule) => scheduled.push(Scheduled {
+          name: name.clone(),
+          next: match &schedule {
+            Schedule::Every(interval) => now.checked_add(*interval),
+            Schedule::Cron(_) => Some(now),
+          },
+          schedule,
+          last_minute: None,
+        }),
+        Err(e) => problems.push(format!("Task {}: {}", name, e)),
+      }
+    }
+    (Scheduler { tasks: scheduled }, problems)
+  }
+
+  /// The tasks that are due at `now`. A task is due once per interval, or once in each minute
+  /// its cron expression matches. Intervals missed while a task was not checked are not made up.
+  pub fn due(&mut self, now: SystemTime) -> Vec<String> {
+    let minute = now
+      .duration_since(UNIX_EPOCH)
+      .map(|d| d.as_secs() / 60)
+      .unwrap_or_default();
+    let mut due = vec![];
+    for task in self.tasks.iter_mut() {
+      let is_due = match &task.schedule {
+        Schedule::Every(interval) if task.next.is_some_and(|next| now >= next) => {
+          task.next = now.checked_add(*interval);
+          true
+        }
+        Schedule::Every(_) => false,
+        Schedule::Cron(cron) if task.last_minute != Some(minute) && cron.matches(now) => {
+          task.last_minute = Some(minute);
+          true
+        }
+        Schedule::Cron(_) => false,
+      };
+      if is_due {
+        due.push(task.name.clone());
+      }
+    }
+    due
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn cron(expression: &str) -> Cron {
+    match expression.parse::<Schedule>().unwrap() {
+      Schedule::Cron(cron) => cron,
+      schedule => panic!("Expected a cron expression, got {:?}", schedule),
+    }
+  }
+
+  fn matching(field: &[bool]) -> Vec<usize> {
+    (0..field.len()).filter(|&n| field[n]).collect()
+  }
+
+  #[test]
+  fn parses_intervals() {
+    assert_eq!(parse_interval("90s"), Some(Duration::from_secs(90)));
+    assert_eq!(parse_interval("30m"), Some(Duration::from_secs(1800)));
+    assert_eq!(parse_interval("2h"), Some(Duration::from_secs(7200)));
+    assert_eq!(parse_interval("1d"), Some(Duration::from_secs(86400)));
+    assert_eq!(parse_interval("0m"), None);
+    assert_eq!(parse_interval("5"), None);
+    assert_eq!(parse_interval("m"), None);
+  }
+
+  #[test]
+  fn rejects_intervals_that_overflow() {
+    assert_eq!(parse_interval("18446744073709551615d"), None);
+    assert_eq!(parse_interval("99999999999999999999s"), None);
+  }
+
+  #[test]
+  fn parses_cron_fields() {
+    let c = cron("*/15 9-17 1,15 * 1-5");
+    assert_eq!(matching(&c.fields[0]), vec![0, 15, 30, 45]);
+    assert_eq!(matching(&c.fields[1]), (9..=17).collect::<Vec<_>>());
+    assert_eq!(matching(&c.fields[2]), vec![1, 15]);
+    assert_eq!(matching(&c.fields[3]), (1..=12).collect::<Vec<_>>());
+    assert_eq!(matching(&c.fields[4]), vec![1, 2, 3, 4, 5]);
+    assert_eq!(c.days_restricted, (true, true));
+  }
+
+  #[test]
+  fn parses_cron_steps_from_a_value_and_sunday_as_seven() {
+    let c = cron("5/20 0 * * 7");
+    assert_eq!(matching(&c.fields[0]), vec![5, 25, 45]);
+    assert_eq!(matching(&c.fields[4]), vec![0, 7]);
+    assert_eq!(c.days_restricted, (false, true));
+  }
+
+  #[test]
+  fn rejects_invalid_cron_expressions() {
+    for expression in [
+      "60 * * * *",
+      "* 24 * * *",
+      "* * 0 * *",
+      "*/0 * * * *",
+      "5-1 * * * *",
+    ] {
+      assert!(
+        expression.parse::<Schedule>().is_err(),
+        "{} should not parse",
+        expression
+      );
+    }
+  }
+
+  #[test]
+  fn interval_tasks_are_due_once_per_interval() {
+    let mut tasks = HashMap::new();
+    tasks.insert(
+      "seed".to_string(),
+      ProjectTask {
+        schedule: Some("1m".into()),
+        ..ProjectTask::default()
+      },
+    );
+    let start = UNIX_EPOCH + Duration::from_secs(1_000_000);
+    let (mut scheduler, problems) = Scheduler::new(&tasks, start);
+    assert!(problems.is_empty());
+    assert!(scheduler.due(start + Duration::from_secs(30)).is_empty());
+    assert_eq!(scheduler.due(start + Duration::from_secs(60)), vec!["seed"]);
+    assert!(scheduler.due(start + Duration::from_secs(90)).is_empty());
+    assert_eq!(
+      scheduler.due(start + Duration::from_secs(120)),
+      vec!["seed"]
+    );
+  }
+
+  #[test]
+  fn intervals_too_long_to_represent_are_never_due() {
+    let mut tasks = HashMap::new();
+    tasks.insert(
+      "never".to_string(),
+      ProjectTask {
+        schedule: Some("18446744073709551s".into()),
+        ..ProjectTask::default()
+      },
+    );
+    let now = SystemTime::now();
+    let (mut scheduler, problems) = Scheduler::new(&tasks, now);
+    assert!(problems.is_empty());
+    assert!(scheduler.due(now + Duration::from_secs(86400)).is_empty());
+  }
+}
@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+/// How much recent output is kept in memory for each component, for the dashboard, the `:logs`
+/// command, environment reports and the HTTP API. The oldest lines are dropped once either
+/// limit is reached, so a chatty component cannot use up the machine's memory.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(default)]
+pub struct Retention {
+  /// The number of lines kept.
+  pub lines: usize,
+  /// The size the kept lines may add up to.
+  pub max_bytes: usize,
+}
+
+impl Default for Retention {
+  fn default() -> Self {
+    Retention {
+      lines: 2000,
+      max_bytes: 1024 * 1024,
+    }
+  }
+}
+
+/// Something kept in a Buffer, which knows about how much memory it takes.
+pub(crate) trait Weight {
+  fn weight(&self) -> usize;
+}
+
+impl Weight for String {
+  fn weight(&self) -> usize {
+    self.len()
+  }
+}
+
+/// Entries kept within a retention, oldest first. The newest entry is always kept, even when
+/// it is larger than the retention allows on its own.
+pub(crate) struct Buffer<T> {
+  retention: Retention,
+  entries: VecDeque<T>,
+  bytes: usize,
+}
+
+impl<T: Weight> Buffer<T> {
+  pub fn new(retention: Retention) -> Self {
+    Buffer {
+      retention,
+      entries: VecDeque::new(),
+      bytes: 0,
+    }
+  }
+
+  pub fn push(&mut self, entry: T) {
+    self.bytes += entry.weight();
+    self.entries.push_back(entry);
+    while self.entries.len() > self.retention.lines.max(1)
+      || (self.bytes > self.retention.max_bytes && self.entries.len() > 1)
+    {
+      if let Some(dropped) = self.entries.pop_front() {
+        self.bytes -= dropped.weight();
+      }
+    }
+  }
+
+  pub fn entries(&self) -> &VecDeque<T> {
+    &self.entries
+  }
+}
+
+/// The recent output of each component of a session, kept across restarts.
+pub(crate) struct Scrollback {
+  retention: Retention,
+  components: Mutex<HashMap<String, Buffer<String>>>,
+}
+
+impl Scrollback {
+  pub fn new(retention: Retention) -> Self {
+    Scrollback {
+      retention,
+      components: Mutex::new(HashMap::new()),
+    }
+  }
+
+  pub fn push(&self, component: &str, line: &str) {
+    self
+      .components
+      .lock()
+      .unwrap()
+      .entry(component.to_lowercase())
+      .or_insert_with(|| Buffer::new(self.retention.clone()))
+      .push(line.to_string());
+  }
+
+  /// The last lines of a component's output, oldest first, or every line kept without a count.
+  /// None when the component has not written anything.
+  pub fn tail(&self, component: &str, count: Option<usize>) -> Option<Vec<String>> {
+    let components = self.components.lock().unwrap();
+    let entries = components.get(&component.to_lowercase())?.entries();
+    let skip = entries.len() - count.unwrap_or(entries.len()).min(entries.len());
+    Some(entries.iter().skip(skip).cloned().collect())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn drops_the_oldest_lines_past_either_limit() {
+    let mut buffer = Buffer::new(Retention {
+      lines: 3,
+      max_bytes: 10,
+    });
+    for line in ["aa", "bb", "cc", "dd"] {
+      buffer.push(line.to_string());
+    }
+    assert_eq!(buffer.entries(), &["bb", "cc", "dd"]);
+    buffer.push("eeeeeeee".to_string());
+    assert_eq!(buffer.entries(), &["dd", "eeeeeeee"]);
+    buffer.push("a line longer than the limit".to_string());
+    assert_eq!(buffer.entries(), &["a line longer than the limit"]);
+  }
+
+  #[test]
+  fn tails_are_taken_from_the_end() {
+    let scrollback = Scrollback::new(Retention::default());
+    for line in ["one", "two", "three"] {
+      scrollback.push("Api", line);
+    }
+    assert_eq!(scrollback.tail("api", Some(2)).unwrap(), ["two", "three"]);
+    assert_eq!(scrollback.tail("api", Some(5)).unwrap().len(), 3);
+    assert_eq!(scrollback.tail("api", None).unwrap().len(), 3);
+    assert!(scrollback.tail("worker", None).is_none());
+  }
+}
@@ -1,27 +1,113 @@
+use crate::plugin;
+use crate::process::{self, Identity};
+use crate::supervisor::expand_env;
+use crate::telemetry::Span;
+use crate::{ui, Message, TerminalColor};
+use rs_docker::container::Container;
 use rs_docker::Docker;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
 use std::io;
+use std::net::{SocketAddr, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::thread;
+use std::time::{Duration, Instant};
+use subprocess::{Exec, NullFile, Redirection};
 
-/// The type of the service. Currently only Docker is supported.
-#[derive(Clone, Deserialize, PartialEq)]
+/// The type of the service. Services are either docker containers, long running local
+/// processes, or provided by a plugin named after the type, see the plugin module.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Default, Debug)]
+#[serde(from = "String", into = "String")]
 pub enum ServiceType {
+  #[default]
   DockerContainer,
+  Process,
+  Plugin(String),
 }
 
-impl Default for ServiceType {
-  fn default() -> Self {
-    ServiceType::DockerContainer
+impl From<String> for ServiceType {
+  fn from(s: String) -> Self {
+    match s.as_str() {
+      "DockerContainer" => ServiceType::DockerContainer,
+      "Process" => ServiceType::Process,
+      _ => ServiceType::Plugin(s),
+    }
   }
 }
 
-/// Services are external support systems used by the component. Currently only docker containers
-/// are supported. Support for services is also limited to MacOS and Linux platforms.
-#[derive(Clone, Deserialize, PartialEq)]
+impl From<ServiceType> for String {
+  fn from(service_type: ServiceType) -> Self {
+    match service_type {
+      ServiceType::DockerContainer => "DockerContainer".into(),
+      ServiceType::Process => "Process".into(),
+      ServiceType::Plugin(name) => name,
+    }
+  }
+}
+
+/// Services are external support systems used by the component. Services are either docker
+/// containers or local processes started from a shell command. Support for services is limited
+/// to MacOS and Linux platforms.
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
 #[serde(default)]
 pub struct Service {
   pub service_type: ServiceType,
   pub container: Option<String>,
   pub name: String,
+  /// The shell command used to launch a process service.
+  pub command: Option<String>,
+  /// The working directory of a process service, relative to the project root.
+  pub path: Option<String>,
+  pub env: HashMap<String, String>,
+  /// When enabled the container's log stream is followed and forwarded into the supervisor
+  /// output alongside the component output.
+  pub logs: bool,
+  pub color: TerminalColor,
+  /// Profiles that enable the service. Services without profiles are always enabled.
+  pub profiles: Vec<String>,
+  /// A command that succeeds once the service is ready, such as `pg_isready`. It runs inside
+  /// the container for container services and from the service's path otherwise. The service
+  /// is not considered started until it does.
+  pub ready_command: Option<String>,
+  /// Seconds to keep trying the ready command before the service fails to start.
+  pub ready_timeout_secs: u64,
+  /// Milliseconds between tries of the ready command.
+  pub ready_interval_ms: u64,
+  /// The image a container service's container is created from when it does not exist yet.
+  pub image: Option<String>,
+  /// Ports the service publishes, as given to `docker create -p`. The service is not considered
+  /// started until its TCP ports on the host accept connections.
+  pub ports: Vec<String>,
+  /// Volumes mounted in the created container, as given to `docker create -v`.
+  pub volumes: Vec<String>,
+  /// When enabled stopping a container service also removes the container and its named
+  /// volumes, so it is created fresh from its image the next time. Only what conductor created
+  /// is removed. Set by `services down --volumes`, it is not read from the config so data is
+  /// never lost by merely stopping a service.
+  #[serde(skip)]
+  pub remove_volumes: bool,
+}
+
+/// Whether a service is running, as far as conductor can tell.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ServiceStatus {
+  Running,
+  Stopped,
+  /// Plugins are not asked about their services, so whether they run is not known.
+  Unknown,
+}
+
+impl fmt::Display for ServiceStatus {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ServiceStatus::Running => write!(f, "running"),
+      ServiceStatus::Stopped => write!(f, "stopped"),
+      ServiceStatus::Unknown => write!(f, "unknown"),
+    }
+  }
 }
 
 impl Default for Service {
@@ -30,58 +116,619 @@ impl Default for Service {
       name: String::from(""),
       container: None,
       service_type: ServiceType::default(),
+      command: None,
+      path: None,
+      env: HashMap::new(),
+      logs: false,
+      color: TerminalColor::Cyan,
+      profiles: vec![],
+      ready_command: None,
+      ready_timeout_secs: 60,
+      ready_interval_ms: 1000,
+      image: None,
+      ports: vec![],
+      volumes: vec![],
+      remove_volumes: false,
     }
   }
 }
 
 impl Service {
+  /// Returns true if the service has no profiles or one of its profiles is active.
+  pub fn in_profiles(&self, profiles: &[&str]) -> bool {
+    self.profiles.is_empty()
+      || self
+        .profiles
+        .iter()
+        .any(|a| profiles.iter().any(|b| a == b))
+  }
+
   pub fn get_container_name(&self) -> String {
     self.container.as_ref().unwrap_or(&self.name).clone()
   }
-  pub fn start(&self) -> io::Result<String> {
-    start_container(&self.get_container_name())
+  pub fn start(&self, root_path: &Path) -> io::Result<String> {
+    match &self.service_type {
+      ServiceType::DockerContainer => start_container(self),
+      ServiceType::Process => start_process(self, root_path),
+      ServiceType::Plugin(name) => plugin::call(name, self, root_path, "start"),
+    }
+  }
+
+  /// Returns whether the service's container is running, or the process in its pid file is
+  /// alive.
+  pub fn status(&self, root_path: &Path) -> io::Result<ServiceStatus> {
+    let running = match &self.service_type {
+      ServiceType::DockerContainer => {
+        let name = self.get_container_name();
+        running_containers()?
+          .iter()
+          .any(|c| c.Names.iter().any(|n| n.trim_start_matches('/') == name))
+      }
+      ServiceType::Process => running_pid(&runtime_file(root_path, &self.name, "pid")).is_some(),
+      ServiceType::Plugin(_) => return Ok(ServiceStatus::Unknown),
+    };
+    Ok(match running {
+      true => ServiceStatus::Running,
+      false => ServiceStatus::Stopped,
+    })
+  }
+
+  /// Waits for the published ports to accept connections and then runs the ready command until
+  /// it succeeds, giving up once the timeout passes. Services without either are ready as soon
+  /// as they start.
+  pub fn wait_ready(&self, root_path: &Path) -> io::Result<()> {
+    let timeout = Duration::from_secs(self.ready_timeout_secs);
+    let deadline = Instant::now() + timeout;
+    for port in self.host_ports() {
+      self.wait_port(port, deadline)?;
+    }
+    let command = match &self.ready_command {
+      Some(command) => command,
+      None => return Ok(()),
+    };
+    loop {
+      let exec = match &self.service_type {
+        ServiceType::DockerContainer => {
+          Exec::cmd("docker").args(&["exec", &self.get_container_name(), "sh", "-c", command])
+        }
+        _ => Exec::shell(command)
+          .env_extend(&self.env_vars()[..])
+          .cwd(self.dir(root_path)),
+      };
+      let mut p = exec
+        .stdout(NullFile)
+        .stderr(NullFile)
+        .popen()
+        .map_err(io::Error::other)?;
+      // A try that hangs is stopped when the time is up.
+      let remaining = deadline.saturating_duration_since(Instant::now());
+      match p.wait_timeout(remaining).map_err(io::Error::other)? {
+        Some(status) if status.success() => return Ok(()),
+        Some(_) => {}
+        None => {
+          let _ = p.kill();
+          let _ = p.wait();
+        }
+      }
+      if Instant::now() >= deadline {
+        return Err(io::Error::new(
+          io::ErrorKind::TimedOut,
+          format!(
+            "{} was not ready after {}s, {} did not succeed",
+            self.name,
+            timeout.as_secs(),
+            command
+          ),
+        ));
+      }
+      thread::sleep(Duration::from_millis(self.ready_interval_ms));
+    }
+  }
+
+  /// Tries to connect to a port on the host until it accepts connections or the deadline passes.
+  fn wait_port(&self, port: u16, deadline: Instant) -> io::Result<()> {
+    let addr = SocketAddr::from(([127, 0, 0, 1], port));
+    loop {
+      let remaining = deadline.saturating_duration_since(Instant::now());
+      let attempt = remaining.clamp(Duration::from_millis(1), Duration::from_secs(1));
+      if TcpStream::connect_timeout(&addr, attempt).is_ok() {
+        return Ok(());
+      }
+      if Instant::now() >= deadline {
+        return Err(io::Error::new(
+          io::ErrorKind::TimedOut,
+          format!(
+            "{} was not accepting connections on port {} after {}s",
+            self.name, port, self.ready_timeout_secs
+          ),
+        ));
+      }
+      thread::sleep(Duration::from_millis(self.ready_interval_ms).min(remaining));
+    }
+  }
+
+  /// The TCP ports published on the host. A port is written as `container`, `host:container` or
+  /// `ip:host:container`, optionally followed by a protocol. Ranges are not waited for. Docker
+  /// picks the host port of a container port published on its own, which is read from the
+  /// running container.
+  fn host_ports(&self) -> Vec<u16> {
+    self
+      .published_ports()
+      .into_iter()
+      .map(|(_, host)| host)
+      .collect()
+  }
+
+  /// The TCP ports declared for the service as container and host port pairs. Container ports
+  /// without a host port are left out for containers, as their host port is not known up front.
+  fn declared_ports(&self) -> Vec<(u16, u16)> {
+    self
+      .ports
+      .iter()
+      .filter_map(|spec| {
+        let (spec, protocol) = spec.split_once('/').unwrap_or((spec, "tcp"));
+        if protocol != "tcp" {
+          return None;
+        }
+        let mut parts = spec.rsplit(':');
+        let container: u16 = parts.next()?.trim().parse().ok()?;
+        let host = match parts.next() {
+          Some(host) => host.trim().parse().ok()?,
+          None if self.service_type == ServiceType::DockerContainer => return None,
+          None => container,
+        };
+        Some((container, host))
+      })
+      .collect()
+  }
+
+  /// The TCP ports the service publishes as container and host port pairs. A running container
+  /// is asked for the ports it actually publishes, the declared ports are used otherwise.
+  fn published_ports(&self) -> Vec<(u16, u16)> {
+    if self.service_type != ServiceType::DockerContainer {
+      return self.declared_ports();
+    }
+    let capture = match Exec::cmd("docker")
+      .args(&["port", &self.get_container_name()])
+      .stdout(Redirection::Pipe)
+      .stderr(NullFile)
+      .capture()
+    {
+      Ok(capture) if capture.success() => capture,
+      _ => return self.declared_ports(),
+    };
+    // Lines look like `5432/tcp -> 0.0.0.0:5432`, once for each address the port is bound to.
+    let mut ports = vec![];
+    for line in capture.stdout_str().lines() {
+      let parsed = line.split_once(" -> ").and_then(|(container, host)| {
+        let container = container.strip_suffix("/tcp")?.trim().parse().ok()?;
+        let host = host.rsplit(':').next()?.trim().parse().ok()?;
+        Some((container, host))
+      });
+      if let Some(port) = parsed.filter(|p| !ports.contains(p)) {
+        ports.push(port);
+      }
+    }
+    ports
+  }
+
+  /// Env vars telling components how to reach the service, named after it such as
+  /// `SERVICE_POSTGRES_HOST`. `_PORT` is the first published port and `_PORT_<container port>`
+  /// is set for each of them.
+  pub fn connection_env(&self) -> HashMap<String, String> {
+    let prefix: String = format!("SERVICE_{}", self.name)
+      .chars()
+      .map(|c| match c.is_ascii_alphanumeric() {
+        true => c.to_ascii_uppercase(),
+        false => '_',
+      })
+      .collect();
+    let mut env = HashMap::new();
+    env.insert(format!("{}_HOST", prefix), "localhost".to_string());
+    let ports = self.published_ports();
+    if let Some((_, host)) = ports.first() {
+      env.insert(format!("{}_PORT", prefix), host.to_string());
+    }
+    for (container, host) in ports {
+      env.insert(format!("{}_PORT_{}", prefix, container), host.to_string());
+    }
+    env
+  }
+
+  /// The named volumes the container mounts. Host paths mounted into it are not volumes and are
+  /// left out, Windows paths such as `C:\data` included.
+  fn named_volumes(&self) -> Vec<String> {
+    self
+      .volumes
+      .iter()
+      .filter_map(|v| named_volume(&expand_env(v)))
+      .collect()
+  }
+
+  /// The directory a process service runs in.
+  fn dir(&self, root_path: &Path) -> PathBuf {
+    let mut path = root_path.to_path_buf();
+    if let Some(p) = &self.path {
+      path.push(expand_env(p));
+    }
+    path
+  }
+
+  /// The env a process service runs with, expanded.
+  fn env_vars(&self) -> Vec<(String, String)> {
+    self
+      .env
+      .iter()
+      .map(|(k, v)| (k.clone(), expand_env(v)))
+      .collect()
+  }
+
+  pub fn stop(&self, root_path: &Path) -> io::Result<String> {
+    match &self.service_type {
+      ServiceType::DockerContainer if self.remove_volumes => remove_container(self),
+      ServiceType::DockerContainer => stop_container(&self.get_container_name()),
+      ServiceType::Process => stop_process(self, root_path),
+      ServiceType::Plugin(name) => plugin::call(name, self, root_path, "stop"),
+    }
+  }
+  /// Returns a command that follows the log output of the service. Only new output is streamed.
+  /// For containers the command exits when the container stops.
+  pub fn follow_logs(&self, root_path: &Path) -> io::Result<Exec> {
+    let exec = match &self.service_type {
+      ServiceType::DockerContainer => Exec::cmd("docker")
+        .arg("logs")
+        .arg("--follow")
+        .arg("--tail")
+        .arg("0")
+        .arg(self.get_container_name()),
+      ServiceType::Process => Exec::cmd("tail")
+        .arg("-n")
+        .arg("0")
+        .arg("-F")
+        .arg(runtime_file(root_path, &self.name, "log")),
+      ServiceType::Plugin(name) => plugin::follow_logs(name, self, root_path)?,
+    };
+    Ok(exec.stdout(Redirection::Pipe).stderr(Redirection::Merge))
+  }
+}
+
+/// Returns the path of a runtime file for a service, such as the pid or log file of a process
+/// service or the request given to a plugin. These are kept under `.conductor/services` in the
+/// project root.
+pub(crate) fn runtime_file(root_path: &Path, name: &str, ext: &str) -> PathBuf {
+  let mut path = root_path.to_path_buf();
+  path.push(".conductor");
+  path.push("services");
+  path.push(format!("{}.{}", name, ext));
+  path
+}
+
+/// Reads the pid on the first line of a pid file.
+pub(crate) fn read_pid(pid_file: &Path) -> Option<u32> {
+  fs::read_to_string(pid_file)
+    .ok()?
+    .lines()
+    .next()?
+    .trim()
+    .parse()
+    .ok()
+}
+
+/// Reads a pid file and returns the pid if it still belongs to the process that was started. The
+/// identity of the process is recorded on the second line, pid files without one are never
+/// trusted.
+pub(crate) fn running_pid(pid_file: &Path) -> Option<(u32, Identity)> {
+  let body = fs::read_to_string(pid_file).ok()?;
+  let mut lines = body.lines();
+  let pid = lines.next()?.trim().parse().ok()?;
+  let identity: Identity = serde_json::from_str(lines.next()?).ok()?;
+  match process::is_same(pid, &identity) {
+    true => Some((pid, identity)),
+    false => None,
+  }
+}
+
+/// Starts a process service in the background. The process id is recorded in a pid file so
+/// the service can be stopped later, and its output is written to a log file. If the pid file
+/// points to the process it was written for the service is considered to be running already. The
+/// shell running the command leads its own process group, so stopping the service stops what the
+/// command started as well.
+fn start_process(service: &Service, root_path: &Path) -> io::Result<String> {
+  let pid_file = runtime_file(root_path, &service.name, "pid");
+  if let Some((pid, _)) = running_pid(&pid_file) {
+    return Ok(pid.to_string());
+  }
+  let command = service.command.clone().ok_or_else(|| {
+    io::Error::new(
+      io::ErrorKind::InvalidInput,
+      "No command specified for process service",
+    )
+  })?;
+  if let Some(dir) = pid_file.parent() {
+    fs::create_dir_all(dir)?;
+  }
+  let log = fs::OpenOptions::new()
+    .create(true)
+    .append(true)
+    .open(runtime_file(root_path, &service.name, "log"))?;
+  let child = process::shell(&command)
+    .envs(service.env_vars())
+    .current_dir(service.dir(root_path))
+    .stdout(log.try_clone()?)
+    .stderr(log)
+    .spawn()?;
+  let pid = child.id();
+  let identity = process::identity(pid)
+    .and_then(|identity| serde_json::to_string(&identity).ok())
+    .unwrap_or_default();
+  fs::write(&pid_file, format!("{}\n{}\n", pid, identity))?;
+  Ok(pid.to_string())
+}
+
+fn stop_process(service: &Service, root_path: &Path) -> io::Result<String> {
+  let pid_file = runtime_file(root_path, &service.name, "pid");
+  let pid = match read_pid(&pid_file) {
+    Some(pid) => pid,
+    None => return Ok(String::new()),
+  };
+  if running_pid(&pid_file).is_some() {
+    // Descendants that left the group are found before the shell goes away and they are
+    // reparented.
+    let descendants = process::descendants(pid);
+    process::terminate_group(pid).or_else(|_| process::terminate(pid))?;
+    for descendant in descendants {
+      let _ = process::terminate(descendant);
+    }
+  }
+  fs::remove_file(&pid_file)?;
+  Ok(pid.to_string())
+}
+
+static DOCKER_SOCKET: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Sets the socket the docker daemon is reached on, or goes back to the default socket.
+pub fn use_docker_socket(path: Option<&Path>) {
+  *DOCKER_SOCKET.write().unwrap() = path.map(Path::to_path_buf);
+}
+
+/// Connects to the docker daemon. The docker client only connects once a request is made and
+/// panics if the socket is missing, so its presence is checked up front.
+fn connect_docker() -> io::Result<Docker> {
+  let socket = DOCKER_SOCKET
+    .read()
+    .unwrap()
+    .clone()
+    .unwrap_or_else(|| PathBuf::from("/var/run/docker.sock"));
+  if !socket.exists() {
+    return Err(io::Error::new(
+      io::ErrorKind::NotFound,
+      format!("Docker socket not found at {}", socket.display()),
+    ));
+  }
+  Docker::connect(&format!("unix://{}", socket.display()))
+}
+
+/// Starts a container service. A container that does not exist yet is created first when the
+/// service has an image.
+fn start_container(service: &Service) -> io::Result<String> {
+  let name = service.get_container_name();
+  let mut docker = connect_docker()?;
+  if let Some(image) = &service.image {
+    let exists = docker
+      .get_containers(true)?
+      .iter()
+      .any(|c| c.Names.iter().any(|n| n.trim_start_matches('/') == name));
+    if !exists {
+      create_container(service, &name, image)?;
+    }
+  }
+  docker.start_container(&name)
+}
+
+/// Pulls the image and creates the container from it with the service's ports, env and volumes.
+/// Env values are handed to docker through its own environment rather than its arguments, where
+/// anyone listing processes could read them.
+fn create_container(service: &Service, name: &str, image: &str) -> io::Result<()> {
+  ui::message(Message::ContainerCreating {
+    container: name.to_string(),
+    image: image.to_string(),
+  });
+  docker_command(Exec::cmd("docker").args(&["pull", image]), "pull")?;
+  // Volumes that already exist are kept as they are, without the label.
+  for volume in service.named_volumes() {
+    let args = ["volume", "create", "--label", CREATED_LABEL, &volume];
+    docker_command(Exec::cmd("docker").args(&args), "volume create")?;
+  }
+  let mut create = vec![
+    "create".to_string(),
+    "--name".into(),
+    name.into(),
+    "--label".into(),
+    CREATED_LABEL.into(),
+  ];
+  for port in service.ports.iter() {
+    create.extend(["-p".into(), port.clone()]);
+  }
+  let env = service.env_vars();
+  for (key, _) in env.iter() {
+    create.extend(["-e".into(), key.clone()]);
   }
-  pub fn stop(&self) -> io::Result<String> {
-    stop_container(&self.get_container_name())
+  for volume in service.volumes.iter() {
+    create.extend(["-v".into(), expand_env(volume)]);
   }
+  create.push(image.into());
+  docker_command(Exec::cmd("docker").args(&create).env_extend(&env), "create")
 }
 
-fn start_container(name: &str) -> io::Result<String> {
-  let mut docker = Docker::connect("unix:///var/run/docker.sock")?;
-  docker.start_container(name)
+/// Runs a docker command, failing with what it printed to stderr if it does not succeed.
+fn docker_command(exec: Exec, name: &str) -> io::Result<()> {
+  let capture = exec
+    .stdout(Redirection::Pipe)
+    .stderr(Redirection::Pipe)
+    .capture()
+    .map_err(io::Error::other)?;
+  match capture.success() {
+    true => Ok(()),
+    false => Err(io::Error::other(format!(
+      "docker {} failed: {}",
+      name,
+      capture.stderr_str().trim()
+    ))),
+  }
 }
 
-fn stop_container(name: &str) -> io::Result<String> {
-  let mut docker = Docker::connect("unix:///var/run/docker.sock")?;
+pub(crate) fn stop_container(name: &str) -> io::Result<String> {
+  let mut docker = connect_docker()?;
   docker.stop_container(name)
 }
 
+/// Stops and removes a container along with its anonymous volumes and the named volumes the
+/// service mounts, as long as conductor created them. Containers made by hand are only stopped
+/// and whatever does not exist is left alone.
+fn remove_container(service: &Service) -> io::Result<String> {
+  let name = service.get_container_name();
+  match created_by_conductor("container", &name)? {
+    Some(true) if docker_remove(&["rm", "--force", "--volumes", &name])? => {
+      ui::message(Message::ContainerRemoved {
+        container: name.clone(),
+      });
+    }
+    Some(false) => {
+      ui::message(Message::ContainerKept {
+        container: name.clone(),
+      });
+      return stop_container(&name);
+    }
+    _ => {}
+  }
+  for volume in service.named_volumes() {
+    if created_by_conductor("volume", &volume)? == Some(true)
+      && docker_remove(&["volume", "rm", "--force", &volume])?
+    {
+      ui::message(Message::VolumeRemoved { volume });
+    }
+  }
+  Ok(name)
+}
+
+/// The label put on the containers and volumes conductor creates, so only those are removed.
+const CREATED_LABEL: &str = "dev.conductor.created=true";
+
+/// Whether a container or volume carries conductor's label, None if it does not exist.
+fn created_by_conductor(kind: &str, name: &str) -> io::Result<Option<bool>> {
+  let labels = match kind {
+    "container" => ".Config.Labels",
+    _ => ".Labels",
+  };
+  let (key, value) = CREATED_LABEL.split_once('=').unwrap_or_default();
+  let format = format!("{{{{ index {} \"{}\" }}}}", labels, key);
+  let capture = Exec::cmd("docker")
+    .args(&[kind, "inspect", "--format", &format, name])
+    .stdout(Redirection::Pipe)
+    .stderr(Redirection::Pipe)
+    .capture()
+    .map_err(io::Error::other)?;
+  match capture.success() {
+    true => Ok(Some(capture.stdout_str().trim() == value)),
+    false if capture.stderr_str().contains("No such") => Ok(None),
+    false => Err(io::Error::other(format!(
+      "docker {} inspect failed: {}",
+      kind,
+      capture.stderr_str().trim()
+    ))),
+  }
+}
+
+/// The name of the volume a mount uses, None for host paths. Docker volume names start with a
+/// letter or digit and only have letters, digits and `_.-` in them, which tells them apart from
+/// paths, Windows drive letters included.
+fn named_volume(mount: &str) -> Option<String> {
+  let bytes = mount.as_bytes();
+  let drive = bytes.len() > 2
+    && bytes[0].is_ascii_alphabetic()
+    && bytes[1] == b':'
+    && (bytes[2] == b'\\' || bytes[2] == b'/');
+  if drive {
+    return None;
+  }
+  let (source, _) = mount.split_once(':')?;
+  let named = source.starts_with(|c: char| c.is_ascii_alphanumeric())
+    && source
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || "_.-".contains(c));
+  match named {
+    true => Some(source.to_string()),
+    false => None,
+  }
+}
+
+/// Runs a docker command that removes something. Returns true if something was removed, docker
+/// prints the name of what it removed.
+fn docker_remove(args: &[&str]) -> io::Result<bool> {
+  let capture = Exec::cmd("docker")
+    .args(args)
+    .stdout(Redirection::Pipe)
+    .stderr(Redirection::Pipe)
+    .capture()
+    .map_err(io::Error::other)?;
+  let stderr = capture.stderr_str();
+  if !capture.success() && !stderr.contains("No such") {
+    return Err(io::Error::other(format!(
+      "docker {} failed: {}",
+      args[..args.len() - 1].join(" "),
+      stderr.trim()
+    )));
+  }
+  Ok(capture.success() && !capture.stdout_str().trim().is_empty())
+}
+
+/// Returns every docker container, including those that are stopped.
+pub fn all_containers() -> io::Result<Vec<Container>> {
+  let mut docker = connect_docker()?;
+  docker.get_containers(true)
+}
+
+/// Returns all currently running docker containers.
+pub fn running_containers() -> io::Result<Vec<Container>> {
+  let mut docker = connect_docker()?;
+  docker.get_containers(false)
+}
+
 pub struct ServiceLauncher {
   services: Vec<Service>,
+  root_path: PathBuf,
 }
 
 impl Iterator for ServiceLauncher {
   type Item = Result<Service, (Service, std::io::Error)>;
 
   fn next(&mut self) -> Option<Result<Service, (Service, std::io::Error)>> {
-    match self.services.pop() {
-      Some(service) => match service.start() {
-        Ok(_) => Some(Ok(service)),
-        Err(e) => Some(Err((service, e))),
-      },
-      None => None,
+    let service = self.services.pop()?;
+    let span = Span::new(format!("service {}", service.name))
+      .attribute("conductor.service", service.name.as_str());
+    let result = service
+      .start(&self.root_path)
+      .and_then(|_| service.wait_ready(&self.root_path));
+    span.end(result.is_err());
+    match result {
+      Ok(_) => Some(Ok(service)),
+      Err(e) => Some(Err((service, e))),
     }
   }
 }
 
 impl ServiceLauncher {
-  pub fn new(services: Vec<Service>) -> ServiceLauncher {
-    ServiceLauncher { services }
+  pub fn new(services: Vec<Service>, root_path: &Path) -> ServiceLauncher {
+    ServiceLauncher {
+      services,
+      root_path: root_path.to_path_buf(),
+    }
   }
 }
 
 pub struct ServiceTerminator {
   services: Vec<Service>,
+  root_path: PathBuf,
 }
 
 impl Iterator for ServiceTerminator {
@@ -89,7 +736,7 @@ impl Iterator for ServiceTerminator {
 
   fn next(&mut self) -> Option<Result<Service, (Service, std::io::Error)>> {
     match self.services.pop() {
-      Some(service) => match service.stop() {
+      Some(service) => match service.stop(&self.root_path) {
         Ok(_) => Some(Ok(service)),
         Err(e) => Some(Err((service, e))),
       },
@@ -99,7 +746,37 @@ impl Iterator for ServiceTerminator {
 }
 
 impl ServiceTerminator {
-  pub fn new(services: Vec<Service>) -> ServiceTerminator {
-    ServiceTerminator { services }
+  pub fn new(services: Vec<Service>, root_path: &Path) -> ServiceTerminator {
+    ServiceTerminator {
+      services,
+      root_path: root_path.to_path_buf(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn tells_named_volumes_from_host_paths() {
+    assert_eq!(
+      named_volume("pgdata:/var/lib/postgresql/data").unwrap(),
+      "pgdata"
+    );
+    assert_eq!(
+      named_volume("my_app.data-1:/data:ro").unwrap(),
+      "my_app.data-1"
+    );
+    for mount in [
+      "./data:/data",
+      "/srv/data:/data",
+      "~/data:/data",
+      "C:\\data:/data",
+      "d:/data:/data",
+      "/data",
+    ] {
+      assert_eq!(named_volume(mount), None, "{}", mount);
+    }
   }
 }
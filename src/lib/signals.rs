@@ -0,0 +1,73 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::unix::io::FromRawFd;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{Mutex, Once};
+use std::thread;
+
+/// The signals that shut a session down: ctrl-c, termination by a service manager or CI and the
+/// terminal going away.
+const SHUTDOWN: &[libc::c_int] = &[libc::SIGINT, libc::SIGTERM, libc::SIGHUP];
+
+type Handler = Box<dyn Fn(libc::c_int) + Send>;
+
+static HANDLER: Mutex<Option<Handler>> = Mutex::new(None);
+static INSTALL: Once = Once::new();
+/// The pipe signals are written to, only async-signal-safe calls are made in the signal handler
+/// and the registered handler is called from a thread reading the other end.
+static PIPE: AtomicI32 = AtomicI32::new(-1);
+
+/// Calls the handler with the signal whenever one of the shutdown signals is received. A later
+/// call replaces the handler.
+pub fn on_shutdown<F>(handler: F) -> io::Result<()>
+where
+  F: Fn(libc::c_int) + Send + 'static,
+{
+  *HANDLER.lock().unwrap() = Some(Box::new(handler));
+  let mut installed = Ok(());
+  INSTALL.call_once(|| installed = install());
+  installed
+}
+
+extern "C" fn notify(signal: libc::c_int) {
+  let byte = signal as u8;
+  unsafe {
+    libc::write(
+      PIPE.load(Ordering::Relaxed),
+      &byte as *const u8 as *const libc::c_void,
+      1,
+    )
+  };
+}
+
+fn install() -> io::Result<()> {
+  let mut fds = [0; 2];
+  if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+    return Err(io::Error::last_os_error());
+  }
+  for fd in fds.iter() {
+    unsafe { libc::fcntl(*fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+  }
+  PIPE.store(fds[1], Ordering::Relaxed);
+  let mut signals = unsafe { File::from_raw_fd(fds[0]) };
+  thread::spawn(move || {
+    let mut signal = [0u8; 1];
+    while let Ok(1) = signals.read(&mut signal) {
+      if let Some(handler) = HANDLER.lock().unwrap().as_ref() {
+        handler(signal[0] as libc::c_int);
+      }
+    }
+  });
+  for signal in SHUTDOWN.iter() {
+    unsafe {
+      let mut action: libc::sigaction = std::mem::zeroed();
+      action.sa_sigaction = notify as *const () as libc::sighandler_t;
+      action.sa_flags = libc::SA_RESTART;
+      libc::sigemptyset(&mut action.sa_mask);
+      if libc::sigaction(*signal, &action, std::ptr::null_mut()) != 0 {
+        return Err(io::Error::last_os_error());
+      }
+    }
+  }
+  Ok(())
+}
@@ -0,0 +1,281 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use subprocess::{Exec, Redirection};
+
+/// An archive a component's path is unpacked from, for code that does not live in git such as
+/// vendored SDKs and tools.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug, Default)]
+#[serde(default)]
+pub struct Source {
+  /// The archive to download, a tarball compressed with anything tar understands or a zip.
+  pub url: String,
+  /// The SHA-256 checksum of the archive. Archives that do not match are not unpacked.
+  pub sha256: Option<String>,
+  /// Leading directories to remove from the paths in the archive, as with tar's
+  /// `--strip-components`. Archives with everything in one top level directory need 1.
+  pub strip_components: u32,
+}
+
+impl Source {
+  /// Identifies what is unpacked, so a changed source can be told apart from the one a path was
+  /// unpacked from.
+  pub fn fingerprint(&self) -> String {
+    format!(
+      "{} {} {}",
+      self.url,
+      self.sha256.as_deref().unwrap_or("-"),
+      self.strip_components
+    )
+  }
+
+  /// Downloads the archive and unpacks it into dest, replacing what is there. Nothing is
+  /// changed unless the download, the checksum and unpacking all succeed.
+  pub fn fetch(&self, dest: &Path) -> io::Result<()> {
+    let parent = dest.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(parent)?;
+    let name = dest.file_name().unwrap_or_default().to_string_lossy();
+    let work = parent.join(format!(".{}.download-{}", name, std::process::id()));
+    fs::create_dir_all(&work)?;
+    let result = self.unpack_into(&work).and_then(|unpacked| {
+      if dest.exists() {
+        fs::remove_dir_all(dest)?;
+      }
+      fs::rename(unpacked, dest)
+    });
+    let _ = fs::remove_dir_all(&work);
+    result
+  }
+
+  /// Downloads, verifies and unpacks the archive in a working directory, returning the directory
+  /// holding its contents.
+  fn unpack_into(&self, work: &Path) -> io::Result<PathBuf> {
+    let zip = self
+      .url
+      .split(['?', '#'])
+      .next()
+      .unwrap_or_default()
+      .ends_with(".zip");
+    let archive = work.join(if zip { "archive.zip" } else { "archive" });
+    run(
+      Exec::cmd("curl")
+        .args(&["-fsSL", "-o"])
+        .arg(&archive)
+        .arg(&self.url),
+      &format!("Could not download {}", self.url),
+    )?;
+    if let Some(expected) = &self.sha256 {
+      let actual = sha256(&archive)?;
+      if !actual.eq_ignore_ascii_case(expected.trim()) {
+        return Err(io::Error::other(format!(
+          "The checksum of {} is {}, expected {}",
+          self.url, actual, expected
+        )));
+      }
+    }
+    let unpacked = work.join("unpacked");
+    fs::create_dir_all(&unpacked)?;
+    if !zip {
+      run(
+        Exec::cmd("tar")
+          .arg("-xf")
+          .arg(&archive)
+          .arg("-C")
+          .arg(&unpacked)
+          .arg(format!("--strip-components={}", self.strip_components)),
+        &format!("Could not unpack {}", self.url),
+      )?;
+      return Ok(unpacked);
+    }
+    run(
+      Exec::cmd("unzip")
+        .arg("-q")
+        .arg(&archive)
+        .arg("-d")
+        .arg(&unpacked),
+      &format!("Could not unpack {}", self.url),
+    )?;
+    // unzip can not strip directories, so the single directory at each level is descended into.
+    let mut dir = unpacked;
+    for _ in 0..self.strip_components {
+      let entries: Vec<PathBuf> = fs::read_dir(&dir)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .collect();
+      dir = match entries.as_slice() {
+        [only] if only.is_dir() => only.clone(),
+        _ => {
+          return Err(io::Error::other(format!(
+            "{} does not have a single directory to strip",
+            self.url
+          )))
+        }
+      };
+    }
+    Ok(dir)
+  }
+}
+
+fn run(exec: Exec, problem: &str) -> io::Result<()> {
+  let capture = exec
+    .stdout(Redirection::Pipe)
+    .stderr(Redirection::Pipe)
+    .capture()
+    .map_err(|e| io::Error::other(format!("{}: {}", problem, e)))?;
+  match capture.success() {
+    true => Ok(()),
+    false => Err(io::Error::other(format!(
+      "{}: {}",
+      problem,
+      capture.stderr_str().trim()
+    ))),
+  }
+}
+
+/// The SHA-256 checksum of a file, in hex.
+pub(crate) fn sha256(path: &Path) -> io::Result<String> {
+  let mut file = fs::File::open(path)?;
+  let mut context = ring::digest::Context::new(&ring::digest::SHA256);
+  let mut buffer = [0; 64 * 1024];
+  loop {
+    match file.read(&mut buffer)? {
+      0 => break,
+      read => context.update(&buffer[..read]),
+    }
+  }
+  let digest = context.finish();
+  Ok(
+    digest
+      .as_ref()
+      .iter()
+      .map(|b| format!("{:02x}", b))
+      .collect(),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::env;
+
+  /// A working directory for a test with an sdk-1.0 directory to archive, removed when dropped.
+  struct Scratch(PathBuf);
+
+  impl Scratch {
+    fn new(name: &str) -> Scratch {
+      let dir = env::temp_dir().join(format!("conductor-source-{}-{}", name, std::process::id()));
+      let _ = fs::remove_dir_all(&dir);
+      fs::create_dir_all(dir.join("sdk-1.0/bin")).unwrap();
+      fs::write(dir.join("sdk-1.0/bin/tool"), "tool").unwrap();
+      Scratch(dir)
+    }
+  }
+
+  impl Drop for Scratch {
+    fn drop(&mut self) {
+      let _ = fs::remove_dir_all(&self.0);
+    }
+  }
+
+  fn archive(dir: &Path, exec: Exec) -> String {
+    assert!(exec.cwd(dir).join().unwrap().success());
+    let name = fs::read_dir(dir)
+      .unwrap()
+      .filter_map(|e| e.ok())
+      .map(|e| e.file_name().to_string_lossy().to_string())
+      .find(|name| name.starts_with("sdk."))
+      .unwrap();
+    format!("file://{}", dir.join(name).display())
+  }
+
+  #[test]
+  fn checksums_files() {
+    let scratch = Scratch::new("checksum");
+    let empty = scratch.0.join("empty");
+    fs::write(&empty, "").unwrap();
+    assert_eq!(
+      sha256(&empty).unwrap(),
+      "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+    );
+  }
+
+  #[test]
+  fn unpacks_tarballs_stripping_directories() {
+    let scratch = Scratch::new("tar");
+    let url = archive(
+      &scratch.0,
+      Exec::cmd("tar").args(&["-czf", "sdk.tar.gz", "sdk-1.0"]),
+    );
+    let dest = scratch.0.join("vendor/sdk");
+    let source = Source {
+      url,
+      sha256: Some(
+        sha256(&scratch.0.join("sdk.tar.gz"))
+          .unwrap()
+          .to_uppercase(),
+      ),
+      strip_components: 1,
+    };
+    source.fetch(&dest).unwrap();
+    assert_eq!(fs::read_to_string(dest.join("bin/tool")).unwrap(), "tool");
+    // Nothing but the unpacked archive is left behind.
+    assert_eq!(fs::read_dir(scratch.0.join("vendor")).unwrap().count(), 1);
+  }
+
+  #[test]
+  fn unpacks_zips_stripping_directories() {
+    let scratch = Scratch::new("zip");
+    let url = archive(
+      &scratch.0,
+      Exec::cmd("zip").args(&["-qr", "sdk.zip", "sdk-1.0"]),
+    );
+    let dest = scratch.0.join("sdk");
+    let source = Source {
+      url,
+      sha256: None,
+      strip_components: 1,
+    };
+    source.fetch(&dest).unwrap();
+    assert_eq!(fs::read_to_string(dest.join("bin/tool")).unwrap(), "tool");
+    let too_deep = Source {
+      strip_components: 3,
+      ..source
+    };
+    assert!(too_deep.fetch(&scratch.0.join("deep")).is_err());
+  }
+
+  #[test]
+  fn keeps_the_path_when_the_checksum_differs() {
+    let scratch = Scratch::new("mismatch");
+    let url = archive(
+      &scratch.0,
+      Exec::cmd("tar").args(&["-czf", "sdk.tar.gz", "sdk-1.0"]),
+    );
+    let dest = scratch.0.join("sdk");
+    fs::create_dir_all(&dest).unwrap();
+    fs::write(dest.join("old"), "old").unwrap();
+    let source = Source {
+      url,
+      sha256: Some("0".repeat(64)),
+      strip_components: 0,
+    };
+    let error = source.fetch(&dest).unwrap_err();
+    assert!(error.to_string().contains("expected 0000"), "{}", error);
+    assert_eq!(fs::read_to_string(dest.join("old")).unwrap(), "old");
+    assert!(!dest.join("sdk-1.0").exists());
+  }
+
+  #[test]
+  fn fingerprints_every_setting() {
+    let source = Source {
+      url: "https://example.com/sdk.tar.gz".into(),
+      sha256: None,
+      strip_components: 0,
+    };
+    let stripped = Source {
+      strip_components: 1,
+      ..source.clone()
+    };
+    assert_ne!(source.fingerprint(), stripped.fingerprint());
+  }
+}
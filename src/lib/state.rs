@@ -0,0 +1,140 @@
+use crate::process::{self, Identity, Usage};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The lifecycle state of a component as reported in the state file.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ComponentStatus {
+  Starting,
+  Running,
+  Stopped,
+  Failed,
+}
+
+impl fmt::Display for ComponentStatus {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ComponentStatus::Starting => write!(f, "starting"),
+      ComponentStatus::Running => write!(f, "running"),
+      ComponentStatus::Stopped => write!(f, "stopped"),
+      ComponentStatus::Failed => write!(f, "failed"),
+    }
+  }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ComponentState {
+  pub name: String,
+  pub status: ComponentStatus,
+  pub ready: bool,
+  pub pid: Option<u32>,
+  pub ports: Vec<u16>,
+  /// The exit code of the component's process once it has exited on its own.
+  #[serde(default)]
+  pub exit_code: Option<i32>,
+  /// When the component's process was started, in seconds since the unix epoch.
+  #[serde(default)]
+  pub started: Option<u64>,
+  /// The number of times the component has been restarted during the session.
+  #[serde(default)]
+  pub restarts: usize,
+  /// The services the component uses.
+  #[serde(default)]
+  pub services: Vec<String>,
+  /// The CPU and memory used by the component's processes when they were last measured.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub usage: Option<Usage>,
+  /// Whether the component's processes are frozen with `conductor pause`.
+  #[serde(default)]
+  pub paused: bool,
+  /// The start time and command line of the process, to check the pid still belongs to it.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub process: Option<Identity>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ServiceState {
+  pub name: String,
+  pub container: String,
+}
+
+/// A snapshot of a running session. This is written to `.conductor/state.json` in the project
+/// root whenever a component or service changes state so external tools can inspect the session
+/// without talking to the supervisor directly.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct SessionState {
+  pub project: String,
+  pub pid: u32,
+  /// The start time and command line of the session's own process.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub process: Option<Identity>,
+  pub components: Vec<ComponentState>,
+  pub services: Vec<ServiceState>,
+}
+
+impl ComponentState {
+  /// The status as shown to people, paused when a running component is paused.
+  pub fn describe(&self) -> String {
+    match self.paused && self.status == ComponentStatus::Running {
+      true => "paused".into(),
+      false => self.status.to_string(),
+    }
+  }
+
+  /// Seconds since the component was started, if it is running.
+  pub fn uptime(&self, now: SystemTime) -> Option<u64> {
+    let now = now.duration_since(UNIX_EPOCH).ok()?.as_secs();
+    match self.status {
+      ComponentStatus::Running => self.started.map(|s| now.saturating_sub(s)),
+      _ => None,
+    }
+  }
+}
+
+impl SessionState {
+  /// The location of the state file for a project rooted at the given path.
+  pub fn path(root_path: &Path) -> PathBuf {
+    let mut path = root_path.to_path_buf();
+    path.push(".conductor");
+    path.push("state.json");
+    path
+  }
+
+  /// Writes the state file. The file is written to a temporary location first and then moved
+  /// into place so readers never observe a partially written file.
+  pub fn write(&self, root_path: &Path) -> io::Result<()> {
+    let path = SessionState::path(root_path);
+    if let Some(dir) = path.parent() {
+      fs::create_dir_all(dir)?;
+    }
+    let tmp_path = path.with_extension("json.tmp");
+    let body = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+    fs::write(&tmp_path, body)?;
+    fs::rename(&tmp_path, &path)
+  }
+
+  /// Returns true if the session that wrote the state is still running. A state file that does
+  /// not record the session's identity is assumed to be live while its pid is.
+  pub fn is_live(&self) -> bool {
+    match &self.process {
+      Some(identity) => process::is_same(self.pid, identity),
+      None => process::is_alive(self.pid),
+    }
+  }
+
+  /// Reads the state file of a previous or currently running session.
+  pub fn read(root_path: &Path) -> io::Result<SessionState> {
+    let body = fs::read_to_string(SessionState::path(root_path))?;
+    serde_json::from_str(&body).map_err(io::Error::other)
+  }
+
+  /// Removes the state file once the session has ended.
+  pub fn remove(root_path: &Path) -> io::Result<()> {
+    fs::remove_file(SessionState::path(root_path))
+  }
+}
@@ -1,272 +1,2051 @@
+use crate::cancel::{CancellationToken, Completion};
+use crate::chaos::Chaos;
+use crate::command::{Command, USAGE};
+use crate::control::{self, Request};
+#[cfg(unix)]
+use crate::dashboard::Dashboard;
+use crate::env::{forget_env_commands, read_env_file, resolve_env_commands, resolve_secrets};
+use crate::expand::{self, CallSite, Functions};
+use crate::http;
+use crate::journal::Journal;
+#[cfg(unix)]
+use crate::keys;
+use crate::limits::Confinement;
+use crate::lines::{self, read_lines};
+use crate::metrics::Metrics;
+use crate::notify::NotificationEvent;
+use crate::pattern::Pattern;
+use crate::process::{self, Usage, UsageMonitor};
+use crate::proxy;
+use crate::reload::{self, ConfigWatch};
+use crate::schedule::Scheduler;
+use crate::scrollback::{Retention, Scrollback};
+use crate::service::{ServiceLauncher, ServiceTerminator};
+#[cfg(unix)]
+use crate::signals;
+use crate::state::{ComponentState, ComponentStatus, ServiceState, SessionState};
 use crate::task::Task;
-use crate::{ui, Component, Project};
-use crossbeam::channel::{after, unbounded, Receiver, Select, Sender};
+use crate::telemetry::Span;
+use crate::{ui, Component, ExitCode, Message, Overflow, Project, Service};
+use crossbeam::channel::{
+  after, bounded, never, unbounded, Receiver, RecvTimeoutError, Select, Sender,
+};
 use log::{debug, info, warn};
-use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::File;
+use std::io;
 use std::io::prelude::*;
-use std::io::BufReader;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, OnceLock, RwLock, RwLockReadGuard};
 use std::thread;
-use std::time::Duration;
-use subprocess::{Exec, Popen, Redirection};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use subprocess::{Exec, Redirection};
 
-struct ReadOutAdapter(Arc<Mutex<Popen>>);
+/// Tuning options for the supervisor's event loop. The defaults are suitable for most projects,
+/// projects with a large number of components may want to raise the intervals to reduce idle
+/// load.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(default)]
+pub struct Tuning {
+  /// How long the event loop waits for output before checking for completed workers.
+  pub select_timeout_ms: u64,
+  /// How often a running component's process was checked for exit. Processes are waited on
+  /// without polling now, the setting is accepted and ignored.
+  #[deprecated(note = "component processes are no longer polled for exit")]
+  pub wait_interval_ms: u64,
+  /// How long a stopped component's processes get to exit after being asked to, before they
+  /// are killed.
+  pub stop_grace_ms: u64,
+  /// The buffer size used when reading component output.
+  pub read_buffer_size: usize,
+  /// The number of recent output lines shown for each component in environment reports.
+  pub log_tail_lines: usize,
+  /// The size a component's log under `.conductor/logs` grows to before it is rotated.
+  pub log_max_bytes: u64,
+  /// How much recent output is kept in memory for each component.
+  pub scrollback: Retention,
+}
+
+impl Default for Tuning {
+  #[allow(deprecated)]
+  fn default() -> Self {
+    Tuning {
+      select_timeout_ms: 500,
+      wait_interval_ms: 200,
+      stop_grace_ms: 5000,
+      read_buffer_size: 8 * 1024,
+      log_tail_lines: 50,
+      log_max_bytes: 5 * 1024 * 1024,
+      scrollback: Retention::default(),
+    }
+  }
+}
 
-impl Read for ReadOutAdapter {
-  fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-    self.0.lock().unwrap().stdout.as_mut().unwrap().read(buf)
+impl Tuning {
+  pub fn select_timeout(&self) -> Duration {
+    Duration::from_millis(self.select_timeout_ms)
+  }
+  #[deprecated(note = "component processes are no longer polled for exit")]
+  #[allow(deprecated)]
+  pub fn wait_interval(&self) -> Duration {
+    Duration::from_millis(self.wait_interval_ms)
   }
+  pub fn stop_grace(&self) -> Duration {
+    Duration::from_millis(self.stop_grace_ms)
+  }
+}
+
+/// Something that happened in a session, as delivered to subscribers of Supervisor::subscribe.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SessionEvent {
+  /// An event from a component or one of its services.
+  Component(Box<ComponentEvent>),
+  /// A message about the session, such as the environment coming up. These are the messages
+  /// printed to the console.
+  Message(Message),
+}
+
+/// Options for a single invocation that control how the supervisor runs.
+#[derive(Clone, Default, Debug)]
+pub struct RunOptions {
+  /// Hold back component output until every component is ready, then announce that the
+  /// environment is up.
+  pub wait: bool,
+  /// Read `:` prefixed commands from stdin while running, see Command.
+  pub interactive: bool,
+  /// Forwards lines read from stdin to the running components named, or to every running
+  /// component when none are. Lines starting with a `:` are still read as commands.
+  pub broadcast: Option<Vec<String>>,
+  /// Show a full screen dashboard instead of printing output.
+  pub tui: bool,
+  /// Launch components even if that exceeds the guardrails.
+  pub force: bool,
+  /// A directory each component's output is written to with timestamps, as `<component>.log`.
+  pub log_dir: Option<PathBuf>,
+  /// Components and services whose output is hidden from the start, as with `:mute`.
+  pub mute: Vec<String>,
+  /// Components and services whose output is the only output shown, as with `:filter`.
+  pub only: Vec<String>,
+  /// Shows only lines of output matching a pattern, as with `:grep`.
+  pub filter: Option<Pattern>,
+  /// Stop the session as soon as a component that is not retried exits with an error.
+  pub fail_fast: bool,
+  /// Stop the session once every component that is not kept alive or retried has finished and
+  /// print a summary of how they went.
+  pub ci: bool,
+  /// An address to serve the session's status on over HTTP, such as `127.0.0.1:3939`.
+  pub listen: Option<String>,
+  /// Prints the CPU and memory used by each component every this many seconds.
+  pub stats: Option<u64>,
+  /// Leaves the console and signals alone so an application embedding the supervisor can show
+  /// the session itself, using the events from Supervisor::subscribe, and end it with the
+  /// shutdown token.
+  pub headless: bool,
+  /// Starts components without running their build commands first.
+  pub no_build: bool,
+  /// Prints what would be run instead of running it.
+  pub dry_run: bool,
+  /// Environment variables given on the command line. They take precedence over the env of
+  /// components, groups and tasks.
+  pub env: HashMap<String, String>,
+  /// A config file that is reloaded when it changes, updating the running components.
+  pub watch: Option<ConfigWatch>,
+  /// A file every event of the session is written to, to be played back with `conductor replay`.
+  pub record: Option<PathBuf>,
+  /// Kills running components at random once the environment is up.
+  pub chaos: Option<Chaos>,
+}
+
+/// The state of a component along with its most recent output, used to explain what the
+/// environment looked like when something went wrong.
+pub struct ComponentReport {
+  pub component: Component,
+  pub state: ComponentState,
+  pub output: Vec<String>,
 }
 
 /// Supervisor controls the exection of tasks and components. It handles launching them,
 /// tracking them, relaunching them on failure, and managing all the reading threads.
 pub struct Supervisor {
   workers: Arc<Mutex<Vec<Worker>>>,
-  project: Project,
+  project: RwLock<Project>,
+  service_sender: Sender<ComponentEvent>,
+  service_receiver: Receiver<ComponentEvent>,
+  followed_services: Arc<Mutex<HashSet<String>>>,
+  shutdown_token: CancellationToken,
+  group_tokens: Mutex<HashMap<String, CancellationToken>>,
+  service_usage: Mutex<HashMap<String, usize>>,
+  options: RunOptions,
+  up: AtomicBool,
+  up_waiters: Mutex<Vec<Sender<()>>>,
+  journal: Journal,
+  outcome: Mutex<ExitCode>,
+  interrupted: Arc<AtomicBool>,
+  restarts: Mutex<HashMap<String, Component>>,
+  /// Components, in lowercase, that were restarted and restart their dependents once ready.
+  cascades: Mutex<HashSet<String>>,
+  muted: Mutex<HashSet<String>>,
+  /// The only component or service whose output is shown, in lowercase.
+  filter: Mutex<Vec<String>>,
+  pattern: Mutex<Option<Pattern>>,
+  metrics: Arc<Metrics>,
+  /// When each retried component recently failed, used to detect crash loops.
+  failures: Mutex<HashMap<String, VecDeque<Instant>>>,
+  subscribers: Mutex<Vec<Sender<SessionEvent>>>,
+  /// Where commands are sent while Supervisor::init is running.
+  commands: Mutex<Option<Sender<Request>>>,
+  /// The ports picked for the auto ports of each component by name, in lowercase. A component
+  /// keeps them when it is restarted.
+  allocated_ports: Mutex<HashMap<String, Vec<(String, u16)>>>,
+  /// The functions for the config strings of each component by name, in lowercase. Each launch
+  /// gets its own from them, keeping the ports picked by `${free_port()}`.
+  functions: Mutex<HashMap<String, Functions>>,
+  /// The span of each component's current run by name, in lowercase, for the invocation's trace.
+  spans: Mutex<HashMap<String, Span>>,
+  scrollback: Arc<Scrollback>,
+  /// Measures the usage of the running components, started the first time it is needed.
+  usage_monitor: OnceLock<UsageMonitor>,
 }
 
 impl Supervisor {
   /// Sets up a new supervisor instance.
   pub fn new(project: &Project) -> Self {
+    Supervisor::with_options(project, RunOptions::default())
+  }
+
+  /// Sets up a new supervisor instance with options for the invocation.
+  pub fn with_options(project: &Project, options: RunOptions) -> Self {
+    let (service_sender, service_receiver) = unbounded();
+    let muted = project
+      .components
+      .iter()
+      .filter(|c| c.quiet)
+      .map(|c| c.name.to_lowercase())
+      .chain(options.mute.iter().map(|n| n.to_lowercase()))
+      .collect();
+    let filter = options.only.iter().map(|n| n.to_lowercase()).collect();
+    let pattern = options.filter.clone();
+    let journal = Journal::new(&project.root_path, project.tuning.log_max_bytes);
+    if let Some(path) = &options.record {
+      if let Err(e) = journal.record(path) {
+        ui::message(Message::RecordingFailed {
+          path: path.display().to_string(),
+          error: e.to_string(),
+        });
+      }
+    }
     Supervisor {
       workers: Arc::new(Mutex::new(vec![])),
-      project: project.clone(),
+      project: RwLock::new(project.clone()),
+      service_sender,
+      service_receiver,
+      followed_services: Arc::new(Mutex::new(HashSet::new())),
+      shutdown_token: CancellationToken::new(),
+      group_tokens: Mutex::new(HashMap::new()),
+      service_usage: Mutex::new(HashMap::new()),
+      options,
+      up: AtomicBool::new(false),
+      up_waiters: Mutex::new(vec![]),
+      journal,
+      outcome: Mutex::new(ExitCode::Success),
+      interrupted: Arc::new(AtomicBool::new(false)),
+      restarts: Mutex::new(HashMap::new()),
+      cascades: Mutex::new(HashSet::new()),
+      muted: Mutex::new(muted),
+      filter: Mutex::new(filter),
+      pattern: Mutex::new(pattern),
+      metrics: Arc::new(Metrics::default()),
+      failures: Mutex::new(HashMap::new()),
+      subscribers: Mutex::new(vec![]),
+      commands: Mutex::new(None),
+      allocated_ports: Mutex::new(HashMap::new()),
+      functions: Mutex::new(HashMap::new()),
+      spans: Mutex::new(HashMap::new()),
+      scrollback: Arc::new(Scrollback::new(project.tuning.scrollback.clone())),
+      usage_monitor: OnceLock::new(),
+    }
+  }
+
+  /// Runs the supervisor on its own thread and returns a handle for controlling it, for tests and
+  /// applications that drive the session from code.
+  pub fn start(self) -> SupervisorHandle {
+    let supervisor = Arc::new(self);
+    let running = Arc::clone(&supervisor);
+    SupervisorHandle {
+      supervisor,
+      thread: thread::spawn(move || running.init()),
+    }
+  }
+
+  /// Returns a receiver that gets every event of the session from now on, for applications that
+  /// show the session themselves. Dropping the receiver unsubscribes.
+  pub fn subscribe(&self) -> Receiver<SessionEvent> {
+    let (tx, rx) = unbounded();
+    self.subscribers.lock().unwrap().push(tx);
+    rx
+  }
+
+  /// Sends an event to the subscribers, dropping those that have gone away.
+  fn publish(&self, event: SessionEvent) {
+    self
+      .subscribers
+      .lock()
+      .unwrap()
+      .retain(|s| s.send(event.clone()).is_ok());
+  }
+
+  /// Returns true if the session is shown on the console.
+  fn console(&self) -> bool {
+    !self.options.headless
+  }
+
+  /// The project being run. It is replaced when the config file changes during the session.
+  fn project(&self) -> RwLockReadGuard<'_, Project> {
+    self.project.read().unwrap()
+  }
+
+  /// Returns the root cancellation token. Cancelling it shuts down every worker and causes
+  /// Supervisor::init to return.
+  pub fn shutdown_token(&self) -> CancellationToken {
+    self.shutdown_token.clone()
+  }
+
+  /// Returns the cancellation token for a group. Components spawned as part of the group use a
+  /// child of this token.
+  pub fn group_token(&self, group_name: &str) -> CancellationToken {
+    self
+      .group_tokens
+      .lock()
+      .unwrap()
+      .entry(group_name.to_lowercase())
+      .or_insert_with(|| self.shutdown_token.child())
+      .clone()
+  }
+
+  /// Returns a receiver that gets a message once every component is ready. The receiver is
+  /// disconnected if Supervisor::init returns before that happens.
+  pub fn environment_up(&self) -> Receiver<()> {
+    let (tx, rx) = bounded(1);
+    let mut waiters = self.up_waiters.lock().unwrap();
+    if self.up.load(Ordering::SeqCst) {
+      let _ = tx.send(());
+    } else {
+      waiters.push(tx);
+    }
+    rx
+  }
+
+  /// Reports the state and recent output of every component. Workers for a component that has
+  /// been relaunched are superseded by the most recent worker for that component.
+  pub fn report(&self) -> Vec<ComponentReport> {
+    let tail = self.project().tuning.log_tail_lines;
+    let workers = self.workers.lock().unwrap();
+    let mut reports: Vec<ComponentReport> = vec![];
+    for w in workers.iter() {
+      let report = ComponentReport {
+        component: w.component.clone(),
+        state: w.state(),
+        output: self
+          .scrollback
+          .tail(&w.component.name, Some(tail))
+          .unwrap_or_default(),
+      };
+      match reports
+        .iter_mut()
+        .find(|r| r.component.name == report.component.name)
+      {
+        Some(r) => *r = report,
+        None => reports.push(report),
+      }
+    }
+    reports
+  }
+
+  /// Requests shutdown of all running workers for a component. The returned completion resolves
+  /// once they have exited.
+  pub fn cancel_component(&self, name: &str) -> Completion {
+    let workers = self.workers.lock().unwrap();
+    let pending = workers
+      .iter()
+      .filter(|w| !w.completed && w.component.name.to_lowercase() == name.to_lowercase())
+      .map(|w| {
+        w.token.cancel();
+        w.done.clone()
+      })
+      .collect();
+    Completion::new(pending)
+  }
+
+  /// Requests shutdown of all running workers spawned as part of a group.
+  pub fn cancel_group(&self, group_name: &str) -> Completion {
+    self.group_token(group_name).cancel();
+    let workers = self.workers.lock().unwrap();
+    let pending = workers
+      .iter()
+      .filter(|w| !w.completed && w.group.as_deref() == Some(&group_name.to_lowercase()))
+      .map(|w| w.done.clone())
+      .collect();
+    Completion::new(pending)
+  }
+
+  /// Requests shutdown of every worker.
+  pub fn cancel_all(&self) -> Completion {
+    self.shutdown_token.cancel();
+    let workers = self.workers.lock().unwrap();
+    let pending = workers
+      .iter()
+      .filter(|w| !w.completed)
+      .map(|w| w.done.clone())
+      .collect();
+    Completion::new(pending)
+  }
+
+  /// The exit code describing how the session went. Failures are recorded as they happen and the
+  /// most significant one is reported.
+  pub fn exit_code(&self) -> ExitCode {
+    if self.interrupted.load(Ordering::SeqCst) {
+      return ExitCode::Interrupted;
+    }
+    *self.outcome.lock().unwrap()
+  }
+
+  /// Carries out a command against the running session. Commands that fail return a message
+  /// explaining why.
+  pub fn execute(&self, command: Command) -> Result<(), String> {
+    match command {
+      Command::Start(name) => {
+        let component = self.component_by_name(&name)?;
+        if self.is_running(&component.name) {
+          return Err(format!("Component {} is already running", component.name));
+        }
+        self.spawn_component(&component, HashMap::new());
+      }
+      Command::Input(name, text) => {
+        let component = self.component_by_name(&name)?;
+        let workers = self.workers.lock().unwrap();
+        let worker = workers
+          .iter()
+          .find(|w| !w.completed && w.component.name == component.name)
+          .ok_or_else(|| format!("Component {} is not running", component.name))?;
+        worker
+          .input
+          .send(text)
+          .map_err(|_| format!("Component {} is not reading input", component.name))?;
+      }
+      Command::Broadcast(names, text) => {
+        let names = names
+          .iter()
+          .map(|name| self.component_by_name(name).map(|c| c.name))
+          .collect::<Result<Vec<_>, _>>()?;
+        let workers = self.workers.lock().unwrap();
+        let sent = workers
+          .iter()
+          .filter(|w| !w.completed)
+          .filter(|w| names.is_empty() || names.contains(&w.component.name))
+          .filter(|w| w.input.send(format!("{}\n", text)).is_ok())
+          .count();
+        if sent == 0 {
+          return Err("No running component is reading input".into());
+        }
+      }
+      Command::Stop(name) => {
+        let component = self.component_by_name(&name)?;
+        if !self.is_running(&component.name) {
+          return Err(format!("Component {} is not running", component.name));
+        }
+        self.cancel_component(&component.name);
+      }
+      Command::Restart(name) => {
+        let component = self.component_by_name(&name)?;
+        self.restart(component);
+      }
+      Command::Pause(name) => {
+        let component = self.component_by_name(&name)?;
+        self.pause(&component.name, true)?;
+      }
+      Command::Resume(name) => {
+        let component = self.component_by_name(&name)?;
+        self.pause(&component.name, false)?;
+      }
+      Command::Replace(component) => {
+        // Later restarts of the component keep its new definition.
+        let mut project = self.project.write().unwrap();
+        match project
+          .components
+          .iter_mut()
+          .find(|c| c.name.to_lowercase() == component.name.to_lowercase())
+        {
+          Some(c) => {
+            *c = Component {
+              color: c.color.clone(),
+              ..*component.clone()
+            }
+          }
+          None => project.components.push(*component.clone()),
+        }
+        drop(project);
+        self.restart(self.component_by_name(&component.name)?);
+      }
+      Command::Mute(name) | Command::Unmute(name) | Command::Filter(Some(name))
+        if !self.has_output(&name) =>
+      {
+        return Err(format!("Unknown component or service {}", name));
+      }
+      Command::Mute(name) => {
+        self.muted.lock().unwrap().insert(name.to_lowercase());
+        self.announce(Message::OutputMuted { name }, false);
+      }
+      Command::Unmute(name) => {
+        self.muted.lock().unwrap().remove(&name.to_lowercase());
+        self.announce(Message::OutputUnmuted { name }, false);
+      }
+      Command::Filter(name) => {
+        let mut filter = self.filter.lock().unwrap();
+        match name {
+          Some(name) if *filter != [name.to_lowercase()] => {
+            *filter = vec![name.to_lowercase()];
+            drop(filter);
+            self.announce(Message::OutputFiltered { name }, false);
+          }
+          _ => {
+            filter.clear();
+            drop(filter);
+            self.announce(Message::OutputUnfiltered, false);
+          }
+        }
+      }
+      Command::Grep(pattern) => {
+        let pattern = pattern.map(|p| p.parse::<Pattern>()).transpose()?;
+        let message = match &pattern {
+          Some(pattern) => Message::OutputMatching {
+            pattern: pattern.to_string(),
+          },
+          None => Message::OutputUnmatched,
+        };
+        *self.pattern.lock().unwrap() = pattern;
+        self.announce(message, false);
+      }
+      Command::Logs(name) => return self.execute(Command::Tail(name, None)),
+      Command::Tail(name, count) => {
+        let component = self.component_by_name(&name)?;
+        let report = self
+          .report()
+          .into_iter()
+          .find(|r| r.component.name == component.name)
+          .ok_or_else(|| format!("Component {} has not run", component.name))?;
+        let origin = ui::Origin {
+          pid: report.state.pid,
+          restarts: report.state.restarts,
+        };
+        let output = self.scrollback.tail(&component.name, count);
+        for line in output.unwrap_or_default() {
+          ui::component_message(&report.component, origin, line);
+        }
+      }
+      Command::RunTask(name) => {
+        self.spawn_task(name)?;
+      }
+      Command::Status => {
+        self.measure_usage();
+        let states: Vec<ComponentState> = self.report().into_iter().map(|r| r.state).collect();
+        ui::component_states(&states);
+      }
+      Command::Help => {
+        for (usage, description) in USAGE.iter() {
+          ui::message(Message::CommandHelp {
+            usage: usage.to_string(),
+            description: description.to_string(),
+          });
+        }
+      }
+      Command::Apply(components) => {
+        let running: Vec<Component> = self
+          .report()
+          .into_iter()
+          .filter(|r| r.state.status != ComponentStatus::Stopped)
+          .filter(|r| r.state.status != ComponentStatus::Failed)
+          .map(|r| r.component)
+          .collect();
+        for c in running.iter() {
+          if !components.iter().any(|d| d.name == c.name) {
+            self.cancel_component(&c.name);
+          }
+        }
+        for c in components.iter() {
+          match running.iter().find(|r| r.name == c.name) {
+            None => self.spawn_component(c, HashMap::new()),
+            Some(r) if r != c => {
+              self
+                .restarts
+                .lock()
+                .unwrap()
+                .insert(c.name.to_lowercase(), c.clone());
+              self.cancel_component(&c.name);
+            }
+            Some(_) => {}
+          }
+        }
+      }
+    }
+    Ok(())
+  }
+
+  /// Stops a component and starts it again with the given definition, or starts it if it is not
+  /// running.
+  fn restart(&self, component: Component) {
+    self.announce(
+      Message::ComponentRestarting {
+        component: component.name.clone(),
+      },
+      false,
+    );
+    // A running component is respawned by the event loop once its shutdown is processed.
+    if self.is_running(&component.name) {
+      self
+        .restarts
+        .lock()
+        .unwrap()
+        .insert(component.name.to_lowercase(), component.clone());
+      self.cancel_component(&component.name);
+    } else {
+      self.spawn_component(&component, HashMap::new());
+    }
+  }
+
+  /// Freezes the processes of a running component, or lets them continue.
+  fn pause(&self, name: &str, pause: bool) -> Result<(), String> {
+    let workers = self.workers.lock().unwrap();
+    let worker = workers
+      .iter()
+      .rev()
+      .find(|w| !w.completed && w.component.name == name)
+      .ok_or_else(|| format!("Component {} is not running", name))?;
+    let pid = worker
+      .pid
+      .ok_or_else(|| format!("Component {} has not started yet", name))?;
+    if worker.paused.load(Ordering::SeqCst) == pause {
+      return Err(match pause {
+        true => format!("Component {} is already paused", name),
+        false => format!("Component {} is not paused", name),
+      });
+    }
+    let result = match pause {
+      true => process::pause_group(pid),
+      false => process::resume_group(pid),
+    };
+    result.map_err(|e| format!("Could not pause or resume {}: {}", name, e))?;
+    worker.paused.store(pause, Ordering::SeqCst);
+    self.write_state(&workers);
+    drop(workers);
+    let component = name.to_string();
+    let message = match pause {
+      true => Message::ComponentPaused { component },
+      false => Message::ComponentResumed { component },
+    };
+    self.announce(message, false);
+    Ok(())
+  }
+
+  /// Restarts the running components that wait for a component with `wait_for`.
+  fn restart_dependents(&self, name: &str) {
+    let dependents: Vec<Component> = self
+      .project()
+      .components
+      .iter()
+      .filter(|c| {
+        c.wait_for
+          .as_ref()
+          .and_then(|w| w.component.as_ref())
+          .is_some_and(|upstream| upstream.to_lowercase() == name.to_lowercase())
+      })
+      .filter(|c| self.is_running(&c.name))
+      .cloned()
+      .collect();
+    for dependent in dependents {
+      self.restart(dependent);
     }
   }
 
+  fn component_by_name(&self, name: &str) -> Result<Component, String> {
+    self
+      .project()
+      .components
+      .iter()
+      .find(|c| c.name.to_lowercase() == name.to_lowercase())
+      .cloned()
+      .ok_or_else(|| format!("Unknown component {}", name))
+  }
+
+  fn is_running(&self, name: &str) -> bool {
+    self
+      .workers
+      .lock()
+      .unwrap()
+      .iter()
+      .any(|w| !w.completed && w.component.name == name)
+  }
+
+  /// Returns true if a component or service with the name produces output.
+  fn has_output(&self, name: &str) -> bool {
+    self.component_by_name(name).is_ok() || self.project().service_by_name(name).is_some()
+  }
+
+  /// Returns true if a line of output from a component or service is hidden, either because the
+  /// source was muted, because the output is filtered to something else or because the line does
+  /// not match the pattern output is filtered by.
+  fn is_muted(&self, name: &str, line: &str) -> bool {
+    let name = name.to_lowercase();
+    let filter = self.filter.lock().unwrap();
+    let filtered = !filter.is_empty() && !filter.contains(&name);
+    let unmatched = match self.pattern.lock().unwrap().as_ref() {
+      Some(pattern) => !pattern.is_match(line),
+      None => false,
+    };
+    filtered || unmatched || self.muted.lock().unwrap().contains(&name)
+  }
+
+  /// Records a failure for the exit code.
+  pub fn record(&self, code: ExitCode) {
+    let mut outcome = self.outcome.lock().unwrap();
+    *outcome = outcome.and(code);
+  }
+
   /// Returns an iterator that will run all services that a component depends on.
-  pub fn run_component_services(&self, component: &Component) -> crate::service::ServiceLauncher {
+  pub fn run_component_services(&self, component: &Component) -> ServiceLauncher {
     let services = component
       .services
       .iter()
-      .map(|sn| self.project.service_by_name(sn))
-      .flatten()
+      .filter_map(|sn| self.project().service_by_name(sn))
       .collect();
-    crate::service::ServiceLauncher::new(services)
+    ServiceLauncher::new(services, &self.project().root_path)
   }
 
   /// Returns an iterator that will run all services that a component depends on.
-  pub fn shutdown_component_services(
-    &self,
-    component: &Component,
-  ) -> crate::service::ServiceTerminator {
+  pub fn shutdown_component_services(&self, component: &Component) -> ServiceTerminator {
     let services = component
       .services
       .iter()
-      .map(|sn| self.project.service_by_name(sn))
-      .flatten()
+      .filter_map(|sn| self.project().service_by_name(sn))
       .collect();
-    crate::service::ServiceTerminator::new(services)
+    ServiceTerminator::new(services, &self.project().root_path)
   }
 
-  /// Runs a single command for a task. This is a blocking operation
-  /// tasks are not run in parallel.
-  pub fn run_task_command(&self, task: &Task, cmd: String) {
-    let mut root_path = self.project.root_path.clone();
-    root_path.push(expand_env(task.path.to_str().unwrap()));
-    let mut env: HashMap<_, _> = std::env::vars().collect();
-    env.extend(task.env.clone());
-    let env_vars: Vec<(String, String)> =
-      env.into_iter().map(|(k, v)| (k, expand_env(&v))).collect();
-    ui::system_message(cmd.clone());
-    let stream = Exec::shell(cmd)
+  /// Registers a component as a consumer of its services. Returns the services that had no
+  /// other consumers and need to be started.
+  fn acquire_services(&self, component: &Component) -> Vec<Service> {
+    let mut usage = self.service_usage.lock().unwrap();
+    component
+      .services
+      .iter()
+      .filter_map(|sn| self.project().service_by_name(sn))
+      .filter(|s| {
+        let count = usage.entry(s.name.clone()).or_insert(0);
+        *count += 1;
+        *count == 1
+      })
+      .collect()
+  }
+
+  /// Removes a component as a consumer of its services. Returns the services that no longer have
+  /// any consumers and can be stopped.
+  fn release_services(&self, component: &Component) -> Vec<Service> {
+    let mut usage = self.service_usage.lock().unwrap();
+    component
+      .services
+      .iter()
+      .filter_map(|sn| self.project().service_by_name(sn))
+      .filter(|s| match usage.get_mut(&s.name) {
+        Some(count) if *count > 1 => {
+          *count -= 1;
+          false
+        }
+        Some(_) => {
+          usage.remove(&s.name);
+          true
+        }
+        None => false,
+      })
+      .collect()
+  }
+
+  /// Drops a consumer of a service that failed to start. The entry is removed once nothing uses
+  /// the service, so the next consumer tries to start it again.
+  fn forget_service(&self, name: &str) {
+    let mut usage = self.service_usage.lock().unwrap();
+    if let Some(count) = usage.get_mut(name) {
+      *count = count.saturating_sub(1);
+      if *count == 0 {
+        usage.remove(name);
+      }
+    }
+  }
+
+  /// Stops services that are no longer used by any running component.
+  fn stop_unused_services(&self, component: &Component) {
+    for result in
+      ServiceTerminator::new(self.release_services(component), &self.project().root_path)
+    {
+      match result {
+        Ok(s) => {
+          self.metrics.service_stopped(&s.name);
+          self.announce(Message::ServiceStopped { service: s.name }, false)
+        }
+        Err((s, e)) => {
+          self.record(ExitCode::Service);
+          self.announce(
+            Message::ServiceStopFailed {
+              service: s.name,
+              error: e.to_string(),
+            },
+            false,
+          )
+        }
+      }
+    }
+  }
+
+  /// Follows the container logs for a service that has logging enabled. The output is sent
+  /// through the supervisor's service channel, rather than the worker's, so the log stream does
+  /// not keep a finished worker alive. Each service is only followed once.
+  fn follow_service_logs(&self, service: &Service, component: &Component) {
+    if !service.logs {
+      return;
+    }
+    if !self
+      .followed_services
+      .lock()
+      .unwrap()
+      .insert(service.name.clone())
+    {
+      return;
+    }
+    let sender = self.service_sender.clone();
+    let followed = Arc::clone(&self.followed_services);
+    let service = service.clone();
+    let component = component.clone();
+    let root_path = self.project().root_path.clone();
+    thread::spawn(move || {
+      let stream = service
+        .follow_logs(&root_path)
+        .and_then(|exec| exec.stream_stdout().map_err(std::io::Error::other));
+      match stream {
+        Ok(stream) => read_lines(stream, lines::DEFAULT_CAPACITY, |body| {
+          let _ = sender.send(ComponentEvent::service_output(
+            component.clone(),
+            service.name.clone(),
+            body,
+          ));
+        }),
+        Err(e) => {
+          let _ = sender.send(ComponentEvent::error(
+            component.clone(),
+            Message::ServiceFollowFailed {
+              service: service.name.clone(),
+              error: e.to_string(),
+            }
+            .to_string(),
+          ));
+        }
+      }
+      followed.lock().unwrap().remove(&service.name);
+    });
+  }
+
+  /// Runs a single command of a task, streaming its output. Returns true if the command
+  /// succeeded.
+  pub fn run_task_command(&self, task: &Task, cmd: String) -> bool {
+    let span = Span::new(format!("task {}", task.name))
+      .attribute("conductor.task", task.name.as_str())
+      .attribute("conductor.command", cmd.as_str());
+    let succeeded = self.run_command(task, cmd);
+    span.end(!succeeded);
+    succeeded
+  }
+
+  fn run_command(&self, task: &Task, cmd: String) -> bool {
+    let functions = Functions::new(&self.project().root_path);
+    let (root_path, env_vars) = match self.task_environment(task, &functions) {
+      Some(environment) => environment,
+      None => return false,
+    };
+    let cmd = functions.at("command").apply(&cmd);
+    ui::message(Message::TaskCommand {
+      command: cmd.clone(),
+    });
+    let mut p = match Exec::shell(&cmd)
       .env_extend(&env_vars[..])
       .cwd(root_path)
       .stdout(Redirection::Pipe)
       .stderr(Redirection::Merge)
-      .stream_stdout()
-      .unwrap();
-
-    let reader = BufReader::new(stream);
-    let _ = reader.lines().for_each(|line| {
-      if let Ok(body) = line {
-        ui::task_message(&task, body);
+      .popen()
+    {
+      Ok(p) => p,
+      Err(e) => {
+        ui::message(Message::error(e));
+        return false;
       }
+    };
+
+    // Output is read on its own thread so a command that runs too long can be killed.
+    let stdout = p.stdout.take().unwrap();
+    let t = task.clone();
+    let output = thread::spawn(move || {
+      read_lines(stdout, lines::DEFAULT_CAPACITY, |body| {
+        ui::task_message(&t, body);
+      });
     });
+    let status = match task.timeout {
+      Some(timeout) => match p.wait_timeout(timeout) {
+        Ok(Some(status)) => status,
+        Ok(None) => {
+          // Whatever the command started is stopped along with it.
+          if let Some(pid) = p.pid() {
+            for child in process::descendants(pid) {
+              let _ = process::terminate(child);
+            }
+          }
+          let _ = p.kill();
+          let _ = p.wait();
+          ui::message(Message::TaskTimedOut {
+            command: cmd,
+            seconds: timeout.as_secs(),
+          });
+          return false;
+        }
+        Err(_) => return false,
+      },
+      None => match p.wait() {
+        Ok(status) => status,
+        Err(_) => return false,
+      },
+    };
+    let _ = output.join();
+    status.success()
+  }
+
+  /// The directory a task's commands run in and their environment, resolved the way it is for
+  /// components. Problems resolving it are reported and none is returned.
+  pub(crate) fn task_environment(
+    &self,
+    task: &Task,
+    functions: &Functions,
+  ) -> Option<(PathBuf, Vec<(String, String)>)> {
+    let mut root_path = self.project().root_path.clone();
+    root_path.push(expand_env(task.path.to_str().unwrap()));
+    let mut env = HashMap::new();
+    match self.project().dotenv.load(&root_path) {
+      Ok(vars) => env.extend(vars),
+      Err(e) => ui::message(Message::error(e)),
+    }
+    env.extend(self.project().env.clone());
+    if let Some(env_file) = &task.env_file {
+      match read_env_file(&root_path.join(expand_env(env_file))) {
+        Ok(vars) => env.extend(vars),
+        Err(e) => ui::message(Message::error(e)),
+      }
+    }
+    env.extend(task.env.clone());
+    env.extend(self.options.env.clone());
+    // Only the values conductor sets are expanded, those inherited are passed on as they are.
+    let mut env: HashMap<_, _> = std::env::vars()
+      .chain(env.into_iter().map(|(k, v)| {
+        let v = expand_launch(&v, &functions.at(&k));
+        (k, v)
+      }))
+      .collect();
+    for commands in [&self.project().env_commands, &task.env_commands].iter() {
+      match resolve_env_commands(commands, &self.project().root_path) {
+        Ok(vars) => env.extend(vars),
+        Err(e) => {
+          ui::message(Message::error(e));
+          return None;
+        }
+      }
+    }
+    for secrets in [&self.project().secrets, &task.secrets].iter() {
+      match resolve_secrets(secrets, &root_path) {
+        Ok(vars) => env.extend(vars),
+        Err(e) => {
+          ui::message(Message::error(e));
+          return None;
+        }
+      }
+    }
+    Some((root_path, env.into_iter().collect()))
   }
 
   /// Spawns a component by creating a shell and running its start command. Sets up a thread
-  /// for reading the output and a thred for minitoring for kill signals.
+  /// for reading the output and a thred for minitoring for cancellation.
   /// This also creates a worker instance and sets up the pipeline for events to be read from
   /// Supervisor::init()
   pub fn spawn_component(&self, component: &Component, extra_env: HashMap<String, String>) {
+    self.spawn_worker(component, extra_env, None, component.initial_delay, None);
+  }
+
+  /// Spawns a component as a member of a group. The worker's cancellation token is a child of
+  /// the group's token. In a serial group the component starts once the previous member is
+  /// ready or has finished.
+  pub fn spawn_group_component(
+    &self,
+    group_name: &str,
+    component: &Component,
+    extra_env: HashMap<String, String>,
+    previous: Option<&str>,
+  ) {
+    self.spawn_worker(
+      component,
+      extra_env,
+      Some(group_name.to_lowercase()),
+      component.initial_delay,
+      previous.map(String::from),
+    );
+  }
+
+  /// Picks free ports for a component's auto ports and returns it with them added to its ports.
+  /// Ports already picked for a name earlier in the session are kept. A component whose auto
+  /// ports were added already is returned as it is.
+  fn allocate_ports(&self, component: &Component) -> io::Result<Component> {
+    let mut component = component.clone();
+    if component.auto_ports.is_empty() {
+      return Ok(component);
+    }
+    let mut allocated = self.allocated_ports.lock().unwrap();
+    let key = component.name.to_lowercase();
+    let previous = allocated.remove(&key).unwrap_or_default();
+    // Listeners are held until every port is picked so the same one is not handed out twice.
+    let mut listeners = vec![];
+    let mut ports = vec![];
+    for name in component.auto_ports.drain(..) {
+      let port = match previous.iter().find(|(n, _)| *n == name) {
+        Some((_, port)) => *port,
+        None => {
+          let listener = TcpListener::bind(("127.0.0.1", 0))?;
+          let port = listener.local_addr()?.port();
+          listeners.push(listener);
+          port
+        }
+      };
+      ports.push((name, port));
+    }
+    component.ports.extend(ports.iter().map(|(_, port)| *port));
+    allocated.insert(key, ports);
+    Ok(component)
+  }
+
+  /// The env telling a component which ports were picked for it, `PORT` for the first and
+  /// `PORT_<NAME>` for each.
+  fn port_env(&self, component: &Component) -> HashMap<String, String> {
+    let allocated = self.allocated_ports.lock().unwrap();
+    let ports = match allocated.get(&component.name.to_lowercase()) {
+      Some(ports) => ports,
+      None => return HashMap::new(),
+    };
+    let mut env = HashMap::new();
+    if let Some((_, port)) = ports.first() {
+      env.insert("PORT".to_string(), port.to_string());
+    }
+    for (name, port) in ports {
+      let name: String = name
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+          true => c.to_ascii_uppercase(),
+          false => '_',
+        })
+        .collect();
+      env.insert(format!("PORT_{}", name), port.to_string());
+    }
+    env
+  }
+
+  fn spawn_worker(
+    &self,
+    component: &Component,
+    extra_env: HashMap<String, String>,
+    group: Option<String>,
+    delay: Option<u64>,
+    previous: Option<String>,
+  ) {
+    let component = &match self.allocate_ports(component) {
+      Ok(component) => component,
+      Err(e) => {
+        self.announce(
+          Message::ComponentError {
+            component: component.name.clone(),
+            error: format!("Could not pick free ports: {}", e),
+          },
+          false,
+        );
+        component.clone()
+      }
+    };
+    // Output waiting to be shown is bounded by the backlog, see Component::backpressure. The
+    // channel itself is not, so the events of the worker are never held up by output.
     let (data_sender, data_receiver) = unbounded();
-    let (kill_tx, kill_rx) = unbounded();
+    let backlog = Arc::new(Backlog::new(component.backpressure.buffer));
+    let (done_tx, done_rx) = unbounded::<()>();
+    let (input, input_receiver) = unbounded::<String>();
+    let mut extra_env = extra_env;
+    extra_env.extend(self.options.env.clone());
+    let token = match &group {
+      Some(group_name) => self.group_token(group_name).child(),
+      None => self.shutdown_token.child(),
+    };
+    // The span covers waiting for dependencies and building as well as running.
+    self.spans.lock().unwrap().insert(
+      component.name.to_lowercase(),
+      Span::new(format!("component {}", component.name))
+        .attribute("conductor.component", component.name.as_str()),
+    );
+    let paused = Arc::new(AtomicBool::new(false));
     let worker = Worker {
-      project: self.project.clone(),
       extra_env: extra_env.clone(),
       running: true,
       completed: false,
+      failed: false,
+      ready: false,
+      pid: None,
       component: component.clone(),
       data_receiver,
-      kill_signal: kill_tx,
+      backlog: Arc::clone(&backlog),
+      token: token.clone(),
+      group,
+      done: done_rx.clone(),
+      input,
+      exit_code: None,
+      started: None,
+      restarts: 0,
+      usage: None,
+      paused: Arc::clone(&paused),
     };
 
-    for service in self.run_component_services(component) {
-      match service {
-        Ok(service) => {
-          let _ = data_sender.send(ComponentEvent::service_start(
-            component.clone(),
-            service.name.clone(),
-          ));
-        }
-        Err((service, e)) => {
-          let _ = data_sender.send(ComponentEvent::error(
-            component.clone(),
-            format!("Could not start service [{}]: {}", service.name, e),
-          ));
-        }
-      }
-    }
-
+    // Only services without an existing consumer are started, by the thread below as waiting
+    // for them to be ready can take a while. Services that fail to start are released again by
+    // the event loop so the next consumer will retry them.
+    let services = self.acquire_services(component);
+    let used_services: Vec<Service> = component
+      .services
+      .iter()
+      .filter_map(|name| self.project().service_by_name(name))
+      .collect();
+    let port_env = self.port_env(component);
+    let functions = self
+      .functions
+      .lock()
+      .unwrap()
+      .entry(component.name.to_lowercase())
+      .or_insert_with(|| Functions::new(&self.project().root_path))
+      .relaunch();
+    let tuning = self.project().tuning.clone();
+    let use_pty = component.pty.unwrap_or(self.project().pty);
+    let merge_stderr = self.project().output.merge_stderr;
+    let project_env = self.project().env.clone();
+    let dotenv = self.project().dotenv.clone();
+    let project_env_commands = self.project().env_commands.clone();
+    let project_secrets = self.project().secrets.clone();
     let component = component.clone();
-    let mut root_path = self.project.root_path.clone();
+    let mut root_path = self.project().root_path.clone();
+    let project_root = root_path.clone();
+    let workers = Arc::clone(&self.workers);
+    let build = !self.options.no_build;
     info!("starting spawn thread for {}", &component.name);
     thread::spawn(move || {
-      if let Some(delay) = component.delay {
-        thread::sleep(Duration::from_secs(delay));
+      // Held for the lifetime of the thread, dropping it signals completion to anyone waiting
+      // on the worker.
+      let _done = done_tx;
+      for service in ServiceLauncher::new(services, &project_root) {
+        let _ = data_sender.send(match service {
+          Ok(service) => ComponentEvent::service_start(component.clone(), service.name),
+          Err((service, e)) => {
+            ComponentEvent::service_failed(component.clone(), service.name, e.to_string())
+          }
+        });
+      }
+      // Read once the services are up, so the ports their containers publish are known.
+      let mut service_env: HashMap<String, String> = used_services
+        .iter()
+        .flat_map(|s| s.connection_env())
+        .collect();
+      service_env.extend(port_env);
+      if let Some(delay) = delay {
+        let _ = token.cancelled().recv_timeout(Duration::from_secs(delay));
+      }
+      if token.is_cancelled() {
+        let _ = data_sender.send(ComponentEvent::shutdown(component.clone(), false));
+        return;
+      }
+
+      if let Some(previous) = &previous {
+        // The latest worker for the previous member decides. One that failed is waited on again
+        // if it is retried.
+        let state = || {
+          let workers = workers.lock().unwrap();
+          workers
+            .iter()
+            .rev()
+            .find(|w| w.component.name == *previous)
+            .map(|w| match w.completed {
+              true => (!w.failed, w.failed && !w.component.retry),
+              false => (w.ready, false),
+            })
+        };
+        loop {
+          match state() {
+            Some((true, _)) => break,
+            Some((_, true)) => {
+              let msg = Message::PreviousFailed {
+                component: component.name.clone(),
+                previous: previous.clone(),
+              };
+              let _ = data_sender.send(ComponentEvent::error(component.clone(), msg.to_string()));
+              let _ = data_sender.send(ComponentEvent::shutdown(component.clone(), true));
+              return;
+            }
+            _ => {}
+          }
+          if token
+            .cancelled()
+            .recv_timeout(Duration::from_millis(100))
+            .is_ok()
+          {
+            let _ = data_sender.send(ComponentEvent::shutdown(component.clone(), false));
+            return;
+          }
+        }
       }
 
-      // Setup the environment variables
-      let mut env: HashMap<_, _> = std::env::vars().collect();
-      env.extend(component.env.clone());
-      env.extend(extra_env);
-      let env_vars: Vec<(String, String)> =
-        env.into_iter().map(|(k, v)| (k, expand_env(&v))).collect();
       root_path.push(expand_env(component.get_path().to_str().unwrap()));
-      // Create the execution command and shell
-      let exec = Exec::shell(component.start.clone())
-        .env_extend(&env_vars[..])
-        .cwd(root_path)
-        .stdout(Redirection::Pipe)
-        .stderr(Redirection::Merge);
-
-      // Execute the process and return a popen. This goes into an Arc and a mutex so the
-      // kill signal can poll and kill, while we pass the reading stream into a seperate thread.
-      //  We also setup a stream adapter and a bufreader to read out the data from the reading thread.
-      let _ = data_sender.send(ComponentEvent::start(component.clone()));
-      let popen = match exec.popen() {
-        Ok(p) => Arc::new(Mutex::new(p)),
+      if let Some(wait_for) = &component.wait_for {
+        let ready = |name: &str| {
+          workers.lock().unwrap().iter().any(|w| {
+            !w.completed && w.ready && w.component.name.to_lowercase() == name.to_lowercase()
+          })
+        };
+        let started = Instant::now();
+        while let Some(condition) = wait_for.unmet(&root_path, ready) {
+          if started.elapsed() >= Duration::from_secs(wait_for.timeout_secs) {
+            let msg = Message::WaitTimedOut {
+              condition,
+              seconds: wait_for.timeout_secs,
+            };
+            let _ = data_sender.send(ComponentEvent::error(component.clone(), msg.to_string()));
+            let _ = data_sender.send(ComponentEvent::shutdown(component.clone(), true));
+            return;
+          }
+          if token
+            .cancelled()
+            .recv_timeout(Duration::from_millis(wait_for.interval_ms))
+            .is_ok()
+          {
+            let _ = data_sender.send(ComponentEvent::shutdown(component.clone(), false));
+            return;
+          }
+        }
+      }
+      // Setup the environment variables. Values from the dotenv files in the component's path sit
+      // below the project env, those from the env file, the connection env of services and the
+      // picked ports below the component's env.
+      let mut env = HashMap::new();
+      match dotenv.load(&root_path) {
+        Ok(vars) => env.extend(vars),
         Err(e) => {
           let _ = data_sender.send(ComponentEvent::error(component.clone(), format!("{}", e)));
-          let _ = data_sender.send(ComponentEvent::shutdown(component.clone()));
+        }
+      }
+      env.extend(project_env);
+      if let Some(env_file) = &component.env_file {
+        match read_env_file(&root_path.join(expand_env(env_file))) {
+          Ok(vars) => env.extend(vars),
+          Err(e) => {
+            let _ = data_sender.send(ComponentEvent::error(component.clone(), format!("{}", e)));
+          }
+        }
+      }
+      env.extend(service_env);
+      env.extend(component.env.clone());
+      env.extend(extra_env);
+      // Only the values set here are expanded, those inherited are passed on as they are. Calls
+      // such as ${free_port()} are told apart by the variable they are made in.
+      let mut env: HashMap<_, _> = std::env::vars()
+        .chain(env.into_iter().map(|(k, v)| {
+          let v = expand_launch(&v, &functions.at(&k));
+          (k, v)
+        }))
+        .collect();
+      // Command outputs and secrets are resolved last and used verbatim. A component is not
+      // started without them.
+      for commands in [&project_env_commands, &component.env_commands].iter() {
+        match resolve_env_commands(commands, &project_root) {
+          Ok(vars) => env.extend(vars),
+          Err(e) => {
+            let _ = data_sender.send(ComponentEvent::error(component.clone(), format!("{}", e)));
+            let _ = data_sender.send(ComponentEvent::shutdown(component.clone(), true));
+            return;
+          }
+        }
+      }
+      for secrets in [&project_secrets, &component.secrets].iter() {
+        match resolve_secrets(secrets, &root_path) {
+          Ok(vars) => env.extend(vars),
+          Err(e) => {
+            let _ = data_sender.send(ComponentEvent::error(component.clone(), format!("{}", e)));
+            let _ = data_sender.send(ComponentEvent::shutdown(component.clone(), true));
+            return;
+          }
+        }
+      }
+      let env_vars: Vec<(String, String)> = env.into_iter().collect();
+      if build {
+        let commands: Vec<String> = component
+          .build
+          .iter()
+          .enumerate()
+          .map(|(n, c)| functions.at(&format!("build {}", n)).apply(c))
+          .collect();
+        let built = run_commands(
+          &component,
+          "Build",
+          &commands,
+          &root_path,
+          &env_vars,
+          &data_sender,
+          merge_stderr,
+        );
+        if !built || token.is_cancelled() {
+          let _ = data_sender.send(ComponentEvent::shutdown(component.clone(), !built));
+          return;
+        }
+      }
+      // A port that is already taken would otherwise show up as a crash somewhere in the output.
+      let ports_in_use = component.ports_in_use();
+      for port in ports_in_use.iter() {
+        let msg = Message::PortInUse {
+          port: *port,
+          pid: process::listening_on(*port),
+        };
+        let _ = data_sender.send(ComponentEvent::error(component.clone(), msg.to_string()));
+      }
+      if !ports_in_use.is_empty() {
+        let _ = data_sender.send(ComponentEvent::shutdown(component.clone(), true));
+        return;
+      }
+      let start = match component.start_command() {
+        Ok(start) => start,
+        Err(e) => {
+          let _ = data_sender.send(ComponentEvent::error(component.clone(), e));
+          let _ = data_sender.send(ComponentEvent::shutdown(component.clone(), true));
           return;
         }
       };
-      let stream = ReadOutAdapter(Arc::clone(&popen));
-      let reader = BufReader::new(stream);
-
-      let sender = data_sender.clone();
-      let cmp = component.clone();
-      // spawn the reading thread that will read the stdout of the process until the popen goes out of scope
-      // which occures either as a result of the process exiting or the kill signal being received.
-      std::thread::spawn(move || {
-        let c = cmp.clone();
-        let _ = reader.lines().for_each(|line| {
-          if let Ok(body) = line {
-            let _ = sender.send(ComponentEvent::output(c.clone(), body));
-          } else {
-            warn!("Error reading from reader");
+      // Create the execution command and shell.
+      let mut command = process::shell(&functions.at("start").apply(start));
+      command
+        .envs(env_vars.iter().cloned())
+        .current_dir(&root_path);
+      if let Some(nice) = component.nice {
+        process::set_nice(&mut command, nice);
+      }
+      // Limits are a safety net, a component is still started if they can not be applied.
+      let confinement = component.limits.as_ref().and_then(|limits| {
+        match Confinement::create(&component.name, limits).and_then(|confinement| {
+          confinement.add(&mut command)?;
+          Ok(confinement)
+        }) {
+          Ok(confinement) => Some(confinement),
+          Err(e) => {
+            let msg = Message::LimitsNotApplied {
+              error: e.to_string(),
+            };
+            let _ = data_sender.send(ComponentEvent::error(component.clone(), msg.to_string()));
+            None
+          }
+        }
+      });
+      let pty = match use_pty {
+        true => match process::open_pty() {
+          Ok(pty) => Some(pty),
+          Err(e) => {
+            let _ = data_sender.send(ComponentEvent::error(component.clone(), format!("{}", e)));
+            None
           }
+        },
+        false => None,
+      };
+
+      // Execute the process. Its output is read and its exit waited on by threads of their own,
+      // so this thread blocks until the process exits or the worker's cancellation token is
+      // cancelled, and then stops it.
+      let spawned = connect(&mut command, pty, merge_stderr)
+        .and_then(|streams| Ok((command.spawn()?, streams)));
+      // The command holds the child's side of the streams, the end of the output is only seen
+      // once it is closed.
+      drop(command);
+      let (mut child, (stream, errors, input)) = match spawned {
+        Ok(spawned) => {
+          let _ = data_sender.send(ComponentEvent::start(
+            component.clone(),
+            Some(spawned.0.id()),
+          ));
+          spawned
+        }
+        Err(e) => {
+          let _ = data_sender.send(ComponentEvent::error(component.clone(), format!("{}", e)));
+          let _ = data_sender.send(ComponentEvent::shutdown(component.clone(), true));
+          return;
+        }
+      };
+      // Where there are no process groups the job keeps what the process starts from outliving
+      // the component.
+      if let Some(Err(e)) = confinement.as_ref().map(|c| c.assign(&child)) {
+        let msg = Message::LimitsNotApplied {
+          error: e.to_string(),
+        };
+        let _ = data_sender.send(ComponentEvent::error(component.clone(), msg.to_string()));
+      }
+      // The process started at the default level when its nice level was not allowed.
+      if let Some(nice) = component.nice {
+        if process::nice_of(child.id()).is_some_and(|current| current != nice) {
+          let msg = Message::NiceNotApplied { nice };
+          let _ = data_sender.send(ComponentEvent::error(component.clone(), msg.to_string()));
+        }
+      }
+      let job = process::Job::new().and_then(|job| {
+        job.assign(&child)?;
+        Ok(job)
+      });
+      if let Err(e) = &job {
+        warn!("Could not assign {} to a job: {}", component.name, e);
+      }
+      if !component.on_start.is_empty() {
+        let sender = data_sender.clone();
+        let cmp = component.clone();
+        let path = root_path.clone();
+        let env = env_vars.clone();
+        thread::spawn(move || {
+          run_commands(
+            &cmp,
+            "Hook",
+            &cmp.on_start,
+            &path,
+            &env,
+            &sender,
+            merge_stderr,
+          )
         });
+      }
+      let stdin = input.or_else(|| {
+        child
+          .stdin
+          .take()
+          .map(|stdin| Box::new(stdin) as Box<dyn Write + Send>)
       });
 
+      // Input for the process is written from its own thread until the worker finishes.
+      if let Some(mut stdin) = stdin {
+        let done = done_rx.clone();
+        thread::spawn(move || loop {
+          crossbeam::select! {
+            recv(input_receiver) -> text => match text {
+              Ok(text) => {
+                if stdin.write_all(text.as_bytes()).and_then(|_| stdin.flush()).is_err() {
+                  break;
+                }
+              }
+              Err(_) => break,
+            },
+            recv(done) -> _ => break,
+          }
+        });
+      }
+
+      // Components without readiness checks are ready as soon as they start. Output checks are
+      // handled by the reading thread and port or command checks are polled in a probe thread
+      // until they pass or the worker finishes.
+      let readiness = component.ready.clone();
+      let ready_output = Arc::new(Mutex::new(
+        readiness.as_ref().and_then(|r| r.output.clone()),
+      ));
+      // Set once the process produces output or passes a readiness check, for the start timeout.
+      // Being ready without checks does not count.
+      let alive = Arc::new(AtomicBool::new(false));
+      // Set once the component is ready, health checks only start then.
+      let ready = Arc::new(AtomicBool::new(false));
+      let health_done = done_rx.clone();
+      match readiness {
+        None => {
+          ready.store(true, Ordering::SeqCst);
+          let _ = data_sender.send(ComponentEvent::ready(component.clone()));
+        }
+        Some(readiness) if readiness.has_probe() => {
+          let sender = data_sender.clone();
+          let cmp = component.clone();
+          let path = root_path.clone();
+          let env = env_vars.clone();
+          let done = done_rx;
+          let alive = Arc::clone(&alive);
+          let ready = Arc::clone(&ready);
+          thread::spawn(move || loop {
+            if readiness.probe(&path, &env) {
+              alive.store(true, Ordering::SeqCst);
+              ready.store(true, Ordering::SeqCst);
+              let _ = sender.send(ComponentEvent::ready(cmp));
+              break;
+            }
+            match done.recv_timeout(Duration::from_millis(readiness.interval_ms)) {
+              Err(RecvTimeoutError::Timeout) => continue,
+              _ => break,
+            }
+          });
+        }
+        Some(_) => {}
+      }
+
+      // A reading thread for each stream reads the output of the process until the stream is
+      // closed, which occurs either as a result of the process exiting or being killed.
+      let streams = std::iter::once((stream, OutputStream::Stdout))
+        .chain(errors.map(|errors| (errors, OutputStream::Stderr)));
+      let dropped = Arc::new(AtomicUsize::new(0));
+      let (reading, read) = bounded::<()>(0);
+      for (stream, kind) in streams {
+        let capacity = tuning.read_buffer_size;
+        let sender = data_sender.clone();
+        let c = component.clone();
+        let output_seen = Arc::clone(&alive);
+        let output_ready = Arc::clone(&ready);
+        let ready_output = Arc::clone(&ready_output);
+        let dropped = Arc::clone(&dropped);
+        let backlog = Arc::clone(&backlog);
+        let overflow = component.backpressure.overflow;
+        let ansi = component.ansi;
+        let reading = reading.clone();
+        std::thread::spawn(move || {
+          let _reading = reading;
+          read_lines(stream, capacity, |body| {
+            let body = ansi.apply(body);
+            output_seen.store(true, Ordering::SeqCst);
+            let mut ready_output = ready_output.lock().unwrap();
+            if ready_output.as_ref().map(|o| body.contains(o)) == Some(true) {
+              *ready_output = None;
+              output_ready.store(true, Ordering::SeqCst);
+              let _ = sender.send(ComponentEvent::ready(c.clone()));
+            }
+            drop(ready_output);
+            forward_output(&sender, &backlog, &c, body, kind, overflow, &dropped);
+          });
+          let lines = dropped.swap(0, Ordering::SeqCst);
+          if lines > 0 {
+            let marker = Message::OutputDropped { lines }.to_string();
+            let _ = sender.send(ComponentEvent::output_on(c, marker, kind));
+          }
+        });
+      }
+
+      // Health checks run until the worker finishes. A component that fails enough of them in a
+      // row is stopped from the loop below and restarted by the event loop.
+      let (unhealthy_sender, mut unhealthy) = bounded(1);
+      if let Some(check) = component.health_check.clone() {
+        let ready = Arc::clone(&ready);
+        let paused = Arc::clone(&paused);
+        let path = root_path.clone();
+        let env = env_vars.clone();
+        thread::spawn(move || {
+          let interval = Duration::from_secs(check.interval_secs.max(1));
+          let mut failures = 0;
+          while let Err(RecvTimeoutError::Timeout) = health_done.recv_timeout(interval) {
+            // A paused component is not expected to respond.
+            if !ready.load(Ordering::SeqCst) || paused.load(Ordering::SeqCst) {
+              continue;
+            }
+            match check.passes(&path, &env) {
+              true => failures = 0,
+              false => failures += 1,
+            }
+            if failures >= check.failures.max(1) {
+              let _ = unhealthy_sender.send(());
+              break;
+            }
+          }
+        });
+      }
+
+      // The process is waited on by a thread of its own, so the loop below sleeps until the
+      // process exits, the worker is cancelled, a health check gives up or the start timeout
+      // passes.
+      let pid = child.id();
+      let (exit_sender, exited) = bounded(1);
+      thread::spawn(move || {
+        let _ = exit_sender.send(child.wait());
+      });
+      let cancelled = token.cancelled();
+      let start_timeout = component
+        .start_timeout
+        .map_or_else(never, |seconds| after(Duration::from_secs(seconds)));
+      let mut exit = exited.clone();
+      let mut status = None;
+      let mut failed = false;
       loop {
-        thread::sleep(Duration::from_millis(200));
-        let mut p = popen.lock().unwrap();
-        if let Ok(Some(_)) = p.wait_timeout(Duration::new(0, 0)) {
-          if !component.keep_alive {
-            info!("Component has exited");
+        crossbeam::select! {
+          recv(cancelled) -> _ => {
+            info!("killing process");
             break;
           }
+          recv(start_timeout) -> _ => {
+            if !alive.load(Ordering::SeqCst) {
+              let seconds = component.start_timeout.unwrap_or_default();
+              let msg = Message::StartTimedOut { seconds };
+              let _ = data_sender.send(ComponentEvent::error(component.clone(), msg.to_string()));
+              failed = true;
+              break;
+            }
+          }
+          recv(unhealthy) -> result => {
+            // The channel closes if the health checks end without giving up.
+            if result.is_err() {
+              unhealthy = never();
+              continue;
+            }
+            let failures = component
+              .health_check
+              .as_ref()
+              .map_or(0, |check| check.failures.max(1));
+            let _ = data_sender.send(ComponentEvent::unhealthy(component.clone(), failures));
+            break;
+          }
+          recv(exit) -> result => {
+            status = result.ok().and_then(Result::ok);
+            // Components kept alive stay up until they are stopped.
+            if !component.keep_alive {
+              info!("Component has exited");
+              break;
+            }
+            exit = never();
+          }
         }
-        if let Ok(()) = kill_rx.try_recv() {
-          info!("killing process");
-          break;
-        }
       }
-      let mut p = popen.lock().unwrap();
-      let _ = p.kill();
+      let exit_code = status.as_ref().and_then(process::exit_code);
+      if status.as_ref().is_some_and(|s| !s.success()) && !component.keep_alive {
+        failed = true;
+      }
+      // The start command runs in a shell, anything it launched is stopped along with it. The
+      // shell leads its own process group, descendants that left the group are stopped one by
+      // one.
+      let _ = process::terminate_group(pid);
+      // A paused process only acts on the signal once it continues.
+      if paused.load(Ordering::SeqCst) {
+        let _ = process::resume_group(pid);
+      }
+      for descendant in process::descendants(pid) {
+        let _ = process::terminate(descendant);
+      }
+      // Processes that do not exit within the grace period are killed.
+      if status.is_none() && exited.recv_timeout(tuning.stop_grace()).is_err() {
+        let _ = process::kill(pid);
+        let _ = exited.recv();
+      }
+      drop(job);
+      // Output still being read is shown before the exit, unless something that escaped the
+      // process group keeps the streams open.
+      drop(reading);
+      let _ = read.recv_timeout(Duration::from_secs(5));
+      if confinement.as_ref().is_some_and(|c| c.memory_exceeded()) {
+        let limit = component.limits.as_ref().and_then(|l| l.memory.clone());
+        let msg = Message::MemoryExceeded {
+          limit: limit.unwrap_or_default(),
+        };
+        let _ = data_sender.send(ComponentEvent::error(component.clone(), msg.to_string()));
+        failed = true;
+      }
+      drop(confinement);
       info!("ending read loop");
-      let _ = data_sender.send(ComponentEvent::shutdown(component.clone()));
+      let hooks = match failed {
+        true => &component.on_crash,
+        false => &component.on_stop,
+      };
+      run_commands(
+        &component,
+        "Hook",
+        hooks,
+        &root_path,
+        &env_vars,
+        &data_sender,
+        merge_stderr,
+      );
+      let _ = data_sender.send(ComponentEvent::exited(component.clone(), failed, exit_code));
     });
 
     let workers = &mut self.workers.lock().unwrap();
-    workers.push(worker);
+    let restarts = workers
+      .iter()
+      .filter(|w| w.component.name == worker.component.name)
+      .count();
+    workers.push(Worker { restarts, ..worker });
   }
 
   /// Starts the main run loop for the launched components.
   /// Begins a blocking read of all events comming from all components and outputing them through
   /// the ui module. Retriable components will also be relaunched here.
   pub fn init(&self) {
-    let workers_lock = Arc::clone(&self.workers);
-    let running = Arc::new(AtomicBool::new(true));
-    let r = running.clone();
-    let _ = ctrlc::set_handler(move || {
-      r.store(false, Ordering::SeqCst);
-      crate::ui::system_message("shutting down".into());
+    let shutdown_token = self.shutdown_token.clone();
+    let interrupted = Arc::clone(&self.interrupted);
+    let console = self.console();
+    let interrupt = move || {
+      if console {
+        crate::ui::message(Message::ShuttingDown);
+      }
       info!("ctrl-c signal caught");
-      let mut workers = workers_lock.lock().unwrap();
-      for w in workers.iter_mut() {
-        w.completed = true;
-        if w.running {
-          info!("sending kill signal");
-          let _ = w.kill_signal.send(());
+      interrupted.store(true, Ordering::SeqCst);
+      shutdown_token.cancel();
+    };
+    // An application embedding the supervisor handles signals itself and ends the session with
+    // the shutdown token.
+    #[cfg(unix)]
+    if console {
+      let shutdown = interrupt.clone();
+      let workers = Arc::clone(&self.workers);
+      let handled = signals::on_shutdown(move |signal| {
+        // Components run in their own process groups, so the signal is passed on to them before
+        // the orderly shutdown begins.
+        for w in workers.lock().unwrap().iter().filter(|w| !w.completed) {
+          if let Some(pid) = w.pid {
+            let _ = process::signal_group(pid, signal);
+          }
+        }
+        shutdown();
+      });
+      if let Err(e) = handled {
+        warn!("Could not handle signals: {}", e);
+      }
+    }
+    #[cfg(not(unix))]
+    if console {
+      let _ = ctrlc::set_handler(interrupt.clone());
+    }
+
+    // Commands come from the palette and the control socket. The sender is held for the whole
+    // loop so the receiver only disconnects when init returns.
+    let (command_sender, commands) = unbounded::<Request>();
+    *self.commands.lock().unwrap() = Some(command_sender.clone());
+    // Lines are broadcast instead of reading keys, they are typed with the terminal's own editing.
+    let broadcast = self.options.broadcast.clone().filter(|_| !self.options.tui);
+    if let Some(names) = &broadcast {
+      let sender = command_sender.clone();
+      let names = names.clone();
+      thread::spawn(move || broadcast_input(sender, names));
+    }
+    // Keys are read until the session ends, when the terminal's settings are restored.
+    #[cfg(unix)]
+    let _keys = match self.options.interactive && !self.options.tui && broadcast.is_none() {
+      true => keys::listen(command_sender.clone())
+        .map_err(|e| warn!("Could not read keys from the terminal: {}", e))
+        .ok(),
+      false => None,
+    };
+    #[cfg(not(unix))]
+    if self.options.interactive && !self.options.tui && broadcast.is_none() {
+      let sender = command_sender.clone();
+      thread::spawn(move || read_commands(sender));
+    }
+    // The dashboard is restored when it is dropped at the end of the session.
+    #[cfg(unix)]
+    let _dashboard = match self.options.tui {
+      true => Dashboard::start(&self.project(), command_sender.clone(), interrupt)
+        .map_err(|e| ui::message(Message::error(e)))
+        .ok(),
+      false => None,
+    };
+    // Another session answering on the socket keeps it, commands keep going to that session.
+    let control = match control::listen(
+      &self.project().root_path,
+      command_sender.clone(),
+      self.scrollback.clone(),
+    ) {
+      Ok(socket) => Some(socket),
+      Err(e) if e.kind() == io::ErrorKind::AddrInUse => {
+        ui::message(Message::error(e));
+        None
+      }
+      Err(e) => {
+        warn!("Could not open the control socket: {}", e);
+        None
+      }
+    };
+    let proxy = self.project().proxy.clone();
+    if !proxy.routes.is_empty() {
+      // Routes go to the ports picked for components that have been started.
+      let components: Vec<Component> = self
+        .project()
+        .components
+        .iter()
+        .map(|c| {
+          let started = self
+            .allocated_ports
+            .lock()
+            .unwrap()
+            .contains_key(&c.name.to_lowercase());
+          match started {
+            true => self.allocate_ports(c).unwrap_or_else(|_| c.clone()),
+            false => c.clone(),
+          }
+        })
+        .collect();
+      let mut routes = vec![];
+      for route in proxy.routes {
+        match route.target(&components) {
+          Ok(port) => routes.push((route, port)),
+          Err(problem) => self.announce(Message::ConfigWarning { problem }, false),
+        }
+      }
+      match proxy::listen(&proxy.listen, routes) {
+        Ok(address) => self.announce(
+          Message::ProxyListening {
+            address: address.to_string(),
+          },
+          false,
+        ),
+        Err(e) => self.announce(
+          Message::error(format!("Could not listen on {}: {}", proxy.listen, e)),
+          false,
+        ),
+      }
+    }
+    let server = self.options.listen.as_ref().and_then(|address| {
+      match http::listen(
+        address,
+        &self.project().root_path,
+        command_sender.clone(),
+        self.metrics.clone(),
+        self.scrollback.clone(),
+      ) {
+        Ok(server) => {
+          self.announce(
+            Message::Listening {
+              address: server.address.to_string(),
+            },
+            false,
+          );
+          Some(server)
+        }
+        Err(e) => {
+          self.announce(
+            Message::error(format!("Could not listen on {}: {}", address, e)),
+            false,
+          );
+          None
         }
       }
-      drop(workers);
     });
 
     let workers_lock = Arc::clone(&self.workers);
+    let mut environment_up = false;
+    let mut config_modified = self.options.watch.as_ref().and_then(|w| w.modified());
+    let mut config_checked = Instant::now();
+    // Scheduled tasks start once the environment is up. The runs still going are kept so a task
+    // is not started again before its last run finished.
+    let mut scheduler: Option<Scheduler> = None;
+    let mut scheduled_runs: HashMap<String, thread::JoinHandle<()>> = HashMap::new();
+    let mut schedule_checked = Instant::now();
+    // Usage is measured for --stats, and for the dashboard which shows it in its sidebar.
+    let usage_interval = match (self.options.stats, self.options.tui) {
+      (Some(seconds), _) => Some(Duration::from_secs(seconds.max(1))),
+      (None, true) => Some(Duration::from_secs(2)),
+      (None, false) => None,
+    };
+    let mut usage_measured = Instant::now();
+    let mut chaos_struck = Instant::now();
     loop {
-      let mut workers = workers_lock.lock().unwrap();
+      if let Some(interval) = usage_interval {
+        if usage_measured.elapsed() >= interval {
+          usage_measured = Instant::now();
+          self.measure_usage();
+          if self.options.stats.is_some() && self.console() {
+            for r in self.report().iter().filter(|r| r.state.pid.is_some()) {
+              if let Some(usage) = r.state.usage {
+                ui::message(Message::usage(&r.component.name, usage));
+              }
+            }
+          }
+        }
+      }
+      if environment_up && schedule_checked.elapsed() >= Duration::from_secs(1) {
+        schedule_checked = Instant::now();
+        let now = SystemTime::now();
+        let scheduler = scheduler.get_or_insert_with(|| {
+          let (scheduler, problems) = Scheduler::new(&self.project().tasks, now);
+          for problem in problems {
+            self.announce(Message::ConfigWarning { problem }, false);
+          }
+          scheduler
+        });
+        scheduled_runs.retain(|_, run| !run.is_finished());
+        for task in scheduler.due(now) {
+          if scheduled_runs.contains_key(&task) {
+            self.announce(Message::ScheduleSkipped { task }, false);
+            continue;
+          }
+          self.announce(Message::TaskScheduled { task: task.clone() }, false);
+          match self.spawn_task(task.clone()) {
+            Ok(run) => {
+              scheduled_runs.insert(task, run);
+            }
+            Err(e) => self.announce(Message::error(e), false),
+          }
+        }
+      }
+      if let Some(chaos) = &self.options.chaos {
+        if environment_up && chaos_struck.elapsed() >= chaos.interval {
+          chaos_struck = Instant::now();
+          self.unleash(chaos);
+        }
+      }
+      if let Some(watch) = &self.options.watch {
+        if config_checked.elapsed() >= Duration::from_secs(1) {
+          config_checked = Instant::now();
+          let modified = watch.modified();
+          if modified != config_modified {
+            config_modified = modified;
+            self.reload(watch);
+            // Schedules are rebuilt from the tasks of the reloaded config.
+            scheduler = None;
+          }
+        }
+      }
+      let workers = workers_lock.lock().unwrap();
 
       // If there are workers present and all of them have completed we can
-      // hault.
-      if workers.len() > 0 && workers.iter().all(|i| i.completed) {
+      // hault. A session shut down before any component was started ends right away.
+      let ended = !workers.is_empty() || self.shutdown_token.is_cancelled();
+      if ended && workers.iter().all(|i| i.completed) {
         drop(workers);
         break;
       }
+      if self.options.ci
+        && !self.shutdown_token.is_cancelled()
+        && components_finished(&workers, environment_up)
+      {
+        self.shutdown_token.cancel();
+      }
+
+      // The environment is announced once every worker that is still active is ready.
+      if !environment_up && environment_ready(&workers) {
+        environment_up = true;
+        self.announce(Message::EnvironmentUp, false);
+        let mut waiters = self.up_waiters.lock().unwrap();
+        self.up.store(true, Ordering::SeqCst);
+        for w in waiters.drain(..) {
+          let _ = w.send(());
+        }
+      }
+      // When waiting for the environment output is held back until it is up.
+      let quiet = self.options.wait && !environment_up;
 
       // If no workers have been added and or there are no workers currently running
       // we should sleep for moment and wait for a worker to get added to the pool.
       // This assumes init was called before a worker was spawned.
-      if workers.len() == 0 || !workers.iter().any(|i| i.running) {
-        thread::sleep(Duration::from_millis(500));
+      if workers.is_empty() || !workers.iter().any(|i| i.running) {
         drop(workers);
+        // Commands can still spawn components while nothing is running.
+        if let Ok(request) = commands.recv_timeout(self.project().tuning.select_timeout()) {
+          let _ = request.reply.send(self.execute(request.command));
+        }
         continue;
       }
 
-      // Get a list of all workers that are currently running.
-      let mut running_workers = workers
-        .iter_mut()
+      // The receivers of the running workers are selected on without holding the lock on the
+      // workers, so the threads starting components can check on the others meanwhile.
+      let receivers = workers
+        .iter()
         .filter(|i| i.running)
-        .collect::<Vec<&mut Worker>>();
-      // build up a selector for all the receivers on the running workers.
+        .map(|w| w.data_receiver.clone())
+        .collect::<Vec<_>>();
+      drop(workers);
       let mut sel = Select::new();
-      for w in running_workers.iter() {
-        sel.recv(&w.data_receiver);
+      for r in receivers.iter() {
+        sel.recv(r);
       }
-      let timeout = after(Duration::from_millis(500));
+      sel.recv(&self.service_receiver);
+      sel.recv(&commands);
+      let timeout = after(self.project().tuning.select_timeout());
       sel.recv(&timeout);
       // select for a message from one of the workers that has an available message
       let oper = sel.select();
       let index = oper.index();
-      if index == running_workers.len() {
+      if index == receivers.len() + 2 {
         let _ = oper.recv(&timeout);
         debug!("Timeout reading from worker");
-        drop(workers);
+        continue;
+      }
+      if index == receivers.len() {
+        if let Ok(msg) = oper.recv(&self.service_receiver) {
+          self.emit(&msg);
+          match msg.body {
+            ComponentEventBody::ServiceOutput { service_name, body } => {
+              if let Some(service) = self.project().service_by_name(&service_name) {
+                if !quiet && !self.is_muted(&service.name, &body) && self.console() {
+                  crate::ui::service_message(&service, body)
+                }
+              }
+            }
+            ComponentEventBody::ComponentError { body } => {
+              self.announce(Message::Error { error: body }, false)
+            }
+            _ => {}
+          }
+        }
+        continue;
+      }
+      if index == receivers.len() + 1 {
+        let request = oper.recv(&commands);
+        if let Ok(request) = request {
+          let _ = request.reply.send(self.execute(request.command));
+        }
         continue;
       }
 
-      match oper.recv(&running_workers[index].data_receiver) {
+      let msg = oper.recv(&receivers[index]);
+      // Workers are only added while the lock was released, those running are where they were.
+      let mut workers = workers_lock.lock().unwrap();
+      let mut running_workers = workers
+        .iter_mut()
+        .filter(|i| i.running)
+        .collect::<Vec<&mut Worker>>();
+      running_workers[index].backlog.taken();
+      if let Ok(msg) = &msg {
+        self.emit(msg);
+      }
+      match msg {
         Ok(msg) => match msg.body {
-          ComponentEventBody::Output { body } => {
-            crate::ui::component_message(&workers[index].component, body)
+          ComponentEventBody::Output { body, stream } => {
+            self.scrollback.push(&msg.component.name, &body);
+            let rotation = &msg.component.log_rotation;
+            self.journal.output(&msg.component.name, &body, rotation);
+            self.metrics.output_line(&msg.component.name);
+            if let Some(path) = self.log_file(&msg.component) {
+              self.journal.log(&path, &body, rotation);
+            }
+            if !quiet && !self.is_muted(&msg.component.name, &body) && self.console() {
+              let origin = ui::Origin {
+                pid: running_workers[index].pid,
+                restarts: running_workers[index].restarts,
+              };
+              match stream {
+                OutputStream::Stdout => ui::component_message(&msg.component, origin, body),
+                OutputStream::Stderr => ui::component_stderr(&msg.component, origin, body),
+              }
+            }
           }
-          ComponentEventBody::ComponentStart => {
-            crate::ui::system_message(format!("Component {} started", msg.component.name));
+          ComponentEventBody::ComponentStart { pid } => {
+            running_workers[index].pid = pid;
+            running_workers[index].started = Some(SystemTime::now());
+            self.write_state(&workers);
+            self.announce(
+              Message::ComponentStarted {
+                component: msg.component.name.clone(),
+              },
+              quiet,
+            );
             debug!(
               "Current workers: {:?}",
               workers
@@ -275,28 +2054,169 @@ impl Supervisor {
                 .collect::<Vec<String>>()
             );
           }
-          ComponentEventBody::ComponentError { body } => crate::ui::system_error(format!(
-            "Component error [{}]: {}",
-            msg.component.name, body
-          )),
+          ComponentEventBody::ComponentError { body } => self.announce(
+            Message::ComponentError {
+              component: msg.component.name.clone(),
+              error: body,
+            },
+            quiet,
+          ),
+          ComponentEventBody::ComponentReady => {
+            if !running_workers[index].ready {
+              running_workers[index].ready = true;
+              self.write_state(&workers);
+              self.announce(
+                Message::ComponentReady {
+                  component: msg.component.name.clone(),
+                },
+                quiet,
+              );
+              let name = msg.component.name.to_lowercase();
+              if self.cascades.lock().unwrap().remove(&name) {
+                drop(workers);
+                self.restart_dependents(&msg.component.name);
+                continue;
+              }
+            }
+          }
+          ComponentEventBody::ComponentUnhealthy { failures } => {
+            // The worker stops the process, the restart happens once its shutdown is processed.
+            if !running_workers[index].token.is_cancelled() {
+              self
+                .restarts
+                .lock()
+                .unwrap()
+                .insert(msg.component.name.to_lowercase(), msg.component.clone());
+            }
+            self.notify_failure(&msg.component);
+            self.announce(
+              Message::ComponentUnhealthy {
+                component: msg.component.name.clone(),
+                failures,
+              },
+              quiet,
+            );
+          }
           ComponentEventBody::ServiceStart { service_name } => {
-            crate::ui::system_message(format!("Service started {}", service_name))
+            if let Some(service) = self.project().service_by_name(&service_name) {
+              self.follow_service_logs(&service, &msg.component);
+            }
+            self.metrics.service_started(&service_name);
+            self.write_state(&workers);
+            self.announce(
+              Message::ServiceStarted {
+                service: service_name,
+              },
+              quiet,
+            );
+          }
+          ComponentEventBody::ServiceOutput { service_name, body } => {
+            if let Some(service) = self.project().service_by_name(&service_name) {
+              if !quiet && !self.is_muted(&service.name, &body) && self.console() {
+                crate::ui::service_message(&service, body)
+              }
+            }
+          }
+          ComponentEventBody::ServiceFailed {
+            service_name,
+            error,
+          } => {
+            self.forget_service(&service_name);
+            self.record(ExitCode::Service);
+            self.metrics.service_failure(&service_name);
+            self.announce(
+              Message::ServiceStartFailed {
+                service: service_name,
+                error,
+              },
+              false,
+            );
           }
-          ComponentEventBody::ComponentShutdown => {
-            crate::ui::system_message(format!("Component {} shutdown", msg.component.name));
-            if msg.component.retry && !running_workers[index].completed {
+          ComponentEventBody::ComponentShutdown { failed, exit_code } => {
+            running_workers[index].exit_code = exit_code;
+            if let Some(started) = running_workers[index].started {
+              let ran = started.elapsed().unwrap_or_default();
+              self.metrics.component_ran(&msg.component.name, ran);
+            }
+            // Retried components recover from failures by being restarted, unless they keep
+            // failing.
+            let mut crash_looping = false;
+            if failed {
+              running_workers[index].failed = true;
+              self.notify_failure(&msg.component);
+              crash_looping = msg.component.retry
+                && !running_workers[index].token.is_cancelled()
+                && self.crash_looping(&msg.component);
+              if !msg.component.retry || crash_looping {
+                self.record(ExitCode::Component);
+                if self.options.fail_fast && !self.shutdown_token.is_cancelled() {
+                  self.announce(
+                    Message::FailFast {
+                      component: msg.component.name.clone(),
+                    },
+                    false,
+                  );
+                  self.shutdown_token.cancel();
+                }
+              }
+            }
+            let shutdown = match exit_code {
+              Some(code) => Message::ComponentExited {
+                component: msg.component.name.clone(),
+                code,
+              },
+              None => Message::ComponentShutdown {
+                component: msg.component.name.clone(),
+              },
+            };
+            self.announce(shutdown, false);
+            // A requested restart replaces the component with the definition it was given.
+            let replacement = self
+              .restarts
+              .lock()
+              .unwrap()
+              .remove(&msg.component.name.to_lowercase());
+            let retry =
+              msg.component.retry && !crash_looping && !running_workers[index].token.is_cancelled();
+            if (replacement.is_some() || retry) && !running_workers[index].completed {
               info!("component {} as retry enabled", &msg.component.name);
-              // We need to drop workers here to release the lock because spawn_component will attempt to
+              // We need to drop workers here to release the lock because spawn_worker will attempt to
               // get a lock.
               let extra_env = running_workers[index].extra_env.clone();
+              let group = running_workers[index].group.clone();
+              running_workers[index].completed = true;
+              self.write_state(&workers);
               drop(workers);
-              if running.load(Ordering::SeqCst) {
-                self.spawn_component(&msg.component.clone(), extra_env);
+              // Dependents are restarted once the new run is ready, so they connect to it.
+              let component = replacement.as_ref().unwrap_or(&msg.component);
+              if component.restart_dependents {
+                self
+                  .cascades
+                  .lock()
+                  .unwrap()
+                  .insert(component.name.to_lowercase());
+              }
+              // The replacement worker is spawned before releasing the old one so shared
+              // services are not bounced during the restart.
+              match replacement {
+                Some(component) => self.spawn_worker(&component, extra_env, group, None, None),
+                None => self.spawn_worker(
+                  &msg.component,
+                  extra_env,
+                  group,
+                  msg.component.restart_delay,
+                  None,
+                ),
               }
+              self.stop_unused_services(&msg.component);
               continue;
-            } else {
+            } else if !running_workers[index].completed {
               info!("component {} has completed", &msg.component.name);
               running_workers[index].completed = true;
+              self.write_state(&workers);
+              drop(workers);
+              self.stop_unused_services(&msg.component);
+              continue;
             }
           }
         },
@@ -304,63 +2224,617 @@ impl Supervisor {
           // The worker's data channel erorred/closed mark this worker as no longer running.
           info!("channel closed marking worker complete");
           running_workers[index].running = false;
-          running_workers[index].completed = true;
-          let _ = running_workers[index].kill_signal.send(());
+          running_workers[index].token.cancel();
+          if !running_workers[index].completed {
+            running_workers[index].completed = true;
+            let component = running_workers[index].component.clone();
+            drop(workers);
+            self.stop_unused_services(&component);
+          }
         }
       };
     }
 
-    // Using a hash set here to get unique service names so we
-    // shutdown each one once.
-    let mut services = HashSet::new();
-    let workers = self.workers.lock().unwrap();
-    for worker in workers.iter() {
-      for service_name in worker.component.services.iter() {
-        services.insert(service_name);
+    // Every consumer has completed at this point, but any services still marked as used are
+    // stopped so nothing is left running.
+    let remaining: Vec<String> = self
+      .service_usage
+      .lock()
+      .unwrap()
+      .drain()
+      .map(|(name, _)| name)
+      .collect();
+    let services = remaining
+      .iter()
+      .filter_map(|name| self.project().service_by_name(name))
+      .collect();
+    for result in ServiceTerminator::new(services, &self.project().root_path) {
+      match result {
+        Ok(s) => {
+          self.metrics.service_stopped(&s.name);
+          self.announce(Message::ServiceStopped { service: s.name }, false)
+        }
+        Err((s, e)) => {
+          self.record(ExitCode::Service);
+          self.announce(
+            Message::ServiceStopFailed {
+              service: s.name,
+              error: e.to_string(),
+            },
+            false,
+          )
+        }
+      }
+    }
+    if self.console() {
+      let states = component_states(&self.workers.lock().unwrap());
+      ui::summary(&self.metrics.summary(states), &self.metrics.services());
+    }
+    let _ = SessionState::remove(&self.project().root_path);
+    drop(control);
+    drop(server);
+    self.project().terminal.clear();
+    self.up_waiters.lock().unwrap().clear();
+    self.commands.lock().unwrap().take();
+  }
+
+  /// Replaces the project with the changed config file. Added components are started, removed
+  /// ones are stopped and running components whose definition changed are restarted. A config
+  /// that fails to load is reported and the session carries on as it was. Env commands run again
+  /// for the components started from then on.
+  fn reload(&self, watch: &ConfigWatch) {
+    let mut project = match watch.load() {
+      Ok(project) => project,
+      Err(e) => {
+        let problem = e.to_string();
+        return self.announce(Message::ConfigError { problem }, false);
+      }
+    };
+    reload::keep_colors(&self.project(), &mut project);
+    forget_env_commands();
+    let changes = reload::changes(&self.project(), &project);
+    *self.project.write().unwrap() = project;
+    for c in changes.removed.iter().filter(|c| self.is_running(&c.name)) {
+      self.announce(
+        Message::ComponentRemoved {
+          component: c.name.clone(),
+        },
+        false,
+      );
+      self.cancel_component(&c.name);
+    }
+    for c in changes.changed.iter().filter(|c| self.is_running(&c.name)) {
+      self.announce(
+        Message::ComponentRestarting {
+          component: c.name.clone(),
+        },
+        false,
+      );
+      // The component is respawned with its new definition once its shutdown is processed.
+      self
+        .restarts
+        .lock()
+        .unwrap()
+        .insert(c.name.to_lowercase(), c.clone());
+      self.cancel_component(&c.name);
+    }
+    for c in changes.added.iter() {
+      self.announce(
+        Message::ComponentAdded {
+          component: c.name.clone(),
+        },
+        false,
+      );
+      self.spawn_component(c, HashMap::new());
+    }
+    self.announce(
+      Message::ConfigReloaded {
+        path: watch.path.display().to_string(),
+      },
+      false,
+    );
+  }
+
+  /// Where a component's output is logged, if anywhere.
+  fn log_file(&self, component: &Component) -> Option<PathBuf> {
+    match &component.log_file {
+      Some(file) => Some(self.project().root_path.join(expand_env(file))),
+      None => self
+        .options
+        .log_dir
+        .as_ref()
+        .map(|dir| dir.join(format!("{}.log", component.name))),
+    }
+  }
+
+  /// Records a message in the journal and shows it. Quiet messages are only shown if they are
+  /// errors.
+  /// Writes a component event as a line of JSON when the json output format is used. Output
+  /// from muted components and services is left out.
+  fn emit(&self, event: &ComponentEvent) {
+    self.publish(SessionEvent::Component(Box::new(event.clone())));
+    self.trace(event);
+    let color = match &event.body {
+      ComponentEventBody::ServiceOutput { service_name, .. } => self
+        .project()
+        .service_by_name(service_name)
+        .map(|s| s.color)
+        .unwrap_or_default(),
+      _ => event.component.color.clone(),
+    };
+    self.journal.component(event, &color);
+    if !ui::json() || !self.console() {
+      return;
+    }
+    let muted = match &event.body {
+      ComponentEventBody::Output { body, .. } => self.is_muted(&event.component.name, body),
+      ComponentEventBody::ServiceOutput { service_name, body } => self.is_muted(service_name, body),
+      _ => false,
+    };
+    if !muted {
+      let (name, body) = event.body.describe();
+      ui::event("component", &event.component.name, name, body);
+    }
+  }
+
+  /// Marks the event on the span of the component's run, ending it when the component shuts
+  /// down.
+  fn trace(&self, event: &ComponentEvent) {
+    let mut spans = self.spans.lock().unwrap();
+    let key = event.component.name.to_lowercase();
+    match &event.body {
+      ComponentEventBody::ComponentStart { pid } => {
+        if let Some(span) = spans.get_mut(&key) {
+          span.event("start");
+          if let Some(pid) = pid {
+            span.set("process.pid", *pid);
+          }
+        }
+      }
+      ComponentEventBody::ComponentReady => {
+        if let Some(span) = spans.get_mut(&key) {
+          span.event("ready");
+        }
+      }
+      ComponentEventBody::ComponentShutdown { failed, exit_code } => {
+        if let Some(mut span) = spans.remove(&key) {
+          if let Some(code) = exit_code {
+            span.set("process.exit_code", *code);
+          }
+          span.end(*failed);
+        }
+      }
+      _ => {}
+    }
+  }
+
+  /// Notifies about a failed component. Retried components are only notified about once they
+  /// are crash looping.
+  fn notify_failure(&self, component: &Component) {
+    if component.retry {
+      return;
+    }
+    let project = self.project().name.clone();
+    let text = Message::ComponentFailed {
+      project: project.clone(),
+      component: component.name.clone(),
+    };
+    self.project().notifications.send(
+      NotificationEvent::Failure,
+      &project,
+      &component.name,
+      text.to_string(),
+    );
+  }
+
+  /// Records a failure of a retried component. Returns true when it has failed too often
+  /// within its crash loop window to be restarted again, which is announced and notified.
+  fn crash_looping(&self, component: &Component) -> bool {
+    let limit = &component.crash_loop;
+    if limit.failures == 0 {
+      return false;
+    }
+    let mut failures = self.failures.lock().unwrap();
+    let recent = failures.entry(component.name.clone()).or_default();
+    let now = Instant::now();
+    recent.push_back(now);
+    while recent
+      .front()
+      .is_some_and(|t| now.duration_since(*t) > Duration::from_secs(limit.window_secs))
+    {
+      recent.pop_front();
+    }
+    if recent.len() < limit.failures {
+      return false;
+    }
+    // A component restarted by hand later gets a fresh window.
+    recent.clear();
+    drop(failures);
+    let project = self.project().name.clone();
+    let msg = Message::ComponentCrashLooping {
+      project: project.clone(),
+      component: component.name.clone(),
+      failures: limit.failures,
+      seconds: limit.window_secs,
+    };
+    self.project().notifications.send(
+      NotificationEvent::CrashLoop,
+      &project,
+      &component.name,
+      msg.to_string(),
+    );
+    self.announce(msg, false);
+    true
+  }
+
+  fn announce(&self, msg: Message, quiet: bool) {
+    self.journal.event(&msg.to_string());
+    self.journal.message(&msg);
+    self.publish(SessionEvent::Message(msg.clone()));
+    if (!quiet || msg.is_error()) && self.console() {
+      ui::message(msg);
+    }
+  }
+
+  /// Runs a task on its own thread so the session keeps streaming output meanwhile. Returns the
+  /// thread, which finishes with the task.
+  fn spawn_task(&self, name: String) -> Result<thread::JoinHandle<()>, String> {
+    let project = self.project().clone();
+    if let Some(tasks) = project.foreach_tasks(&name) {
+      let tasks = tasks?;
+      return Ok(thread::spawn(move || {
+        let supr = Supervisor::new(&project);
+        crate::project::run_foreach(&supr, &name, tasks, false);
+      }));
+    }
+    let task = project
+      .find_task(&name)
+      .ok_or_else(|| format!("Unknown task {}", name))?;
+    if let Some(e) = task.unavailable {
+      return Err(e);
+    }
+    Ok(thread::spawn(move || {
+      let supr = Supervisor::new(&project);
+      let t = task.clone();
+      for cmd in task {
+        if !supr.run_task_command(&t, cmd) {
+          break;
+        }
+      }
+    }))
+  }
+
+  /// Kills each running component with the chance chaos gives it. The component is killed as if
+  /// it crashed, so it is retried or left failed as configured.
+  fn unleash(&self, chaos: &Chaos) {
+    if self.shutdown_token.is_cancelled() {
+      return;
+    }
+    let running: Vec<(String, u32)> = self
+      .workers
+      .lock()
+      .unwrap()
+      .iter()
+      .filter(|w| w.running && !w.completed && !w.token.is_cancelled())
+      .filter_map(|w| Some((w.component.name.clone(), w.pid?)))
+      .collect();
+    for (component, pid) in running {
+      if !chaos.strikes() {
+        continue;
+      }
+      self.announce(
+        Message::ChaosKilled {
+          component: component.clone(),
+        },
+        false,
+      );
+      if let Err(e) = process::kill_group(pid) {
+        warn!("Could not kill {}: {}", component, e);
+      }
+    }
+  }
+
+  /// Records the CPU and memory last measured for the processes of each running component in
+  /// the state file. They are measured on the monitor's thread, which is started by the first
+  /// call, so components have no usage until it has measured them.
+  fn measure_usage(&self) {
+    let monitor = self.usage_monitor.get_or_init(|| {
+      UsageMonitor::start(Duration::from_secs(self.options.stats.unwrap_or(2).max(1)))
+    });
+    let mut workers = self.workers.lock().unwrap();
+    let pids = workers
+      .iter()
+      .filter(|w| !w.completed)
+      .filter_map(|w| w.pid)
+      .collect();
+    let usage = monitor.watch(pids);
+    for w in workers.iter_mut() {
+      w.usage = match w.completed {
+        true => None,
+        false => w.pid.and_then(|pid| usage.get(&pid).cloned()),
+      };
+    }
+    self.write_state(&workers);
+  }
+
+  /// Writes the current state of all workers to the session state file. Workers for a component
+  /// that has been relaunched are superseded by the most recent worker for that component.
+  fn write_state(&self, workers: &[Worker]) {
+    let mut components = component_states(workers);
+    // Recorded so a later session can tell the processes apart from ones that reuse their pids.
+    for c in components.iter_mut() {
+      c.process = c
+        .pid
+        .filter(|_| c.status == ComponentStatus::Running)
+        .and_then(process::identity);
+    }
+    let mut services: Vec<ServiceState> = vec![];
+    for w in workers.iter() {
+      if w.completed {
+        continue;
+      }
+      for service in w
+        .component
+        .services
+        .iter()
+        .filter_map(|sn| self.project().service_by_name(sn))
+      {
+        if !services.iter().any(|s| s.name == service.name) {
+          services.push(ServiceState {
+            container: service.get_container_name(),
+            name: service.name,
+          });
+        }
       }
     }
-    for service_name in services {
-      if let Some(service) = self.project.service_by_name(service_name) {
-        let _ = service.stop();
+    ui::align(
+      workers
+        .iter()
+        .filter(|w| !w.completed)
+        .map(|w| w.component.name.as_str())
+        .chain(services.iter().map(|s| s.name.as_str())),
+    );
+    self
+      .project()
+      .terminal
+      .update(&self.project().name, &components);
+    let state = SessionState {
+      project: self.project().name.clone(),
+      pid: std::process::id(),
+      process: process::identity(std::process::id()),
+      components,
+      services,
+    };
+    if let Err(e) = state.write(&self.project().root_path) {
+      warn!("Could not write state file: {}", e);
+    }
+  }
+}
+
+/// A supervisor running on its own thread, returned by Supervisor::start.
+pub struct SupervisorHandle {
+  supervisor: Arc<Supervisor>,
+  thread: thread::JoinHandle<()>,
+}
+
+impl SupervisorHandle {
+  /// Starts a component in the running session.
+  pub fn spawn_component(&self, component: &Component, extra_env: HashMap<String, String>) {
+    self.supervisor.spawn_component(component, extra_env)
+  }
+
+  /// Stops a running component.
+  pub fn stop_component(&self, name: &str) -> Result<(), String> {
+    self.execute(Command::Stop(name.to_string()))
+  }
+
+  /// Stops a component and starts it again.
+  pub fn restart_component(&self, name: &str) -> Result<(), String> {
+    self.execute(Command::Restart(name.to_string()))
+  }
+
+  /// Carries out a command against the running session, as if it was typed into the palette.
+  /// Waits for the session to start if it has not yet.
+  pub fn execute(&self, command: Command) -> Result<(), String> {
+    while !self.thread.is_finished() {
+      let sender = self.supervisor.commands.lock().unwrap().clone();
+      match sender {
+        Some(sender) => return submit(&sender, command),
+        None => thread::sleep(self.supervisor.project().tuning.select_timeout()),
       }
-      crate::ui::system_message(format!("Service stopped {}", service_name))
     }
+    Err("The session is not running".into())
+  }
+
+  /// Returns a receiver that gets every event of the session from now on.
+  pub fn subscribe(&self) -> Receiver<SessionEvent> {
+    self.supervisor.subscribe()
+  }
+
+  /// Returns a receiver that gets a message once every component is ready.
+  pub fn environment_up(&self) -> Receiver<()> {
+    self.supervisor.environment_up()
+  }
+
+  /// Stops every component and service. Use SupervisorHandle::wait to wait for them to stop.
+  pub fn shutdown(&self) {
+    self.supervisor.shutdown_token.cancel();
+  }
+
+  /// Waits for the session to end and returns how it went. A session without components has
+  /// nothing to wait for, so it is shut down and returns at once.
+  pub fn wait(self) -> ExitCode {
+    if self.supervisor.workers.lock().unwrap().is_empty() {
+      self.shutdown();
+    }
+    let _ = self.thread.join();
+    self.supervisor.exit_code()
   }
 }
 
 struct Worker {
-  pub project: Project,
-  pub kill_signal: Sender<()>,
+  pub token: CancellationToken,
+  pub group: Option<String>,
+  pub done: Receiver<()>,
   pub running: bool,
   pub completed: bool,
+  pub failed: bool,
+  pub ready: bool,
+  pub pid: Option<u32>,
   pub component: Component,
   pub data_receiver: Receiver<ComponentEvent>,
+  /// Wakes the threads reading output when events are taken off the data receiver.
+  backlog: Arc<Backlog>,
   pub extra_env: HashMap<String, String>,
+  /// Text written to the process's stdin.
+  pub input: Sender<String>,
+  /// The exit code of the process, if it exited on its own.
+  pub exit_code: Option<i32>,
+  /// When the process was started.
+  pub started: Option<SystemTime>,
+  /// The number of workers that ran the component before this one.
+  pub restarts: usize,
+  /// The CPU and memory used by the component's processes when they were last measured.
+  usage: Option<Usage>,
+  /// Whether the process is frozen, shared with the thread running it.
+  paused: Arc<AtomicBool>,
+}
+
+impl Worker {
+  fn state(&self) -> ComponentState {
+    let status = if self.completed && self.failed {
+      ComponentStatus::Failed
+    } else if self.completed {
+      ComponentStatus::Stopped
+    } else if self.pid.is_some() {
+      ComponentStatus::Running
+    } else {
+      ComponentStatus::Starting
+    };
+    ComponentState {
+      name: self.component.name.clone(),
+      status,
+      ready: self.ready,
+      pid: self.pid,
+      ports: self.component.ports.clone(),
+      exit_code: self.exit_code,
+      started: self
+        .started
+        .and_then(|s| s.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs()),
+      restarts: self.restarts,
+      services: self.component.services.clone(),
+      usage: self.usage,
+      paused: self.paused.load(Ordering::SeqCst),
+      process: None,
+    }
+  }
 }
 
-#[derive(Debug, PartialEq)]
-enum ComponentEventBody {
-  Output { body: String },
-  ComponentStart,
-  ComponentShutdown,
-  ServiceStart { service_name: String },
+/// What happened to a component.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ComponentEventBody {
+  /// A line of output.
+  Output {
+    body: String,
+    stream: OutputStream,
+  },
+  /// The process was started.
+  ComponentStart {
+    pid: Option<u32>,
+  },
+  /// The component passed its readiness checks.
+  ComponentReady,
+  /// The component failed its health checks and is being stopped to be restarted.
+  ComponentUnhealthy {
+    failures: u32,
+  },
+  /// The process exited or was stopped.
+  ComponentShutdown {
+    failed: bool,
+    exit_code: Option<i32>,
+  },
+  /// A service the component uses was started.
+  ServiceStart {
+    service_name: String,
+  },
+  /// A service the component uses could not be started.
+  ServiceFailed {
+    service_name: String,
+    error: String,
+  },
+  /// A line of output from a service the component uses.
+  ServiceOutput {
+    service_name: String,
+    body: String,
+  },
   // ServiceShutdown { service_name: String },
-  ComponentError { body: String },
+  ComponentError {
+    body: String,
+  },
+}
+
+/// The stream a line of component output was written to. Output is tagged as stdout when the
+/// streams are merged.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputStream {
+  Stdout,
+  Stderr,
+}
+
+impl ComponentEventBody {
+  /// The name of the event and its details, as written in the json output format.
+  pub(crate) fn describe(&self) -> (&'static str, serde_json::Value) {
+    match self {
+      ComponentEventBody::Output { body, stream } => match stream {
+        OutputStream::Stdout => ("output", body.as_str().into()),
+        OutputStream::Stderr => ("stderr", body.as_str().into()),
+      },
+      ComponentEventBody::ComponentStart { pid } => ("start", serde_json::json!({ "pid": pid })),
+      ComponentEventBody::ComponentReady => ("ready", serde_json::Value::Null),
+      ComponentEventBody::ComponentUnhealthy { failures } => {
+        ("unhealthy", serde_json::json!({ "failures": failures }))
+      }
+      ComponentEventBody::ComponentShutdown { failed, exit_code } => (
+        "shutdown",
+        serde_json::json!({ "failed": failed, "exit_code": exit_code }),
+      ),
+      ComponentEventBody::ServiceStart { service_name } => (
+        "service_start",
+        serde_json::json!({ "service": service_name }),
+      ),
+      ComponentEventBody::ServiceFailed {
+        service_name,
+        error,
+      } => (
+        "service_failed",
+        serde_json::json!({ "service": service_name, "error": error }),
+      ),
+      ComponentEventBody::ServiceOutput { service_name, body } => (
+        "service_output",
+        serde_json::json!({ "service": service_name, "output": body }),
+      ),
+      ComponentEventBody::ComponentError { body } => ("error", body.as_str().into()),
+    }
+  }
 }
 
 /// Used to send events from a running component. Holds a copy of the component itself as well
 /// as the event that occured.
-#[derive(Debug, PartialEq)]
-struct ComponentEvent {
+#[derive(Clone, Debug, PartialEq)]
+pub struct ComponentEvent {
   pub component: Component,
   pub body: ComponentEventBody,
 }
 
 impl ComponentEvent {
   pub fn output(component: Component, body: String) -> Self {
+    ComponentEvent::output_on(component, body, OutputStream::Stdout)
+  }
+  pub fn output_on(component: Component, body: String, stream: OutputStream) -> Self {
     ComponentEvent {
       component,
-      body: ComponentEventBody::Output { body },
+      body: ComponentEventBody::Output { body, stream },
     }
   }
   pub fn error(component: Component, body: String) -> Self {
@@ -369,16 +2843,37 @@ impl ComponentEvent {
       body: ComponentEventBody::ComponentError { body },
     }
   }
-  pub fn start(component: Component) -> Self {
+  pub fn start(component: Component, pid: Option<u32>) -> Self {
+    ComponentEvent {
+      component,
+      body: ComponentEventBody::ComponentStart { pid },
+    }
+  }
+  pub fn ready(component: Component) -> Self {
+    ComponentEvent {
+      component,
+      body: ComponentEventBody::ComponentReady,
+    }
+  }
+  pub fn unhealthy(component: Component, failures: u32) -> Self {
+    ComponentEvent {
+      component,
+      body: ComponentEventBody::ComponentUnhealthy { failures },
+    }
+  }
+  pub fn shutdown(component: Component, failed: bool) -> Self {
     ComponentEvent {
       component,
-      body: ComponentEventBody::ComponentStart,
+      body: ComponentEventBody::ComponentShutdown {
+        failed,
+        exit_code: None,
+      },
     }
   }
-  pub fn shutdown(component: Component) -> Self {
+  pub fn exited(component: Component, failed: bool, exit_code: Option<i32>) -> Self {
     ComponentEvent {
       component,
-      body: ComponentEventBody::ComponentShutdown,
+      body: ComponentEventBody::ComponentShutdown { failed, exit_code },
     }
   }
   pub fn service_start(component: Component, service_name: String) -> Self {
@@ -387,11 +2882,290 @@ impl ComponentEvent {
       body: ComponentEventBody::ServiceStart { service_name },
     }
   }
+  pub fn service_failed(component: Component, service_name: String, error: String) -> Self {
+    ComponentEvent {
+      component,
+      body: ComponentEventBody::ServiceFailed {
+        service_name,
+        error,
+      },
+    }
+  }
+  pub fn service_output(component: Component, service_name: String, body: String) -> Self {
+    ComponentEvent {
+      component,
+      body: ComponentEventBody::ServiceOutput { service_name, body },
+    }
+  }
+}
+
+/// The output of a component's process, its errors included, and where its input is written
+/// when that is not the process's own stdin.
+type Streams = (
+  Box<dyn Read + Send>,
+  Option<Box<dyn Read + Send>>,
+  Option<Box<dyn Write + Send>>,
+);
+
+/// Connects the streams of a component's command. Errors get a stream of their own unless
+/// `merge` is set. Under a pseudo-terminal every stream uses the child's side and the
+/// controlling side is returned.
+fn connect(
+  command: &mut std::process::Command,
+  pty: Option<(File, File)>,
+  merge: bool,
+) -> io::Result<Streams> {
+  match pty {
+    Some((master, slave)) => {
+      command
+        .stdin(slave.try_clone()?)
+        .stderr(slave.try_clone()?)
+        .stdout(slave);
+      Ok((Box::new(master.try_clone()?), None, Some(Box::new(master))))
+    }
+    None if merge => {
+      let (reader, writer) = io::pipe()?;
+      command
+        .stdin(Stdio::piped())
+        .stderr(writer.try_clone()?)
+        .stdout(writer);
+      Ok((Box::new(reader), None, None))
+    }
+    None => {
+      let (reader, writer) = io::pipe()?;
+      let (errors, error_writer) = io::pipe()?;
+      command
+        .stdin(Stdio::piped())
+        .stderr(error_writer)
+        .stdout(writer);
+      Ok((Box::new(reader), Some(Box::new(errors)), None))
+    }
+  }
+}
+
+/// Sends a line of a component's output to the session. Under the drop policy a line that does
+/// not fit is counted instead, and the count is sent as a marker ahead of the next line that
+/// does.
+fn forward_output(
+  sender: &Sender<ComponentEvent>,
+  backlog: &Backlog,
+  component: &Component,
+  body: String,
+  stream: OutputStream,
+  overflow: Overflow,
+  dropped: &AtomicUsize,
+) {
+  let event = ComponentEvent::output_on(component.clone(), body, stream);
+  if overflow == Overflow::Block {
+    backlog.admit(sender, true);
+    let _ = sender.send(event);
+    return;
+  }
+  let lines = dropped.swap(0, Ordering::SeqCst);
+  if lines > 0 {
+    if !backlog.admit(sender, false) {
+      dropped.fetch_add(lines + 1, Ordering::SeqCst);
+      return;
+    }
+    let marker = Message::OutputDropped { lines }.to_string();
+    let _ = sender.send(ComponentEvent::output_on(component.clone(), marker, stream));
+  }
+  if backlog.admit(sender, false) {
+    let _ = sender.send(event);
+  } else {
+    dropped.fetch_add(1, Ordering::SeqCst);
+  }
+}
+
+/// Bounds the events waiting on a worker's channel for the output sent to it. The channel also
+/// carries the worker's other events, which are sent from the event loop itself and would
+/// deadlock it if they had to wait for room, so it is unbounded and only output is held back or
+/// dropped once `capacity` events are waiting.
+struct Backlog {
+  capacity: usize,
+  lock: Mutex<()>,
+  taken: Condvar,
+}
+
+impl Backlog {
+  fn new(capacity: usize) -> Self {
+    Backlog {
+      capacity: capacity.max(1),
+      lock: Mutex::new(()),
+      taken: Condvar::new(),
+    }
+  }
+
+  /// Whether there is room for a line of output, waiting until there is if `wait` is set.
+  fn admit(&self, sender: &Sender<ComponentEvent>, wait: bool) -> bool {
+    let mut guard = self.lock.lock().unwrap();
+    while sender.len() >= self.capacity {
+      if !wait {
+        return false;
+      }
+      // The timeout keeps a reader from waiting forever on a session that stopped reading.
+      guard = self
+        .taken
+        .wait_timeout(guard, Duration::from_millis(100))
+        .unwrap()
+        .0;
+    }
+    true
+  }
+
+  /// Wakes the readers waiting for room, called once an event is taken off the channel.
+  fn taken(&self) {
+    let _guard = self.lock.lock().unwrap();
+    self.taken.notify_all();
+  }
+}
+
+/// Runs a component's build or hook commands in order with its environment in its path. Their
+/// output is shown as the component's output and a failing command skips the rest. Returns true
+/// if every command succeeded.
+fn run_commands(
+  component: &Component,
+  kind: &str,
+  commands: &[String],
+  path: &Path,
+  env: &[(String, String)],
+  sender: &Sender<ComponentEvent>,
+  merge_stderr: bool,
+) -> bool {
+  for command in commands {
+    let popen = Exec::shell(command)
+      .env_extend(env)
+      .cwd(path)
+      .stdout(Redirection::Pipe)
+      .stderr(match merge_stderr {
+        true => Redirection::Merge,
+        false => Redirection::Pipe,
+      })
+      .popen();
+    let mut p = match popen {
+      Ok(p) => p,
+      Err(e) => {
+        let _ = sender.send(ComponentEvent::error(component.clone(), format!("{}", e)));
+        return false;
+      }
+    };
+    // stderr is read on a thread of its own so neither pipe fills up while the other is read.
+    let errors = p.stderr.take().map(|stderr| {
+      let sender = sender.clone();
+      let component = component.clone();
+      thread::spawn(move || {
+        read_lines(stderr, lines::DEFAULT_CAPACITY, |body| {
+          let body = component.ansi.apply(body);
+          let _ = sender.send(ComponentEvent::output_on(
+            component.clone(),
+            body,
+            OutputStream::Stderr,
+          ));
+        })
+      })
+    });
+    if let Some(stdout) = p.stdout.take() {
+      read_lines(stdout, lines::DEFAULT_CAPACITY, |body| {
+        let body = component.ansi.apply(body);
+        let _ = sender.send(ComponentEvent::output(component.clone(), body));
+      });
+    }
+    if let Some(errors) = errors {
+      let _ = errors.join();
+    }
+    if !p.wait().map(|s| s.success()).unwrap_or(false) {
+      let _ = sender.send(ComponentEvent::error(
+        component.clone(),
+        format!("{} {} failed", kind, command),
+      ));
+      return false;
+    }
+  }
+  true
+}
+
+/// Forwards lines typed into the command palette, those starting with a `:`, until stdin closes.
+/// Commands that cannot be parsed or fail are reported here.
+#[cfg(not(unix))]
+fn read_commands(sender: Sender<Request>) {
+  for line in std::io::stdin().lock().lines().map_while(Result::ok) {
+    let command = match line.trim().strip_prefix(':') {
+      Some(command) => command.parse(),
+      None => continue,
+    };
+    if let Err(e) = command.and_then(|command| submit(&sender, command)) {
+      ui::message(Message::Error { error: e });
+    }
+  }
+}
+
+/// Forwards lines read from stdin to running components until stdin closes. Lines starting with a
+/// `:` are read as commands instead.
+fn broadcast_input(sender: Sender<Request>, names: Vec<String>) {
+  for line in std::io::stdin().lock().lines().map_while(Result::ok) {
+    let command = match line.trim().strip_prefix(':') {
+      Some(command) => command.parse(),
+      None => Ok(Command::Broadcast(names.clone(), line)),
+    };
+    if let Err(e) = command.and_then(|command| submit(&sender, command)) {
+      ui::message(Message::Error { error: e });
+    }
+  }
+}
+
+/// Sends a command to the session and waits for its result.
+pub(crate) fn submit(sender: &Sender<Request>, command: Command) -> Result<(), String> {
+  let (request, result) = Request::new(command);
+  sender
+    .send(request)
+    .map_err(|_| "The session is shutting down".to_string())?;
+  result
+    .recv()
+    .unwrap_or_else(|_| Err("The session is shutting down".into()))
+}
+
+/// The state of each component, restarted components are listed once with their latest state.
+fn component_states(workers: &[Worker]) -> Vec<ComponentState> {
+  let mut components: Vec<ComponentState> = vec![];
+  for w in workers.iter() {
+    let state = w.state();
+    match components.iter_mut().find(|c| c.name == state.name) {
+      Some(c) => *c = state,
+      None => components.push(state),
+    }
+  }
+  components
+}
+
+/// Returns true if every component expected to exit on its own has. Components that are kept
+/// alive or retried run until the session ends, when there are only those the environment
+/// coming up is enough.
+fn components_finished(workers: &[Worker], environment_up: bool) -> bool {
+  let mut finite = workers
+    .iter()
+    .filter(|w| !w.component.keep_alive && !w.component.retry)
+    .peekable();
+  match finite.peek() {
+    Some(_) => finite.all(|w| w.completed),
+    None => environment_up,
+  }
+}
+
+/// Returns true if there are active workers and all of them are ready.
+fn environment_ready(workers: &[Worker]) -> bool {
+  let mut active = workers.iter().filter(|w| !w.completed).peekable();
+  active.peek().is_some() && active.all(|w| w.ready)
 }
 
 /// Expands a string using environment variables.
-/// Environment variables are detected as %VAR% and replaced with the coorisponding
-/// environment variable value
-fn expand_env(str: &str) -> String {
-  expand_str::expand_string_with_env(str).unwrap_or_else(|_| str.to_string())
+/// Environment variables are detected as $VAR, ${VAR} or %VAR% and replaced with the
+/// coorisponding environment variable value, see expand::expand.
+pub(crate) fn expand_env(str: &str) -> String {
+  expand::expand(str, |name| std::env::var(name).ok())
+}
+
+/// Expands a string like expand_env for a launch, also replacing calls such as `${uuid()}` with
+/// their result.
+pub(crate) fn expand_launch(str: &str, functions: &CallSite) -> String {
+  expand::expand_calling(str, |name| std::env::var(name).ok(), functions)
 }
@@ -1,5 +1,130 @@
+use crate::platform;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::time::Duration;
+
+/// A project task as configured. Tasks that only have commands are written as the list of them.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug, Default)]
+#[serde(from = "TaskConfig", into = "TaskConfig")]
+pub struct ProjectTask {
+  pub commands: Vec<String>,
+  /// The commands on Windows, in place of `commands`.
+  pub commands_windows: Option<Vec<String>>,
+  /// The commands on unix like systems, in place of `commands`.
+  pub commands_unix: Option<Vec<String>>,
+  /// Runs the task periodically while the environment is up, every interval such as `30m` or
+  /// at the times matching a cron expression such as `0 * * * *`.
+  pub schedule: Option<String>,
+  /// Seconds a command of the task may run before it is killed and the task fails.
+  pub timeout: Option<u64>,
+  /// The directory the commands run in, relative to the project root.
+  pub path: Option<String>,
+  /// Environment variables for the commands.
+  pub env: HashMap<String, String>,
+  /// Runs the commands in each component's directory with its environment instead, for every
+  /// component or those listed.
+  pub foreach_components: Option<Foreach>,
+}
+
+/// The components a task runs in, `true` for every component or a list of their names.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(untagged)]
+pub enum Foreach {
+  All(bool),
+  Only(Vec<String>),
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+enum TaskConfig {
+  Commands(Vec<String>),
+  Full {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    commands: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    commands_windows: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    commands_unix: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    schedule: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timeout: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    env: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    foreach_components: Option<Foreach>,
+  },
+}
+
+impl From<TaskConfig> for ProjectTask {
+  fn from(config: TaskConfig) -> Self {
+    match config {
+      TaskConfig::Commands(commands) => ProjectTask {
+        commands,
+        ..ProjectTask::default()
+      },
+      TaskConfig::Full {
+        commands,
+        commands_windows,
+        commands_unix,
+        schedule,
+        timeout,
+        path,
+        env,
+        foreach_components,
+      } => ProjectTask {
+        commands,
+        commands_windows,
+        commands_unix,
+        schedule,
+        timeout,
+        path,
+        env,
+        foreach_components,
+      },
+    }
+  }
+}
+
+impl From<ProjectTask> for TaskConfig {
+  fn from(task: ProjectTask) -> Self {
+    let only_commands = task.commands_windows.is_none()
+      && task.commands_unix.is_none()
+      && task.schedule.is_none()
+      && task.timeout.is_none()
+      && task.path.is_none()
+      && task.env.is_empty()
+      && task.foreach_components.is_none();
+    match only_commands {
+      true => TaskConfig::Commands(task.commands),
+      false => TaskConfig::Full {
+        commands: task.commands,
+        commands_windows: task.commands_windows,
+        commands_unix: task.commands_unix,
+        schedule: task.schedule,
+        timeout: task.timeout,
+        path: task.path,
+        env: task.env,
+        foreach_components: task.foreach_components,
+      },
+    }
+  }
+}
+
+impl ProjectTask {
+  /// The commands for the platform conductor runs on.
+  pub fn platform_commands(&self, name: &str) -> Result<&Vec<String>, String> {
+    platform::select(
+      name,
+      &self.commands,
+      self.commands_windows.as_ref(),
+      self.commands_unix.as_ref(),
+    )
+  }
+}
 
 #[derive(Clone)]
 pub struct Task {
@@ -7,6 +132,16 @@ pub struct Task {
   pub path: PathBuf,
   pub commands: Vec<String>,
   pub env: HashMap<String, String>,
+  /// A dotenv style file, relative to the task's path, loaded below the task's env.
+  pub env_file: Option<String>,
+  /// Env values produced by commands, applied above the task's env.
+  pub env_commands: HashMap<String, String>,
+  /// Commands producing environment values at launch, applied above the task's env.
+  pub secrets: HashMap<String, String>,
+  /// How long a command may run before it is killed and counted as failed.
+  pub timeout: Option<Duration>,
+  /// Why the task can not run here, when it only has commands for another platform.
+  pub unavailable: Option<String>,
 }
 
 impl Task {
@@ -21,6 +156,11 @@ impl Task {
       path: path.into(),
       commands,
       env,
+      env_file: None,
+      env_commands: HashMap::new(),
+      secrets: HashMap::new(),
+      timeout: None,
+      unavailable: None,
     };
     task.commands.reverse();
     task
@@ -0,0 +1,323 @@
+use crate::random;
+use crate::ExitCode;
+use crossbeam::channel::{unbounded, Receiver, RecvTimeoutError, Sender};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::env;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Spans are sent to the collector in batches of up to this many, or once the oldest waited this
+/// long.
+const BATCH_SIZE: usize = 64;
+const BATCH_DELAY: Duration = Duration::from_secs(2);
+/// How long sending a batch may take, and how long exiting waits for the last one.
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Where the trace of each invocation is sent, an OpenTelemetry collector taking OTLP over HTTP.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug, Default)]
+#[serde(default)]
+pub struct Telemetry {
+  /// The base URL of the collector, such as `http://localhost:4318`. Spans are posted to
+  /// `/v1/traces` under it. OTEL_EXPORTER_OTLP_ENDPOINT is used when unset, nothing is traced
+  /// without either.
+  pub endpoint: Option<String>,
+  /// The `service.name` of the spans, OTEL_SERVICE_NAME or `conductor` when unset.
+  pub service_name: Option<String>,
+}
+
+/// The invocation being traced. Spans that end are handed to the exporting thread, which sends
+/// them while the invocation goes on.
+struct Trace {
+  trace_id: String,
+  root: Span,
+  spans: Sender<OtlpSpan>,
+  exported: Receiver<()>,
+}
+
+static TRACE: Mutex<Option<Trace>> = Mutex::new(None);
+
+/// Starts tracing the invocation when a collector is configured. Its span is named after the
+/// subcommand, as the other arguments can hold secrets, and the spans ended until export_trace
+/// are its children.
+pub fn trace_invocation(telemetry: &Telemetry, project: &str, command: &str) {
+  let endpoint = match telemetry
+    .endpoint
+    .clone()
+    .or_else(|| env::var("OTEL_EXPORTER_OTLP_ENDPOINT").ok())
+    .filter(|e| !e.trim().is_empty())
+  {
+    Some(endpoint) => endpoint,
+    None => return,
+  };
+  let service_name = telemetry
+    .service_name
+    .clone()
+    .or_else(|| env::var("OTEL_SERVICE_NAME").ok())
+    .unwrap_or_else(|| "conductor".into());
+  let url = format!("{}/v1/traces", endpoint.trim_end_matches('/'));
+  let (spans, received) = unbounded();
+  let (done, exported) = unbounded();
+  thread::spawn(move || {
+    export(&url, &service_name, received);
+    let _ = done.send(());
+  });
+  let root =
+    Span::new(format!("conductor {}", command).trim_end()).attribute("conductor.project", project);
+  *TRACE.lock().unwrap() = Some(Trace {
+    trace_id: random_hex(16),
+    root,
+    spans,
+    exported,
+  });
+}
+
+/// Ends the invocation's span and waits a moment for the spans not sent yet to reach the
+/// collector. A collector that cannot be reached is only logged, it never changes how the
+/// invocation went.
+pub fn export_trace(code: ExitCode) {
+  let trace = match TRACE.lock().unwrap().take() {
+    Some(trace) => trace,
+    None => return,
+  };
+  let root = trace.root.attribute("conductor.exit_code", code.code());
+  let _ = trace
+    .spans
+    .send(root.otlp(&trace.trace_id, None, code != ExitCode::Success));
+  // Closing the channel has the exporting thread send what is left and finish.
+  drop(trace.spans);
+  let _ = trace.exported.recv_timeout(EXPORT_TIMEOUT);
+}
+
+/// Sends the spans to the collector in batches until the channel is closed.
+fn export(url: &str, service_name: &str, spans: Receiver<OtlpSpan>) {
+  let agent = ureq::AgentBuilder::new().timeout(EXPORT_TIMEOUT).build();
+  let mut batch = vec![];
+  loop {
+    let received = match batch.is_empty() {
+      true => spans.recv().map_err(|_| RecvTimeoutError::Disconnected),
+      false => spans.recv_timeout(BATCH_DELAY),
+    };
+    let closed = match received {
+      Ok(span) => {
+        batch.push(span);
+        if batch.len() < BATCH_SIZE {
+          continue;
+        }
+        false
+      }
+      Err(RecvTimeoutError::Timeout) => false,
+      Err(RecvTimeoutError::Disconnected) => true,
+    };
+    if !batch.is_empty() {
+      let request = ExportRequest::new(service_name, std::mem::take(&mut batch));
+      if let Err(e) = agent.post(url).send_json(&request) {
+        log::warn!("Could not export the trace to {}: {}", url, e);
+      }
+    }
+    if closed {
+      return;
+    }
+  }
+}
+
+/// Something timed as part of the invocation's trace, such as a component's lifetime or a task
+/// command. Spans are dropped when the invocation is not traced.
+pub(crate) struct Span {
+  id: String,
+  name: String,
+  start: SystemTime,
+  attributes: Vec<(String, Value)>,
+  events: Vec<(String, SystemTime)>,
+}
+
+impl Span {
+  pub fn new(name: impl Into<String>) -> Self {
+    Span {
+      id: random_hex(8),
+      name: name.into(),
+      start: SystemTime::now(),
+      attributes: vec![],
+      events: vec![],
+    }
+  }
+
+  pub fn attribute(mut self, key: &str, value: impl Into<Value>) -> Self {
+    self.set(key, value);
+    self
+  }
+
+  pub fn set(&mut self, key: &str, value: impl Into<Value>) {
+    self.attributes.push((key.to_string(), value.into()));
+  }
+
+  /// Marks the moment something happened during the span, such as a component becoming ready.
+  pub fn event(&mut self, name: &str) {
+    self.events.push((name.to_string(), SystemTime::now()));
+  }
+
+  /// Ends the span now, as a child of the invocation's, and hands it to be exported.
+  pub fn end(self, failed: bool) {
+    if let Some(trace) = TRACE.lock().unwrap().as_ref() {
+      let span = self.otlp(&trace.trace_id, Some(&trace.root.id), failed);
+      let _ = trace.spans.send(span);
+    }
+  }
+
+  /// The span as it is exported, ending now.
+  fn otlp(&self, trace_id: &str, parent: Option<&str>, failed: bool) -> OtlpSpan {
+    OtlpSpan {
+      trace_id: trace_id.to_string(),
+      span_id: self.id.clone(),
+      parent_span_id: parent.map(String::from),
+      name: self.name.clone(),
+      // An internal span.
+      kind: 1,
+      start_time_unix_nano: nanos(self.start),
+      end_time_unix_nano: nanos(SystemTime::now()),
+      attributes: self
+        .attributes
+        .iter()
+        .map(|(key, value)| KeyValue::new(key, value))
+        .collect(),
+      events: self
+        .events
+        .iter()
+        .map(|(name, time)| OtlpEvent {
+          name: name.clone(),
+          time_unix_nano: nanos(*time),
+        })
+        .collect(),
+      status: Status {
+        code: if failed { 2 } else { 1 },
+      },
+    }
+  }
+}
+
+/// The body of an OTLP/HTTP export in the JSON encoding, see
+/// https://opentelemetry.io/docs/specs/otlp/#json-protobuf-encoding
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportRequest {
+  resource_spans: Vec<ResourceSpans>,
+}
+
+impl ExportRequest {
+  fn new(service_name: &str, spans: Vec<OtlpSpan>) -> Self {
+    ExportRequest {
+      resource_spans: vec![ResourceSpans {
+        resource: Resource {
+          attributes: vec![KeyValue::new("service.name", &service_name.into())],
+        },
+        scope_spans: vec![ScopeSpans {
+          scope: Scope {
+            name: "conductor",
+            version: env!("CARGO_PKG_VERSION"),
+          },
+          spans,
+        }],
+      }],
+    }
+  }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ResourceSpans {
+  resource: Resource,
+  scope_spans: Vec<ScopeSpans>,
+}
+
+#[derive(Serialize)]
+struct Resource {
+  attributes: Vec<KeyValue>,
+}
+
+#[derive(Serialize)]
+struct ScopeSpans {
+  scope: Scope,
+  spans: Vec<OtlpSpan>,
+}
+
+#[derive(Serialize)]
+struct Scope {
+  name: &'static str,
+  version: &'static str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct OtlpSpan {
+  trace_id: String,
+  span_id: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  parent_span_id: Option<String>,
+  name: String,
+  kind: u8,
+  start_time_unix_nano: String,
+  end_time_unix_nano: String,
+  attributes: Vec<KeyValue>,
+  events: Vec<OtlpEvent>,
+  status: Status,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct OtlpEvent {
+  name: String,
+  time_unix_nano: String,
+}
+
+#[derive(Serialize)]
+struct Status {
+  code: u8,
+}
+
+#[derive(Serialize)]
+struct KeyValue {
+  key: String,
+  value: AnyValue,
+}
+
+impl KeyValue {
+  /// An attribute, integers as `intValue` and anything else as a string.
+  fn new(key: &str, value: &Value) -> Self {
+    let value = match value {
+      Value::Number(n) if n.is_i64() => AnyValue::Int(n.to_string()),
+      Value::Bool(b) => AnyValue::Bool(*b),
+      Value::String(s) => AnyValue::String(s.clone()),
+      other => AnyValue::String(other.to_string()),
+    };
+    KeyValue {
+      key: key.to_string(),
+      value,
+    }
+  }
+}
+
+#[derive(Serialize)]
+enum AnyValue {
+  #[serde(rename = "stringValue")]
+  String(String),
+  /// 64 bit integers are written as strings in OTLP JSON.
+  #[serde(rename = "intValue")]
+  Int(String),
+  #[serde(rename = "boolValue")]
+  Bool(bool),
+}
+
+/// Nanoseconds since the unix epoch, as a string as OTLP JSON has them.
+fn nanos(time: SystemTime) -> String {
+  time
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_nanos())
+    .unwrap_or_default()
+    .to_string()
+}
+
+/// A random id of the given number of bytes in hex, as trace and span ids are written.
+fn random_hex(bytes: usize) -> String {
+  random::hex(bytes).unwrap_or_else(|_| "0".repeat(bytes * 2))
+}
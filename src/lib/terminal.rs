@@ -0,0 +1,105 @@
+use crate::state::{ComponentState, ComponentStatus};
+use serde::{Deserialize, Serialize};
+use std::io::{self, IsTerminal, Write};
+
+/// Terminal integration escapes that reflect the health of the environment outside the output
+/// stream, such as in a tab title. Everything is off by default and nothing is emitted unless
+/// stdout is a terminal.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug, Default)]
+#[serde(default)]
+pub struct TerminalIntegration {
+  /// Sets the window title to a summary of the running and failing components.
+  pub title: bool,
+  /// Sets the `conductor_status`, `conductor_running` and `conductor_failing` user vars
+  /// understood by iTerm2 and WezTerm.
+  pub user_vars: bool,
+}
+
+impl TerminalIntegration {
+  /// Updates the terminal with the current state of the components.
+  pub fn update(&self, project: &str, components: &[ComponentState]) {
+    if !self.enabled() {
+      return;
+    }
+    let count = |status: ComponentStatus| components.iter().filter(|c| c.status == status).count();
+    let running = count(ComponentStatus::Running);
+    let failing = count(ComponentStatus::Failed);
+    let starting = components
+      .iter()
+      .any(|c| c.status != ComponentStatus::Stopped && !c.ready);
+    let status = if failing > 0 {
+      "failing"
+    } else if starting {
+      "starting"
+    } else if running > 0 {
+      "healthy"
+    } else {
+      "stopped"
+    };
+    if self.title {
+      let title = if failing > 0 {
+        format!("{}: {} running, {} failing", project, running, failing)
+      } else {
+        format!("{}: {} running", project, running)
+      };
+      osc(&format!("0;{}", title));
+    }
+    if self.user_vars {
+      user_var("conductor_status", status);
+      user_var("conductor_running", &running.to_string());
+      user_var("conductor_failing", &failing.to_string());
+    }
+  }
+
+  /// Resets the title and marks the environment as stopped once the session ends.
+  pub fn clear(&self) {
+    if !self.enabled() {
+      return;
+    }
+    if self.title {
+      osc("0;");
+    }
+    if self.user_vars {
+      user_var("conductor_status", "stopped");
+      user_var("conductor_running", "0");
+      user_var("conductor_failing", "0");
+    }
+  }
+
+  fn enabled(&self) -> bool {
+    (self.title || self.user_vars) && io::stdout().is_terminal()
+  }
+}
+
+fn user_var(name: &str, value: &str) {
+  osc(&format!(
+    "1337;SetUserVar={}={}",
+    name,
+    base64(value.as_bytes())
+  ));
+}
+
+fn osc(body: &str) {
+  let mut stdout = io::stdout();
+  let _ = write!(stdout, "\x1b]{}\x07", body);
+  let _ = stdout.flush();
+}
+
+fn base64(bytes: &[u8]) -> String {
+  const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+  let mut encoded = String::new();
+  for chunk in bytes.chunks(3) {
+    let n = chunk
+      .iter()
+      .enumerate()
+      .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+    for i in 0..4 {
+      if i <= chunk.len() {
+        encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+      } else {
+        encoded.push('=');
+      }
+    }
+  }
+  encoded
+}
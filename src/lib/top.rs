@@ -0,0 +1,287 @@
+use crate::daemon;
+use crate::process::{Usage, UsageSampler};
+use crate::state::{ComponentState, ComponentStatus, SessionState};
+use crate::ui;
+#[cfg(unix)]
+use crossbeam::channel::{never, tick, unbounded, Receiver};
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, SystemTime};
+#[cfg(unix)]
+use termion::event::Key;
+#[cfg(unix)]
+use termion::input::TermRead;
+#[cfg(unix)]
+use termion::raw::IntoRawMode;
+#[cfg(unix)]
+use termion::screen::AlternateScreen;
+#[cfg(unix)]
+use tui::backend::TermionBackend;
+#[cfg(unix)]
+use tui::layout::{Constraint, Direction, Layout};
+#[cfg(unix)]
+use tui::style::{Color, Modifier, Style};
+#[cfg(unix)]
+use tui::text::Span;
+#[cfg(unix)]
+use tui::widgets::{Block, Borders, Cell, Paragraph, Row, Table};
+#[cfg(unix)]
+use tui::Terminal;
+
+/// The headers of the columns.
+const HEADERS: [&str; 7] = [
+  "Component",
+  "Status",
+  "Pid",
+  "CPU %",
+  "Memory",
+  "Restarts",
+  "Uptime",
+];
+
+/// The key bindings shown at the bottom of the screen.
+#[cfg(unix)]
+const HINTS: &str = "sort by: n name  s status  c cpu  m memory  r restarts  q quit";
+
+/// The column the components are sorted by. Usage and restarts are sorted highest first.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SortBy {
+  Name,
+  Status,
+  Cpu,
+  Memory,
+  Restarts,
+}
+
+impl FromStr for SortBy {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "name" => Ok(SortBy::Name),
+      "status" => Ok(SortBy::Status),
+      "cpu" => Ok(SortBy::Cpu),
+      "memory" => Ok(SortBy::Memory),
+      "restarts" => Ok(SortBy::Restarts),
+      _ => Err(format!(
+        "Expected name, status, cpu, memory or restarts to sort by, got {}",
+        s
+      )),
+    }
+  }
+}
+
+impl SortBy {
+  #[cfg(unix)]
+  fn from_key(key: Key) -> Option<Self> {
+    match key {
+      Key::Char('n') => Some(SortBy::Name),
+      Key::Char('s') => Some(SortBy::Status),
+      Key::Char('c') => Some(SortBy::Cpu),
+      Key::Char('m') => Some(SortBy::Memory),
+      Key::Char('r') => Some(SortBy::Restarts),
+      _ => None,
+    }
+  }
+
+  fn sort(self, rows: &mut [(ComponentState, Usage)]) {
+    match self {
+      SortBy::Name => rows.sort_by(|a, b| a.0.name.cmp(&b.0.name)),
+      SortBy::Status => rows.sort_by_key(|(c, _)| (c.status.to_string(), c.name.clone())),
+      SortBy::Cpu => rows.sort_by(|a, b| b.1.cpu.total_cmp(&a.1.cpu)),
+      SortBy::Memory => rows.sort_by_key(|(_, u)| std::cmp::Reverse(u.memory_kb)),
+      SortBy::Restarts => rows.sort_by_key(|(c, _)| std::cmp::Reverse(c.restarts)),
+    }
+  }
+}
+
+/// Shows the components of the running session in a full screen table refreshed every interval,
+/// with the CPU and memory their processes use, how often they restarted and their state. Usage
+/// is measured here, so the session does not need to run with `--stats`. Returns once q is
+/// pressed or the session ends.
+#[cfg(unix)]
+pub fn run(root_path: &Path, mut sort: SortBy, interval: Duration) -> io::Result<()> {
+  if daemon::live_session(root_path).is_none() {
+    return Err(io::Error::other("No session is running"));
+  }
+  let screen = AlternateScreen::from(io::stdout().into_raw_mode()?);
+  let mut terminal = Terminal::new(TermionBackend::new(screen))?;
+  terminal.hide_cursor()?;
+  let mut keys = read_keys();
+  let refresh = tick(interval);
+  let mut sampler = sampler(root_path);
+  let mut rows = measure(root_path, &mut sampler);
+  loop {
+    sort.sort(&mut rows);
+    terminal.draw(|f| {
+      let area = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .split(f.size());
+      let header = Row::new(HEADERS.iter().map(|h| Cell::from(*h)))
+        .style(Style::default().add_modifier(Modifier::BOLD));
+      let now = SystemTime::now();
+      let table = Table::new(rows.iter().map(|(c, usage)| row(c, usage, now)))
+        .header(header)
+        .block(
+          Block::default()
+            .borders(Borders::ALL)
+            .title("conductor top"),
+        )
+        .widths(&[
+          Constraint::Min(16),
+          Constraint::Length(9),
+          Constraint::Length(8),
+          Constraint::Length(7),
+          Constraint::Length(11),
+          Constraint::Length(9),
+          Constraint::Length(9),
+        ]);
+      f.render_widget(table, area[0]);
+      f.render_widget(
+        Paragraph::new(Span::styled(HINTS, Style::default().fg(Color::DarkGray))),
+        area[1],
+      );
+    })?;
+    crossbeam::select! {
+      recv(keys) -> key => match key {
+        Ok(Key::Char('q')) | Ok(Key::Esc) | Ok(Key::Ctrl('c')) => break,
+        Ok(key) => {
+          if let Some(by) = SortBy::from_key(key) {
+            sort = by;
+          }
+        }
+        Err(_) => keys = never(),
+      },
+      recv(refresh) -> _ => {
+        if daemon::live_session(root_path).is_none() {
+          break;
+        }
+        rows = measure(root_path, &mut sampler);
+      }
+    }
+  }
+  terminal.show_cursor()?;
+  Ok(())
+}
+
+/// Prints the table of components every interval where the terminal can not be drawn on, sorted
+/// by a column. Returns once the session ends.
+#[cfg(not(unix))]
+pub fn run(root_path: &Path, sort: SortBy, interval: Duration) -> io::Result<()> {
+  if daemon::live_session(root_path).is_none() {
+    return Err(io::Error::other("No session is running"));
+  }
+  let mut sampler = sampler(root_path);
+  while daemon::live_session(root_path).is_some() {
+    let mut rows = measure(root_path, &mut sampler);
+    sort.sort(&mut rows);
+    let now = SystemTime::now();
+    let mut table = (2..=5).fold(ui::Table::new(&HEADERS), |table, column| {
+      table.align(column, ui::Align::Right)
+    });
+    for (c, usage) in rows.iter() {
+      table.row(
+        columns(c, usage, now)
+          .into_iter()
+          .map(ui::Cell::new)
+          .collect(),
+      );
+    }
+    // Clears the screen and moves to its top left corner before each refresh.
+    print!("\x1b[2J\x1b[H");
+    ui::table(&table);
+    thread::sleep(interval);
+  }
+  Ok(())
+}
+
+/// A sampler that has already measured the session's processes once, so the first refresh
+/// shows how much CPU they use rather than none.
+fn sampler(root_path: &Path) -> UsageSampler {
+  let mut sampler = UsageSampler::new();
+  measure(root_path, &mut sampler);
+  thread::sleep(UsageSampler::MIN_INTERVAL);
+  sampler
+}
+
+/// Reads the state of the session's components and measures the usage of the running ones.
+/// CPU use is measured since the previous refresh.
+fn measure(root_path: &Path, sampler: &mut UsageSampler) -> Vec<(ComponentState, Usage)> {
+  let components = SessionState::read(root_path)
+    .map(|state| state.components)
+    .unwrap_or_default();
+  let pids: Vec<u32> = components
+    .iter()
+    .filter(|c| c.status != ComponentStatus::Stopped)
+    .filter_map(|c| c.pid)
+    .collect();
+  let usage = sampler.measure(&pids);
+  components
+    .into_iter()
+    .map(|c| {
+      let u = c
+        .pid
+        .and_then(|pid| usage.get(&pid).copied())
+        .unwrap_or_default();
+      (c, u)
+    })
+    .collect()
+}
+
+#[cfg(unix)]
+fn row<'a>(c: &ComponentState, usage: &Usage, now: SystemTime) -> Row<'a> {
+  let status = match c.status {
+    ComponentStatus::Running if c.paused => Color::Blue,
+    ComponentStatus::Running if c.ready => Color::Green,
+    ComponentStatus::Running | ComponentStatus::Starting => Color::Yellow,
+    ComponentStatus::Failed => Color::Red,
+    ComponentStatus::Stopped => Color::DarkGray,
+  };
+  Row::new(
+    columns(c, usage, now)
+      .into_iter()
+      .enumerate()
+      .map(|(n, text)| match n {
+        1 => Cell::from(text).style(Style::default().fg(status)),
+        _ => Cell::from(text),
+      }),
+  )
+}
+
+/// The text of each column for a component. Usage is only shown while it runs.
+fn columns(c: &ComponentState, usage: &Usage, now: SystemTime) -> Vec<String> {
+  let running = c.status == ComponentStatus::Running;
+  vec![
+    c.name.clone(),
+    c.describe(),
+    c.pid.map_or_else(|| "-".into(), |pid| pid.to_string()),
+    match running {
+      true => format!("{:.1}", usage.cpu),
+      false => "-".into(),
+    },
+    match running {
+      true => format!("{:.1} MB", usage.memory_kb as f64 / 1024.0),
+      false => "-".into(),
+    },
+    c.restarts.to_string(),
+    c.uptime(now)
+      .map_or_else(|| "-".into(), |s| ui::duration(Duration::from_secs(s))),
+  ]
+}
+
+/// Reads keys from the terminal on a separate thread.
+#[cfg(unix)]
+fn read_keys() -> Receiver<Key> {
+  let (sender, keys) = unbounded();
+  thread::spawn(move || {
+    for key in io::stdin().keys().map_while(Result::ok) {
+      if sender.send(key).is_err() {
+        break;
+      }
+    }
+  });
+  keys
+}
@@ -1,41 +1,807 @@
+use crate::journal::{local_timestamp, utc_timestamp};
+use crate::metrics::ComponentSummary;
+use crate::state::{ComponentState, ComponentStatus};
 use crate::task::Task;
-use crate::{Component, TerminalColor};
-use ansi_term::Colour::*;
+use crate::{Component, ComponentReport, Message, Pattern, Service, TerminalColor};
+use ansi_term::Colour::{self, *};
+use ansi_term::Style;
+use crossbeam::channel::Sender;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::{Duration, SystemTime};
+
+/// Settings that control how output is shown. These are set once at startup from the project's
+/// `output` section and the command line.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(default)]
+pub struct Settings {
+  /// Prefix each line of output with the time it was received.
+  pub timestamps: bool,
+  /// The strftime format of the timestamps. `%3f` is replaced with the milliseconds.
+  pub timestamp_format: String,
+  /// How output is written.
+  pub format: Format,
+  /// Colors output. Color is also turned off by the `NO_COLOR` environment variable and when
+  /// stdout is not a terminal.
+  pub color: bool,
+  /// Styles lines of component and service output matching a pattern. The first rule a line
+  /// matches is used.
+  pub highlights: Vec<Highlight>,
+  /// Reads the stderr of components together with their stdout, as one stream, instead of
+  /// showing it apart.
+  pub merge_stderr: bool,
+  /// The prefix of each line of output, such as `{time} {name:>12} |`. The placeholders are
+  /// `{time}`, `{name}`, `{pid}` and `{restarts}`, and take a width and alignment as in
+  /// `{name:<10}`. The name is shown in brackets when none is given.
+  pub log_format: Option<String>,
+}
+
+/// Makes lines matching a pattern stand out, whichever component printed them.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct Highlight {
+  pub pattern: Pattern,
+  /// The color of the line. `Auto` keeps the line's own color.
+  #[serde(default)]
+  pub color: TerminalColor,
+  #[serde(default)]
+  pub bold: bool,
+}
+
+impl Highlight {
+  fn style(&self) -> Style {
+    let style = colour(&self.color).map_or_else(Style::new, Colour::normal);
+    match self.bold {
+      true => style.bold(),
+      false => style,
+    }
+  }
+}
+
+/// Styles a line of output with the first highlight rule it matches.
+fn highlight(line: String) -> String {
+  match settings()
+    .highlights
+    .iter()
+    .find(|h| h.pattern.is_match(&line))
+  {
+    Some(h) => paint(h.style(), &line),
+    None => line,
+  }
+}
+
+impl Default for Settings {
+  fn default() -> Self {
+    Settings {
+      timestamps: false,
+      timestamp_format: "%H:%M:%S.%3f".into(),
+      format: Format::Text,
+      color: true,
+      highlights: vec![],
+      merge_stderr: false,
+      log_format: None,
+    }
+  }
+}
+
+/// How output is written. In the json format every component event is written to stdout as a
+/// line of JSON and system messages go to stderr.
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+  Text,
+  Json,
+}
+
+impl FromStr for Format {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "text" => Ok(Format::Text),
+      "json" => Ok(Format::Json),
+      _ => Err(format!("Unknown output format {}", s)),
+    }
+  }
+}
+
+static SETTINGS: RwLock<Option<Arc<Settings>>> = RwLock::new(None);
+
+/// Sets how output is shown from now on. Anything printed before it is called uses the defaults.
+pub fn configure(settings: Settings) {
+  *SETTINGS.write().unwrap() = Some(Arc::new(settings));
+}
+
+fn settings() -> Arc<Settings> {
+  SETTINGS.read().unwrap().clone().unwrap_or_default()
+}
+
+static COLOR_ALLOWED: OnceLock<bool> = OnceLock::new();
+
+/// Returns true if output should be colored.
+fn color() -> bool {
+  let allowed = *COLOR_ALLOWED.get_or_init(|| {
+    let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    !no_color && io::stdout().is_terminal()
+  });
+  allowed && settings().color
+}
+
+/// Styles text when output is colored.
+fn paint<S: Into<Style>>(style: S, text: impl AsRef<str>) -> String {
+  if color() {
+    style.into().paint(text.as_ref()).to_string()
+  } else {
+    text.as_ref().to_string()
+  }
+}
+
+static PREFIX_WIDTH: AtomicUsize = AtomicUsize::new(0);
+
+/// Pads the names printed before each line of output to the longest of the given names so the
+/// output lines up.
+pub fn align<'a>(names: impl Iterator<Item = &'a str>) {
+  let width = names.map(|n| n.chars().count()).max().unwrap_or(0);
+  PREFIX_WIDTH.store(width, Ordering::Relaxed);
+}
+
+/// The spaces that line up output after a name.
+fn padding(name: &str) -> String {
+  let width = PREFIX_WIDTH.load(Ordering::Relaxed);
+  " ".repeat(width.saturating_sub(name.chars().count()))
+}
+
+/// Returns true if output is written as JSON.
+pub fn json() -> bool {
+  settings().format == Format::Json
+}
+
+/// Prints an event from a component or task as a line of JSON.
+pub fn event(source: &str, name: &str, event: &str, body: serde_json::Value) {
+  println!("{}", event_line(source, name, event, body));
+}
+
+/// An event from a component or task as it is written in the json format, stamped with the
+/// current time.
+pub(crate) fn event_line(
+  source: &str,
+  name: &str,
+  event: &str,
+  body: serde_json::Value,
+) -> serde_json::Value {
+  let mut line = serde_json::Map::new();
+  line.insert(source.into(), name.into());
+  line.insert("event".into(), event.into());
+  line.insert("timestamp".into(), utc_timestamp(SystemTime::now()).into());
+  line.insert("body".into(), body);
+  serde_json::Value::Object(line)
+}
+
+/// The timestamp printed before a line of output, if enabled.
+fn timestamp() -> String {
+  let settings = settings();
+  if !settings.timestamps {
+    return String::new();
+  }
+  let time = local_timestamp(&settings.timestamp_format, SystemTime::now());
+  format!("{} ", paint(Fixed(8), time))
+}
+
+/// The process a line of output came from, for the log format.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Origin {
+  pub pid: Option<u32>,
+  /// The number of times the component has been restarted during the session.
+  pub restarts: usize,
+}
+
+/// The text shown before a line of output from a component, service or task. Without a log
+/// format the name is shown between the brackets, after the timestamp if enabled.
+fn prefix(name: &str, color: &TerminalColor, brackets: (&str, &str), origin: Origin) -> String {
+  if let Some(format) = &settings().log_format {
+    return formatted_prefix(format, name, color, origin);
+  }
+  format!(
+    "{}{}{}{}{}",
+    timestamp(),
+    paint(White.bold(), brackets.0),
+    colored_name(color, name),
+    paint(White.bold(), brackets.1),
+    padding(name)
+  )
+}
+
+/// Renders the log format for a line of output. Unknown placeholders are left as they are.
+fn formatted_prefix(format: &str, name: &str, color: &TerminalColor, origin: Origin) -> String {
+  let mut prefix = String::new();
+  let mut rest = format;
+  while let Some(start) = rest.find('{') {
+    prefix.push_str(&rest[..start]);
+    let end = match rest[start..].find('}') {
+      Some(end) => start + end,
+      None => {
+        rest = &rest[start..];
+        break;
+      }
+    };
+    let placeholder = &rest[start + 1..end];
+    let (key, spec) = placeholder.split_once(':').unwrap_or((placeholder, ""));
+    let value = match key {
+      "time" => local_timestamp(&settings().timestamp_format, SystemTime::now()),
+      "name" => name.to_string(),
+      "pid" => origin.pid.map_or_else(|| "-".into(), |pid| pid.to_string()),
+      "restarts" => origin.restarts.to_string(),
+      _ => {
+        prefix.push_str(&rest[start..=end]);
+        rest = &rest[end + 1..];
+        continue;
+      }
+    };
+    let value = pad(&value, spec);
+    prefix.push_str(&match key {
+      "time" => paint(Fixed(8), value),
+      "name" => colored_name(color, &value),
+      _ => value,
+    });
+    rest = &rest[end + 1..];
+  }
+  prefix.push_str(rest);
+  prefix
+}
+
+/// Pads a value to the width in a spec such as `>12`, `<8` or `^10`. Values are left aligned
+/// unless the spec says otherwise.
+fn pad(value: &str, spec: &str) -> String {
+  let (align, width) = match spec.chars().next() {
+    Some(c @ ('<' | '>' | '^')) => (c, &spec[1..]),
+    _ => ('<', spec),
+  };
+  let width = width.trim().parse().unwrap_or(0);
+  match align {
+    '>' => format!("{:>width$}", value, width = width),
+    '^' => format!("{:^width$}", value, width = width),
+    _ => format!("{:<width$}", value, width = width),
+  }
+}
+
+/// Where a line of output came from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Source {
+  System,
+  Component(String),
+  Service(String),
+  Task(String),
+}
+
+/// A line of output that was captured instead of printed.
+#[derive(Clone, Debug)]
+pub struct Line {
+  pub source: Source,
+  pub text: String,
+  pub error: bool,
+}
+
+static CAPTURE: Mutex<Option<Sender<Line>>> = Mutex::new(None);
+
+/// Sends all output to a channel instead of printing it, used while the dashboard owns the
+/// terminal. Passing None goes back to printing.
+pub fn capture(sender: Option<Sender<Line>>) {
+  *CAPTURE.lock().unwrap() = sender;
+}
+
+/// Hands a line to the capturing channel, returns false if output is not being captured.
+fn captured(source: Source, text: &str, error: bool) -> bool {
+  match CAPTURE.lock().unwrap().as_ref() {
+    Some(sender) => sender
+      .send(Line {
+        source,
+        text: text.to_string(),
+        error,
+      })
+      .is_ok(),
+    None => false,
+  }
+}
 
 pub fn system_message(str: String) {
-  let l_bracket = Red.bold().paint("-=[");
-  let r_bracket = Red.bold().paint("]=-");
-  let msg = White.bold().paint(str);
+  if captured(Source::System, &str, false) {
+    return;
+  }
+  let l_bracket = paint(Red.bold(), "-=[");
+  let r_bracket = paint(Red.bold(), "]=-");
+  let msg = paint(White.bold(), str);
 
-  println!("{} {} {}", l_bracket, msg, r_bracket);
+  if json() {
+    eprintln!("{} {} {}", l_bracket, msg, r_bracket);
+  } else {
+    println!("{} {} {}", l_bracket, msg, r_bracket);
+  }
 }
 
 pub fn system_error(str: String) {
-  let l_bracket = Red.bold().paint("-=[");
-  let r_bracket = Red.bold().paint("]=-");
-  let msg = Red.bold().paint(str);
+  if captured(Source::System, &str, true) {
+    return;
+  }
+  let l_bracket = paint(Red.bold(), "-=[");
+  let r_bracket = paint(Red.bold(), "]=-");
+  let msg = paint(Red.bold(), str);
+
+  if json() {
+    eprintln!("{} {} {}", l_bracket, msg, r_bracket);
+  } else {
+    println!("{} {} {}", l_bracket, msg, r_bracket);
+  }
+}
 
-  println!("{} {} {}", l_bracket, msg, r_bracket);
+/// Prints a message from the catalog, errors are highlighted.
+pub fn message(msg: Message) {
+  if msg.is_error() {
+    system_error(msg.to_string())
+  } else {
+    system_message(msg.to_string())
+  }
+}
+
+/// Prints a message as a plain line beneath the system message it belongs to.
+pub fn detail(msg: Message) {
+  let str = msg.to_string();
+  if captured(Source::System, &str, false) {
+    return;
+  }
+  if json() {
+    eprintln!("{}", str);
+  } else {
+    println!("{}", str);
+  }
 }
 
 pub fn task_message(task: &Task, msg: String) {
-  let l_bracket = White.bold().paint("[");
-  let r_bracket = White.bold().paint("]");
-  let name = format!("{}", Purple.bold().paint(&task.name));
-  println!("{}{}{} {}", l_bracket, name, r_bracket, msg);
-}
-
-pub fn component_message(cmp: &Component, msg: String) {
-  let name: String = match cmp.color {
-    TerminalColor::White => format!("{}", White.bold().paint(&cmp.name)),
-    TerminalColor::Blue => format!("{}", Blue.bold().paint(&cmp.name)),
-    TerminalColor::Red => format!("{}", Red.bold().paint(&cmp.name)),
-    TerminalColor::Green => format!("{}", Green.bold().paint(&cmp.name)),
-    TerminalColor::Purple => format!("{}", Purple.bold().paint(&cmp.name)),
-    TerminalColor::Yellow => format!("{}", Yellow.bold().paint(&cmp.name)),
-    TerminalColor::Cyan => format!("{}", Cyan.bold().paint(&cmp.name)),
+  if captured(Source::Task(task.name.clone()), &msg, false) {
+    return;
+  }
+  if json() {
+    return event("task", &task.name, "output", msg.into());
+  }
+  let prefix = prefix(
+    &task.name,
+    &TerminalColor::Purple,
+    ("[", "]"),
+    Origin::default(),
+  );
+  println!("{} {}", prefix, msg);
+}
+
+pub fn component_message(cmp: &Component, origin: Origin, msg: String) {
+  component_output(cmp, origin, msg, false)
+}
+
+/// Prints a line a component wrote to stderr, dimmed red unless a highlight matches it.
+pub fn component_stderr(cmp: &Component, origin: Origin, msg: String) {
+  component_output(cmp, origin, msg, true)
+}
+
+fn component_output(cmp: &Component, origin: Origin, msg: String, stderr: bool) {
+  // Output is written by the supervisor as component events in the json format.
+  if captured(Source::Component(cmp.name.clone()), &msg, stderr) || json() {
+    return;
+  }
+  let highlighted = settings()
+    .highlights
+    .iter()
+    .any(|h| h.pattern.is_match(&msg));
+  let msg = match stderr && !highlighted {
+    true => paint(Red.dimmed(), &msg),
+    false => highlight(msg),
   };
-  let l_bracket = White.bold().paint("[");
-  let r_bracket = White.bold().paint("]");
-  println!("{}{}{} {}", l_bracket, name, r_bracket, msg);
+  // Colors the component set are reset so they do not carry over to the next line.
+  let reset = match msg.contains('\u{1b}') {
+    true => "\u{1b}[0m",
+    false => "",
+  };
+  let prefix = prefix(&cmp.name, &cmp.color, ("[", "]"), origin);
+  println!("{} {}{}", prefix, msg, reset);
+}
+
+pub fn service_message(service: &Service, msg: String) {
+  if captured(Source::Service(service.name.clone()), &msg, false) || json() {
+    return;
+  }
+  let prefix = prefix(&service.name, &service.color, ("(", ")"), Origin::default());
+  println!("{} {}", prefix, highlight(msg));
+}
+
+/// Prints the state and recent output of each component.
+pub fn environment_report(reports: &[ComponentReport]) {
+  message(Message::EnvironmentReport);
+  for r in reports.iter() {
+    message(Message::ComponentStatus {
+      component: r.component.name.clone(),
+      status: r.state.describe(),
+      pid: r.state.pid,
+      ready: r.state.ready,
+    });
+    let origin = Origin {
+      pid: r.state.pid,
+      restarts: r.state.restarts,
+    };
+    for line in r.output.iter() {
+      component_message(&r.component, origin, line.clone());
+    }
+  }
+}
+
+/// How the text of a column is lined up.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Align {
+  Left,
+  Right,
+}
+
+/// A cell of a table, painted in a color when output is colored.
+#[derive(Clone, Debug)]
+pub struct Cell {
+  text: String,
+  color: Option<TerminalColor>,
+}
+
+impl Cell {
+  pub fn new(text: impl ToString) -> Self {
+    Cell {
+      text: text.to_string(),
+      color: None,
+    }
+  }
+
+  /// Paints the cell in a color, in bold.
+  pub fn colored(mut self, color: TerminalColor) -> Self {
+    self.color = Some(color);
+    self
+  }
+}
+
+impl From<&str> for Cell {
+  fn from(text: &str) -> Self {
+    Cell::new(text)
+  }
+}
+
+impl From<String> for Cell {
+  fn from(text: String) -> Self {
+    Cell::new(text)
+  }
+}
+
+/// Rows printed with their columns lined up under a bold header.
+pub struct Table {
+  columns: Vec<(String, Align)>,
+  rows: Vec<Vec<Cell>>,
+}
+
+impl Table {
+  /// A table with the given headers, its columns aligned to the left.
+  pub fn new(headers: &[&str]) -> Self {
+    Table {
+      columns: headers
+        .iter()
+        .map(|h| (h.to_string(), Align::Left))
+        .collect(),
+      rows: vec![],
+    }
+  }
+
+  /// Lines up the text of a column, counting from 0.
+  pub fn align(mut self, column: usize, align: Align) -> Self {
+    if let Some(c) = self.columns.get_mut(column) {
+      c.1 = align;
+    }
+    self
+  }
+
+  /// Adds a row. Missing cells are left empty and extra ones are dropped.
+  pub fn row(&mut self, cells: Vec<Cell>) {
+    self.rows.push(cells);
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.rows.is_empty()
+  }
+
+  /// The lines of the table, the header first.
+  fn lines(&self) -> Vec<String> {
+    let widths: Vec<usize> = self
+      .columns
+      .iter()
+      .enumerate()
+      .map(|(i, (header, _))| {
+        self
+          .rows
+          .iter()
+          .filter_map(|r| r.get(i))
+          .map(|c| c.text.chars().count())
+          .chain(std::iter::once(header.chars().count()))
+          .max()
+          .unwrap_or(0)
+      })
+      .collect();
+    let line = |cells: Vec<(&str, Option<Style>)>| {
+      let last = cells.len().saturating_sub(1);
+      let text: Vec<String> = cells
+        .into_iter()
+        .zip(self.columns.iter().zip(widths.iter()))
+        .enumerate()
+        .map(|(i, ((text, painted), ((_, align), width)))| {
+          // The last column is not padded so lines do not end in spaces.
+          let text = match (align, i == last) {
+            (Align::Right, _) => format!("{:>width$}", text, width = width),
+            (Align::Left, false) => format!("{:<width$}", text, width = width),
+            (Align::Left, true) => text.to_string(),
+          };
+          match painted {
+            Some(style) => paint(style, text),
+            None => text,
+          }
+        })
+        .collect();
+      text.join("  ")
+    };
+    let mut lines = vec![line(
+      self
+        .columns
+        .iter()
+        .map(|(header, _)| (header.as_str(), Some(White.bold())))
+        .collect(),
+    )];
+    for row in self.rows.iter() {
+      lines.push(line(
+        (0..self.columns.len())
+          .map(|i| match row.get(i) {
+            Some(cell) => (cell.text.as_str(), cell.color.as_ref().map(style)),
+            None => ("", None),
+          })
+          .collect(),
+      ));
+    }
+    lines
+  }
+}
+
+/// Prints a table. Tables are only printed in the text format, in the json format the messages
+/// for their rows are written instead.
+pub fn table(table: &Table) {
+  if json() {
+    return;
+  }
+  for line in table.lines() {
+    if !captured(Source::System, &line, false) {
+      println!("{}", line);
+    }
+  }
+}
+
+/// Shows the state, pid and usage of each component of a session, as a table or as a status
+/// message for each in the json format.
+pub fn component_states(states: &[ComponentState]) {
+  if json() {
+    for c in states {
+      message(Message::ComponentStatus {
+        component: c.name.clone(),
+        status: c.describe(),
+        pid: c.pid,
+        ready: c.ready,
+      });
+      if let Some(usage) = c.usage {
+        message(Message::usage(&c.name, usage));
+      }
+    }
+    return;
+  }
+  let mut rows = Table::new(&["Component", "Status", "Ready", "Pid", "CPU %", "Memory"])
+    .align(3, Align::Right)
+    .align(4, Align::Right)
+    .align(5, Align::Right);
+  for c in states {
+    let status = Cell::new(c.describe());
+    let status = match c.status {
+      ComponentStatus::Running if c.paused => status.colored(TerminalColor::Yellow),
+      ComponentStatus::Running => status.colored(TerminalColor::Green),
+      ComponentStatus::Failed => status.colored(TerminalColor::Red),
+      _ => status,
+    };
+    let (cpu, memory) = match c.usage {
+      Some(usage) => (
+        format!("{:.1}", usage.cpu),
+        format!("{:.1} MB", usage.memory_kb as f64 / 1024.0),
+      ),
+      None => ("-".into(), "-".into()),
+    };
+    rows.row(vec![
+      c.name.as_str().into(),
+      status,
+      if c.ready { "yes" } else { "no" }.into(),
+      c.pid
+        .map_or_else(|| "-".into(), |pid| pid.to_string())
+        .into(),
+      cpu.into(),
+      memory.into(),
+    ]);
+  }
+  table(&rows);
+}
+
+/// Prints a table of how each component did over the session and the services it started and
+/// stopped. The json format's shutdown events already carry this.
+pub fn summary(components: &[ComponentSummary], services: &[(String, bool)]) {
+  if json() {
+    return;
+  }
+  message(Message::Summary);
+  let mut rows = Table::new(&[
+    "Component",
+    "Status",
+    "Uptime",
+    "Restarts",
+    "Output",
+    "Exit code",
+  ])
+  .align(2, Align::Right)
+  .align(3, Align::Right)
+  .align(4, Align::Right);
+  for c in components.iter() {
+    let status = Cell::new(&c.state.status);
+    let status = match c.state.status {
+      ComponentStatus::Failed => status.colored(TerminalColor::Red),
+      _ => status,
+    };
+    let exit_code = c
+      .state
+      .exit_code
+      .map(crate::process::describe_exit_code)
+      .unwrap_or_else(|| "-".into());
+    rows.row(vec![
+      c.state.name.as_str().into(),
+      status,
+      duration(c.uptime).into(),
+      Cell::new(c.state.restarts),
+      Cell::new(c.output_lines),
+      exit_code.into(),
+    ]);
+  }
+  table(&rows);
+  if services.is_empty() {
+    return;
+  }
+  let mut rows = Table::new(&["Service", "Status"]);
+  for (name, stopped) in services.iter() {
+    let status = match stopped {
+      true => Cell::new("stopped"),
+      false => Cell::new("running").colored(TerminalColor::Yellow),
+    };
+    rows.row(vec![name.as_str().into(), status]);
+  }
+  println!();
+  table(&rows);
+}
+
+/// A short, rounded description of a length of time, such as `1h05m` or `42s`.
+pub(crate) fn duration(time: Duration) -> String {
+  let secs = time.as_secs();
+  match secs {
+    0..=59 => format!("{}s", secs),
+    60..=3599 => format!("{}m{:02}s", secs / 60, secs % 60),
+    _ => format!("{}h{:02}m", secs / 3600, secs % 3600 / 60),
+  }
+}
+
+/// The terminal colour a color is shown in, none for `Auto` which has no color of its own.
+fn colour(color: &TerminalColor) -> Option<Colour> {
+  match color {
+    TerminalColor::White => Some(White),
+    TerminalColor::Blue => Some(Blue),
+    TerminalColor::Red => Some(Red),
+    TerminalColor::Green => Some(Green),
+    TerminalColor::Purple => Some(Purple),
+    TerminalColor::Yellow => Some(Yellow),
+    TerminalColor::Cyan => Some(Cyan),
+    TerminalColor::Indexed(n) => Some(Fixed(*n)),
+    TerminalColor::Auto => None,
+  }
+}
+
+/// The bold style names and cells in a color are shown in.
+fn style(color: &TerminalColor) -> Style {
+  colour(color).unwrap_or(Yellow).bold()
+}
+
+fn colored_name(color: &TerminalColor, name: &str) -> String {
+  paint(style(color), name)
+}
+
+/// Asks a question on the terminal. An empty answer gives the default.
+pub fn prompt(question: &str, default: &str) -> String {
+  if default.is_empty() {
+    print!("{} ", paint(White.bold(), question));
+  } else {
+    print!(
+      "{} {} ",
+      paint(White.bold(), question),
+      paint(White, format!("[{}]", default))
+    );
+  }
+  let _ = io::stdout().flush();
+  let mut answer = String::new();
+  let _ = io::stdin().read_line(&mut answer);
+  match answer.trim() {
+    "" => default.to_string(),
+    answer => answer.to_string(),
+  }
+}
+
+/// Forwards lines typed on the terminal to a component until `~.` is typed on its own line or
+/// stdin closes. Returns false if a line could not be sent.
+pub fn attach<F>(component: &str, send: F) -> bool
+where
+  F: Fn(String) -> Result<(), String>,
+{
+  message(Message::Attached {
+    component: component.to_string(),
+  });
+  let mut sent = true;
+  for line in io::stdin().lock().lines().map_while(Result::ok) {
+    if line.trim() == "~." {
+      break;
+    }
+    if let Err(e) = send(format!("{}\n", line)) {
+      message(Message::Error { error: e });
+      sent = false;
+      break;
+    }
+  }
+  message(Message::Detached {
+    component: component.to_string(),
+  });
+  sent
+}
+
+/// Asks a yes or no question on the terminal. Anything other than an explicit yes is treated as
+/// a no.
+pub fn confirm(question: &str) -> bool {
+  print!(
+    "{} {} ",
+    paint(White.bold(), question),
+    paint(White, "[y/N]")
+  );
+  let _ = io::stdout().flush();
+  let mut answer = String::new();
+  if io::stdin().read_line(&mut answer).is_err() {
+    return false;
+  }
+  matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn prefix(format: &str) -> String {
+    let origin = Origin {
+      pid: Some(42),
+      restarts: 2,
+    };
+    formatted_prefix(format, "api", &TerminalColor::Blue, origin)
+  }
+
+  #[test]
+  fn fills_in_placeholders() {
+    assert_eq!(prefix("{pid:>5} #{restarts} |"), "   42 #2 |");
+    assert_eq!(prefix("{unknown} {pid:<4}|"), "{unknown} 42  |");
+  }
+
+  #[test]
+  fn keeps_unclosed_placeholders_as_written() {
+    assert_eq!(prefix("abc {name"), "abc {name");
+    assert_eq!(prefix("{pid} {name"), "42 {name");
+  }
 }
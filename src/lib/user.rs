@@ -0,0 +1,97 @@
+use crate::{Project, TerminalColor};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Settings of the person running conductor, kept outside of the shared project configs. They
+/// are read from `config.yml` in the conductor directory of the user's config directory.
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct UserConfig {
+  /// Colors output when true, the default, and never when false.
+  pub color: Option<bool>,
+  /// The docker daemon's socket, in place of /var/run/docker.sock.
+  pub docker_socket: Option<PathBuf>,
+  /// The directory component output is logged to when `--log-dir` is not given.
+  pub log_dir: Option<PathBuf>,
+  /// Environment variables for every project. They take precedence over a project's env but
+  /// not over the env of its components, tasks and services.
+  pub env: HashMap<String, String>,
+  /// The age identity file `!encrypted` config values are decrypted with, unless the identity is
+  /// given in `CONDUCTOR_AGE_KEY`.
+  pub age_identity: Option<PathBuf>,
+  /// Settings for particular projects, by project name.
+  pub projects: HashMap<String, UserProject>,
+}
+
+/// The settings of a user for one project.
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct UserProject {
+  /// Where components are checked out, by component name, in place of their configured path.
+  /// Relative paths are relative to the project root.
+  pub paths: HashMap<String, String>,
+  /// Environment variables for the project, taking precedence over the user's env.
+  pub env: HashMap<String, String>,
+  /// Colors of components, by component name.
+  pub colors: HashMap<String, TerminalColor>,
+}
+
+impl UserConfig {
+  /// The location of the user config, under `$XDG_CONFIG_HOME` or `~/.config`.
+  pub fn path() -> Option<PathBuf> {
+    let dir = match env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()) {
+      Some(dir) => PathBuf::from(dir),
+      None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(dir.join("conductor").join("config.yml"))
+  }
+
+  /// Reads the user config. Users without one get the defaults.
+  pub fn load() -> io::Result<Self> {
+    let path = match UserConfig::path() {
+      Some(path) => path,
+      None => return Ok(UserConfig::default()),
+    };
+    let config = match fs::read_to_string(&path) {
+      Ok(config) => config,
+      Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(UserConfig::default()),
+      Err(e) => {
+        return Err(io::Error::new(
+          e.kind(),
+          format!("{}: {}", path.display(), e),
+        ))
+      }
+    };
+    // An empty file is a config without settings rather than a parse error.
+    if config.trim().is_empty() {
+      return Ok(UserConfig::default());
+    }
+    serde_yaml::from_str(&config)
+      .map_err(|e| io::Error::other(format!("{}: {}", path.display(), e)))
+  }
+
+  /// Applies the settings that belong to a project's config.
+  pub(crate) fn apply(&self, project: &mut Project) {
+    if let Some(color) = self.color {
+      project.output.color = color;
+    }
+    project.env.extend(self.env.clone());
+    let settings = match self.projects.get(&project.name) {
+      Some(settings) => settings,
+      None => return,
+    };
+    project.env.extend(settings.env.clone());
+    for c in project.components.iter_mut() {
+      if let Some(path) = settings.paths.get(&c.name) {
+        c.path = Some(path.clone());
+      }
+      if let Some(color) = settings.colors.get(&c.name) {
+        c.color = color.clone();
+      }
+    }
+  }
+}
@@ -0,0 +1,304 @@
+use crate::schedule::Schedule;
+use crate::service::ServiceType;
+use crate::Project;
+use std::collections::{HashMap, HashSet};
+
+/// A problem found in a project configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Issue {
+  /// The configuration will fail at runtime.
+  Error(String),
+  /// The configuration works but is likely not what was intended.
+  Warning(String),
+}
+
+/// Cross checks the references inside a project configuration. Problems are reported with
+/// enough context to fix them, such as the closest matching name for an unknown reference.
+pub fn validate(project: &Project) -> Vec<Issue> {
+  let mut issues = vec![];
+  let component_names: Vec<&str> = project.components.iter().map(|c| c.name.as_str()).collect();
+  let service_names: Vec<&str> = project.services.iter().map(|s| s.name.as_str()).collect();
+  let group_names: Vec<&str> = project.groups.iter().map(|g| g.name.as_str()).collect();
+
+  duplicates("component", &component_names, &mut issues);
+  duplicates("service", &service_names, &mut issues);
+  duplicates("group", &group_names, &mut issues);
+
+  for route in project.proxy.routes.iter() {
+    if route.host.is_none() && route.path.is_none() {
+      issues.push(Issue::Warning(format!(
+        "Proxy route {} has no host or path and matches every request",
+        route
+      )));
+    }
+    if let Err(problem) = route.target(&project.components) {
+      issues.push(Issue::Error(problem));
+    }
+  }
+
+  let member_names: Vec<&str> = component_names
+    .iter()
+    .chain(group_names.iter())
+    .cloned()
+    .collect();
+  for group in project.groups.iter() {
+    for name in group.components.iter() {
+      if !contains(&member_names, name) {
+        issues.push(Issue::Error(format!(
+          "Group {} references unknown component or group {}{}",
+          group.name,
+          name,
+          suggestion(name, &member_names)
+        )));
+      }
+    }
+    if let Err(problem) = project.group_components(group) {
+      issues.push(Issue::Error(problem));
+    }
+  }
+
+  for component in project.components.iter() {
+    for name in component.services.iter() {
+      if !contains(&service_names, name) {
+        issues.push(Issue::Error(format!(
+          "Component {} uses unknown service {}{}",
+          component.name,
+          name,
+          suggestion(name, &service_names)
+        )));
+      }
+    }
+    for task in component.task_names() {
+      let has_commands = |tasks: &HashMap<String, Vec<String>>| {
+        tasks
+          .get(task)
+          .map(|cmds| cmds.iter().any(|c| !c.trim().is_empty()))
+      };
+      let shared = has_commands(&component.tasks) == Some(true);
+      let windows = has_commands(&component.tasks_windows);
+      let unix = has_commands(&component.tasks_unix);
+      if !shared && windows != Some(true) && unix != Some(true) {
+        issues.push(Issue::Error(format!(
+          "Task {}:{} has no commands",
+          component.name, task
+        )));
+      } else if let Some(platform) = platform_gap(shared, windows.is_some(), unix.is_some()) {
+        issues.push(Issue::Warning(format!(
+          "Task {}:{} has no commands for {}",
+          component.name, task, platform
+        )));
+      }
+    }
+    if let Some(name) = component
+      .wait_for
+      .as_ref()
+      .and_then(|w| w.component.as_ref())
+    {
+      if !contains(&component_names, name) {
+        issues.push(Issue::Error(format!(
+          "Component {} waits for unknown component {}{}",
+          component.name,
+          name,
+          suggestion(name, &component_names)
+        )));
+      }
+    }
+    for (name, condition) in [
+      ("only_if", &component.only_if),
+      ("unless", &component.unless),
+    ] {
+      match condition {
+        Some(c) if c.command.is_none() && c.env.is_none() => issues.push(Issue::Error(format!(
+          "Component {}: {} needs a command or an env variable",
+          component.name, name
+        ))),
+        Some(c) if c.equals.is_some() && c.env.is_none() => issues.push(Issue::Error(format!(
+          "Component {}: {} has equals without an env variable",
+          component.name, name
+        ))),
+        _ => {}
+      }
+    }
+    let start = !component.start.trim().is_empty();
+    let (windows, unix) = (
+      component.start_windows.is_some(),
+      component.start_unix.is_some(),
+    );
+    if !start && !windows && !unix && !component.includes_project {
+      issues.push(Issue::Error(format!(
+        "Component {} has no start command",
+        component.name
+      )));
+    }
+    if let Some(platform) = platform_gap(start, windows, unix) {
+      issues.push(Issue::Warning(format!(
+        "Component {} has no start command for {}",
+        component.name, platform
+      )));
+    }
+    if let Some(ready) = &component.ready {
+      if ready.output.is_none() && !ready.has_probe() {
+        issues.push(Issue::Warning(format!(
+          "Component {} has a ready block without an output, port or command check and will never be ready",
+          component.name
+        )));
+      }
+    }
+    if !project.root_path.join(component.get_path()).is_dir() {
+      if component.repo.is_some() {
+        issues.push(Issue::Warning(format!(
+          "Component {} has not been cloned yet, run conductor setup",
+          component.name
+        )));
+      } else if component.source.is_some() {
+        issues.push(Issue::Warning(format!(
+          "Component {} has not been downloaded yet, run conductor setup",
+          component.name
+        )));
+      } else {
+        issues.push(Issue::Error(format!(
+          "Component {} path {} does not exist",
+          component.name,
+          component.get_path().display()
+        )));
+      }
+    }
+  }
+
+  for component in project.components.iter() {
+    if let Some(nice) = component.nice.filter(|n| !(-20..=19).contains(n)) {
+      issues.push(Issue::Error(format!(
+        "Component {} has nice level {}, it must be from -20 to 19",
+        component.name, nice
+      )));
+    }
+    if let Some(source) = &component.source {
+      if component.repo.is_some() {
+        issues.push(Issue::Error(format!(
+          "Component {} has both a repo and a source, it can only have one",
+          component.name
+        )));
+      }
+      if source.url.trim().is_empty() {
+        issues.push(Issue::Error(format!(
+          "The source of component {} has no url",
+          component.name
+        )));
+      } else if source.sha256.is_none() {
+        issues.push(Issue::Warning(format!(
+          "The source of component {} has no sha256, its download is not verified",
+          component.name
+        )));
+      }
+    }
+    if let Some(problem) = component.limits.as_ref().and_then(|l| l.problem()) {
+      issues.push(Issue::Error(format!(
+        "Component {}: {}",
+        component.name, problem
+      )));
+    }
+  }
+
+  for (name, task) in project.tasks.iter() {
+    let shared = task.commands.iter().any(|c| !c.trim().is_empty());
+    let (windows, unix) = (
+      task.commands_windows.is_some(),
+      task.commands_unix.is_some(),
+    );
+    if !shared && !windows && !unix {
+      issues.push(Issue::Error(format!("Task {} has no commands", name)));
+    }
+    if let Some(platform) = platform_gap(shared, windows, unix) {
+      issues.push(Issue::Warning(format!(
+        "Task {} has no commands for {}",
+        name, platform
+      )));
+    }
+    if let Some(schedule) = &task.schedule {
+      if let Err(e) = schedule.parse::<Schedule>() {
+        issues.push(Issue::Error(format!("Task {}: {}", name, e)));
+      }
+    }
+  }
+
+  for service in project.services.iter() {
+    if service.service_type == ServiceType::Process && service.command.is_none() {
+      issues.push(Issue::Error(format!(
+        "Process service {} has no command",
+        service.name
+      )));
+    }
+    if let ServiceType::Plugin(name) = &service.service_type {
+      if !crate::plugin::is_installed(name) {
+        issues.push(Issue::Error(format!(
+          "Service {} is of type {}, which needs {} on the PATH",
+          service.name,
+          name,
+          crate::plugin::executable(name)
+        )));
+      }
+    }
+  }
+
+  issues
+}
+
+fn contains(names: &[&str], name: &str) -> bool {
+  names
+    .iter()
+    .any(|n| n.to_lowercase() == name.to_lowercase())
+}
+
+fn duplicates(kind: &str, names: &[&str], issues: &mut Vec<Issue>) {
+  let mut seen = HashSet::new();
+  let mut reported = HashSet::new();
+  for name in names.iter().map(|n| n.to_lowercase()) {
+    if !seen.insert(name.clone()) && reported.insert(name.clone()) {
+      issues.push(Issue::Error(format!(
+        "The {} name {} is used more than once",
+        kind, name
+      )));
+    }
+  }
+}
+
+/// Suggests the closest known name for a misspelled reference.
+fn suggestion(name: &str, known: &[&str]) -> String {
+  known
+    .iter()
+    .map(|k| (distance(&name.to_lowercase(), &k.to_lowercase()), k))
+    .filter(|(d, _)| *d <= 3)
+    .min_by_key(|(d, _)| *d)
+    .map(|(_, k)| format!(", did you mean {}?", k))
+    .unwrap_or_default()
+}
+
+/// The edit distance between two strings.
+fn distance(a: &str, b: &str) -> usize {
+  let b: Vec<char> = b.chars().collect();
+  let mut row: Vec<usize> = (0..=b.len()).collect();
+  for (i, ca) in a.chars().enumerate() {
+    let mut previous = row[0];
+    row[0] = i + 1;
+    for (j, cb) in b.iter().enumerate() {
+      let current = row[j + 1];
+      row[j + 1] = if ca == *cb {
+        previous
+      } else {
+        1 + previous.min(row[j]).min(row[j + 1])
+      };
+      previous = current;
+    }
+  }
+  row[b.len()]
+}
+
+/// The platform left without commands when only some platforms have them, `shared` being
+/// whether there are commands for all platforms.
+fn platform_gap(shared: bool, windows: bool, unix: bool) -> Option<&'static str> {
+  match (shared, windows, unix) {
+    (false, true, false) => Some("unix"),
+    (false, false, true) => Some("windows"),
+    _ => None,
+  }
+}
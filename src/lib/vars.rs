@@ -0,0 +1,167 @@
+use serde_yaml::Value;
+use std::collections::HashMap;
+
+/// What a reference to a project variable starts with, as in `${vars.api_port}`.
+const PREFIX: &str = "${vars.";
+
+/// The variables a parsed config defines in its `vars` section. Numbers and booleans are kept as
+/// they are written.
+pub fn defined(config: &Value) -> Result<HashMap<String, String>, String> {
+  let vars = match config.get("vars") {
+    Some(Value::Mapping(vars)) => vars,
+    Some(Value::Null) | None => return Ok(HashMap::new()),
+    Some(_) => return Err("vars must be a map of names to values".into()),
+  };
+  vars
+    .iter()
+    .map(|(name, value)| {
+      let name = scalar(name).ok_or("Variable names must be strings")?;
+      let value = scalar(value)
+        .ok_or_else(|| format!("Variable {} must be a string, number or boolean", name))?;
+      Ok((name, value))
+    })
+    .collect()
+}
+
+fn scalar(value: &Value) -> Option<String> {
+  match value {
+    Value::String(s) => Some(s.clone()),
+    Value::Number(n) => Some(n.to_string()),
+    Value::Bool(b) => Some(b.to_string()),
+    _ => None,
+  }
+}
+
+/// Replaces the references to project variables in the strings of a parsed config with their
+/// values, so a value can not change the structure of the config. A string that is nothing but
+/// a reference becomes a number when the value is one, as in `port: ${vars.api_port}`.
+/// References to variables that are not defined are an error, so a typo does not end up in a
+/// command.
+pub fn interpolate(config: &mut Value, vars: &HashMap<String, String>) -> Result<(), String> {
+  match config {
+    Value::String(s) if s.contains(PREFIX) => {
+      *config = match reference(s) {
+        Some(name) => typed(lookup(name, vars)?),
+        None => Value::String(substitute(s, vars)?),
+      };
+      Ok(())
+    }
+    Value::Sequence(values) => values.iter_mut().try_for_each(|v| interpolate(v, vars)),
+    Value::Mapping(mapping) => mapping
+      .iter_mut()
+      .try_for_each(|(_, v)| interpolate(v, vars)),
+    _ => Ok(()),
+  }
+}
+
+/// The name of the variable a string consists of, if it is a single reference.
+fn reference(text: &str) -> Option<&str> {
+  let name = text.strip_prefix(PREFIX)?.strip_suffix('}')?;
+  match name.contains('}') {
+    true => None,
+    false => Some(name.trim()),
+  }
+}
+
+fn lookup<'a>(name: &str, vars: &'a HashMap<String, String>) -> Result<&'a str, String> {
+  vars
+    .get(name)
+    .map(String::as_str)
+    .ok_or_else(|| format!("Unknown variable {}", name))
+}
+
+fn typed(value: &str) -> Value {
+  match serde_yaml::from_str(value) {
+    Ok(Value::Number(n)) => Value::Number(n),
+    _ => Value::String(value.to_string()),
+  }
+}
+
+fn substitute(text: &str, vars: &HashMap<String, String>) -> Result<String, String> {
+  let mut out = String::with_capacity(text.len());
+  let mut rest = text;
+  while let Some(start) = rest.find(PREFIX) {
+    out.push_str(&rest[..start]);
+    let after = &rest[start + PREFIX.len()..];
+    let end = after
+      .find('}')
+      .ok_or_else(|| format!("Unclosed variable reference {}", &rest[start..]))?;
+    out.push_str(lookup(after[..end].trim(), vars)?);
+    rest = &after[end + 1..];
+  }
+  out.push_str(rest);
+  Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn load(config: &str, vars: &[(&str, &str)]) -> Result<Value, String> {
+    let mut config: Value = serde_yaml::from_str(config).unwrap();
+    let mut defined = defined(&config)?;
+    defined.extend(vars.iter().map(|(k, v)| (k.to_string(), v.to_string())));
+    interpolate(&mut config, &defined)?;
+    Ok(config)
+  }
+
+  #[test]
+  fn replaces_references_in_strings() {
+    let config = load(
+      "vars: {host: localhost, port: 4000}\nenv: {URL: 'http://${vars.host}:${vars.port}/'}",
+      &[],
+    )
+    .unwrap();
+    assert_eq!(config["env"]["URL"], "http://localhost:4000/");
+  }
+
+  #[test]
+  fn keeps_numbers_of_whole_references() {
+    let config = load(
+      "vars: {port: 4000, name: api}\nport: ${vars.port}\nname: ${vars.name}",
+      &[],
+    )
+    .unwrap();
+    assert_eq!(config["port"], 4000);
+    assert_eq!(config["name"], "api");
+  }
+
+  #[test]
+  fn keeps_the_structure_of_the_config() {
+    let value = "a: b # c\n'd\": [e]";
+    let config = load(
+      "start: echo ${vars.value}\nenv: {VALUE: '${vars.value}'}",
+      &[("value", value)],
+    )
+    .unwrap();
+    assert_eq!(config["start"], Value::String(format!("echo {}", value)));
+    assert_eq!(config["env"]["VALUE"], value);
+    assert_eq!(config.as_mapping().unwrap().len(), 2);
+  }
+
+  #[test]
+  fn ignores_comments() {
+    let config = load("# Set ${vars.missing} to change it\nname: api", &[]).unwrap();
+    assert_eq!(config["name"], "api");
+  }
+
+  #[test]
+  fn gives_precedence_to_variables_from_outside() {
+    let mut config: Value = serde_yaml::from_str("vars: {port: 4000}\nport: ${vars.port}").unwrap();
+    let mut vars: HashMap<String, String> = [("port".to_string(), "5000".to_string())].into();
+    for (name, value) in defined(&config).unwrap() {
+      vars.entry(name).or_insert(value);
+    }
+    interpolate(&mut config, &vars).unwrap();
+    assert_eq!(config["port"], 5000);
+  }
+
+  #[test]
+  fn rejects_unknown_and_unclosed_references() {
+    let unknown = load("start: ${vars.missing}", &[]).unwrap_err();
+    assert_eq!(unknown, "Unknown variable missing");
+    let unclosed = load("start: echo ${vars.port", &[("port", "1")]).unwrap_err();
+    assert_eq!(unclosed, "Unclosed variable reference ${vars.port");
+    assert!(load("vars: {list: [1]}", &[]).is_err());
+  }
+}
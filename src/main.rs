@@ -1,24 +1,121 @@
-use clap::{App, Arg, SubCommand};
-use conductor::{ui, Project};
+use clap::{App, AppSettings, Arg, SubCommand};
+use conductor::{
+  config_location, create, encrypt, export_trace, fetch_config, finish_detached, is_remote, replay,
+  scaffold, take_detached, trace_invocation, ui, use_docker_socket, wait_for_notifications, Chaos,
+  ConfigWatch, ExitCode, Message, NewComponent, Pattern, Project, RunOptions, SetupOptions,
+  UserConfig,
+};
 // use pty::fork::Fork;
+use std::collections::HashMap;
 use std::env;
+use std::io::{self, IsTerminal, Read};
 use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn main() {
   // Fork::from_ptmx().unwrap();
 
-  let matches = handle_cli()?;
-  if let Err(e) = run(matches) {
-    println!("Error: {}", e)
-  }
-  Ok(())
+  // Errors that reach this point come from finding or loading the configuration.
+  let code = match UserConfig::load()
+    .map_err(Box::from)
+    .and_then(|user| run(handle_cli(&user)?, user))
+  {
+    Ok(code) => code,
+    Err(e) => {
+      println!("Error: {}", e);
+      ExitCode::Config
+    }
+  };
+  export_trace(code);
+  wait_for_notifications();
+  std::process::exit(code.code())
 }
 
-fn run(matches: clap::ArgMatches<'_>) -> Result<(), std::boxed::Box<dyn std::error::Error>> {
-  if matches.is_present("debug") {
-    let _ = simple_logger::init_with_level(log::Level::Debug);
+fn run(
+  matches: clap::ArgMatches<'_>,
+  user: UserConfig,
+) -> Result<ExitCode, std::boxed::Box<dyn std::error::Error>> {
+  // Warnings are always shown, debug output only when asked for.
+  let level = match matches.is_present("debug") {
+    true => log::Level::Debug,
+    false => log::Level::Warn,
+  };
+  let _ = simple_logger::init_with_level(level);
+  // A session started by `run -d` is given the same arguments and runs in the foreground.
+  let detached = take_detached();
+  // Messages printed before the config is loaded follow the output flags too.
+  ui::configure(output_settings(&matches, ui::Settings::default())?);
+  // Scaffolding runs before any configuration exists.
+  if let ("init", Some(m)) = matches.subcommand() {
+    return Ok(scaffold(&env::current_dir()?, m.is_present("interactive")));
+  }
+  if let ("new", Some(m)) = matches.subcommand() {
+    let dir = env::current_dir()?.join(m.value_of("dir").unwrap_or_default());
+    let default_name = dir
+      .file_name()
+      .map(|n| n.to_string_lossy().to_string())
+      .unwrap_or_default();
+    // Values not given on the command line are asked for when someone is there to answer.
+    let ask = |arg: &str, question: &str, default: &str| match m.value_of(arg) {
+      Some(value) => value.to_string(),
+      None if io::stdin().is_terminal() => ui::prompt(question, default),
+      None => default.to_string(),
+    };
+    let name = ask("name", "Project name", &default_name);
+    let org = ask("org", "Organization", "");
+    let template = m.value_of("template").unwrap_or_default();
+    return Ok(create(&dir, template, &name, &org));
+  }
+  // Recordings can be played back without the project they were made in.
+  if let ("replay", Some(m)) = matches.subcommand() {
+    let speed = match m.value_of("speed").unwrap_or("1").parse::<f64>() {
+      Ok(speed) if speed > 0.0 => speed,
+      _ => {
+        ui::message(Message::ConfigError {
+          problem: "Expected a number above 0 for --speed".into(),
+        });
+        return Ok(ExitCode::Config);
+      }
+    };
+    let file = Path::new(m.value_of("file").unwrap_or_default());
+    return Ok(match replay(file, speed) {
+      Ok(()) => ExitCode::Success,
+      Err(e) => {
+        ui::message(Message::error(e));
+        ExitCode::Failure
+      }
+    });
+  }
+  // Values are encrypted for the config without loading it.
+  if let ("encrypt", Some(m)) = matches.subcommand() {
+    let value = match m.value_of("value") {
+      Some(value) => value.to_string(),
+      None => {
+        let mut value = String::new();
+        io::stdin().read_to_string(&mut value)?;
+        value.trim_end_matches(&['\r', '\n'][..]).to_string()
+      }
+    };
+    let recipients: Vec<&str> = m.values_of("recipient").into_iter().flatten().collect();
+    return Ok(match encrypt(&value, &recipients) {
+      Ok(encrypted) => {
+        println!("{}", encrypted);
+        ExitCode::Success
+      }
+      Err(e) => {
+        ui::message(Message::error(e));
+        ExitCode::Failure
+      }
+    });
   }
+  // A config from a URL describes the directory conductor is run in, not the cache it is kept in.
+  let location = match matches.value_of("config") {
+    Some(url) if is_remote(url) => Some(config_location(url)?),
+    _ => None,
+  };
   let config_fp = match matches.value_of("config") {
+    Some(url) if is_remote(url) => Some(fetch_config(url)?),
     Some(fp_str) => {
       let fp: PathBuf = fp_str.into();
       if fp.is_file() {
@@ -27,30 +124,431 @@ fn run(matches: clap::ArgMatches<'_>) -> Result<(), std::boxed::Box<dyn std::err
         None
       }
     }
-    None => find_config("conductor.yml"),
+    None => find_config(CONFIG_FILES),
+  };
+  // Completion runs on every press of tab, where errors would end up on the command line.
+  if let ("__complete", Some(m)) = matches.subcommand() {
+    let project = config_fp.and_then(|fp| {
+      let location = location.as_deref().unwrap_or(&fp);
+      Project::load_at(&fp, location, &HashMap::new(), &user).ok()
+    });
+    let names = project.map(|p| p.completions(m.value_of("kind")));
+    return Ok(match names {
+      Some(Err(problem)) => {
+        ui::message(Message::ConfigError { problem });
+        ExitCode::Config
+      }
+      Some(Ok(names)) => {
+        names.iter().for_each(|name| println!("{}", name));
+        ExitCode::Success
+      }
+      None => ExitCode::Success,
+    });
   }
-  .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "config not found"))?;
-  let mut project = Project::load(&config_fp)?;
-  let mut root_path = config_fp;
-  root_path.pop();
-
-  // collect tags
-  let tags: Vec<&str> = match matches.value_of("tags") {
-    Some(tags_r) => tags_r.split(',').collect(),
+  let config_fp = config_fp
+    .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "config not found"))?;
+  let vars = parse_vars(values(&matches, "var"))?;
+  use_docker_socket(user.docker_socket.as_deref());
+  let mut project = Project::load_at(
+    &config_fp,
+    location.as_deref().unwrap_or(&config_fp),
+    &vars,
+    &user,
+  )?;
+
+  // collect tags, these are accepted before or after the subcommand and can be repeated
+  let tags: Vec<&str> = values(&matches, "tags")
+    .into_iter()
+    .flat_map(|tags| tags.split(','))
+    .collect();
+  project.filter_tags(&tags);
+
+  project.output = output_settings(&matches, project.output.clone())?;
+  ui::configure(project.output.clone());
+  let command = matches.subcommand_name().unwrap_or_default();
+  trace_invocation(&project.telemetry, &project.name, command);
+
+  let mut overrides = HashMap::new();
+  for pair in values(&matches, "env") {
+    match pair.split_once('=') {
+      Some((key, value)) if !key.is_empty() => overrides.insert(key.to_string(), value.to_string()),
+      _ => {
+        ui::message(Message::ConfigError {
+          problem: format!("Expected KEY=VALUE for --env, got {}", pair),
+        });
+        return Ok(ExitCode::Config);
+      }
+    };
+  }
+
+  let except = values(&matches, "except");
+  if let Err(problem) = project.filter_except(&except) {
+    ui::message(Message::ConfigError { problem });
+    return Ok(ExitCode::Config);
+  }
+
+  // collect profiles, these are accepted before or after the subcommand
+  let profiles: Vec<&str> = match matches
+    .value_of("profile")
+    .or_else(|| matches.subcommand().1.and_then(|m| m.value_of("profile")))
+  {
+    Some(profiles_r) => profiles_r.split(',').collect(),
     _ => vec![],
   };
-  project.filter_tags(&tags);
 
-  if project
-    .run_names(vec![matches.subcommand().0.to_string()])
-    .is_ok()
+  // The command palette is only available when someone is at the terminal to type into it.
+  let interactive = io::stdin().is_terminal();
+  let log_dir = matches
+    .value_of("log-dir")
+    .or_else(|| matches.subcommand().1.and_then(|m| m.value_of("log-dir")))
+    .map(|dir| env::current_dir().map(|cwd| cwd.join(dir)))
+    .transpose()?
+    .or(user.log_dir);
+  let force = flag(&matches, "force");
+  let fail_fast = flag(&matches, "fail-fast");
+  let no_build = flag(&matches, "no-build");
+  let dry_run = flag(&matches, "dry-run");
+  // CI runs are never interactive.
+  let ci = flag(&matches, "ci");
+  let interactive = interactive && !ci;
+  let mute: Vec<String> = values(&matches, "mute")
+    .into_iter()
+    .map(String::from)
+    .collect();
+  let only: Vec<String> = values(&matches, "only")
+    .into_iter()
+    .map(String::from)
+    .collect();
+  let filter = match matches
+    .value_of("filter")
+    .or_else(|| matches.subcommand().1.and_then(|m| m.value_of("filter")))
+    .map(str::parse::<Pattern>)
+    .transpose()
   {
-    return Ok(());
+    Ok(filter) => filter,
+    Err(problem) => {
+      ui::message(Message::ConfigError { problem });
+      return Ok(ExitCode::Config);
+    }
+  };
+  let stats = match matches
+    .value_of("stats")
+    .or_else(|| matches.subcommand().1.and_then(|m| m.value_of("stats")))
+    .map(str::parse::<u64>)
+    .transpose()
+  {
+    Ok(stats) => stats,
+    Err(_) => {
+      ui::message(Message::ConfigError {
+        problem: "Expected a number of seconds for --stats".into(),
+      });
+      return Ok(ExitCode::Config);
+    }
+  };
+  let chaos = match flag(&matches, "chaos") {
+    false => Ok(None),
+    true => option(&matches, "chaos-interval")
+      .unwrap_or("30")
+      .parse::<u64>()
+      .map_err(|_| "Expected a number of seconds for --chaos-interval".to_string())
+      .and_then(|interval| {
+        let probability = option(&matches, "chaos-probability")
+          .unwrap_or("0.2")
+          .parse::<f64>()
+          .map_err(|_| "Expected a probability from 0 to 1 for --chaos-probability".to_string())?;
+        Chaos::new(interval, probability).map(Some)
+      }),
+  };
+  let chaos = match chaos {
+    Ok(chaos) => chaos,
+    Err(problem) => {
+      ui::message(Message::ConfigError { problem });
+      return Ok(ExitCode::Config);
+    }
+  };
+  let listen = matches
+    .value_of("listen")
+    .or_else(|| matches.subcommand().1.and_then(|m| m.value_of("listen")))
+    .map(String::from);
+  let record = option(&matches, "record")
+    .map(|file| env::current_dir().map(|cwd| cwd.join(file)))
+    .transpose()?;
+  // Runs of the whole project pick up changes to the config file, CI runs stay as they started.
+  // Configs from a URL are not downloaded again while running.
+  let watch = match ci || location.is_some() {
+    true => None,
+    false => Some(ConfigWatch {
+      path: config_fp.clone(),
+      tags: tags.iter().map(|t| t.to_string()).collect(),
+      profiles: profiles.iter().map(|p| p.to_string()).collect(),
+      except: except.iter().map(|e| e.to_string()).collect(),
+      vars: vars.clone(),
+    }),
+  };
+
+  if let Ok(code) = project.run_names(
+    vec![matches.subcommand().0.to_string()],
+    &RunOptions {
+      interactive,
+      force,
+      log_dir: log_dir.clone(),
+      mute: mute.clone(),
+      only: only.clone(),
+      filter: filter.clone(),
+      fail_fast,
+      ci,
+      listen: listen.clone(),
+      record: record.clone(),
+      stats,
+      chaos: chaos.clone(),
+      env: overrides.clone(),
+      no_build,
+      dry_run,
+      ..RunOptions::default()
+    },
+  ) {
+    return Ok(code);
   }
 
-  match matches.subcommand() {
-    ("setup", _) => project.setup(),
+  let code = match matches.subcommand() {
+    ("setup", Some(m)) => {
+      let names: Vec<&str> = m.values_of("component").into_iter().flatten().collect();
+      project.setup(
+        &names,
+        &SetupOptions {
+          sync: m.is_present("sync"),
+          // Forcing init implies running it.
+          reinit: m.is_present("reinit") || m.is_present("force-init"),
+          force_init: m.is_present("force-init"),
+          latest: m.is_present("latest"),
+        },
+      )
+    }
+    ("lock", Some(m)) => {
+      let names: Vec<&str> = m.values_of("component").into_iter().flatten().collect();
+      project.lock(&names)
+    }
+    ("audit", Some(m)) => project.audit(m.is_present("kill")),
+    ("cleanup", Some(m)) => project.cleanup(m.is_present("kill")),
+    ("validate", _) => project.validate(),
+    ("doctor", _) => {
+      project.filter_profiles(&profiles);
+      project.doctor()
+    }
+    ("graph", Some(m)) => {
+      project.filter_profiles(&profiles);
+      project.graph(m.value_of("format").unwrap_or_default())
+    }
+    ("report", Some(m)) => project.report(m.value_of("output")),
+    ("add", Some(m)) => {
+      let m = m.subcommand_matches("component").unwrap_or(m);
+      // Values not given on the command line are asked for when someone is there to answer.
+      let ask = |arg: &str, question: &str| match m.value_of(arg) {
+        Some(value) => value.to_string(),
+        None if interactive => ui::prompt(question, ""),
+        None => String::new(),
+      };
+      let component = NewComponent {
+        name: ask("name", "Component name"),
+        repo: ask("repo", "Git repository (optional)"),
+        path: ask("path", "Path (optional, the name by default)"),
+        start: ask("start", "Start command"),
+      };
+      project.add_component(&config_fp, &component)
+    }
+    ("import", Some(m)) => project.import(
+      &config_fp,
+      m.value_of("format").unwrap_or_default(),
+      m.value_of("file"),
+    ),
+    ("export", Some(m)) => {
+      project.filter_profiles(&profiles);
+      project.export(
+        m.value_of("format").unwrap_or_default(),
+        m.value_of("output"),
+      )
+    }
+    ("build", Some(m)) | ("test", Some(m)) => {
+      let names: Vec<&str> = m.values_of("component").into_iter().flatten().collect();
+      let jobs = match m.value_of("jobs").map(str::parse::<usize>) {
+        Some(Ok(jobs)) if jobs > 0 => jobs,
+        Some(_) => {
+          ui::message(Message::ConfigError {
+            problem: "Expected a positive number for --jobs".into(),
+          });
+          return Ok(ExitCode::Config);
+        }
+        // Test suites run one at a time unless asked so their output is not interleaved.
+        None if matches.subcommand_name() == Some("test") => 1,
+        None => thread::available_parallelism().map_or(1, |n| n.get()),
+      };
+      project.filter_profiles(&profiles);
+      project.filter_conditions();
+      match matches.subcommand_name() {
+        Some("test") => project.test(&names, jobs, &overrides),
+        _ => project.build(&names, jobs, &overrides),
+      }
+    }
+    ("apply", Some(m)) => project.apply(Path::new(m.value_of("spec").unwrap_or_default())),
+    ("logs", Some(m)) => project.logs(
+      m.value_of("component").unwrap_or_default(),
+      m.value_of("since"),
+      m.is_present("follow"),
+    ),
+    ("attach", Some(m)) => project.attach(m.value_of("component")),
+    ("exec", Some(m)) => {
+      let command: Vec<&str> = m.values_of("command").into_iter().flatten().collect();
+      project.exec(
+        m.value_of("component").unwrap_or_default(),
+        &command,
+        &overrides,
+      )
+    }
+    ("each", Some(m)) => {
+      let command: Vec<&str> = m.values_of("command").into_iter().flatten().collect();
+      project.filter_profiles(&profiles);
+      project.filter_conditions();
+      project.each(&command, m.value_of("group"), &overrides)
+    }
+    ("config", Some(m)) => match m.subcommand() {
+      ("dump", Some(_)) => {
+        project.filter_profiles(&profiles);
+        project.dump()
+      }
+      _ => unreachable!("the config subcommand requires a subcommand"),
+    },
+    ("env", Some(m)) => project.env(
+      m.value_of("name").unwrap_or_default(),
+      m.value_of("group"),
+      &overrides,
+      m.is_present("export"),
+    ),
+    ("shell", Some(m)) => project.shell(m.value_of("component").unwrap_or_default(), &overrides),
+    ("restart", Some(m)) => project.restart(m.value_of("component").unwrap_or_default()),
+    ("stop", Some(m)) => project.stop(m.value_of("component").unwrap_or_default()),
+    ("pause", Some(m)) => project.pause(m.value_of("component").unwrap_or_default(), true),
+    ("resume", Some(m)) => project.pause(m.value_of("component").unwrap_or_default(), false),
+    ("mute", Some(m)) => project.mute(m.value_of("name").unwrap_or_default(), true),
+    ("unmute", Some(m)) => project.mute(m.value_of("name").unwrap_or_default(), false),
+    ("status", Some(_)) => project.status(),
+    ("tail", Some(m)) => match m.value_of("lines").map(str::parse::<usize>).transpose() {
+      Ok(lines) => project.tail(m.value_of("component").unwrap_or_default(), lines),
+      Err(e) => {
+        ui::message(Message::ConfigError {
+          problem: format!("Invalid number of lines: {}", e),
+        });
+        ExitCode::Config
+      }
+    },
+    ("console", Some(_)) => project.console(),
+    ("wait", Some(m)) => match m.value_of("timeout").unwrap_or("60").parse::<u64>() {
+      Ok(timeout) => {
+        let names: Vec<&str> = m.values_of("name").into_iter().flatten().collect();
+        project.wait(&names, timeout)
+      }
+      Err(_) => {
+        ui::message(Message::ConfigError {
+          problem: "Expected a number of seconds for --timeout".into(),
+        });
+        ExitCode::Config
+      }
+    },
+    ("top", Some(m)) => match m.value_of("interval").unwrap_or("2").parse::<u64>() {
+      Ok(interval) => project.top(m.value_of("sort").unwrap_or_default(), interval),
+      Err(_) => {
+        ui::message(Message::ConfigError {
+          problem: "Expected a number of seconds for --interval".into(),
+        });
+        ExitCode::Config
+      }
+    },
+    ("services", Some(m)) => {
+      project.filter_profiles(&profiles);
+      let names: Vec<&str> = m
+        .subcommand()
+        .1
+        .and_then(|m| m.values_of("service"))
+        .into_iter()
+        .flatten()
+        .collect();
+      match m.subcommand_name() {
+        Some("up") => project.services_up(&names),
+        Some("down") => {
+          let volumes = m.subcommand().1.is_some_and(|m| m.is_present("volumes"));
+          project.services_down(&names, volumes)
+        }
+        Some("status") => project.services_status(&names),
+        _ => unreachable!("the services subcommand requires a subcommand"),
+      }
+    }
+    ("e2e", Some(m)) => {
+      project.filter_profiles(&profiles);
+      project.filter_conditions();
+      let timeout = match m.value_of("timeout").unwrap_or("300").parse::<u64>() {
+        Ok(timeout) => Duration::from_secs(timeout),
+        Err(_) => {
+          ui::message(Message::ConfigError {
+            problem: "Expected a number of seconds for --timeout".into(),
+          });
+          return Ok(ExitCode::Config);
+        }
+      };
+      match project.e2e(m.value_of("task").unwrap_or_default(), timeout) {
+        Ok(code) => code,
+        Err(e) => {
+          ui::message(Message::error(e));
+          ExitCode::Config
+        }
+      }
+    }
+    ("run", Some(m)) if m.is_present("wait") && !detached => {
+      let timeout = match m.value_of("wait-timeout").unwrap_or("60").parse::<u64>() {
+        Ok(timeout) => timeout,
+        Err(_) => {
+          ui::message(Message::ConfigError {
+            problem: "Expected a number of seconds for --wait-timeout".into(),
+          });
+          return Ok(ExitCode::Config);
+        }
+      };
+      match project.detach() {
+        ExitCode::Success => {
+          let names: Vec<&str> = m.values_of("component").into_iter().flatten().collect();
+          project.wait(&names, timeout)
+        }
+        code => code,
+      }
+    }
+    ("run", Some(m)) if m.is_present("detach") && !detached => project.detach(),
     ("run", Some(m)) => {
+      let options = RunOptions {
+        wait: m.is_present("wait"),
+        interactive,
+        broadcast: m.is_present("broadcast").then(|| {
+          m.values_of("broadcast")
+            .into_iter()
+            .flatten()
+            .map(String::from)
+            .collect()
+        }),
+        tui: m.is_present("tui") && !ci,
+        force,
+        log_dir,
+        mute,
+        only,
+        filter,
+        fail_fast,
+        ci,
+        listen,
+        record,
+        stats,
+        chaos,
+        env: overrides,
+        no_build,
+        dry_run,
+        headless: false,
+        watch: None,
+      };
       let component_names: Vec<String> = m
         .values_of("component")
         .map(|c| c.collect())
@@ -59,49 +557,220 @@ fn run(matches: clap::ArgMatches<'_>) -> Result<(), std::boxed::Box<dyn std::err
         .map(String::from)
         .collect();
       if !component_names.is_empty() {
-        let _ = project.run_names(component_names);
-        return Ok(());
+        match project.run_names(component_names, &options) {
+          Ok(code) => code,
+          Err(e) => {
+            ui::message(Message::error(e));
+            ExitCode::Config
+          }
+        }
       } else {
         if project.components.is_empty() {
-          ui::system_error("No components to run".into());
-          return Ok(());
+          ui::message(Message::NoComponents);
+          return Ok(ExitCode::Config);
         }
-        project.filter_default();
-        project.run();
+        project.filter_profiles(&profiles);
+        project.filter_conditions();
+        project.run(&RunOptions { watch, ..options })
       }
     }
     _ => {
-      project.filter_default();
-      project.run();
+      project.filter_profiles(&profiles);
+      project.filter_conditions();
+      project.run(&RunOptions {
+        interactive,
+        force,
+        log_dir,
+        mute,
+        only,
+        filter,
+        fail_fast,
+        ci,
+        listen,
+        record,
+        stats,
+        chaos,
+        env: overrides,
+        no_build,
+        dry_run,
+        watch,
+        ..RunOptions::default()
+      })
     }
   };
-  Ok(())
+  if detached {
+    finish_detached(&project.root_path);
+  }
+  Ok(code)
+}
+
+/// Applies the output flags to the output settings of the config.
+fn output_settings(
+  matches: &clap::ArgMatches<'_>,
+  mut settings: ui::Settings,
+) -> Result<ui::Settings, String> {
+  if flag(matches, "timestamps") {
+    settings.timestamps = true;
+  }
+  if flag(matches, "no-color") {
+    settings.color = false;
+  }
+  // The report subcommand has its own --output for the report's path.
+  if let Some(format) = matches.value_of("output").or_else(|| {
+    matches
+      .subcommand_matches("run")
+      .and_then(|m| m.value_of("output"))
+  }) {
+    settings.format = format.parse()?;
+  }
+  Ok(settings)
+}
+
+/// Returns every value of an option, given either before or after the subcommand.
+fn values<'a>(matches: &'a clap::ArgMatches<'_>, name: &str) -> Vec<&'a str> {
+  matches
+    .values_of(name)
+    .into_iter()
+    .flatten()
+    .chain(
+      matches
+        .subcommand()
+        .1
+        .and_then(|m| m.values_of(name))
+        .into_iter()
+        .flatten(),
+    )
+    .collect()
+}
+
+/// Returns the value of an option, given either before or after the subcommand.
+fn option<'a>(matches: &'a clap::ArgMatches<'_>, name: &str) -> Option<&'a str> {
+  matches
+    .value_of(name)
+    .or_else(|| matches.subcommand().1.and_then(|m| m.value_of(name)))
+}
+
+/// Returns true if a flag was given, either before or after the subcommand.
+fn flag(matches: &clap::ArgMatches<'_>, name: &str) -> bool {
+  matches.is_present(name)
+    || matches
+      .subcommand()
+      .1
+      .map(|m| m.is_present(name))
+      .unwrap_or(false)
+}
+
+/// `--var`, accepted before or after any subcommand.
+fn var_arg<'a, 'b>() -> Arg<'a, 'b> {
+  Arg::with_name("var")
+    .long("var")
+    .value_name("NAME=VALUE")
+    .help("override a variable of the config, can be given more than once")
+    .takes_value(true)
+    .multiple(true)
+    .number_of_values(1)
+    .global(true)
+}
+
+/// `--record`, accepted before or after `run`.
+fn record_arg<'a, 'b>() -> Arg<'a, 'b> {
+  Arg::with_name("record")
+    .long("record")
+    .value_name("FILE")
+    .help("write every event of the session to FILE, to play back with conductor replay")
+    .takes_value(true)
+    .global(true)
+}
+
+/// `--chaos` and its settings, accepted before or after `run`.
+fn chaos_args<'a, 'b>() -> [Arg<'a, 'b>; 3] {
+  [
+    Arg::with_name("chaos")
+      .long("chaos")
+      .help("kill running components at random to check that the environment recovers")
+      .global(true),
+    Arg::with_name("chaos-interval")
+      .long("chaos-interval")
+      .value_name("SECS")
+      .help("how often --chaos considers killing each component, every 30 seconds by default")
+      .takes_value(true)
+      .global(true),
+    Arg::with_name("chaos-probability")
+      .long("chaos-probability")
+      .value_name("P")
+      .help(
+        "the chance from 0 to 1 of --chaos killing each component every interval, 0.2 by default",
+      )
+      .takes_value(true)
+      .global(true),
+  ]
+}
+
+/// Reads the variables given as NAME=VALUE with `--var`.
+fn parse_vars<'a>(
+  pairs: impl IntoIterator<Item = &'a str>,
+) -> Result<HashMap<String, String>, String> {
+  pairs
+    .into_iter()
+    .map(|pair| match pair.split_once('=') {
+      Some((name, value)) if !name.is_empty() => Ok((name.to_string(), value.to_string())),
+      _ => Err(format!("Expected NAME=VALUE for --var, got {}", pair)),
+    })
+    .collect()
+}
+
+/// The variables given with `--var`, for loading the config the project's subcommands are read
+/// from before the arguments can be parsed in full. The project's subcommands are unknown at
+/// this point, so the variables given after one are parsed from what follows it. Arguments that
+/// do not parse are left for the full parse to report.
+fn early_vars(args: App<'_, '_>) -> Result<HashMap<String, String>, String> {
+  let matches = match args
+    .setting(AppSettings::AllowExternalSubcommands)
+    .get_matches_from_safe(env::args_os())
+  {
+    Ok(matches) => matches,
+    Err(_) => return Ok(HashMap::new()),
+  };
+  let mut pairs: Vec<String> = values(&matches, "var")
+    .into_iter()
+    .map(String::from)
+    .collect();
+  if let Some(rest) = matches.subcommand().1.and_then(|m| m.values_of_os("")) {
+    let trailing = App::new("conductor")
+      .arg(var_arg())
+      .get_matches_from_safe(std::iter::once("conductor".into()).chain(rest.map(|a| a.to_owned())));
+    if let Ok(trailing) = trailing {
+      pairs.extend(values(&trailing, "var").into_iter().map(String::from));
+    }
+  }
+  parse_vars(pairs.iter().map(String::as_str))
 }
 
-fn find_config(config: &str) -> Option<PathBuf> {
+/// The config file names searched for, in order of preference.
+const CONFIG_FILES: &[&str] = &["conductor.yml", "conductor.toml", "conductor.json"];
+
+fn find_config(configs: &[&str]) -> Option<PathBuf> {
   env::current_dir()
-    .map(|dir| find_file(&dir, config))
+    .map(|dir| find_file(&dir, configs))
     .unwrap_or(None)
 }
 
-fn find_file(starting_directory: &Path, filename: &str) -> Option<PathBuf> {
+fn find_file(starting_directory: &Path, filenames: &[&str]) -> Option<PathBuf> {
   let mut path: PathBuf = starting_directory.into();
-  let file = Path::new(&filename);
 
   loop {
-    path.push(file);
-
-    if path.is_file() {
-      break Some(path);
+    if let Some(file) = filenames.iter().map(|f| path.join(f)).find(|f| f.is_file()) {
+      break Some(file);
     }
 
-    if !(path.pop() && path.pop()) {
+    if !path.pop() {
       break None;
     }
   }
 }
 
-fn handle_cli<'a>() -> Result<clap::ArgMatches<'a>, Box<dyn std::error::Error>> {
+#[allow(clippy::explicit_auto_deref)]
+fn handle_cli<'a>(user: &UserConfig) -> Result<clap::ArgMatches<'a>, Box<dyn std::error::Error>> {
   let version = format!(
     "{}.{}.{}{}",
     env!("CARGO_PKG_VERSION_MAJOR"),
@@ -119,7 +788,7 @@ fn handle_cli<'a>() -> Result<clap::ArgMatches<'a>, Box<dyn std::error::Error>>
         .short("c")
         .long("config")
         .value_name("FILE")
-        .help("The conductor project configuration")
+        .help("The conductor project configuration, a path or an http(s) URL")
         .takes_value(true),
     )
     .arg(
@@ -132,25 +801,769 @@ fn handle_cli<'a>() -> Result<clap::ArgMatches<'a>, Box<dyn std::error::Error>>
       Arg::with_name("tags")
         .short("t")
         .long("tags")
-        .help("limit the operation to only components with a specific tag")
+        .visible_alias("tag")
+        .help("limit the operation to components with any of the tags, can be given more than once")
         .value_name("TAG1,TAG2")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1),
+    )
+    .arg(
+      Arg::with_name("profile")
+        .short("p")
+        .long("profile")
+        .help("run the components and services enabled by a profile")
+        .value_name("PROFILE1,PROFILE2")
+        .takes_value(true),
+    )
+    .arg(
+      Arg::with_name("force")
+        .long("force")
+        .help("launch components even if that exceeds the guardrails"),
+    )
+    .arg(
+      Arg::with_name("log-dir")
+        .long("log-dir")
+        .value_name("DIR")
+        .help("also write each component's output with timestamps to DIR/<component>.log")
+        .takes_value(true),
+    )
+    .arg(
+      Arg::with_name("timestamps")
+        .long("timestamps")
+        .help("prefix each line of output with the time it was received"),
+    )
+    .arg(
+      Arg::with_name("stats")
+        .long("stats")
+        .value_name("SECS")
+        .help("print the CPU and memory used by each component every SECS seconds")
+        .takes_value(true),
+    )
+    .args(&chaos_args())
+    .arg(
+      Arg::with_name("listen")
+        .long("listen")
+        .value_name("ADDR")
+        .help("serve the session's status over HTTP on ADDR, such as 127.0.0.1:3939")
+        .takes_value(true),
+    )
+    .arg(record_arg())
+    .arg(
+      Arg::with_name("ci")
+        .long("ci")
+        .help("stop once every component has finished and print a summary"),
+    )
+    .arg(
+      Arg::with_name("fail-fast")
+        .long("fail-fast")
+        .help("stop everything as soon as a component exits with an error"),
+    )
+    .arg(
+      Arg::with_name("mute")
+        .long("mute")
+        .value_name("NAME")
+        .help("hide the output of a component or service, can be given more than once")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1),
+    )
+    .arg(
+      Arg::with_name("only")
+        .long("only")
+        .value_name("NAME")
+        .help("show only the output of a component or service, can be given more than once")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1),
+    )
+    .arg(
+      Arg::with_name("filter")
+        .long("filter")
+        .value_name("REGEX")
+        .help("show only lines of output matching a regular expression")
+        .takes_value(true),
+    )
+    .arg(
+      Arg::with_name("except")
+        .long("except")
+        .visible_alias("skip")
+        .value_name("NAME")
+        .help("run everything but a component or group, can be given more than once")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1),
+    )
+    .arg(
+      Arg::with_name("env")
+        .short("e")
+        .long("env")
+        .value_name("KEY=VALUE")
+        .help("set an environment variable for every component and task, can be given more than once")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1),
+    )
+    .arg(var_arg())
+    .arg(
+      Arg::with_name("dry-run")
+        .long("dry-run")
+        .help("print what would be run without running anything"),
+    )
+    .arg(
+      Arg::with_name("no-build")
+        .long("no-build")
+        .help("start components without running their build commands"),
+    )
+    .arg(
+      Arg::with_name("no-color")
+        .long("no-color")
+        .help("do not color output"),
+    )
+    .arg(
+      Arg::with_name("output")
+        .long("output")
+        .value_name("FORMAT")
+        .help("write output as text or as a line of JSON for each event")
+        .possible_values(&["text", "json"])
         .takes_value(true),
     )
     .subcommand(
       SubCommand::with_name("setup")
         .about("clone and initialize the project")
         .display_order(1)
+        .arg(
+          Arg::with_name("component")
+            .multiple(true)
+            .help("the components and groups to set up, all components if none are given"),
+        )
         .arg(
           Arg::with_name("tags")
             .short("t")
             .long("tags")
-            .help("limit the operation to only components with a specific tag")
+            .visible_alias("tag")
+            .help("limit the operation to components with any of the tags, can be given more than once")
             .value_name("TAG1,TAG2")
-            .takes_value(true),
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1),
+        )
+        .arg(
+          Arg::with_name("sync")
+            .long("sync")
+            .help("update components that are already cloned from their upstream"),
+        )
+        .arg(
+          Arg::with_name("reinit")
+            .long("reinit")
+            .requires("sync")
+            .help("run the init commands of updated components again if their manifests changed"),
+        )
+        .arg(
+          Arg::with_name("force-init")
+            .long("force-init")
+            .requires("sync")
+            .help("run the init commands of updated components again even if nothing changed"),
+        )
+        .arg(
+          Arg::with_name("latest")
+            .long("latest")
+            .help("check out the latest commits instead of those in conductor.lock and update it"),
         )
         .alias("soundcheck")
         .alias("clone"),
     )
+    .subcommand(
+      SubCommand::with_name("init")
+        .about("write a starter conductor.yml in the current directory")
+        .display_order(1)
+        .arg(
+          Arg::with_name("interactive")
+            .short("i")
+            .long("interactive")
+            .help("prompt for the project name and components"),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("new")
+        .about("create a project directory from a template repository holding a conductor.yml")
+        .display_order(1)
+        .arg(
+          Arg::with_name("dir")
+            .required(true)
+            .help("the directory to create"),
+        )
+        .arg(
+          Arg::with_name("template")
+            .long("template")
+            .value_name("REPO")
+            .takes_value(true)
+            .required(true)
+            .help("the git repository to copy"),
+        )
+        .arg(
+          Arg::with_name("name")
+            .long("name")
+            .takes_value(true)
+            .help("the project name put in place of {{name}}, the directory name by default"),
+        )
+        .arg(
+          Arg::with_name("org")
+            .long("org")
+            .takes_value(true)
+            .help("the organization put in place of {{org}}"),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("validate")
+        .about("check the configuration for mistakes such as unknown references")
+        .display_order(1),
+    )
+    .subcommand(
+      SubCommand::with_name("lock")
+        .about("record the commit of each component repository in conductor.lock")
+        .display_order(1)
+        .arg(
+          Arg::with_name("component")
+            .multiple(true)
+            .help("the components and groups to lock, all components if none are given"),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("doctor")
+        .about("check that docker, repositories, paths, ports and programs are ready for the project")
+        .display_order(1),
+    )
+    .subcommand(
+      SubCommand::with_name("graph")
+        .about("print how components, services and groups depend on each other")
+        .display_order(1)
+        .arg(
+          Arg::with_name("format")
+            .possible_values(&["dot", "mermaid"])
+            .default_value("dot")
+            .help("the format to print the graph in"),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("audit")
+        .about("find and clean up processes and containers left behind by crashed sessions")
+        .display_order(1)
+        .arg(
+          Arg::with_name("kill")
+            .long("kill")
+            .help("clean up everything found without prompting"),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("cleanup")
+        .about("stop the processes and containers a crashed session left running")
+        .display_order(1)
+        .arg(
+          Arg::with_name("kill")
+            .long("kill")
+            .help("stop everything left running without prompting"),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("report")
+        .about("bundle the config, logs and versions into a tarball for bug reports")
+        .display_order(1)
+        .arg(
+          Arg::with_name("output")
+            .short("o")
+            .long("output")
+            .value_name("FILE")
+            .help("where to write the report")
+            .takes_value(true),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("add")
+        .about("add to the config, prompting for what is not given")
+        .display_order(1)
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+          SubCommand::with_name("component")
+            .about("add a component to the end of the components in the config")
+            .arg(
+              Arg::with_name("name")
+                .long("name")
+                .takes_value(true)
+                .help("the name of the component"),
+            )
+            .arg(
+              Arg::with_name("repo")
+                .long("repo")
+                .takes_value(true)
+                .help("the git repository setup clones the component from"),
+            )
+            .arg(
+              Arg::with_name("path")
+                .long("path")
+                .takes_value(true)
+                .help("where the component is, relative to the project root"),
+            )
+            .arg(
+              Arg::with_name("start")
+                .long("start")
+                .takes_value(true)
+                .help("the command that runs the component"),
+            ),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("import")
+        .about("add components to the config from a Procfile")
+        .display_order(1)
+        .arg(
+          Arg::with_name("format")
+            .required(true)
+            .possible_values(&["procfile"])
+            .help("the format to import from"),
+        )
+        .arg(
+          Arg::with_name("file")
+            .help("the file to import, the Procfile in the project root by default"),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("export")
+        .about("write the project as a compose file, Procfile, systemd units or VS Code tasks")
+        .display_order(1)
+        .arg(
+          Arg::with_name("format")
+            .required(true)
+            .possible_values(&["compose", "procfile", "systemd", "vscode"])
+            .help("the format to export to"),
+        )
+        .arg(
+          Arg::with_name("output")
+            .short("o")
+            .long("output")
+            .value_name("FILE")
+            .help("where to write the export, a directory for systemd units")
+            .takes_value(true),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("build")
+        .about("run the build commands of components in dependency order")
+        .display_order(1)
+        .arg(
+          Arg::with_name("component")
+            .multiple(true)
+            .help("the components and groups to build, all components if none are given"),
+        )
+        .arg(
+          Arg::with_name("jobs")
+            .short("j")
+            .long("jobs")
+            .value_name("N")
+            .takes_value(true)
+            .help("the number of builds to run at once, defaults to the number of CPUs"),
+        )
+        .arg(
+          Arg::with_name("tags")
+            .short("t")
+            .long("tags")
+            .visible_alias("tag")
+            .help("limit the operation to components with any of the tags, can be given more than once")
+            .value_name("TAG1,TAG2")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("test")
+        .about("run the test suites of components and fail if any of them fail")
+        .display_order(1)
+        .arg(
+          Arg::with_name("component")
+            .multiple(true)
+            .help("the components and groups to test, all components if none are given"),
+        )
+        .arg(
+          Arg::with_name("jobs")
+            .short("j")
+            .long("jobs")
+            .value_name("N")
+            .takes_value(true)
+            .help("the number of suites to run at once, defaults to one"),
+        )
+        .arg(
+          Arg::with_name("tags")
+            .short("t")
+            .long("tags")
+            .visible_alias("tag")
+            .help("limit the operation to components with any of the tags, can be given more than once")
+            .value_name("TAG1,TAG2")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("apply")
+        .about("start, stop and restart components of the running session to match a spec")
+        .display_order(1)
+        .arg(
+          Arg::with_name("spec")
+            .required(true)
+            .help("a file listing the components and groups that should be running"),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("logs")
+        .about("show the output a component has written, in this session or earlier ones")
+        .display_order(1)
+        .arg(
+          Arg::with_name("component")
+            .required(true)
+            .help("the component to show the output of"),
+        )
+        .arg(
+          Arg::with_name("follow")
+            .short("f")
+            .long("follow")
+            .help("keep showing output as it is written"),
+        )
+        .arg(
+          Arg::with_name("since")
+            .long("since")
+            .value_name("TIME")
+            .takes_value(true)
+            .help("only show output since an interval ago such as 10m, or a UTC time such as 2024-05-01T14:30"),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("__complete")
+        .about("print the component, group and task names of the project for shell completion")
+        .setting(AppSettings::Hidden)
+        .arg(
+          Arg::with_name("kind")
+            .possible_values(&["components", "groups", "tasks"])
+            .help("only print names of this kind"),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("replay")
+        .about("play back a session recorded with --record, with the output it had")
+        .display_order(1)
+        .arg(
+          Arg::with_name("file")
+            .required(true)
+            .help("the recording to play back"),
+        )
+        .arg(
+          Arg::with_name("speed")
+            .long("speed")
+            .value_name("N")
+            .takes_value(true)
+            .default_value("1")
+            .help("play back N times as fast, 0.5 plays back at half speed"),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("encrypt")
+        .about("encrypt a value with age so it can be written in the config as !encrypted")
+        .display_order(1)
+        .arg(
+          Arg::with_name("value")
+            .help("the value to encrypt, read from stdin if not given"),
+        )
+        .arg(
+          Arg::with_name("recipient")
+            .short("r")
+            .long("recipient")
+            .value_name("RECIPIENT")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .required(true)
+            .help("the age public key of someone who can decrypt the value, can be repeated"),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("attach")
+        .about("follow the output of a session running in the background, or send what is typed to a component of the running session, ~. detaches")
+        .display_order(1)
+        .arg(
+          Arg::with_name("component")
+            .help("the component to send input to"),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("restart")
+        .about("stop a component of the running session and start it again with its current config")
+        .display_order(1)
+        .arg(
+          Arg::with_name("component")
+            .required(true)
+            .help("the component to restart"),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("exec")
+        .about("run a command in a component's directory with its environment")
+        .display_order(1)
+        .arg(
+          Arg::with_name("component")
+            .required(true)
+            .help("the component to run the command for"),
+        )
+        .arg(
+          Arg::with_name("command")
+            .required(true)
+            .multiple(true)
+            .last(true)
+            .help("the command to run, after --"),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("each")
+        .about("run a command in every component's directory at once, with their environments")
+        .display_order(1)
+        .arg(
+          Arg::with_name("tags")
+            .short("t")
+            .long("tags")
+            .visible_alias("tag")
+            .help("limit the operation to components with any of the tags, can be given more than once")
+            .value_name("TAG1,TAG2")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1),
+        )
+        .arg(
+          Arg::with_name("group")
+            .long("group")
+            .value_name("GROUP")
+            .takes_value(true)
+            .help("run the command in the members of a group, with the group's env"),
+        )
+        .arg(
+          Arg::with_name("command")
+            .required(true)
+            .multiple(true)
+            .last(true)
+            .help("the command to run, after --"),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("config")
+        .about("inspect the configuration")
+        .display_order(1)
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+          SubCommand::with_name("dump").about(
+            "print the configuration as loaded, after includes, templates, profiles and defaults",
+          ),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("env")
+        .about("print the environment a component or task gets, secrets and env commands masked")
+        .display_order(1)
+        .arg(
+          Arg::with_name("name")
+            .required(true)
+            .help("the component, project task or component:task"),
+        )
+        .arg(
+          Arg::with_name("group")
+            .long("group")
+            .takes_value(true)
+            .value_name("GROUP")
+            .help("include the env a group gives the component"),
+        )
+        .arg(
+          Arg::with_name("export")
+            .long("export")
+            .help("print export statements for the shell"),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("shell")
+        .about("start a shell in a component's directory with its environment")
+        .display_order(1)
+        .arg(
+          Arg::with_name("component")
+            .required(true)
+            .help("the component whose environment the shell gets"),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("stop")
+        .about("stop a component of the running session")
+        .display_order(1)
+        .arg(
+          Arg::with_name("component")
+            .required(true)
+            .help("the component to stop"),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("pause")
+        .about("freeze a component of the running session without stopping it")
+        .display_order(1)
+        .arg(
+          Arg::with_name("component")
+            .required(true)
+            .help("the component to pause"),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("resume")
+        .about("continue a paused component of the running session")
+        .display_order(1)
+        .arg(
+          Arg::with_name("component")
+            .required(true)
+            .help("the component to resume"),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("mute")
+        .about("hide the output of a component or service in the running session")
+        .display_order(1)
+        .arg(
+          Arg::with_name("name")
+            .required(true)
+            .help("the component or service to mute"),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("unmute")
+        .about("show the output of a component or service in the running session again")
+        .display_order(1)
+        .arg(
+          Arg::with_name("name")
+            .required(true)
+            .help("the component or service to unmute"),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("status")
+        .about("show the state of each component of the running session")
+        .display_order(1),
+    )
+    .subcommand(
+      SubCommand::with_name("tail")
+        .about("show the recent output of a component of the running session")
+        .display_order(1)
+        .arg(
+          Arg::with_name("component")
+            .required(true)
+            .help("the component to show the output of"),
+        )
+        .arg(
+          Arg::with_name("lines")
+            .short("n")
+            .long("lines")
+            .value_name("N")
+            .takes_value(true)
+            .help("show only the last N lines"),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("wait")
+        .about("wait until the components of the running session are ready, failing if one of them fails")
+        .display_order(1)
+        .arg(
+          Arg::with_name("name")
+            .multiple(true)
+            .help("the components and groups to wait for, every component when none are given"),
+        )
+        .arg(
+          Arg::with_name("timeout")
+            .long("timeout")
+            .value_name("SECS")
+            .takes_value(true)
+            .help("how long to wait before giving up, 60 seconds by default"),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("console")
+        .about("type commands such as start, stop, logs and run for the running session at a prompt")
+        .display_order(1),
+    )
+    .subcommand(
+      SubCommand::with_name("top")
+        .about("show the CPU, memory, restarts and state of each component as they change")
+        .display_order(1)
+        .arg(
+          Arg::with_name("sort")
+            .long("sort")
+            .value_name("COLUMN")
+            .possible_values(&["name", "status", "cpu", "memory", "restarts"])
+            .default_value("cpu")
+            .help("the column to sort by, which can be changed with its first letter"),
+        )
+        .arg(
+          Arg::with_name("interval")
+            .long("interval")
+            .value_name("SECONDS")
+            .default_value("2")
+            .help("how often to refresh"),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("services")
+        .about("start, stop or check the services without any components")
+        .display_order(1)
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+          SubCommand::with_name("up")
+            .about("start services and wait for them to be ready")
+            .arg(
+              Arg::with_name("service")
+                .multiple(true)
+                .help("the services to start, all services if none are given"),
+            ),
+        )
+        .subcommand(
+          SubCommand::with_name("down")
+            .about("stop services")
+            .arg(
+              Arg::with_name("service")
+                .multiple(true)
+                .help("the services to stop, all services if none are given"),
+            )
+            .arg(
+              Arg::with_name("volumes")
+                .long("volumes")
+                .help("also remove the containers and their named volumes"),
+            ),
+        )
+        .subcommand(
+          SubCommand::with_name("status")
+            .about("show whether services are running")
+            .arg(
+              Arg::with_name("service")
+                .multiple(true)
+                .help("the services to check, all services if none are given"),
+            ),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name("e2e")
+        .about("bring up the environment, run a test task against it and tear it down")
+        .display_order(1)
+        .arg(
+          Arg::with_name("task")
+            .required(true)
+            .help("the project or component:task to run once everything is ready"),
+        )
+        .arg(
+          Arg::with_name("timeout")
+            .long("timeout")
+            .value_name("SECS")
+            .takes_value(true)
+            .help("how long to wait for the environment to be ready, 300 seconds by default"),
+        ),
+    )
     .subcommand(
       SubCommand::with_name("run")
         .about("Launches all project components.")
@@ -159,24 +1572,182 @@ fn handle_cli<'a>() -> Result<clap::ArgMatches<'a>, Box<dyn std::error::Error>>
           Arg::with_name("tags")
             .short("t")
             .long("tags")
-            .help("limit the operation to only components with a specific tag")
+            .visible_alias("tag")
+            .help("limit the operation to components with any of the tags, can be given more than once")
             .value_name("TAG1,TAG2")
-            .takes_value(true),
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1),
         )
         .arg(
             Arg::with_name("component")
                 .multiple(true)
                 .help("a specific component to execute")
         )
+        .arg(
+          Arg::with_name("profile")
+            .short("p")
+            .long("profile")
+            .help("run the components and services enabled by a profile")
+            .value_name("PROFILE1,PROFILE2")
+            .takes_value(true),
+        )
+        .arg(
+          Arg::with_name("wait")
+            .long("wait")
+            .conflicts_with_all(&["tui", "ci"])
+            .help("run the session in the background and return once every component is ready"),
+        )
+        .arg(
+          Arg::with_name("wait-timeout")
+            .long("wait-timeout")
+            .value_name("SECS")
+            .takes_value(true)
+            .requires("wait")
+            .help("how long --wait waits before giving up, 60 seconds by default"),
+        )
+        .arg(
+          Arg::with_name("detach")
+            .short("d")
+            .long("detach")
+            .conflicts_with_all(&["tui", "ci"])
+            .help("run the session in the background, conductor attach follows its output"),
+        )
+        .arg(
+          Arg::with_name("tui")
+            .long("tui")
+            .help("show a full screen dashboard of the components and their output"),
+        )
+        .arg(
+          Arg::with_name("broadcast")
+            .long("broadcast")
+            .value_name("COMPONENTS")
+            .takes_value(true)
+            .min_values(0)
+            .require_equals(true)
+            .use_delimiter(true)
+            .conflicts_with_all(&["tui", "detach"])
+            .help("send lines typed into stdin to every running component, or those listed"),
+        )
+        .arg(
+          Arg::with_name("force")
+            .long("force")
+            .help("launch components even if that exceeds the guardrails"),
+        )
+        .arg(
+          Arg::with_name("log-dir")
+            .long("log-dir")
+            .value_name("DIR")
+            .help("also write each component's output with timestamps to DIR/<component>.log")
+            .takes_value(true),
+        )
+        .arg(
+          Arg::with_name("timestamps")
+            .long("timestamps")
+            .help("prefix each line of output with the time it was received"),
+        )
+        .arg(
+          Arg::with_name("stats")
+            .long("stats")
+            .value_name("SECS")
+            .help("print the CPU and memory used by each component every SECS seconds")
+            .takes_value(true),
+        )
+        .arg(
+          Arg::with_name("listen")
+            .long("listen")
+            .value_name("ADDR")
+            .help("serve the session's status over HTTP on ADDR, such as 127.0.0.1:3939")
+            .takes_value(true),
+        )
+        .arg(
+          Arg::with_name("ci")
+            .long("ci")
+            .help("stop once every component has finished and print a summary"),
+        )
+        .arg(
+          Arg::with_name("fail-fast")
+            .long("fail-fast")
+            .help("stop everything as soon as a component exits with an error"),
+        )
+        .arg(
+          Arg::with_name("mute")
+            .long("mute")
+            .value_name("NAME")
+            .help("hide the output of a component or service, can be given more than once")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1),
+        )
+        .arg(
+          Arg::with_name("only")
+            .long("only")
+            .value_name("NAME")
+            .help("show only the output of a component or service, can be given more than once")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1),
+        )
+        .arg(
+          Arg::with_name("filter")
+            .long("filter")
+            .value_name("REGEX")
+            .help("show only lines of output matching a regular expression")
+            .takes_value(true),
+        )
+        .arg(
+          Arg::with_name("except")
+            .long("except")
+            .visible_alias("skip")
+            .value_name("NAME")
+            .help("run everything but a component or group, can be given more than once")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1),
+        )
+        .arg(
+          Arg::with_name("env")
+            .short("e")
+            .long("env")
+            .value_name("KEY=VALUE")
+            .help("set an environment variable for every component and task, can be given more than once")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1),
+        )
+        .arg(
+          Arg::with_name("dry-run")
+            .long("dry-run")
+            .help("print what would be run without running anything"),
+        )
+        .arg(
+          Arg::with_name("no-build")
+            .long("no-build")
+            .help("start components without running their build commands"),
+        )
+        .arg(
+          Arg::with_name("no-color")
+            .long("no-color")
+            .help("do not color output"),
+        )
+        .arg(
+          Arg::with_name("output")
+            .long("output")
+            .value_name("FORMAT")
+            .help("write output as text or as a line of JSON for each event")
+            .possible_values(&["text", "json"])
+            .takes_value(true),
+        )
         .alias("play")
+        .alias("up")
         .alias("start"),
     );
 
-  let args = match find_config("conductor.yml") {
+  let args = match find_config(CONFIG_FILES) {
     None => args,
     Some(local_config_fp) => {
-      let project = Project::load(&local_config_fp)?;
-
+      let vars = early_vars(args.clone())?;
+      let project = Project::load_with_vars(&local_config_fp, &vars, user)?;
       let mut cmds: Vec<App> = vec![];
 
       // PROJECT LEVEL TASKS
@@ -221,7 +1792,7 @@ fn handle_cli<'a>() -> Result<clap::ArgMatches<'a>, Box<dyn std::error::Error>>
       }
 
       for component in project.components {
-        for task in component.tasks.keys() {
+        for task in component.task_names() {
           cmds.push(
             SubCommand::with_name(&format!("{}:{}", &component.name, &task))
               .about("Run component task")